@@ -0,0 +1,181 @@
+//! Benchmarks of the hot paths over generated scenes of 10k, 100k and 1M splats
+//!
+//! Run all with `cargo bench --bench hot_paths` or a subset by name, like `cargo bench --bench hot_paths -- sort/`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use glam::{Mat3, Mat4, Quat, Vec3};
+use splatter::chunks::{sort_into_chunks, CHUNK_SIZE};
+use splatter::cpu_renderer::{depth_order, render_scene, visible_depth_order, CpuRenderSettings};
+use splatter::culling::cull;
+use splatter::loading::stream_splats_from_ply;
+use splatter::scene::generate::{random_cloud, GenerateSettings};
+use splatter::scene::{Camera, Scene};
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{BufWriter, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+
+const SIZES: [usize; 3] = [10_000, 100_000, 1_000_000];
+
+/// Reading a whole PLY file keeps every vertex as a map of its properties, which takes gigabytes for the largest size.
+const WHOLE_FILE_MAX_SIZE: usize = 100_000;
+
+/// Maps colors to the DC coefficients of spherical harmonics in PLY files.
+const SH_C0: f32 = 0.282_094_8;
+
+const BATCH_SIZE: usize = 1 << 16;
+
+const FRUSTUM_CULLING_TOLERANCE: f32 = 1.1;
+
+const RENDER_WIDTH: u32 = 320;
+const RENDER_HEIGHT: u32 = 240;
+
+fn scene(count: usize) -> Scene {
+    let settings = GenerateSettings {
+        scale: [0.02, 0.01],
+        jitter: 0.5,
+        spherical_harmonics_order: 3,
+        seed: count as u64,
+        ..GenerateSettings::default()
+    };
+    random_cloud(count, 10.0, &settings)
+}
+
+/// Sees all of the scene from outside.
+fn camera() -> Camera {
+    let view = Mat4::look_at_rh(Vec3::new(0.0, 5.0, 25.0), Vec3::ZERO, Vec3::Y);
+    Camera::perspective(view, 60.0_f32.to_radians(), RENDER_WIDTH as f32 / RENDER_HEIGHT as f32, 0.1, 100.0)
+}
+
+/// Writes the splats with the properties of 3D gaussian splatting into a temporary file. This does not go through
+/// `Scene::save_splats_to_ply`, because building the PLY of a million splats in memory takes longer than reading it.
+fn write_ply(scene: &Scene, ascii: bool) -> PathBuf {
+    let encoding = if ascii { "ascii" } else { "binary_little_endian" };
+    let path = std::env::temp_dir().join(format!("splatter_bench_{}_{encoding}.ply", scene.splat_data.len()));
+    let mut writer = BufWriter::new(File::create(&path).unwrap());
+    write!(writer, "ply\nformat {encoding} 1.0\nelement vertex {}\n", scene.splat_data.len()).unwrap();
+    let names = [
+        "x", "y", "z", "f_dc_0", "f_dc_1", "f_dc_2", "opacity", "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3",
+    ];
+    for name in names {
+        writeln!(writer, "property float {name}").unwrap();
+    }
+    writeln!(writer, "end_header").unwrap();
+    for splat in &scene.splat_data {
+        let [tangent, bitangent, normal] = splat.tangent_frame();
+        let rotation = Quat::from_mat3(&Mat3::from_cols(tangent, bitangent, normal)).normalize();
+        let alpha = splat.color[3].clamp(1.0e-6, 1.0 - 1.0e-6);
+        let values = [
+            splat.center[0],
+            splat.center[1],
+            splat.center[2],
+            (splat.color[0] - 0.5) / SH_C0,
+            (splat.color[1] - 0.5) / SH_C0,
+            (splat.color[2] - 0.5) / SH_C0,
+            (alpha / (1.0 - alpha)).ln(),
+            splat.scale[0].ln(),
+            splat.scale[1].ln(),
+            (splat.scale[1] * 1.0e-3).ln(),
+            rotation.w,
+            rotation.x,
+            rotation.y,
+            rotation.z,
+        ];
+        if ascii {
+            let line: Vec<String> = values.iter().map(f32::to_string).collect();
+            writeln!(writer, "{}", line.join(" ")).unwrap();
+        } else {
+            for value in values {
+                writer.write_all(&value.to_le_bytes()).unwrap();
+            }
+        }
+    }
+    writer.flush().unwrap();
+    path
+}
+
+fn ply_parsing(c: &mut Criterion) {
+    for count in SIZES {
+        let scene = scene(count);
+        for ascii in [true, false] {
+            let path = write_ply(&scene, ascii);
+            if !ascii && count <= WHOLE_FILE_MAX_SIZE {
+                let path = path.to_str().unwrap().to_string();
+                c.bench_function(&format!("ply/whole_file/{count}"), move |b| {
+                    b.iter(|| Scene::read_splats_from_ply(&path).unwrap())
+                });
+            }
+            let name = format!("ply/{}/{count}", if ascii { "ascii" } else { "binary" });
+            c.bench_function(&name, move |b| {
+                b.iter(|| {
+                    stream_splats_from_ply(&path, BATCH_SIZE, |batch, _progress| {
+                        black_box(batch);
+                        ControlFlow::Continue(())
+                    })
+                    .unwrap()
+                })
+            });
+        }
+    }
+}
+
+/// What `convert_splat_data` does before uploading the splat buffer.
+fn buffer_packing(c: &mut Criterion) {
+    for count in SIZES {
+        let scene = scene(count);
+        c.bench_function(&format!("packing/{count}"), move |b| {
+            b.iter(|| {
+                let shader_splats = scene.shader_splats(0..scene.splat_data.len());
+                black_box(bytemuck::cast_slice::<_, u8>(&shader_splats).len())
+            })
+        });
+    }
+}
+
+fn depth_sorting(c: &mut Criterion) {
+    for count in SIZES {
+        let scene = scene(count);
+        let camera = camera();
+        c.bench_function(&format!("sort/{count}"), move |b| b.iter(|| depth_order(&scene.splat_data, &camera)));
+        // Looking outwards from the center only a few percent of the scene are visible
+        let scene = self::scene(count);
+        let camera = Camera::perspective(Mat4::look_at_rh(Vec3::ZERO, Vec3::Z, Vec3::Y), 1.0, 1.0, 0.1, 100.0);
+        c.bench_function(&format!("sort/visible/{count}"), move |b| {
+            b.iter(|| visible_depth_order(&scene.splat_data, &camera, FRUSTUM_CULLING_TOLERANCE))
+        });
+    }
+}
+
+fn frustum_culling(c: &mut Criterion) {
+    for count in SIZES {
+        let scene = scene(count);
+        let camera = camera();
+        c.bench_function(&format!("cull/{count}"), move |b| {
+            b.iter(|| cull(&scene.splat_data, &camera, FRUSTUM_CULLING_TOLERANCE))
+        });
+        let mut scene = self::scene(count);
+        let layout = sort_into_chunks(&mut scene, CHUNK_SIZE);
+        c.bench_function(&format!("cull/chunks/{count}"), move |b| {
+            b.iter(|| layout.cull(&scene.splat_data, &camera, FRUSTUM_CULLING_TOLERANCE))
+        });
+    }
+}
+
+fn rasterization(c: &mut Criterion) {
+    for count in SIZES {
+        let scene = scene(count);
+        let camera = camera();
+        let settings = CpuRenderSettings::default();
+        c.bench_function(&format!("rasterize/{count}"), move |b| {
+            b.iter(|| render_scene(&scene, &camera, RENDER_WIDTH, RENDER_HEIGHT, &settings))
+        });
+    }
+}
+
+criterion_group! {
+    name = hot_paths;
+    config = Criterion::default().sample_size(10);
+    targets = ply_parsing, buffer_packing, depth_sorting, frustum_culling, rasterization
+}
+criterion_main!(hot_paths);
@@ -2,7 +2,7 @@ use geometric_algebra::{
     ppga3d::{Rotor, Translator},
     GeometricProduct, One, Signum, Transformation,
 };
-use splatter::{renderer::Renderer, scene::Scene};
+use splatter::{camera_path::CameraPath, renderer::Renderer, scene::{generate, Scene}};
 use std::{collections::HashSet, env, fs::File};
 mod application_framework;
 use splatter::config::{Config,DepthSorting as ConfigDeptSorting};
@@ -42,19 +42,25 @@ fn rotate_around_axis(angle: f32, axis: &[f32; 3]) -> Rotor {
 struct Application {
     renderer: Renderer,
     scene: Scene,
-    file: File,
+    /// The splat file to load in chunks, none for a generated scene.
+    file: Option<File>,
     file_header_size: u16,
     chunks_left_to_load: usize,
     depth_stencil_texture_view: Option<wgpu::TextureView>,
     viewport_size: wgpu::Extent3d,
     camera_rotation: Rotor,
     camera_translation: Translator,
+    /// Replaces the keyboard and mouse controls, if a path was given as second argument.
+    camera_path: Option<CameraPath>,
+    camera_path_time: f32,
     pressed_keys: HashSet<winit::event::VirtualKeyCode>,
 }
 
 impl application_framework::Application for Application {
     fn new(device: &wgpu::Device, queue: &mut wgpu::Queue, _surface_configuration: &wgpu::SurfaceConfiguration) -> Self {
-        let file = File::open(env::args().nth(1).unwrap()).unwrap();
+        // Without a splat file, or with `-` instead, a generated sphere shows up
+        let file = env::args().nth(1).filter(|path| path != "-").map(|path| File::open(path).unwrap());
+        let camera_path = env::args().nth(2).map(|path| CameraPath::load(path).expect("Failed to load camera path"));
         let config = Config {
             surface_configuration: wgpu::SurfaceConfiguration {
                 usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -78,13 +84,27 @@ impl application_framework::Application for Application {
         
         let renderer = Renderer::new(device, config);
         
-        let (file_header_size, splat_count, mut file) = Scene::parse_file_header(file);
-        let mut scene = Scene::new();
-        let chunks_left_to_load = if LOAD_CHUNK_SIZE == 0 {
-            scene.load_chunk(queue, &mut file, file_header_size, 0..splat_count);
-            0
-        } else {
-            (scene.splat_count + LOAD_CHUNK_SIZE - 1) / LOAD_CHUNK_SIZE
+        let (scene, file, file_header_size, chunks_left_to_load) = match file {
+            Some(file) => {
+                let (file_header_size, splat_count, mut file) = Scene::parse_file_header(file);
+                let mut scene = Scene::new();
+                let chunks_left_to_load = if LOAD_CHUNK_SIZE == 0 {
+                    scene.load_chunk(queue, &mut file, file_header_size, 0..splat_count);
+                    0
+                } else {
+                    (scene.splat_count + LOAD_CHUNK_SIZE - 1) / LOAD_CHUNK_SIZE
+                };
+                (scene, Some(file), file_header_size, chunks_left_to_load)
+            }
+            None => {
+                let settings = generate::GenerateSettings {
+                    scale: [0.05, 0.02],
+                    jitter: 0.3,
+                    spherical_harmonics_order: 1,
+                    ..generate::GenerateSettings::default()
+                };
+                (generate::sphere_shell(4096, 1.0, &settings), None, 0, 0)
+            }
         };
         Self {
             renderer,
@@ -96,6 +116,8 @@ impl application_framework::Application for Application {
             viewport_size: wgpu::Extent3d::default(),
             camera_rotation: Rotor::one(),
             camera_translation: Translator::one(),
+            camera_path,
+            camera_path_time: 0.0,
             pressed_keys: HashSet::new(),
         }
     }
@@ -124,10 +146,10 @@ impl application_framework::Application for Application {
     }
 
     fn render(&mut self, device: &wgpu::Device, queue: &mut wgpu::Queue, frame: &wgpu::SurfaceTexture, frame_time: f32) {
-        if self.chunks_left_to_load > 0 {
+        if let (Some(file), true) = (&mut self.file, self.chunks_left_to_load > 0) {
             self.chunks_left_to_load -= 1;
             let load_range = self.chunks_left_to_load * LOAD_CHUNK_SIZE..(self.chunks_left_to_load + 1) * LOAD_CHUNK_SIZE;
-            self.scene.load_chunk(queue, &mut self.file, self.file_header_size, load_range);
+            self.scene.load_chunk(queue, file, self.file_header_size, load_range);
             queue.submit([]);
         }
         for keycode in &self.pressed_keys {
@@ -167,6 +189,11 @@ impl application_framework::Application for Application {
             }
         }
         let _camera_motor = self.camera_translation.geometric_product(self.camera_rotation);
+        if let Some(pose) = self.camera_path.as_ref().and_then(|path| path.sample(self.camera_path_time)) {
+            let aspect_ratio = self.viewport_size.width as f32 / self.viewport_size.height.max(1) as f32;
+            self.scene.camera = pose.camera(aspect_ratio, self.scene.camera.z_near, self.scene.camera.z_far);
+            self.camera_path_time += frame_time;
+        }
         let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
     
         // Create the CommandEncoder
@@ -1,5 +1,7 @@
 use bevy::prelude::*;
 use bevy::render::camera::CameraRenderGraph;
+use splatter::camera::SplatCameraPlugin;
+use splatter::lod::SplatLodPlugin;
 use splatter::render_plugin::GaussianSplatRenderPlugin;
 use splatter::scene::ScenePlugin;
 
@@ -9,6 +11,8 @@ fn main() {
         .add_plugins((
             GaussianSplatRenderPlugin,
             ScenePlugin,
+            SplatCameraPlugin,
+            SplatLodPlugin,
         ))
         .add_systems(Startup, setup)
         .run();
@@ -21,8 +21,6 @@ pub struct GaussianSplatPlugin;
 
 impl Plugin for GaussianSplatPlugin {
     fn build(&self, app: &mut App) {
-        let render_app = app.sub_app_mut(RenderApp);
-
         let config = Config {
             surface_configuration: wgpu::SurfaceConfiguration {
                 usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -44,8 +42,10 @@ impl Plugin for GaussianSplatPlugin {
             splat_scale: 1.0,
         };
 
-        render_app
-            .insert_resource(config.clone())
+        // The main world caps the level of detail cut at the same number of splats
+        app.insert_resource(config.clone());
+        app.sub_app_mut(RenderApp)
+            .insert_resource(config)
             .add_systems(Startup, setup_renderer)
             .add_systems(Last, cleanup_renderer);
     }
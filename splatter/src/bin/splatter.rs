@@ -0,0 +1,710 @@
+//! Command line tools for splat scenes
+
+use glam::{Mat4, Vec3};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use splatter::camera_path::CameraPath;
+use splatter::chunks::{sort_into_chunks, CHUNK_SIZE};
+use splatter::cleanup::{remove_outliers, CleanupSettings};
+use splatter::cpu_renderer::{render_chunks, render_scene, CpuRenderSettings};
+use splatter::decimation::{decimate_with_report, DecimationMode, DecimationSettings, Importance};
+use splatter::evaluation::{evaluate, EvaluationSettings};
+use splatter::lod::{LodBuildSettings, LodTree};
+use splatter::panorama::render_equirectangular;
+use splatter::scene::{Camera, Scene, SceneFormat};
+use splatter::statistics::{diff, Bounds, DiffSettings, DiffSummary, SceneStatistics, StatisticsSettings};
+use splatter::streaming::write_tiled;
+use splatter::training_cameras::read_training_cameras;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: splatter <command> [arguments]
+
+Scenes are read from and written to .ply or .splat files.
+
+Commands:
+  inspect <input>                   Reports the header, property list, splat count and bounds of a scene file
+  convert <input> <output>          Converts between .ply, .splat and streamable .tiles
+      --tile-size <size>              Edge length of the tiles on the ground plane [10]
+  crop <input> <output>             Keeps the splats whose centers are inside a box
+      --min <x,y,z>                   Lower corner of the box
+      --max <x,y,z>                   Upper corner of the box
+  decimate <input> <output>         Keeps the most important splats and reports the quality loss in renders around
+                                    the scene
+      --count <splats>                How many splats to keep
+      --importance <opacity-area|contribution>
+                                      Ranks by opacity times area or by contribution to the renders [opacity-area]
+      --mode <drop|merge>             Drops the others or merges them into their nearest neighbor [drop]
+      --views <count>                 Renders around the scene, 0 skips the quality measurement [8]
+  render <input> <output.png>       Renders a view on the CPU, by default of the whole scene from +z
+      --position <x,y,z>              Position of the camera
+      --target <x,y,z>                Point the camera looks at [center of the scene]
+      --fov <degrees>                 Vertical field of view [60]
+      --width <pixels>                Width of the image [1280]
+      --height <pixels>               Height of the image [720]
+  cleanup <input> <output>          Removes outliers and floaters
+      --neighbors <count>             Nearest neighbors per splat, 0 disables outlier removal [8]
+      --std-ratio <ratio>             Standard deviations above which splats are outliers [2]
+      --floater-scale-ratio <ratio>   Scale relative to the median above which splats can be floaters [10]
+      --floater-opacity <opacity>     Opacity below which large splats are floaters, 0 disables floater removal [0.3]
+  lod <input.ply>                   Builds the level of detail hierarchy and saves it next to the input
+      --output <path>                 Where to save the hierarchy instead [<input>.lod]
+      --branching <count>             Average number of splats merged into a parent [8]
+  tile <input.ply> <output.tiles>   Partitions the splats into tiles which can be streamed
+      --tile-size <size>              Edge length of the tiles on the ground plane [10]
+  panorama <input.ply> <output.png> Renders an equirectangular 360° panorama on the CPU
+      --position <x,y,z>              Where the panorama is taken [0,0,0]
+      --width <pixels>                Width of the image, which is twice its height [2048]
+  flythrough <input.ply> <path.json|path.ron> <output-directory>
+                                    Renders the frames of a camera path on the CPU and reports the time per frame
+      --fps <rate>                    Frames per second of the path [30]
+      --width <pixels>                Width of the frames [640]
+      --height <pixels>               Height of the frames [360]
+  evaluate <input.ply> <cameras> <images-directory>
+                                    Compares CPU renders of the training views with their photos,
+                                    <cameras> is a COLMAP directory, cameras.json or transforms.json
+      --report <path.json>            Saves the metrics of every view and their mean as JSON
+      --perceptual <true|false>       Whether to compute the GMSD perceptual proxy [true]
+  stats <input.ply>                 Reports the splat count, bounds, opacity and scale histograms, spherical harmonics
+                                    energy per band and memory footprint of every encoding
+      --compare <other.ply>           Also reports the splats added, removed and moved in the other scene
+      --search-radius <distance>      Splats further apart are not matched [3 times the median scale]
+      --moved-distance <distance>     Matched splats further apart moved [0.0001]
+  help                              Prints this message
+
+Options of every command but help:
+      --format <text|json>            Output format [text]
+
+Exit codes:
+  0 success, 1 other errors, 2 invalid arguments, 3 missing input, 4 invalid or unsupported input";
+
+/// Exit codes besides success and general failure, see [USAGE].
+const EXIT_USAGE: u8 = 2;
+const EXIT_NOT_FOUND: u8 = 3;
+const EXIT_INVALID_INPUT: u8 = 4;
+
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Io(io::Error),
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        CliError::Io(error)
+    }
+}
+
+/// Positional arguments and `--name value` options of a command.
+struct Arguments {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Arguments {
+    fn parse(args: &[String], known_options: &[&str]) -> Result<Self, CliError> {
+        let mut arguments = Arguments {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                if !known_options.contains(&name) {
+                    return Err(CliError::Usage(format!("unknown option --{name}")));
+                }
+                let value = args.next().ok_or_else(|| CliError::Usage(format!("missing value of --{name}")))?;
+                arguments.options.push((name.to_string(), value.clone()));
+            } else {
+                arguments.positional.push(arg.clone());
+            }
+        }
+        Ok(arguments)
+    }
+
+    fn positional<const N: usize>(&self) -> Result<[&str; N], CliError> {
+        let positional: Vec<&str> = self.positional.iter().map(String::as_str).collect();
+        positional
+            .try_into()
+            .map_err(|positional: Vec<&str>| CliError::Usage(format!("expected {N} arguments, got {}", positional.len())))
+    }
+
+    fn option<T: FromStr>(&self, name: &str, default: T) -> Result<T, CliError> {
+        Ok(self.optional(name)?.unwrap_or(default))
+    }
+
+    /// Whether `--format json` was given.
+    fn json(&self) -> Result<bool, CliError> {
+        match self.option("format", "text".to_string())?.as_str() {
+            "text" => Ok(false),
+            "json" => Ok(true),
+            format => Err(CliError::Usage(format!("unknown format {format}"))),
+        }
+    }
+
+    /// An option without a default.
+    fn optional<T: FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        match self.options.iter().rev().find(|(option, _)| option == name) {
+            Some((_, value)) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| CliError::Usage(format!("invalid value of --{name}: {value}"))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Parses comma separated coordinates like `1,2.5,-3`.
+fn parse_vec3(name: &str, value: &str) -> Result<Vec3, CliError> {
+    let coordinates: Vec<f32> = value
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| CliError::Usage(format!("invalid value of --{name}: {value}")))?;
+    match coordinates[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(CliError::Usage(format!("--{name} needs three coordinates, got {value}"))),
+    }
+}
+
+fn load_scene(path: &str) -> Result<Scene, CliError> {
+    Ok(Scene::load(path).map_err(|error| io::Error::new(error.kind(), format!("{path}: {error}")))?)
+}
+
+/// Results of a command, printed as `name: value` lines or as a JSON object.
+struct Report(Vec<(&'static str, serde_json::Value)>);
+
+impl Report {
+    fn new() -> Self {
+        Report(Vec::new())
+    }
+
+    fn add(mut self, name: &'static str, value: impl Serialize) -> Self {
+        // Going through the text keeps the shortest representation of f32, which a direct conversion widens to f64
+        let text = serde_json::to_string(&value).expect("Values of reports serialize");
+        self.0.push((name, serde_json::from_str(&text).expect("Values of reports serialize")));
+        self
+    }
+
+    fn print(&self, json: bool) {
+        if json {
+            println!("{}", serde_json::to_string_pretty(self).expect("Reports serialize"));
+            return;
+        }
+        let width = self.0.iter().map(|(name, _)| name.len()).max().unwrap_or(0) + 1;
+        for (name, value) in &self.0 {
+            // Lists of plain values read better without brackets and quotes
+            let text = match value {
+                serde_json::Value::String(text) => text.clone(),
+                serde_json::Value::Array(values) if values.iter().all(|value| !value.is_array() && !value.is_object()) => values
+                    .iter()
+                    .map(|value| value.as_str().map_or_else(|| value.to_string(), str::to_string))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                value => value.to_string(),
+            };
+            println!("{:width$} {text}", format!("{name}:"));
+        }
+    }
+}
+
+impl Serialize for Report {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+fn inspect(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["format"])?;
+    let [input] = arguments.positional()?;
+    let json = arguments.json()?;
+    let mut report = Report::new().add("path", input);
+    match SceneFormat::of_path(input) {
+        Some(SceneFormat::Ply) => {
+            let file = File::open(input).map_err(|error| io::Error::new(error.kind(), format!("{input}: {error}")))?;
+            let header = ply_rs::parser::Parser::<ply_rs::ply::DefaultElement>::new()
+                .read_header(&mut BufReader::new(file))
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("{input}: {error}")))?;
+            let elements: Vec<String> = header
+                .elements
+                .values()
+                .map(|element| format!("{} {}", element.name, element.count))
+                .collect();
+            let properties: Vec<String> = header
+                .elements
+                .get("vertex")
+                .map(|vertex| {
+                    vertex
+                        .properties
+                        .values()
+                        .map(|property| format!("{} {}", property_type_name(&property.data_type), property.name))
+                        .collect()
+                })
+                .unwrap_or_default();
+            report = report
+                .add("format", "ply")
+                .add("encoding", format!("{:?}", header.encoding).to_lowercase())
+                .add("elements", elements)
+                .add("properties", properties);
+        }
+        Some(SceneFormat::Splat) => {
+            report = report.add("format", "splat").add(
+                "properties",
+                [
+                    "center[3]",
+                    "color[4]",
+                    "depth",
+                    "scale[2]",
+                    "normal[3]",
+                    "ellipse_basis[3]",
+                    "model_matrix[16]",
+                ]
+                .map(|name| format!("float {name}")),
+            );
+        }
+        None => {}
+    }
+    let scene = load_scene(input)?;
+    let bounds = Bounds::of_centers(&scene.splat_data);
+    report
+        .add("file_bytes", std::fs::metadata(input)?.len())
+        .add("splat_count", scene.splat_count)
+        .add("spherical_harmonics_order", scene.spherical_harmonics_order)
+        .add("bounds_min", bounds.map(|bounds| bounds.min))
+        .add("bounds_max", bounds.map(|bounds| bounds.max))
+        .print(json);
+    Ok(())
+}
+
+/// Names of the types of PLY properties as in the header.
+fn property_type_name(data_type: &ply_rs::ply::PropertyType) -> String {
+    match data_type {
+        ply_rs::ply::PropertyType::Scalar(scalar) => format!("{scalar:?}").to_lowercase(),
+        ply_rs::ply::PropertyType::List(index, scalar) => format!("list {index:?} {scalar:?}").to_lowercase(),
+    }
+}
+
+fn convert(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["tile-size", "format"])?;
+    let [input, output] = arguments.positional()?;
+    let json = arguments.json()?;
+    let tiles = Path::new(output)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("tiles"));
+    if !tiles && SceneFormat::of_path(output).is_none() {
+        return Err(CliError::Usage(format!("unknown format of {output}, expected .ply, .splat or .tiles")));
+    }
+    let tile_size: f32 = arguments.option("tile-size", 10.0)?;
+    if tile_size <= 0.0 {
+        return Err(CliError::Usage("--tile-size has to be positive".to_string()));
+    }
+    let scene = load_scene(input)?;
+    let mut report = Report::new()
+        .add("input", input)
+        .add("output", output)
+        .add("splat_count", scene.splat_count);
+    if tiles {
+        report = report.add("tiles", write_tiled(&scene, tile_size, output)?.tiles.len());
+    } else {
+        scene.save(output)?;
+        report = report.add("spherical_harmonics_order", scene.spherical_harmonics_order);
+    }
+    report.add("file_bytes", std::fs::metadata(output)?.len()).print(json);
+    Ok(())
+}
+
+fn crop(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["min", "max", "format"])?;
+    let [input, output] = arguments.positional()?;
+    let json = arguments.json()?;
+    let required = |name: &str| {
+        let value = arguments
+            .optional::<String>(name)?
+            .ok_or_else(|| CliError::Usage(format!("missing --{name}")))?;
+        parse_vec3(name, &value)
+    };
+    let (min, max) = (required("min")?, required("max")?);
+    if min.cmpgt(max).any() {
+        return Err(CliError::Usage("--min has to be below --max".to_string()));
+    }
+    let mut scene = load_scene(input)?;
+    let removed = scene.retain(|_index, splat| {
+        let center = Vec3::from(splat.center);
+        center.cmpge(min).all() && center.cmple(max).all()
+    });
+    scene.save(output)?;
+    Report::new()
+        .add("input", input)
+        .add("output", output)
+        .add("kept", scene.splat_count)
+        .add("removed", removed)
+        .print(json);
+    Ok(())
+}
+
+fn decimate_scene(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["count", "importance", "mode", "views", "format"])?;
+    let [input, output] = arguments.positional()?;
+    let json = arguments.json()?;
+    let defaults = DecimationSettings::default();
+    let settings = DecimationSettings {
+        target_count: arguments
+            .optional("count")?
+            .ok_or_else(|| CliError::Usage("missing --count".to_string()))?,
+        importance: match arguments.option("importance", "opacity-area".to_string())?.as_str() {
+            "opacity-area" => Importance::OpacityArea,
+            "contribution" => Importance::Contribution,
+            importance => return Err(CliError::Usage(format!("unknown importance {importance}"))),
+        },
+        mode: match arguments.option("mode", "drop".to_string())?.as_str() {
+            "drop" => DecimationMode::Drop,
+            "merge" => DecimationMode::Merge,
+            mode => return Err(CliError::Usage(format!("unknown mode {mode}"))),
+        },
+        view_count: arguments.option("views", defaults.view_count)?,
+        ..defaults
+    };
+    if settings.importance == Importance::Contribution && settings.view_count == 0 {
+        return Err(CliError::Usage("--importance contribution needs --views".to_string()));
+    }
+    let mut scene = load_scene(input)?;
+    let report = decimate_with_report(&mut scene, &settings);
+    scene.save(output)?;
+    Report::new()
+        .add("input", input)
+        .add("output", output)
+        .add("kept", report.output_count)
+        .add("removed", report.removed())
+        .add("merged", report.merged)
+        .add("psnr", report.quality.map(|quality| quality.psnr))
+        .add("ssim", report.quality.map(|quality| quality.ssim))
+        .print(json);
+    Ok(())
+}
+
+fn render_view(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["position", "target", "fov", "width", "height", "format"])?;
+    let [input, output] = arguments.positional()?;
+    let json = arguments.json()?;
+    let fov: f32 = arguments.option("fov", 60.0)?;
+    let width: u32 = arguments.option("width", 1280)?;
+    let height: u32 = arguments.option("height", 720)?;
+    if width == 0 || height == 0 || !(fov > 0.0 && fov < 180.0) {
+        return Err(CliError::Usage(
+            "--width and --height have to be positive and --fov between 0 and 180".to_string(),
+        ));
+    }
+    let position = arguments
+        .optional::<String>("position")?
+        .map(|value| parse_vec3("position", &value))
+        .transpose()?;
+    let target = arguments
+        .optional::<String>("target")?
+        .map(|value| parse_vec3("target", &value))
+        .transpose()?;
+    let scene = load_scene(input)?;
+    // Frames the sphere around the bounds of the scene
+    let bounds = Bounds::of_centers(&scene.splat_data).unwrap_or(Bounds {
+        min: Vec3::ZERO,
+        max: Vec3::ZERO,
+    });
+    let target = target.unwrap_or((bounds.min + bounds.max) * 0.5);
+    let radius = (bounds.size().length() * 0.5).max(1.0e-3);
+    let fov_y = fov.to_radians();
+    let position = position.unwrap_or(target + Vec3::Z * radius / (fov_y * 0.5).sin());
+    if position == target {
+        return Err(CliError::Usage("--position has to differ from --target".to_string()));
+    }
+    let forward = (target - position).normalize();
+    let up = if forward.cross(Vec3::Y).length_squared() < 1.0e-6 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    let view = Mat4::look_at_rh(position, target, up);
+    let z_far = position.distance(target) + 2.0 * radius;
+    let camera = Camera::perspective(view, fov_y, width as f32 / height as f32, z_far * 1.0e-4, z_far);
+    let start = Instant::now();
+    let image = render_scene(&scene, &camera, width, height, &CpuRenderSettings::default());
+    let render_seconds = start.elapsed().as_secs_f64();
+    image.save_png(output)?;
+    Report::new()
+        .add("input", input)
+        .add("output", output)
+        .add("splat_count", scene.splat_count)
+        .add("width", width)
+        .add("height", height)
+        .add("position", position)
+        .add("target", target)
+        .add("render_ms", render_seconds * 1000.0)
+        .print(json);
+    Ok(())
+}
+
+fn cleanup(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["neighbors", "std-ratio", "floater-scale-ratio", "floater-opacity", "format"])?;
+    let [input, output] = arguments.positional()?;
+    let json = arguments.json()?;
+    if SceneFormat::of_path(output).is_none() {
+        return Err(CliError::Usage(format!("unknown format of {output}, expected .ply or .splat")));
+    }
+    let defaults = CleanupSettings::default();
+    let settings = CleanupSettings {
+        neighbor_count: arguments.option("neighbors", defaults.neighbor_count)?,
+        std_ratio: arguments.option("std-ratio", defaults.std_ratio)?,
+        floater_scale_ratio: arguments.option("floater-scale-ratio", defaults.floater_scale_ratio)?,
+        floater_opacity: arguments.option("floater-opacity", defaults.floater_opacity)?,
+    };
+    let mut scene = load_scene(input)?;
+    let report = remove_outliers(&mut scene, &settings);
+    scene.save(output)?;
+    Report::new()
+        .add("input", input)
+        .add("output", output)
+        .add("splats", report.input_count)
+        .add("outliers", report.outliers)
+        .add("floaters", report.floaters)
+        .add("remaining", report.input_count - report.removed())
+        .add("mean_neighbor_distance", report.mean_neighbor_distance)
+        .add("neighbor_distance_std", report.neighbor_distance_std)
+        .add("distance_threshold", report.distance_threshold)
+        .add("scale_threshold", report.scale_threshold)
+        .print(json);
+    Ok(())
+}
+
+fn lod(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["output", "branching", "format"])?;
+    let [input] = arguments.positional()?;
+    let json = arguments.json()?;
+    let output = arguments.option("output", LodTree::path_for_scene(input).to_string_lossy().into_owned())?;
+    let defaults = LodBuildSettings::default();
+    let settings = LodBuildSettings {
+        branching: arguments.option("branching", defaults.branching)?,
+        ..defaults
+    };
+    let scene = load_scene(input)?;
+    let tree = LodTree::build(&scene, &settings);
+    tree.save(&output)?;
+    Report::new()
+        .add("input", input)
+        .add("output", &output)
+        .add("splats", tree.leaf_count)
+        .add("merged", tree.nodes.len() - tree.leaf_count)
+        .add("roots", tree.roots.len())
+        .print(json);
+    Ok(())
+}
+
+fn tile(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["tile-size", "format"])?;
+    let [input, output] = arguments.positional()?;
+    let json = arguments.json()?;
+    let tile_size: f32 = arguments.option("tile-size", 10.0)?;
+    if tile_size <= 0.0 {
+        return Err(CliError::Usage("--tile-size has to be positive".to_string()));
+    }
+    let scene = load_scene(input)?;
+    let index = write_tiled(&scene, tile_size, output)?;
+    let largest = index.tiles.iter().map(|tile| tile.splat_count).max().unwrap_or(0);
+    Report::new()
+        .add("input", input)
+        .add("output", output)
+        .add("splats", scene.splat_count)
+        .add("tiles", index.tiles.len())
+        .add("largest_tile_splats", largest)
+        .print(json);
+    Ok(())
+}
+
+fn panorama(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["position", "width", "format"])?;
+    let [input, output] = arguments.positional()?;
+    let json = arguments.json()?;
+    let position = parse_vec3("position", &arguments.option("position", "0,0,0".to_string())?)?;
+    let width: u32 = arguments.option("width", 2048)?;
+    if width < 4 {
+        return Err(CliError::Usage("--width has to be at least 4".to_string()));
+    }
+    let scene = load_scene(input)?;
+    let image = render_equirectangular(&scene, position, width, &CpuRenderSettings::default());
+    image.save_png(output)?;
+    Report::new()
+        .add("input", input)
+        .add("output", output)
+        .add("splats", scene.splat_count)
+        .add("width", image.width)
+        .add("height", image.height)
+        .print(json);
+    Ok(())
+}
+
+fn flythrough(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["fps", "width", "height", "format"])?;
+    let [input, path, output] = arguments.positional()?;
+    let json = arguments.json()?;
+    let frame_rate: f32 = arguments.option("fps", 30.0)?;
+    let width: u32 = arguments.option("width", 640)?;
+    let height: u32 = arguments.option("height", 360)?;
+    if !(frame_rate > 0.0 && frame_rate.is_finite()) || width == 0 || height == 0 {
+        return Err(CliError::Usage("--fps has to be positive and finite, --width and --height positive".to_string()));
+    }
+    let mut scene = load_scene(input)?;
+    // Every frame culls whole chunks, most of which are out of view
+    let layout = sort_into_chunks(&mut scene, CHUNK_SIZE);
+    let camera_path = CameraPath::load(path).map_err(|error| io::Error::new(error.kind(), format!("{path}: {error}")))?;
+    std::fs::create_dir_all(output)?;
+    let settings = CpuRenderSettings::default();
+    let mut render_seconds = 0.0;
+    let mut frame_count = 0;
+    for (frame, pose) in camera_path.frames(frame_rate).enumerate() {
+        let camera = pose.camera(width as f32 / height as f32, 0.1, 1000.0);
+        let start = Instant::now();
+        let image = render_chunks(&scene, &layout, &camera, width, height, &settings);
+        render_seconds += start.elapsed().as_secs_f64();
+        image.save_png(Path::new(output).join(format!("frame_{frame:05}.png")))?;
+        frame_count += 1;
+    }
+    Report::new()
+        .add("input", input)
+        .add("output", output)
+        .add("splats", scene.splat_count)
+        .add("frames", frame_count)
+        .add("milliseconds_per_frame", (frame_count > 0).then(|| render_seconds * 1000.0 / frame_count as f64))
+        .print(json);
+    Ok(())
+}
+
+fn evaluate_views(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["report", "perceptual", "format"])?;
+    let [input, cameras, images] = arguments.positional()?;
+    let json = arguments.json()?;
+    let settings = EvaluationSettings {
+        perceptual: arguments.option("perceptual", true)?,
+        ..EvaluationSettings::default()
+    };
+    let scene = load_scene(input)?;
+    let cameras = read_training_cameras(cameras).map_err(|error| io::Error::new(error.kind(), format!("{cameras}: {error}")))?;
+    let report = evaluate(&scene, &cameras, images, &settings)?;
+    let saved = arguments.optional::<String>("report")?;
+    if let Some(path) = &saved {
+        report.save(path)?;
+    }
+    if json {
+        Report::new()
+            .add("input", input)
+            .add("views", &report.views)
+            .add("mean", report.mean)
+            .add("missing", &report.missing)
+            .add("report", &saved)
+            .print(true);
+    } else {
+        println!("{report}");
+        if let Some(path) = saved {
+            println!("saved:  {path}");
+        }
+    }
+    if report.views.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("no photos of the training views in {images}")).into());
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct StatsReport {
+    path: String,
+    statistics: SceneStatistics,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compared: Option<ComparedScene>,
+}
+
+#[derive(Serialize)]
+struct ComparedScene {
+    path: String,
+    statistics: SceneStatistics,
+    diff: DiffSummary,
+}
+
+fn stats(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["compare", "search-radius", "moved-distance", "format"])?;
+    let [input] = arguments.positional()?;
+    let json = arguments.json()?;
+    let diff_settings = DiffSettings {
+        search_radius: arguments.optional("search-radius")?,
+        moved_distance: arguments.option("moved-distance", DiffSettings::default().moved_distance)?,
+    };
+    let scene = load_scene(input)?;
+    let settings = StatisticsSettings::default();
+    let mut report = StatsReport {
+        path: input.to_string(),
+        statistics: SceneStatistics::of(&scene, &settings),
+        compared: None,
+    };
+    if let Some(path) = arguments.optional::<String>("compare")? {
+        let other = load_scene(&path)?;
+        report.compared = Some(ComparedScene {
+            statistics: SceneStatistics::of(&other, &settings),
+            diff: diff(&scene, &other, &diff_settings).summary(),
+            path,
+        });
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).map_err(io::Error::from)?);
+    } else {
+        println!("{}\n{}", report.path, report.statistics);
+        if let Some(compared) = &report.compared {
+            println!(
+                "\n{}\n{}\n\n{} -> {}\n{}",
+                compared.path, compared.statistics, report.path, compared.path, compared.diff
+            );
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("inspect") => inspect(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("crop") => crop(&args[1..]),
+        Some("decimate") => decimate_scene(&args[1..]),
+        Some("render") => render_view(&args[1..]),
+        Some("cleanup") => cleanup(&args[1..]),
+        Some("lod") => lod(&args[1..]),
+        Some("tile") => tile(&args[1..]),
+        Some("panorama") => panorama(&args[1..]),
+        Some("flythrough") => flythrough(&args[1..]),
+        Some("evaluate") => evaluate_views(&args[1..]),
+        Some("stats") => stats(&args[1..]),
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => Err(CliError::Usage(format!("unknown command {command}"))),
+        None => Err(CliError::Usage("missing command".to_string())),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage(message)) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            ExitCode::from(EXIT_USAGE)
+        }
+        Err(CliError::Io(error)) => {
+            eprintln!("error: {error}");
+            match error.kind() {
+                io::ErrorKind::NotFound => ExitCode::from(EXIT_NOT_FOUND),
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof | io::ErrorKind::Unsupported => ExitCode::from(EXIT_INVALID_INPUT),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
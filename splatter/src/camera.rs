@@ -0,0 +1,176 @@
+//! Cameras of the splat renderer, taken from the Bevy cameras
+//!
+//! Every frame after the transforms are propagated, the view and projection of each active [Camera3d] are converted into a
+//! [scene::Camera](Camera) together with its viewport and extracted into the render world as [SplatViews].
+//! The first view by render order also becomes the camera of the [Scene], which picking and the renderer use.
+//! Bevy has no fisheye projection, perspective cameras with a [SplatFisheye] component are rendered as one instead.
+
+use crate::scene::{Camera, ProjectionModel, Scene};
+use bevy::prelude::{
+    App, Camera as BevyCamera, Camera3d, Commands, Component, Entity, GlobalTransform, IntoSystemConfigs, Plugin, PostUpdate, Projection, Query, Res,
+    ResMut, Resource, With,
+};
+use bevy::render::camera::{CameraProjection, CameraUpdateSystem};
+use bevy::render::{Extract, ExtractSchedule, RenderApp};
+use bevy::transform::TransformSystem;
+use glam::{Mat4, UVec2, Vec2};
+
+impl Camera {
+    pub fn perspective(view: Mat4, fov_y: f32, aspect_ratio: f32, z_near: f32, z_far: f32) -> Self {
+        Self {
+            projection: Mat4::perspective_rh_gl(fov_y, aspect_ratio, z_near, z_far),
+            view,
+            z_near,
+            z_far,
+            model: ProjectionModel::Perspective,
+        }
+    }
+
+    /// Orthographic camera seeing the view space box from `min` to `max` in x and y.
+    pub fn orthographic(view: Mat4, min: Vec2, max: Vec2, z_near: f32, z_far: f32) -> Self {
+        Self {
+            projection: Mat4::orthographic_rh_gl(min.x, max.x, min.y, max.y, z_near, z_far),
+            view,
+            z_near,
+            z_far,
+            model: ProjectionModel::Orthographic,
+        }
+    }
+
+    /// Converts a Bevy camera whose viewport has the given size.
+    ///
+    /// The aspect ratio comes from the viewport instead of the projection, which Bevy only updates when the window resizes.
+    pub fn from_bevy(transform: &GlobalTransform, projection: &Projection, viewport_size: Vec2) -> Self {
+        let view = Mat4::from_cols_array(&transform.compute_matrix().to_cols_array()).inverse();
+        match projection {
+            Projection::Perspective(perspective) => {
+                let aspect_ratio = viewport_size.x / viewport_size.y.max(1.0);
+                Self::perspective(view, perspective.fov, aspect_ratio, perspective.near, perspective.far)
+            }
+            Projection::Orthographic(orthographic) => {
+                let mut orthographic = orthographic.clone();
+                orthographic.update(viewport_size.x, viewport_size.y);
+                let area = orthographic.area;
+                Self::orthographic(
+                    view,
+                    Vec2::from(area.min.to_array()),
+                    Vec2::from(area.max.to_array()),
+                    orthographic.near,
+                    orthographic.far,
+                )
+            }
+        }
+    }
+}
+
+/// Renders the splats of a perspective camera with an equidistant fisheye instead.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SplatFisheye {
+    /// Angle in radians seen from the top to the bottom edge of the viewport, which may exceed a half turn.
+    pub fov: f32,
+}
+
+/// A camera rendering splats into a part of its target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplatView {
+    pub entity: Entity,
+    pub order: isize,
+    pub camera: Camera,
+    /// Upper left corner of the viewport in physical pixels.
+    pub viewport_position: UVec2,
+    /// Size of the viewport in physical pixels.
+    pub viewport_size: UVec2,
+}
+
+/// The views of all active cameras in ascending render order.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct SplatViews {
+    pub views: Vec<SplatView>,
+}
+
+impl SplatViews {
+    /// The view rendered first, which drives picking and the single camera of the [Scene].
+    pub fn primary(&self) -> Option<&SplatView> {
+        self.views.first()
+    }
+}
+
+pub struct SplatCameraPlugin;
+
+impl Plugin for SplatCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SplatViews>().add_systems(
+            PostUpdate,
+            sync_splat_cameras.after(TransformSystem::TransformPropagate).after(CameraUpdateSystem),
+        );
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<SplatViews>().add_systems(ExtractSchedule, extract_splat_views);
+        }
+    }
+}
+
+type BevyCameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static BevyCamera,
+        &'static Projection,
+        &'static GlobalTransform,
+        Option<&'static SplatFisheye>,
+    ),
+    With<Camera3d>,
+>;
+
+fn sync_splat_cameras(mut splat_views: ResMut<SplatViews>, scene: Option<ResMut<Scene>>, mut scenes: Query<&mut Scene>, cameras: BevyCameraQuery) {
+    let mut views: Vec<SplatView> = cameras
+        .iter()
+        .filter(|(_, camera, _, _, _)| camera.is_active)
+        .filter_map(|(entity, camera, projection, transform, fisheye)| {
+            let viewport = camera.physical_viewport_rect()?;
+            let viewport_size = UVec2::from(viewport.size().to_array());
+            if viewport_size.x == 0 || viewport_size.y == 0 {
+                return None;
+            }
+            // Scaling modes in window units refer to logical pixels
+            let logical_size = camera
+                .logical_viewport_size()
+                .map(|size| Vec2::from(size.to_array()))
+                .unwrap_or(viewport_size.as_vec2());
+            let mut splat_camera = Camera::from_bevy(transform, projection, logical_size);
+            if let (Some(fisheye), ProjectionModel::Perspective) = (fisheye, splat_camera.model) {
+                let aspect_ratio = logical_size.x / logical_size.y.max(1.0);
+                splat_camera = Camera::fisheye(splat_camera.view, fisheye.fov, aspect_ratio, splat_camera.z_near, splat_camera.z_far);
+            }
+            Some(SplatView {
+                entity,
+                order: camera.order,
+                camera: splat_camera,
+                viewport_position: UVec2::from(viewport.min.to_array()),
+                viewport_size,
+            })
+        })
+        .collect();
+    views.sort_by_key(|view| view.order);
+    if splat_views.views != views {
+        splat_views.views = views;
+    }
+
+    let Some(primary) = splat_views.primary() else {
+        return;
+    };
+    if let Some(mut scene) = scene {
+        if scene.camera != primary.camera {
+            scene.camera = primary.camera;
+        }
+    }
+    for mut scene in scenes.iter_mut() {
+        if scene.camera != primary.camera {
+            scene.camera = primary.camera;
+        }
+    }
+}
+
+fn extract_splat_views(mut commands: Commands, splat_views: Extract<Res<SplatViews>>) {
+    commands.insert_resource(splat_views.clone());
+}
@@ -0,0 +1,263 @@
+//! Camera paths for repeatable flythroughs
+//!
+//! A path is a list of poses with timestamps, either recorded from a moving camera or placed as keyframes by hand.
+//! Positions are interpolated with a Catmull-Rom spline, whose tangents take the spacing in time into account,
+//! and orientations with spherical linear interpolation. Paths are saved as JSON or, by file extension, as RON.
+
+use crate::scene::Camera;
+use bevy::prelude::{App, Component, GlobalTransform, Plugin, Projection, Query, Res, Time, Transform, Update};
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn default_fov_y() -> f32 {
+    std::f32::consts::FRAC_PI_4
+}
+
+/// Where the camera is at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraPose {
+    /// Seconds since the start of the path.
+    pub time: f32,
+    pub position: Vec3,
+    pub rotation: Quat,
+    /// Vertical field of view in radians.
+    #[serde(default = "default_fov_y")]
+    pub fov_y: f32,
+}
+
+impl CameraPose {
+    pub fn view(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.rotation, self.position).inverse()
+    }
+
+    pub fn camera(&self, aspect_ratio: f32, z_near: f32, z_far: f32) -> Camera {
+        Camera::perspective(self.view(), self.fov_y, aspect_ratio, z_near, z_far)
+    }
+
+    pub fn from_transform(time: f32, transform: &GlobalTransform, fov_y: f32) -> Self {
+        let (_scale, rotation, translation) = transform.to_scale_rotation_translation();
+        Self {
+            time,
+            position: Vec3::from(translation.to_array()),
+            rotation: Quat::from_array(rotation.to_array()),
+            fov_y,
+        }
+    }
+
+    pub fn to_transform(&self) -> Transform {
+        Transform::from_translation(bevy::math::Vec3::from_array(self.position.to_array()))
+            .with_rotation(bevy::math::Quat::from_array(self.rotation.to_array()))
+    }
+}
+
+/// Poses in ascending order of time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraPose>,
+}
+
+impl CameraPath {
+    /// Places a keyframe, replacing one at the same time.
+    pub fn insert(&mut self, pose: CameraPose) {
+        let index = self.keyframes.partition_point(|keyframe| keyframe.time < pose.time);
+        match self.keyframes.get_mut(index) {
+            Some(keyframe) if keyframe.time == pose.time => *keyframe = pose,
+            _ => self.keyframes.insert(index, pose),
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    /// Tangent of the position spline at a keyframe, from its neighbors.
+    fn tangent(&self, index: usize) -> Vec3 {
+        let previous = &self.keyframes[index.saturating_sub(1)];
+        let next = &self.keyframes[(index + 1).min(self.keyframes.len() - 1)];
+        let duration = next.time - previous.time;
+        if duration <= 0.0 {
+            return Vec3::ZERO;
+        }
+        (next.position - previous.position) / duration
+    }
+
+    /// The interpolated pose at `time`, which is clamped to the path. None for an empty path or a time which is not finite.
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        if !time.is_finite() {
+            return None;
+        }
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some(CameraPose { time, ..*first });
+        }
+        if time >= last.time {
+            return Some(CameraPose { time, ..*last });
+        }
+        let index = self.keyframes.partition_point(|keyframe| keyframe.time <= time) - 1;
+        let (start, end) = (&self.keyframes[index], &self.keyframes[index + 1]);
+        let duration = end.time - start.time;
+        let s = (time - start.time) / duration;
+        // Cubic Hermite basis
+        let s2 = s * s;
+        let s3 = s2 * s;
+        let position = start.position * (2.0 * s3 - 3.0 * s2 + 1.0)
+            + self.tangent(index) * (duration * (s3 - 2.0 * s2 + s))
+            + end.position * (-2.0 * s3 + 3.0 * s2)
+            + self.tangent(index + 1) * (duration * (s3 - s2));
+        Some(CameraPose {
+            time,
+            position,
+            rotation: start.rotation.slerp(end.rotation, s),
+            fov_y: start.fov_y + (end.fov_y - start.fov_y) * s,
+        })
+    }
+
+    /// Poses at a fixed frame rate from the first to the last keyframe, none unless the frame rate is positive and finite.
+    pub fn frames(&self, frame_rate: f32) -> impl Iterator<Item = CameraPose> + '_ {
+        let start = self.keyframes.first().map_or(0.0, |keyframe| keyframe.time);
+        let frame_count = if self.keyframes.is_empty() || !(frame_rate > 0.0 && frame_rate.is_finite()) {
+            0
+        } else {
+            (self.duration() * frame_rate).floor() as usize + 1
+        };
+        (0..frame_count).filter_map(move |frame| self.sample(start + frame as f32 / frame_rate))
+    }
+
+    /// Loads a path from RON if the extension is `ron` and from JSON otherwise.
+    ///
+    /// The keyframes are sorted by time, of keyframes at the same time the last one is kept, see [CameraPath::insert].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let loaded: Self = if is_ron(path) {
+            ron::from_str(&text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?
+        } else {
+            serde_json::from_str(&text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?
+        };
+        if loaded.keyframes.iter().any(|keyframe| !keyframe.time.is_finite()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Keyframe time is not finite"));
+        }
+        let mut sorted = Self::default();
+        for keyframe in loaded.keyframes {
+            sorted.insert(keyframe);
+        }
+        Ok(sorted)
+    }
+
+    /// Saves the path as RON if the extension is `ron` and as JSON otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let text = if is_ron(path) {
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?
+        } else {
+            serde_json::to_string_pretty(self)?
+        };
+        fs::write(path, text)
+    }
+}
+
+fn is_ron(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ron"))
+}
+
+/// Records the pose of this camera while present.
+#[derive(Component, Debug, Clone, Default)]
+pub struct CameraPathRecorder {
+    pub path: CameraPath,
+    /// Seconds between recorded poses, zero records every frame.
+    pub interval: f32,
+    elapsed: f32,
+    next_pose: f32,
+}
+
+impl CameraPathRecorder {
+    pub fn new(interval: f32) -> Self {
+        Self {
+            interval,
+            ..Self::default()
+        }
+    }
+
+    /// Seconds since recording started.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+}
+
+/// Moves this camera along a path.
+#[derive(Component, Debug, Clone)]
+pub struct CameraPathPlayback {
+    pub path: CameraPath,
+    /// Position on the path relative to its first keyframe.
+    pub time: f32,
+    /// Multiplies the passing of time, zero pauses.
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl CameraPathPlayback {
+    pub fn new(path: CameraPath) -> Self {
+        Self {
+            path,
+            time: 0.0,
+            speed: 1.0,
+            looping: false,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        !self.looping && self.time >= self.path.duration()
+    }
+}
+
+pub struct CameraPathPlugin;
+
+impl Plugin for CameraPathPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (record_camera_paths, play_camera_paths));
+    }
+}
+
+fn record_camera_paths(time: Res<Time>, mut recorders: Query<(&mut CameraPathRecorder, &GlobalTransform, Option<&Projection>)>) {
+    for (mut recorder, transform, projection) in recorders.iter_mut() {
+        if recorder.elapsed >= recorder.next_pose {
+            let fov_y = match projection {
+                Some(Projection::Perspective(perspective)) => perspective.fov,
+                _ => default_fov_y(),
+            };
+            let pose = CameraPose::from_transform(recorder.elapsed, transform, fov_y);
+            recorder.path.insert(pose);
+            recorder.next_pose = recorder.elapsed + recorder.interval;
+        }
+        recorder.elapsed += time.delta_seconds();
+    }
+}
+
+fn play_camera_paths(time: Res<Time>, mut cameras: Query<(&mut CameraPathPlayback, &mut Transform, Option<&mut Projection>)>) {
+    for (mut playback, mut transform, projection) in cameras.iter_mut() {
+        let duration = playback.path.duration();
+        let start = playback.path.keyframes.first().map_or(0.0, |keyframe| keyframe.time);
+        let Some(pose) = playback.path.sample(start + playback.time) else {
+            continue;
+        };
+        *transform = pose.to_transform().with_scale(transform.scale);
+        if let Some(mut projection) = projection {
+            if let Projection::Perspective(perspective) = projection.as_mut() {
+                perspective.fov = pose.fov_y;
+            }
+        }
+        playback.time += time.delta_seconds() * playback.speed;
+        if playback.looping && duration > 0.0 {
+            playback.time = playback.time.rem_euclid(duration);
+        } else {
+            playback.time = playback.time.min(duration);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Spatially coherent chunks of splats
+//!
+//! Sorted along a Morton curve through their centers, consecutive splats lie close to each other, so that runs of
+//! [CHUNK_SIZE] of them form compact chunks. Every chunk has a bounding box and a bounding sphere around the spheres of
+//! three standard deviations of its splats, see [bounding_radius]. Whole chunks are culled against the frustum before
+//! any of their splats are looked at, and chunks which appear small on screen can be drawn as a single splat merged
+//! from all of theirs. Tiled files store the splats of every tile in Morton order, so that the resident splats of a
+//! [crate::streaming::StreamingManager] split into chunks without sorting them again.
+//!
+//! The merged proxies of chunks are separate from the level of detail hierarchy of [crate::lod], which is built offline
+//! over the splats of a scene in the order of its file. Sorting a scene into chunks reorders them, so its hierarchy
+//! has to be built again afterwards.
+
+use crate::culling::{bounding_radius, Containment, Frustum};
+use crate::lod::{merge_splats, projected_size};
+use crate::scene::{Camera, Scene, Splat};
+use glam::Vec3;
+use std::ops::Range;
+
+pub const CHUNK_SIZE: usize = 256;
+
+/// Bits per axis of [morton_code].
+const MORTON_BITS: u32 = 10;
+
+/// Interleaves the lowest ten bits of the cell coordinates, starting with x in the lowest bit.
+pub fn morton_code(cell: [u32; 3]) -> u32 {
+    let spread = |value: u32| {
+        let mut value = value & ((1 << MORTON_BITS) - 1);
+        value = (value | value << 16) & 0x0300_00ff;
+        value = (value | value << 8) & 0x0300_f00f;
+        value = (value | value << 4) & 0x030c_30c3;
+        (value | value << 2) & 0x0924_9249
+    };
+    spread(cell[0]) | spread(cell[1]) << 1 | spread(cell[2]) << 2
+}
+
+/// Indices of the centers sorted along a Morton curve through a grid of 1024³ cells over their bounds.
+/// Centers in the same cell keep their order.
+pub fn morton_order(centers: &[Vec3]) -> Vec<u32> {
+    let (min, max) = centers
+        .iter()
+        .fold((Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)), |(min, max), center| {
+            (min.min(*center), max.max(*center))
+        });
+    let last_cell = ((1 << MORTON_BITS) - 1) as f32;
+    let scale = last_cell / (max - min).max(Vec3::splat(f32::MIN_POSITIVE));
+    let mut keys: Vec<(u32, u32)> = centers
+        .iter()
+        .enumerate()
+        .map(|(index, center)| {
+            let cell = ((*center - min) * scale).clamp(Vec3::ZERO, Vec3::splat(last_cell)).as_uvec3();
+            (morton_code(cell.to_array()), index as u32)
+        })
+        .collect();
+    keys.sort_unstable();
+    keys.into_iter().map(|(_code, index)| index).collect()
+}
+
+/// Consecutive splats with their bounds.
+#[derive(Clone)]
+pub struct Chunk {
+    pub splats: Range<usize>,
+    /// Bounds of the spheres of all splats of the chunk.
+    pub min: Vec3,
+    pub max: Vec3,
+    /// Sphere around the center of the bounds which holds the spheres of all splats of the chunk.
+    pub center: Vec3,
+    pub radius: f32,
+    /// Merged from all splats of the chunk to stand in for them, see [merge_splats].
+    pub proxy: Splat,
+}
+
+impl Chunk {
+    /// `splats` are the ones of the chunk, the first of them at `first` in the scene.
+    fn new(splats: &[Splat], first: usize) -> Self {
+        let spheres = splats.iter().map(|splat| (Vec3::from(splat.center), bounding_radius(splat)));
+        let (min, max) = spheres.clone().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), (center, radius)| (min.min(center - radius), max.max(center + radius)),
+        );
+        let center = (min + max) * 0.5;
+        let radius = spheres
+            .map(|(splat_center, radius)| center.distance(splat_center) + radius)
+            .fold(0.0, f32::max);
+        Self {
+            splats: first..first + splats.len(),
+            min,
+            max,
+            center,
+            radius,
+            proxy: merge_splats(splats.iter()),
+        }
+    }
+}
+
+/// What [ChunkLayout::select_detail] selected to draw.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChunkSelection {
+    /// Indices of the visible splats of the chunks which are drawn in full detail, in the order of the chunks.
+    pub splats: Vec<u32>,
+    /// Indices of the visible chunks which are drawn as their proxy.
+    pub proxies: Vec<usize>,
+}
+
+/// Partition of the splats of a scene into chunks.
+#[derive(Clone, Default)]
+pub struct ChunkLayout {
+    pub chunks: Vec<Chunk>,
+}
+
+impl ChunkLayout {
+    /// Chunks of up to `chunk_size` consecutive splats which never cross the boundaries of the `runs`.
+    /// To be compact the splats have to be in Morton order within every run, see [sort_into_chunks].
+    pub fn new(splats: &[Splat], runs: impl IntoIterator<Item = Range<usize>>, chunk_size: usize) -> Self {
+        let mut layout = Self::default();
+        for run in runs {
+            layout.push_run(&splats[run.clone()], run.start, chunk_size);
+        }
+        layout
+    }
+
+    /// Appends the chunks of another run of splats, the first of them at `first` in the scene.
+    pub fn push_run(&mut self, splats: &[Splat], first: usize, chunk_size: usize) {
+        for (index, chunk) in splats.chunks(chunk_size.max(1)).enumerate() {
+            self.chunks.push(Chunk::new(chunk, first + index * chunk_size.max(1)));
+        }
+    }
+
+    pub fn splat_count(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.splats.len()).sum()
+    }
+
+    /// Where every chunk is relative to the frustum. Both its box and its sphere have to reach into the frustum.
+    pub fn classify(&self, frustum: &Frustum) -> Vec<Containment> {
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                if frustum.contains_sphere(chunk.center, chunk.radius) {
+                    frustum.classify_box(chunk.min, chunk.max)
+                } else {
+                    Containment::Outside
+                }
+            })
+            .collect()
+    }
+
+    /// The same splats as [crate::culling::cull] in the order of the chunks. Only the splats of chunks which intersect
+    /// the frustum are tested one by one, the ones of chunks inside of it are all visible and the others none.
+    pub fn cull(&self, splats: &[Splat], camera: &Camera, tolerance: f32) -> Vec<u32> {
+        self.cull_frustum(splats, &Frustum::new(camera, tolerance))
+    }
+
+    /// Like [ChunkLayout::cull], with any frustum.
+    pub fn cull_frustum(&self, splats: &[Splat], frustum: &Frustum) -> Vec<u32> {
+        let mut visible = Vec::new();
+        for (chunk, containment) in self.chunks.iter().zip(self.classify(frustum)) {
+            push_visible(splats, chunk, containment, frustum, &mut visible);
+        }
+        visible
+    }
+
+    /// Culls like [ChunkLayout::cull], but draws visible chunks as their proxy if their bounding sphere appears
+    /// smaller than `pixel_threshold`, measured like the nodes of [crate::lod::LodTree::select_cut].
+    /// `focal_length` is in pixels.
+    pub fn select_detail(&self, splats: &[Splat], camera: &Camera, tolerance: f32, focal_length: f32, pixel_threshold: f32) -> ChunkSelection {
+        let frustum = Frustum::new(camera, tolerance);
+        let camera_position = camera.view.inverse().w_axis.truncate();
+        let mut selection = ChunkSelection::default();
+        for (index, (chunk, containment)) in self.chunks.iter().zip(self.classify(&frustum)).enumerate() {
+            if containment != Containment::Outside && projected_size(chunk.center, chunk.radius, camera_position, focal_length) < pixel_threshold {
+                selection.proxies.push(index);
+            } else {
+                push_visible(splats, chunk, containment, &frustum, &mut selection.splats);
+            }
+        }
+        selection
+    }
+}
+
+/// Appends the visible splats of the chunk.
+fn push_visible(splats: &[Splat], chunk: &Chunk, containment: Containment, frustum: &Frustum, visible: &mut Vec<u32>) {
+    match containment {
+        Containment::Outside => {}
+        Containment::Inside => visible.extend(chunk.splats.start as u32..chunk.splats.end as u32),
+        Containment::Intersecting => visible.extend(
+            chunk
+                .splats
+                .clone()
+                .filter(|index| frustum.contains_splat(&splats[*index]))
+                .map(|index| index as u32),
+        ),
+    }
+}
+
+/// The runs of splats which chunks must not cross: The sources of the scene and the splats before, between and after
+/// them which no source covers, like the ones appended without a source.
+fn source_runs(scene: &Scene) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for source in &scene.sources {
+        if source.splats.start > start {
+            runs.push(start..source.splats.start);
+        }
+        runs.push(source.splats.clone());
+        start = source.splats.end;
+    }
+    if start < scene.splat_data.len() || runs.is_empty() {
+        runs.push(start..scene.splat_data.len());
+    }
+    runs
+}
+
+/// Sorts the splats of every source of the scene in Morton order and splits them into chunks of `chunk_size`.
+pub fn sort_into_chunks(scene: &mut Scene, chunk_size: usize) -> ChunkLayout {
+    let runs = source_runs(scene);
+    let mut order = Vec::with_capacity(scene.splat_data.len());
+    for run in &runs {
+        let centers: Vec<Vec3> = scene.splat_data[run.clone()].iter().map(|splat| Vec3::from(splat.center)).collect();
+        order.extend(morton_order(&centers).into_iter().map(|index| run.start as u32 + index));
+    }
+    scene.permute(&order);
+    ChunkLayout::new(&scene.splat_data, runs, chunk_size)
+}
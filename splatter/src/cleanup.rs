@@ -0,0 +1,132 @@
+//! Removal of outliers and floaters
+//!
+//! Outliers are splats which are far away from their nearest neighbors compared to the rest of the scene.
+//! Floaters are large and faint splats, which optimization tends to leave hanging in the air in front of the cameras.
+
+use crate::scene::Scene;
+use crate::spatial::PointGrid;
+use crate::splat_edit::{SelectionMode, SplatEdit};
+use glam::Vec3;
+use std::fmt;
+
+/// Parameters of [select_outliers] and [remove_outliers].
+#[derive(Debug, Clone)]
+pub struct CleanupSettings {
+    /// Number of nearest neighbors whose mean distance is compared, zero disables the statistical outlier removal.
+    pub neighbor_count: usize,
+    /// Splats with a mean neighbor distance more than this many standard deviations above the average are outliers.
+    pub std_ratio: f32,
+    /// Splats whose larger scale axis exceeds the median of all splats by this factor can be floaters.
+    pub floater_scale_ratio: f32,
+    /// Large splats are only floaters if they are more transparent than this, zero disables the floater removal.
+    pub floater_opacity: f32,
+}
+
+impl Default for CleanupSettings {
+    fn default() -> Self {
+        Self {
+            neighbor_count: 8,
+            std_ratio: 2.0,
+            floater_scale_ratio: 10.0,
+            floater_opacity: 0.3,
+        }
+    }
+}
+
+/// What a cleanup pass found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CleanupReport {
+    pub input_count: usize,
+    pub outliers: usize,
+    /// Floaters which are not also outliers.
+    pub floaters: usize,
+    pub mean_neighbor_distance: f32,
+    pub neighbor_distance_std: f32,
+    /// Mean neighbor distance above which splats are outliers.
+    pub distance_threshold: f32,
+    /// Larger scale axis above which faint splats are floaters.
+    pub scale_threshold: f32,
+}
+
+impl CleanupReport {
+    pub fn removed(&self) -> usize {
+        self.outliers + self.floaters
+    }
+}
+
+impl fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "splats:    {}", self.input_count)?;
+        writeln!(
+            f,
+            "outliers:  {} (mean neighbor distance {:.4} +- {:.4}, threshold {:.4})",
+            self.outliers, self.mean_neighbor_distance, self.neighbor_distance_std, self.distance_threshold
+        )?;
+        writeln!(f, "floaters:  {} (scale threshold {:.4})", self.floaters, self.scale_threshold)?;
+        write!(f, "remaining: {}", self.input_count - self.removed())
+    }
+}
+
+/// Replaces the selection of `edit` by the outliers and floaters of the scene, so that they can be inspected or deleted.
+pub fn select_outliers(edit: &mut SplatEdit, scene: &Scene, settings: &CleanupSettings) -> CleanupReport {
+    let mut report = CleanupReport {
+        input_count: scene.splat_data.len(),
+        distance_threshold: f32::INFINITY,
+        scale_threshold: f32::INFINITY,
+        ..CleanupReport::default()
+    };
+    // Marks outliers first, floaters are added afterwards
+    let mut outlier = vec![false; scene.splat_data.len()];
+    if settings.neighbor_count > 0 && scene.splat_data.len() > settings.neighbor_count {
+        let centers: Vec<Vec3> = scene.splat_data.iter().map(|splat| Vec3::from(splat.center)).collect();
+        let grid = PointGrid::with_density(&centers, settings.neighbor_count as f32);
+        let mean_distances: Vec<f32> = centers
+            .iter()
+            .enumerate()
+            .map(|(index, center)| {
+                // The splat itself is among its nearest neighbors, unless duplicates displace it
+                let neighbors = grid.k_nearest(*center, settings.neighbor_count + 1);
+                let sum: f32 = neighbors
+                    .iter()
+                    .filter(|(neighbor, _)| *neighbor != index)
+                    .take(settings.neighbor_count)
+                    .map(|(_, distance)| distance)
+                    .sum();
+                sum / settings.neighbor_count as f32
+            })
+            .collect();
+        let count = mean_distances.len() as f64;
+        let mean = mean_distances.iter().map(|distance| *distance as f64).sum::<f64>() / count;
+        let variance = mean_distances.iter().map(|distance| (*distance as f64 - mean).powi(2)).sum::<f64>() / count;
+        report.mean_neighbor_distance = mean as f32;
+        report.neighbor_distance_std = variance.sqrt() as f32;
+        report.distance_threshold = report.mean_neighbor_distance + settings.std_ratio * report.neighbor_distance_std;
+        for (outlier, distance) in outlier.iter_mut().zip(mean_distances) {
+            *outlier = distance > report.distance_threshold;
+        }
+    }
+    if settings.floater_opacity > 0.0 && !scene.splat_data.is_empty() {
+        let mut scales: Vec<f32> = scene.splat_data.iter().map(|splat| splat.scale[0].max(splat.scale[1])).collect();
+        let middle = scales.len() / 2;
+        let median = *scales.select_nth_unstable_by(middle, f32::total_cmp).1;
+        report.scale_threshold = median * settings.floater_scale_ratio;
+    }
+    for (splat, outlier) in scene.splat_data.iter().zip(outlier.iter_mut()) {
+        if *outlier {
+            report.outliers += 1;
+        } else if splat.scale[0].max(splat.scale[1]) > report.scale_threshold && splat.color[3] < settings.floater_opacity {
+            report.floaters += 1;
+            *outlier = true;
+        }
+    }
+    edit.select_where(scene, SelectionMode::Replace, |index, _splat| outlier[index]);
+    report
+}
+
+/// Deletes the outliers and floaters of the scene along with their view dependent colors.
+pub fn remove_outliers(scene: &mut Scene, settings: &CleanupSettings) -> CleanupReport {
+    let mut edit = SplatEdit::new(scene);
+    let report = select_outliers(&mut edit, scene, settings);
+    scene.retain(|index, _splat| !edit.is_selected(index));
+    report
+}
@@ -0,0 +1,368 @@
+//! Collision proxy derived from splat scenes
+//!
+//! The splats are deposited into a voxel grid in which every voxel accumulates the opacity of the splats passing through it,
+//! the same way alpha blending accumulates along a ray. Voxels above a threshold are solid.
+//! Characters are upright capsules which slide along walls, step up small ledges and rest on the ground.
+
+use crate::scene::{Scene, Splat};
+use bevy::prelude::*;
+use bevy::tasks::{block_on, AsyncComputeTaskPool, Task};
+use glam::{IVec3, UVec3, Vec3};
+
+/// Tolerance used to keep capsules from getting stuck in surfaces they merely touch.
+const SKIN: f32 = 1.0e-3;
+
+/// Grids with more voxels than this use larger voxels instead, so that far outliers do not exhaust the memory.
+pub const MAX_VOXEL_COUNT: usize = 1 << 24;
+
+/// Voxel size in place of one which is not positive and finite.
+pub const DEFAULT_VOXEL_SIZE: f32 = 0.1;
+
+fn valid_voxel_size(voxel_size: f32) -> f32 {
+    if voxel_size > 0.0 && voxel_size.is_finite() {
+        voxel_size
+    } else {
+        DEFAULT_VOXEL_SIZE
+    }
+}
+
+/// Occupancy of a [Scene] sampled on a regular grid.
+#[derive(Debug, Clone)]
+pub struct VoxelGrid {
+    /// Minimum corner of the grid.
+    pub origin: Vec3,
+    pub voxel_size: f32,
+    pub dimensions: UVec3,
+    /// Accumulated opacity of each voxel, x varies fastest.
+    pub occupancy: Vec<f32>,
+    pub occupancy_threshold: f32,
+}
+
+/// Upright capsule standing on its lowest point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub radius: f32,
+    /// Total height including both hemispheres.
+    pub height: f32,
+}
+
+/// Outcome of [VoxelGrid::move_capsule].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CapsuleMotion {
+    /// New position of the lowest point of the capsule.
+    pub feet: Vec3,
+    pub grounded: bool,
+    /// The horizontal motion was stopped by a wall which was too high to step onto.
+    pub blocked: bool,
+    pub hit_ceiling: bool,
+}
+
+impl VoxelGrid {
+    /// Voxelizes the opacity of all splats in the scene.
+    pub fn from_scene(scene: &Scene, voxel_size: f32, occupancy_threshold: f32) -> Self {
+        Self::from_splats(&scene.splat_data, voxel_size, occupancy_threshold)
+    }
+
+    /// Voxelizes the opacity of the splats. The voxel size is doubled until the grid has at most [MAX_VOXEL_COUNT]
+    /// voxels. Splats with non-finite centers or scales are skipped. A voxel size which is not positive and finite is
+    /// replaced with [DEFAULT_VOXEL_SIZE].
+    pub fn from_splats(splats: &[Splat], voxel_size: f32, occupancy_threshold: f32) -> Self {
+        let splats = || {
+            splats
+                .iter()
+                .filter(|splat| Vec3::from(splat.center).is_finite() && splat.scale[0].is_finite() && splat.scale[1].is_finite())
+        };
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        for splat in splats() {
+            let radius = 3.0 * splat.scale[0].max(splat.scale[1]);
+            min = min.min(Vec3::from(splat.center) - radius);
+            max = max.max(Vec3::from(splat.center) + radius);
+        }
+        if !min.is_finite() || !max.is_finite() {
+            min = Vec3::ZERO;
+            max = Vec3::ZERO;
+        }
+        let mut voxel_size = valid_voxel_size(voxel_size);
+        let dimensions = loop {
+            let dimensions = ((max - min) / voxel_size).ceil().as_uvec3().max(UVec3::ONE);
+            let voxel_count = (dimensions.x as usize)
+                .checked_mul(dimensions.y as usize)
+                .and_then(|count| count.checked_mul(dimensions.z as usize));
+            if voxel_count.is_some_and(|count| count <= MAX_VOXEL_COUNT) {
+                break dimensions;
+            }
+            voxel_size *= 2.0;
+        };
+        // Holds the transmittance of every voxel until all splats are deposited
+        let mut grid = Self {
+            origin: min,
+            voxel_size,
+            dimensions,
+            occupancy: vec![1.0; dimensions.x as usize * dimensions.y as usize * dimensions.z as usize],
+            occupancy_threshold,
+        };
+
+        // Splats smaller than a voxel are widened to the voxel size while keeping their total opacity
+        let half_voxel = 0.5 * voxel_size;
+        for splat in splats() {
+            let center = Vec3::from(splat.center);
+            let [tangent, bitangent, normal] = splat.tangent_frame();
+            let scale = [splat.scale[0].max(half_voxel), splat.scale[1].max(half_voxel)];
+            let alpha = splat.color[3] * (splat.scale[0] * splat.scale[1]) / (scale[0] * scale[1]);
+            let plane_thickness = half_voxel * normal.abs().element_sum();
+            let radius = 3.0 * scale[0].max(scale[1]);
+            let lower = grid.cell_of(center - radius).max(IVec3::ZERO);
+            let upper = grid.cell_of(center + radius).min(grid.dimensions.as_ivec3() - 1);
+            for z in lower.z..=upper.z {
+                for y in lower.y..=upper.y {
+                    for x in lower.x..=upper.x {
+                        let cell = IVec3::new(x, y, z);
+                        let offset = grid.cell_center(cell) - center;
+                        if offset.dot(normal).abs() > plane_thickness {
+                            continue;
+                        }
+                        let u = offset.dot(tangent) / scale[0];
+                        let v = offset.dot(bitangent) / scale[1];
+                        let voxel_alpha = (alpha * (-0.5 * (u * u + v * v)).exp()).min(1.0);
+                        let index = grid.index(cell).unwrap();
+                        grid.occupancy[index] *= 1.0 - voxel_alpha;
+                    }
+                }
+            }
+        }
+        for occupancy in grid.occupancy.iter_mut() {
+            *occupancy = 1.0 - *occupancy;
+        }
+        grid
+    }
+
+    /// The cell containing `point`, which might be outside of the grid.
+    pub fn cell_of(&self, point: Vec3) -> IVec3 {
+        ((point - self.origin) / self.voxel_size).floor().as_ivec3()
+    }
+
+    pub fn cell_center(&self, cell: IVec3) -> Vec3 {
+        self.origin + (cell.as_vec3() + 0.5) * self.voxel_size
+    }
+
+    /// Index into [VoxelGrid::occupancy], if the cell is inside the grid.
+    pub fn index(&self, cell: IVec3) -> Option<usize> {
+        if cell.cmplt(IVec3::ZERO).any() || cell.as_uvec3().cmpge(self.dimensions).any() {
+            return None;
+        }
+        let (cell, dimensions) = (cell.as_uvec3(), self.dimensions);
+        Some(cell.x as usize + dimensions.x as usize * (cell.y as usize + dimensions.y as usize * cell.z as usize))
+    }
+
+    /// Cells outside of the grid are empty.
+    pub fn is_solid(&self, cell: IVec3) -> bool {
+        self.index(cell).is_some_and(|index| self.occupancy[index] >= self.occupancy_threshold)
+    }
+
+    pub fn is_solid_at(&self, point: Vec3) -> bool {
+        self.is_solid(self.cell_of(point))
+    }
+
+    /// Calls `f` with the minimum corner of every solid cell touching the box from `min` to `max`.
+    fn for_each_solid_cell(&self, min: Vec3, max: Vec3, mut f: impl FnMut(Vec3)) {
+        let lower = self.cell_of(min).max(IVec3::ZERO);
+        let upper = self.cell_of(max).min(self.dimensions.as_ivec3() - 1);
+        for z in lower.z..=upper.z {
+            for y in lower.y..=upper.y {
+                for x in lower.x..=upper.x {
+                    let cell = IVec3::new(x, y, z);
+                    if self.is_solid(cell) {
+                        f(self.origin + cell.as_vec3() * self.voxel_size);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Horizontal distance from the vertical line through `point` to the cell starting at `cell_min`.
+    fn horizontal_distance(&self, point: Vec3, cell_min: Vec3) -> f32 {
+        let dx = (cell_min.x - point.x).max(point.x - cell_min.x - self.voxel_size).max(0.0);
+        let dz = (cell_min.z - point.z).max(point.z - cell_min.z - self.voxel_size).max(0.0);
+        (dx * dx + dz * dz).sqrt()
+    }
+
+    /// Checks if the capsule standing at `feet` intersects any solid voxel.
+    pub fn capsule_overlaps(&self, capsule: &Capsule, feet: Vec3) -> bool {
+        let extent = Vec3::new(capsule.radius, 0.0, capsule.radius);
+        let bottom = feet.y + capsule.radius;
+        let top = feet.y + capsule.height - capsule.radius;
+        let mut overlaps = false;
+        self.for_each_solid_cell(feet - extent, feet + extent + Vec3::Y * capsule.height, |cell_min| {
+            let dy = (cell_min.y - top).max(bottom - cell_min.y - self.voxel_size).max(0.0);
+            let dxz = self.horizontal_distance(feet, cell_min);
+            overlaps |= dxz * dxz + dy * dy < (capsule.radius - SKIN) * (capsule.radius - SKIN);
+        });
+        overlaps
+    }
+
+    /// Height at which the capsule would come to rest when lowered from `feet` by at most `max_drop`.
+    pub fn ground_height(&self, capsule: &Capsule, feet: Vec3, max_drop: f32) -> Option<f32> {
+        let extent = Vec3::new(capsule.radius, 0.0, capsule.radius);
+        let mut ground: Option<f32> = None;
+        self.for_each_solid_cell(feet - extent - Vec3::Y * max_drop, feet + extent, |cell_min| {
+            // The feet stand flat on the top face of any cell within the radius,
+            // so that the round bottom of the capsule does not ride up ledges higher than the step height
+            if self.horizontal_distance(feet, cell_min) >= capsule.radius {
+                return;
+            }
+            let top = cell_min.y + self.voxel_size;
+            if top <= feet.y + SKIN && top >= feet.y - max_drop && ground.is_none_or(|ground| top > ground) {
+                ground = Some(top);
+            }
+        });
+        ground
+    }
+
+    /// Moves the capsule by `displacement`, sliding along walls and stepping up ledges of at most `step_height`.
+    pub fn move_capsule(&self, capsule: &Capsule, feet: Vec3, displacement: Vec3, step_height: f32) -> CapsuleMotion {
+        let mut motion = CapsuleMotion {
+            feet,
+            grounded: false,
+            blocked: false,
+            hit_ceiling: false,
+        };
+
+        // Horizontal, in steps of at most half a voxel and one axis at a time to slide along walls
+        let horizontal = Vec3::new(displacement.x, 0.0, displacement.z);
+        let steps = (horizontal.length() / (0.5 * self.voxel_size)).ceil().max(1.0) as usize;
+        for _ in 0..steps {
+            for axis_step in [Vec3::X * horizontal.x, Vec3::Z * horizontal.z] {
+                let candidate = motion.feet + axis_step / steps as f32;
+                if axis_step == Vec3::ZERO || !self.capsule_overlaps(capsule, candidate) {
+                    motion.feet = candidate;
+                    continue;
+                }
+                let raised = candidate + Vec3::Y * step_height;
+                match self.ground_height(capsule, raised, step_height) {
+                    Some(ground) if !self.capsule_overlaps(capsule, Vec3::new(raised.x, ground + SKIN, raised.z)) => {
+                        motion.feet = Vec3::new(raised.x, ground, raised.z);
+                    }
+                    _ => motion.blocked = true,
+                }
+            }
+        }
+
+        // Vertical
+        if displacement.y <= 0.0 {
+            if let Some(ground) = self.ground_height(capsule, motion.feet, -displacement.y + SKIN) {
+                motion.feet.y = ground;
+                motion.grounded = true;
+            } else {
+                motion.feet.y += displacement.y;
+            }
+        } else {
+            let steps = (displacement.y / (0.5 * self.voxel_size)).ceil() as usize;
+            for _ in 0..steps {
+                let candidate = motion.feet + Vec3::Y * displacement.y / steps as f32;
+                if self.capsule_overlaps(capsule, candidate) {
+                    motion.hit_ceiling = true;
+                    break;
+                }
+                motion.feet = candidate;
+            }
+        }
+        motion
+    }
+}
+
+/// Parameters of the [SceneCollider].
+#[derive(Resource, Debug, Clone)]
+pub struct CollisionSettings {
+    /// Edge length of the voxels, [DEFAULT_VOXEL_SIZE] unless positive and finite.
+    pub voxel_size: f32,
+    pub occupancy_threshold: f32,
+}
+
+impl Default for CollisionSettings {
+    fn default() -> Self {
+        Self {
+            voxel_size: DEFAULT_VOXEL_SIZE,
+            occupancy_threshold: 0.5,
+        }
+    }
+}
+
+impl CollisionSettings {
+    /// The voxel size to build with, see [CollisionSettings::voxel_size].
+    pub fn valid_voxel_size(&self) -> f32 {
+        valid_voxel_size(self.voxel_size)
+    }
+}
+
+/// Collision proxy of the splats of the [Scene] resource, or of the first [Scene] entity if the resource has none.
+///
+/// It is rebuilt on the [AsyncComputeTaskPool] whenever the number of splats or the [CollisionSettings] change, or a
+/// [RebuildSceneCollider] event is sent. The previous collider stays in place until the new one is finished, and only
+/// one rebuild runs at a time, so that a scene which is still loading is not voxelized for every batch.
+#[derive(Resource)]
+pub struct SceneCollider {
+    pub grid: VoxelGrid,
+    pub splat_count: usize,
+}
+
+/// Requests a rebuild of the [SceneCollider] after splats were modified without changing their number.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct RebuildSceneCollider;
+
+/// A [SceneCollider] being built in the background.
+#[derive(Resource)]
+struct ColliderBuild {
+    splat_count: usize,
+    task: Task<VoxelGrid>,
+}
+
+pub struct SplatCollisionPlugin;
+
+impl Plugin for SplatCollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CollisionSettings>()
+            .add_event::<RebuildSceneCollider>()
+            .add_systems(Update, update_scene_collider);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_scene_collider(
+    mut commands: Commands,
+    scene: Option<Res<Scene>>,
+    scenes: Query<&Scene>,
+    settings: Res<CollisionSettings>,
+    collider: Option<Res<SceneCollider>>,
+    build: Option<ResMut<ColliderBuild>>,
+    mut rebuild_requests: EventReader<RebuildSceneCollider>,
+    mut rebuild_requested: Local<bool>,
+) {
+    *rebuild_requested |= rebuild_requests.read().count() > 0 || settings.is_changed();
+    if let Some(mut build) = build {
+        if !build.task.is_finished() {
+            return;
+        }
+        let grid = block_on(&mut build.task);
+        commands.insert_resource(SceneCollider {
+            grid,
+            splat_count: build.splat_count,
+        });
+        commands.remove_resource::<ColliderBuild>();
+        return;
+    }
+    let scenes: Vec<&Scene> = scene.as_deref().into_iter().chain(scenes.iter()).collect();
+    let Some(scene) = scenes.iter().find(|scene| !scene.splat_data.is_empty()).or(scenes.first()) else {
+        return;
+    };
+    if collider.is_some_and(|collider| collider.splat_count == scene.splat_count) && !*rebuild_requested {
+        return;
+    }
+    *rebuild_requested = false;
+    let splats = scene.splat_data.clone();
+    let (voxel_size, occupancy_threshold) = (settings.valid_voxel_size(), settings.occupancy_threshold);
+    commands.insert_resource(ColliderBuild {
+        splat_count: scene.splat_count,
+        task: AsyncComputeTaskPool::get().spawn(async move { VoxelGrid::from_splats(&splats, voxel_size, occupancy_threshold) }),
+    });
+}
@@ -0,0 +1,403 @@
+//! Coordinate conventions of captures
+//!
+//! Scenes are captured Z up like in Blender, Y down like in OpenCV and COLMAP or Y up like in Bevy, sometimes left
+//! handed and in units other than meters. A [CoordinateSystem] describes such a convention and is saved next to a scene
+//! as `<scene>.coordinates.json`. A [CoordinateConversion] between two of them moves positions, directions, rotations,
+//! splats and, through the Wigner D-matrices of bands 1 to 3 in [ShTransform], spherical harmonics coefficients.
+
+use crate::scene::Splat;
+use crate::utils::symmetric_eigen;
+use glam::{Mat3, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A signed coordinate axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Axis {
+    #[serde(rename = "+X")]
+    PositiveX,
+    #[serde(rename = "-X")]
+    NegativeX,
+    #[serde(rename = "+Y")]
+    PositiveY,
+    #[serde(rename = "-Y")]
+    NegativeY,
+    #[serde(rename = "+Z")]
+    PositiveZ,
+    #[serde(rename = "-Z")]
+    NegativeZ,
+}
+
+impl Axis {
+    pub fn vector(self) -> Vec3 {
+        match self {
+            Axis::PositiveX => Vec3::X,
+            Axis::NegativeX => Vec3::NEG_X,
+            Axis::PositiveY => Vec3::Y,
+            Axis::NegativeY => Vec3::NEG_Y,
+            Axis::PositiveZ => Vec3::Z,
+            Axis::NegativeZ => Vec3::NEG_Z,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+fn default_meters_per_unit() -> f32 {
+    1.0
+}
+
+/// Which way is up and forward in a scene, and how long its units are.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CoordinateSystem {
+    pub up: Axis,
+    pub forward: Axis,
+    pub handedness: Handedness,
+    #[serde(default = "default_meters_per_unit")]
+    pub meters_per_unit: f32,
+}
+
+impl CoordinateSystem {
+    /// Y up and -Z forward, right handed, which is what the renderer and the rest of Bevy expect.
+    pub const BEVY: Self = Self {
+        up: Axis::PositiveY,
+        forward: Axis::NegativeZ,
+        handedness: Handedness::Right,
+        meters_per_unit: 1.0,
+    };
+
+    /// Z up and +Y forward, right handed, like Blender and many photogrammetry tools.
+    pub const Z_UP: Self = Self {
+        up: Axis::PositiveZ,
+        forward: Axis::PositiveY,
+        handedness: Handedness::Right,
+        meters_per_unit: 1.0,
+    };
+
+    /// Y down and +Z forward, right handed, like OpenCV and COLMAP.
+    pub const OPENCV: Self = Self {
+        up: Axis::NegativeY,
+        forward: Axis::PositiveZ,
+        handedness: Handedness::Right,
+        meters_per_unit: 1.0,
+    };
+
+    /// Y up and +Z forward, left handed, like Unity.
+    pub const LEFT_HANDED_Y_UP: Self = Self {
+        up: Axis::PositiveY,
+        forward: Axis::PositiveZ,
+        handedness: Handedness::Left,
+        meters_per_unit: 1.0,
+    };
+
+    pub fn right(&self) -> Vec3 {
+        match self.handedness {
+            Handedness::Right => self.forward.vector().cross(self.up.vector()),
+            Handedness::Left => self.up.vector().cross(self.forward.vector()),
+        }
+    }
+
+    /// Up and forward have to be perpendicular and the units positive.
+    pub fn is_valid(&self) -> bool {
+        self.up.vector().dot(self.forward.vector()) == 0.0 && self.meters_per_unit > 0.0 && self.meters_per_unit.is_finite()
+    }
+
+    /// Right, up and backward as columns, mapping the right handed Bevy axes onto the axes of this system.
+    fn basis(&self) -> Mat3 {
+        Mat3::from_cols(self.right(), self.up.vector(), -self.forward.vector())
+    }
+
+    /// Where the coordinate system of a scene file is stored.
+    pub fn path_for_scene(scene_path: impl AsRef<Path>) -> PathBuf {
+        scene_path.as_ref().with_extension("coordinates.json")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let system: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if !system.is_valid() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Up and forward axes are not perpendicular"));
+        }
+        Ok(system)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl Default for CoordinateSystem {
+    fn default() -> Self {
+        Self::BEVY
+    }
+}
+
+/// A uniform scale after a rotation or reflection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoordinateConversion {
+    /// Orthogonal, with a negative determinant if the handedness changes.
+    pub linear: Mat3,
+    pub scale: f32,
+}
+
+impl CoordinateConversion {
+    pub const IDENTITY: Self = Self {
+        linear: Mat3::IDENTITY,
+        scale: 1.0,
+    };
+
+    pub fn between(source: &CoordinateSystem, target: &CoordinateSystem) -> Self {
+        Self {
+            linear: target.basis() * source.basis().transpose(),
+            scale: source.meters_per_unit / target.meters_per_unit,
+        }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Self {
+            linear: Mat3::from_quat(rotation),
+            scale: 1.0,
+        }
+    }
+
+    /// Rotates the shortest way from `up` to +Y, for example after [estimate_up].
+    pub fn aligning_up(up: Vec3) -> Self {
+        Self::from_rotation(Quat::from_rotation_arc(up.normalize(), Vec3::Y))
+    }
+
+    /// This conversion followed by `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        Self {
+            linear: next.linear * self.linear,
+            scale: self.scale * next.scale,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            linear: self.linear.transpose(),
+            scale: 1.0 / self.scale,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.linear == Mat3::IDENTITY && self.scale == 1.0
+    }
+
+    pub fn is_reflection(&self) -> bool {
+        self.linear.determinant() < 0.0
+    }
+
+    pub fn position(&self, position: Vec3) -> Vec3 {
+        self.linear * position * self.scale
+    }
+
+    /// Directions are not scaled.
+    pub fn direction(&self, direction: Vec3) -> Vec3 {
+        self.linear * direction
+    }
+
+    /// A rotation given in the source coordinates as the same rotation in the target coordinates.
+    pub fn rotation(&self, rotation: Quat) -> Quat {
+        Quat::from_mat3(&(self.linear * Mat3::from_quat(rotation) * self.linear.transpose())).normalize()
+    }
+
+    /// The orientation of something from its local frame into the source coordinates as one into the target coordinates.
+    ///
+    /// Reflections can not be expressed as rotation, so they also flip the local X axis, mirroring what is oriented.
+    pub fn orientation(&self, orientation: Quat) -> Quat {
+        let mirror = Mat3::from_diagonal(Vec3::new(self.linear.determinant().signum(), 1.0, 1.0));
+        Quat::from_mat3(&(self.linear * Mat3::from_quat(orientation) * mirror)).normalize()
+    }
+
+    pub fn splat(&self, splat: &Splat) -> Splat {
+        Splat {
+            center: self.position(Vec3::from(splat.center)).to_array(),
+            scale: splat.scale.map(|scale| scale * self.scale),
+            normal: self.direction(Vec3::from(splat.normal)).to_array(),
+            ellipse_basis: self.direction(Vec3::from(splat.ellipse_basis)).to_array(),
+            ..splat.clone()
+        }
+    }
+
+    pub fn splats(&self, splats: &mut [Splat]) {
+        for splat in splats {
+            *splat = self.splat(splat);
+        }
+    }
+
+    pub fn spherical_harmonics(&self) -> ShTransform {
+        ShTransform::new(self.linear)
+    }
+}
+
+/// The real spherical harmonics basis up to band 3, as evaluated by `sphericalHarmonicsLookup` in the shader.
+pub fn spherical_harmonics_basis(direction: Vec3) -> [f32; 16] {
+    let Vec3 { x, y, z } = direction;
+    let (xx, yy, zz) = (x * x, y * y, z * z);
+    [
+        0.282_094_8,
+        -0.488_602_5 * y,
+        0.488_602_5 * z,
+        -0.488_602_5 * x,
+        1.092_548_4 * x * y,
+        -1.092_548_4 * y * z,
+        0.315_391_57 * (2.0 * zz - xx - yy),
+        -1.092_548_4 * x * z,
+        0.546_274_2 * (xx - yy),
+        -0.590_043_6 * y * (3.0 * xx - yy),
+        2.890_611_4 * x * y * z,
+        -0.457_045_8 * y * (4.0 * zz - xx - yy),
+        0.373_176_33 * z * (2.0 * zz - 3.0 * xx - 3.0 * yy),
+        -0.457_045_8 * x * (4.0 * zz - xx - yy),
+        1.445_305_7 * z * (xx - yy),
+        -0.590_043_6 * x * (xx - 3.0 * yy),
+    ]
+}
+
+/// Color of spherical harmonics coefficients, one per basis function, in a direction. Without the offset of 0.5.
+pub fn evaluate_spherical_harmonics(coefficients: &[Vec3], direction: Vec3) -> Vec3 {
+    coefficients
+        .iter()
+        .zip(spherical_harmonics_basis(direction))
+        .map(|(coefficient, basis)| *coefficient * basis)
+        .sum()
+}
+
+/// Directions on which the bands are sampled, spread evenly over the sphere.
+const SH_SAMPLE_COUNT: usize = 32;
+
+fn sample_directions() -> impl Iterator<Item = Vec3> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+    (0..SH_SAMPLE_COUNT).map(move |index| {
+        let y = 1.0 - (index as f32 + 0.5) / SH_SAMPLE_COUNT as f32 * 2.0;
+        let radius = (1.0 - y * y).sqrt();
+        let angle = golden_angle * index as f32;
+        Vec3::new(radius * angle.cos(), y, radius * angle.sin())
+    })
+}
+
+/// Solves `matrix * x = rhs` for square `matrix` with `rhs` of several columns, all row major.
+fn solve(mut matrix: Vec<f64>, mut rhs: Vec<f64>, size: usize, columns: usize) -> Vec<f64> {
+    for pivot in 0..size {
+        let best = (pivot..size)
+            .max_by(|a, b| matrix[a * size + pivot].abs().total_cmp(&matrix[b * size + pivot].abs()))
+            .unwrap();
+        for column in 0..size {
+            matrix.swap(pivot * size + column, best * size + column);
+        }
+        for column in 0..columns {
+            rhs.swap(pivot * columns + column, best * columns + column);
+        }
+        for row in 0..size {
+            if row == pivot {
+                continue;
+            }
+            let factor = matrix[row * size + pivot] / matrix[pivot * size + pivot];
+            for column in 0..size {
+                matrix[row * size + column] -= factor * matrix[pivot * size + column];
+            }
+            for column in 0..columns {
+                rhs[row * columns + column] -= factor * rhs[pivot * columns + column];
+            }
+        }
+    }
+    for row in 0..size {
+        for column in 0..columns {
+            rhs[row * columns + column] /= matrix[row * size + row];
+        }
+    }
+    rhs
+}
+
+/// Transforms spherical harmonics coefficients along with the splats, band by band.
+///
+/// The matrix of each band is its Wigner D-matrix in the real basis of [spherical_harmonics_basis]. It is fitted by
+/// least squares to the basis sampled in original and transformed directions, which is exact as rotations and
+/// reflections keep every band to itself, and avoids getting the sign conventions of the basis wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShTransform {
+    /// Row major matrices of the bands 1, 2 and 3, the band 0 is invariant.
+    bands: [Vec<f32>; 3],
+}
+
+impl ShTransform {
+    /// The transform of the colors for the orthogonal `linear` transform of the splats.
+    pub fn new(linear: Mat3) -> Self {
+        let inverse = linear.transpose();
+        let samples: Vec<([f32; 16], [f32; 16])> = sample_directions()
+            .map(|direction| (spherical_harmonics_basis(direction), spherical_harmonics_basis(inverse * direction)))
+            .collect();
+        let bands = [1, 2, 3].map(|band| {
+            let (first, size) = (band * band, 2 * band + 1);
+            // Normal equations of the basis in the new directions against the basis in the original ones
+            let mut normal = vec![0.0; size * size];
+            let mut rhs = vec![0.0; size * size];
+            for (basis, transformed) in &samples {
+                for row in 0..size {
+                    for column in 0..size {
+                        normal[row * size + column] += basis[first + row] as f64 * basis[first + column] as f64;
+                        rhs[row * size + column] += basis[first + row] as f64 * transformed[first + column] as f64;
+                    }
+                }
+            }
+            solve(normal, rhs, size, size).into_iter().map(|value| value as f32).collect()
+        });
+        Self { bands }
+    }
+
+    /// Transforms the coefficients of all complete bands, one color per basis function starting with band 0.
+    pub fn apply(&self, coefficients: &mut [Vec3]) {
+        for (band, matrix) in (1..=3).zip(&self.bands) {
+            let (first, size) = (band * band, 2 * band + 1);
+            let Some(coefficients) = coefficients.get_mut(first..first + size) else {
+                break;
+            };
+            let original = coefficients.to_vec();
+            for (row, coefficient) in coefficients.iter_mut().enumerate() {
+                *coefficient = (0..size).map(|column| original[column] * matrix[row * size + column]).sum();
+            }
+        }
+    }
+}
+
+/// Estimates which way is up from the splats alone, or none without any surfaces.
+///
+/// Surfaces facing up or down, like the ground, floors and table tops, usually cover the most area, so the most common
+/// splat normal weighted by area and opacity gives the vertical axis. Seen along it, the densest layer of splats is
+/// then taken to be the ground, which lies below most of the rest of the scene. This is a heuristic, scenes dominated
+/// by walls or seen from below fool it.
+pub fn estimate_up(splats: &[Splat]) -> Option<Vec3> {
+    let mut tensor = Mat3::ZERO;
+    for splat in splats {
+        let normal = Vec3::from(splat.normal).normalize_or_zero();
+        let weight = splat.scale[0] * splat.scale[1] * splat.color[3];
+        tensor += Mat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z) * weight;
+    }
+    let (weights, axes) = symmetric_eigen(tensor);
+    if weights.x.is_nan() || weights.x <= 0.0 {
+        return None;
+    }
+    let axis = axes.x_axis;
+
+    let mut heights: Vec<f32> = splats.iter().map(|splat| Vec3::from(splat.center).dot(axis)).collect();
+    heights.sort_by(f32::total_cmp);
+    // Outliers would squeeze the layers into few bins
+    let low = heights[heights.len() / 100];
+    let high = heights[heights.len() - 1 - heights.len() / 100];
+    if high <= low {
+        return Some(axis);
+    }
+    const BIN_COUNT: usize = 64;
+    let mut bins = [0usize; BIN_COUNT];
+    for height in heights.iter().filter(|height| (low..=high).contains(*height)) {
+        bins[(((height - low) / (high - low) * BIN_COUNT as f32) as usize).min(BIN_COUNT - 1)] += 1;
+    }
+    let densest = (0..BIN_COUNT).max_by_key(|bin| bins[*bin]).unwrap();
+    Some(if densest < BIN_COUNT / 2 { axis } else { -axis })
+}
@@ -0,0 +1,365 @@
+//! Reference rasterizer on the CPU
+//!
+//! Splats outside of the view are culled, see [crate::culling], and the others projected to first order with
+//! [crate::projection], sorted by depth and blended front to back per pixel,
+//! the way the fragment shader blends them. It is slow, but deterministic and independent of a GPU,
+//! which makes it the reference for tests and offline renders. Bands of rows are rasterized in parallel.
+
+use crate::chunks::ChunkLayout;
+use crate::coordinates::spherical_harmonics_basis;
+use crate::culling::{cull, cull_frustum, Frustum};
+use crate::scene::{Camera, ProjectionModel, Scene, Splat};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use image::{ImageError, RgbaImage};
+use std::io;
+use std::path::Path;
+
+/// Contributions below this are discarded, like in the fragment shader.
+pub(crate) const MIN_ALPHA: f32 = 1.0 / 255.0;
+
+/// A single splat never covers a pixel completely, so that the ones behind it still blend in.
+pub(crate) const MAX_ALPHA: f32 = 0.99;
+
+/// Pixels stop blending once less than this much of the background shines through.
+const MIN_TRANSMITTANCE: f32 = 1.0e-4;
+
+/// An image of colors blended over the background, with their combined coverage as alpha.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuImage {
+    pub width: u32,
+    pub height: u32,
+    /// Rows from top to bottom.
+    pub pixels: Vec<Vec4>,
+}
+
+impl CpuImage {
+    pub fn new(width: u32, height: u32, fill: Vec4) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![fill; width as usize * height as usize],
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Vec4 {
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Vec4) {
+        self.pixels[y as usize * self.width as usize + x as usize] = color;
+    }
+
+    /// Bilinear interpolation at a position in pixels, where the center of the first pixel is at (0.5, 0.5).
+    ///
+    /// Positions outside of the image are clamped to its border.
+    pub fn sample(&self, position: Vec2) -> Vec4 {
+        let max = Vec2::new(self.width as f32 - 1.0, self.height as f32 - 1.0);
+        let position = (position - 0.5).clamp(Vec2::ZERO, max.max(Vec2::ZERO));
+        let (x0, y0) = (position.x.floor() as u32, position.y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let fraction = position - position.floor();
+        let top = self.pixel(x0, y0).lerp(self.pixel(x1, y0), fraction.x);
+        let bottom = self.pixel(x0, y1).lerp(self.pixel(x1, y1), fraction.x);
+        top.lerp(bottom, fraction.y)
+    }
+
+    pub fn to_rgba8(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let color = (self.pixel(x, y).clamp(Vec4::ZERO, Vec4::ONE) * 255.0).round();
+            image::Rgba([color.x as u8, color.y as u8, color.z as u8, color.w as u8])
+        })
+    }
+
+    pub fn from_rgba8(image: &RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.pixels().map(|pixel| Vec4::from(pixel.0.map(|channel| channel as f32 / 255.0))).collect(),
+        }
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.to_rgba8()
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(image_error_to_io)
+    }
+
+    pub fn load_png(path: impl AsRef<Path>) -> io::Result<Self> {
+        let image = image::load(io::BufReader::new(std::fs::File::open(path)?), image::ImageFormat::Png).map_err(image_error_to_io)?;
+        Ok(Self::from_rgba8(&image.to_rgba8()))
+    }
+
+    /// Loads a PNG or JPEG image, depending on the extension.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let image = image::open(path).map_err(image_error_to_io)?;
+        Ok(Self::from_rgba8(&image.to_rgba8()))
+    }
+}
+
+fn image_error_to_io(error: ImageError) -> io::Error {
+    match error {
+        ImageError::IoError(error) => error,
+        error => io::Error::new(io::ErrorKind::InvalidData, error),
+    }
+}
+
+/// Parameters of [render].
+#[derive(Debug, Clone)]
+pub struct CpuRenderSettings {
+    /// Blended behind all splats.
+    pub background: Vec4,
+    /// Multiplies the size of all splats.
+    pub splat_scale: f32,
+    /// Variance in pixels added to every footprint, so that splats smaller than a pixel do not alias.
+    pub blur_variance: f32,
+    /// Splats are cut off beyond this many standard deviations.
+    pub max_sigma: f32,
+    /// Splats outside of the frustum widened by this are not drawn, see [crate::culling::cull]. Larger splat scales
+    /// need a larger tolerance to not cut off the splats at the edges of the image.
+    pub frustum_culling_tolerance: f32,
+}
+
+impl Default for CpuRenderSettings {
+    fn default() -> Self {
+        Self {
+            background: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            splat_scale: 1.0,
+            blur_variance: 0.3,
+            max_sigma: 3.0,
+            frustum_culling_tolerance: 1.0,
+        }
+    }
+}
+
+/// A splat projected into pixels.
+pub(crate) struct Footprint {
+    /// Index of the splat.
+    pub(crate) splat: usize,
+    pub(crate) center: Vec2,
+    /// Inverse of the covariance as (xx, xy, yy).
+    pub(crate) conic: Vec3,
+    pub(crate) color: Vec4,
+    pub(crate) depth: f32,
+    pub(crate) min: Vec2,
+    pub(crate) max: Vec2,
+}
+
+fn footprint(index: usize, splat: &Splat, color: Vec4, camera: &Camera, size: Vec2, settings: &CpuRenderSettings) -> Option<Footprint> {
+    let projected = camera.project_gaussian(Vec3::from(splat.center), splat.covariance() * settings.splat_scale.powi(2))?;
+    if projected.depth < camera.z_near || color.w < MIN_ALPHA {
+        return None;
+    }
+    // Normalized device coordinates have y up, pixels y down
+    let scale = Vec2::new(0.5 * size.x, -0.5 * size.y);
+    let center = Vec2::new(projected.center.x + 1.0, 1.0 - projected.center.y) * 0.5 * size;
+    let xx = projected.covariance.x_axis.x * scale.x * scale.x + settings.blur_variance;
+    let xy = projected.covariance.x_axis.y * scale.x * scale.y;
+    let yy = projected.covariance.y_axis.y * scale.y * scale.y + settings.blur_variance;
+    let determinant = xx * yy - xy * xy;
+    if determinant <= 0.0 || !determinant.is_finite() {
+        return None;
+    }
+    let extent = settings.max_sigma * Vec2::new(xx.sqrt(), yy.sqrt());
+    let min = (center - extent).max(Vec2::ZERO);
+    let max = (center + extent).min(size);
+    if min.x >= max.x || min.y >= max.y {
+        return None;
+    }
+    Some(Footprint {
+        splat: index,
+        center,
+        conic: Vec3::new(yy, -xy, xx) / determinant,
+        color,
+        depth: projected.depth,
+        min,
+        max,
+    })
+}
+
+/// The frustum splats are culled against. Unlike the shaders, which keep the far half of the depths, the CPU renderer
+/// draws everything from the near to the far plane. None for a fisheye, whose view is wider than its pinhole projection.
+fn frustum(camera: &Camera, settings: &CpuRenderSettings) -> Option<Frustum> {
+    if camera.model == ProjectionModel::Fisheye {
+        return None;
+    }
+    // Maps depths from -1..1 to the 0..1 of the frustum
+    let depth_range = Mat4::from_cols(Vec4::X, Vec4::Y, Vec4::new(0.0, 0.0, 0.5, 0.0), Vec4::new(0.0, 0.0, 0.5, 1.0));
+    Some(Frustum::from_view_projection(
+        &(depth_range * camera.projection * camera.view),
+        settings.frustum_culling_tolerance,
+    ))
+}
+
+/// Indices of the splats to draw in ascending order.
+fn visible(splats: &[Splat], camera: &Camera, settings: &CpuRenderSettings) -> Vec<u32> {
+    match frustum(camera, settings) {
+        Some(frustum) => cull_frustum(splats, &frustum),
+        None => (0..splats.len() as u32).collect(),
+    }
+}
+
+/// Footprints of the visible splats in their own colors.
+fn footprints(splats: &[Splat], camera: &Camera, size: Vec2, settings: &CpuRenderSettings) -> Vec<Footprint> {
+    visible(splats, camera, settings)
+        .into_iter()
+        .filter_map(|index| {
+            let splat = &splats[index as usize];
+            footprint(index as usize, splat, Vec4::from(splat.color), camera, size, settings)
+        })
+        .collect()
+}
+
+/// Renders the splats as seen by the camera into an image of the given size.
+pub fn render(splats: &[Splat], camera: &Camera, width: u32, height: u32, settings: &CpuRenderSettings) -> CpuImage {
+    let footprints = footprints(splats, camera, Vec2::new(width as f32, height as f32), settings);
+    rasterize(footprints, width, height, settings, 0).0
+}
+
+/// How much every splat adds to a render of the given size: Its opacity times the transmittance in front of it,
+/// summed over all pixels.
+pub fn contributions(splats: &[Splat], camera: &Camera, width: u32, height: u32, settings: &CpuRenderSettings) -> Vec<f32> {
+    let footprints = footprints(splats, camera, Vec2::new(width as f32, height as f32), settings);
+    rasterize(footprints, width, height, settings, splats.len()).1
+}
+
+/// Indices of the splats from back to front along the view direction, the draw order of
+/// [crate::config::DepthSorting::Cpu]. Splats at the same depth keep their order.
+pub fn depth_order(splats: &[Splat], camera: &Camera) -> Vec<u32> {
+    sort_back_to_front(splats, 0..splats.len() as u32, camera)
+}
+
+/// Like [depth_order], but only of the splats in the view of the camera, see [crate::culling::cull].
+pub fn visible_depth_order(splats: &[Splat], camera: &Camera, frustum_culling_tolerance: f32) -> Vec<u32> {
+    sort_back_to_front(splats, cull(splats, camera, frustum_culling_tolerance), camera)
+}
+
+fn sort_back_to_front(splats: &[Splat], indices: impl IntoIterator<Item = u32>, camera: &Camera) -> Vec<u32> {
+    let mut keys: Vec<(u32, u32)> = indices
+        .into_iter()
+        .map(|index| {
+            // The view looks along -z, so the farthest splats have the lowest z
+            let bits = camera.view.transform_point3(Vec3::from(splats[index as usize].center)).z.to_bits();
+            // Flipped like this the bits of floats order like unsigned integers
+            let key = if bits >> 31 == 1 { !bits } else { bits | 1 << 31 };
+            (key, index)
+        })
+        .collect();
+    keys.sort_unstable();
+    keys.into_iter().map(|(_key, index)| index).collect()
+}
+
+/// Like [render], with the view dependent colors of the scene as seen from the camera.
+pub fn render_scene(scene: &Scene, camera: &Camera, width: u32, height: u32, settings: &CpuRenderSettings) -> CpuImage {
+    render_visible(scene, visible(&scene.splat_data, camera, settings), camera, width, height, settings)
+}
+
+/// Footprints of the visible splats of the scene in their view dependent colors, what [render_scene] blends.
+pub(crate) fn scene_footprints(scene: &Scene, camera: &Camera, width: u32, height: u32, settings: &CpuRenderSettings) -> Vec<Footprint> {
+    let size = Vec2::new(width as f32, height as f32);
+    visible_footprints(scene, visible(&scene.splat_data, camera, settings), camera, size, settings)
+}
+
+/// Like [render_scene], but culls whole chunks of the layout before looking at their splats, see [ChunkLayout::cull].
+/// The layout has to be the one of the scene, see [crate::chunks::sort_into_chunks].
+pub fn render_chunks(scene: &Scene, layout: &ChunkLayout, camera: &Camera, width: u32, height: u32, settings: &CpuRenderSettings) -> CpuImage {
+    let visible = match frustum(camera, settings) {
+        Some(frustum) => {
+            let mut visible = layout.cull_frustum(&scene.splat_data, &frustum);
+            // Splats at the same depth blend in the same order as with render_scene
+            visible.sort_unstable();
+            visible
+        }
+        None => (0..scene.splat_data.len() as u32).collect(),
+    };
+    render_visible(scene, visible, camera, width, height, settings)
+}
+
+fn render_visible(scene: &Scene, visible: Vec<u32>, camera: &Camera, width: u32, height: u32, settings: &CpuRenderSettings) -> CpuImage {
+    let footprints = visible_footprints(scene, visible, camera, Vec2::new(width as f32, height as f32), settings);
+    rasterize(footprints, width, height, settings, 0).0
+}
+
+fn visible_footprints(scene: &Scene, visible: Vec<u32>, camera: &Camera, size: Vec2, settings: &CpuRenderSettings) -> Vec<Footprint> {
+    let eye = camera.view.inverse().w_axis.truncate();
+    visible
+        .into_iter()
+        .filter_map(|index| {
+            let (index, splat) = (index as usize, &scene.splat_data[index as usize]);
+            // Band 0 is the color of the splat, as in the shader the directions point from the camera to the splats
+            let basis = spherical_harmonics_basis((Vec3::from(splat.center) - eye).normalize_or_zero());
+            let coefficients = scene.spherical_harmonics_of(index);
+            let view_dependent: Vec3 = coefficients.iter().zip(&basis[1..]).map(|(coefficient, basis)| *coefficient * *basis).sum();
+            let color = (Vec4::from(splat.color).truncate() + view_dependent).max(Vec3::ZERO).extend(splat.color[3]);
+            footprint(index, splat, color, camera, size, settings)
+        })
+        .collect()
+}
+
+/// Blends the footprints into an image and sums up the contributions of the first `splat_count` splats.
+fn rasterize(mut footprints: Vec<Footprint>, width: u32, height: u32, settings: &CpuRenderSettings, splat_count: usize) -> (CpuImage, Vec<f32>) {
+    footprints.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+
+    let mut image = CpuImage::new(width, height, settings.background);
+    let mut contributions = vec![0.0; splat_count];
+    if width == 0 || height == 0 {
+        return (image, contributions);
+    }
+    let thread_count = std::thread::available_parallelism().map_or(1, |count| count.get());
+    let rows_per_band = (height as usize).div_ceil(thread_count).max(1);
+    std::thread::scope(|scope| {
+        let mut bands = Vec::new();
+        for (band, pixels) in image.pixels.chunks_mut(rows_per_band * width as usize).enumerate() {
+            let footprints = &footprints;
+            bands.push(scope.spawn(move || {
+                let first_row = (band * rows_per_band) as u32;
+                let mut contributions = vec![0.0; splat_count];
+                rasterize_band(footprints, pixels, width, first_row, settings.background, &mut contributions);
+                contributions
+            }));
+        }
+        for band in bands {
+            for (sum, contribution) in contributions.iter_mut().zip(band.join().unwrap()) {
+                *sum += contribution;
+            }
+        }
+    });
+    (image, contributions)
+}
+
+fn rasterize_band(footprints: &[Footprint], pixels: &mut [Vec4], width: u32, first_row: u32, background: Vec4, contributions: &mut [f32]) {
+    let row_count = (pixels.len() / width as usize) as u32;
+    let mut color = vec![Vec3::ZERO; pixels.len()];
+    let mut transmittance = vec![1.0f32; pixels.len()];
+    let band_min = first_row as f32;
+    let band_max = (first_row + row_count) as f32;
+    for footprint in footprints {
+        if footprint.max.y <= band_min || footprint.min.y >= band_max {
+            continue;
+        }
+        let first_y = footprint.min.y.max(band_min) as u32;
+        let last_y = (footprint.max.y.min(band_max).ceil() as u32).min(first_row + row_count);
+        let (first_x, last_x) = (footprint.min.x as u32, (footprint.max.x.ceil() as u32).min(width));
+        for y in first_y..last_y {
+            for x in first_x..last_x {
+                let index = (y - first_row) as usize * width as usize + x as usize;
+                if transmittance[index] < MIN_TRANSMITTANCE {
+                    continue;
+                }
+                let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - footprint.center;
+                let power = footprint.conic.x * offset.x * offset.x + 2.0 * footprint.conic.y * offset.x * offset.y + footprint.conic.z * offset.y * offset.y;
+                let alpha = (footprint.color.w * (-0.5 * power).exp()).min(MAX_ALPHA);
+                if alpha < MIN_ALPHA {
+                    continue;
+                }
+                color[index] += footprint.color.truncate() * alpha * transmittance[index];
+                if let Some(contribution) = contributions.get_mut(footprint.splat) {
+                    *contribution += alpha * transmittance[index];
+                }
+                transmittance[index] *= 1.0 - alpha;
+            }
+        }
+    }
+    for ((pixel, color), transmittance) in pixels.iter_mut().zip(color).zip(transmittance) {
+        *pixel = (color + background.truncate() * transmittance).extend(1.0 - transmittance + background.w * transmittance);
+    }
+}
@@ -0,0 +1,121 @@
+//! Frustum culling on the CPU
+//!
+//! Follows `isInFrustum` of the shaders: A position is inside if its normalized device coordinates are within the
+//! `frustum_culling_tolerance` horizontally and vertically, and from 0 to 1 in depth. Tolerances above 1 keep a margin
+//! around the image. Unlike the shaders, which only test the center, the CPU culler keeps every splat whose sphere of
+//! three standard deviations reaches into that frustum, so that splats at the edges of the image are not cut off.
+
+use crate::scene::{Camera, Splat};
+use glam::{Mat4, Vec3, Vec4};
+
+/// Radius of the bounding sphere of a splat in standard deviations.
+pub const CULLING_SIGMAS: f32 = 3.0;
+
+/// Below this many splats per thread culling is not worth spawning threads.
+const MIN_CHUNK_SIZE: usize = 4096;
+
+/// `worldToClipSpace` of the shaders: The normalized device coordinates of a position.
+pub fn world_to_clip_space(view_projection: &Mat4, position: Vec3) -> Vec3 {
+    let homogenous = *view_projection * position.extend(1.0);
+    homogenous.truncate() / (homogenous.w + 0.0000001)
+}
+
+/// `isInFrustum` of the shaders.
+pub fn is_in_frustum(clip_space_position: Vec3, tolerance: f32) -> bool {
+    clip_space_position.x.abs() < tolerance && clip_space_position.y.abs() < tolerance && (clip_space_position.z - 0.5).abs() < 0.5
+}
+
+/// Radius of the sphere around the center of a splat which holds [CULLING_SIGMAS] of its standard deviations.
+pub fn bounding_radius(splat: &Splat) -> f32 {
+    CULLING_SIGMAS * splat.scale[0].abs().max(splat.scale[1].abs())
+}
+
+/// Where a volume is relative to a [Frustum].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+/// The region [is_in_frustum] accepts as planes in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Normal and offset of every plane, positive inside.
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    pub fn new(camera: &Camera, tolerance: f32) -> Self {
+        Self::from_view_projection(&(camera.projection * camera.view), tolerance)
+    }
+
+    pub fn from_view_projection(view_projection: &Mat4, tolerance: f32) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|index| view_projection.row(index));
+        // Multiplied by w the bounds of the normalized device coordinates become linear
+        Self {
+            planes: [w * tolerance - x, w * tolerance + x, w * tolerance - y, w * tolerance + y, z, w - z],
+        }
+    }
+
+    /// Whether the sphere reaches into the frustum. Near the corners this can keep spheres just outside of it.
+    pub fn contains_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center) + plane.w > -radius * plane.truncate().length())
+    }
+
+    pub fn contains_splat(&self, splat: &Splat) -> bool {
+        self.contains_sphere(Vec3::from(splat.center), bounding_radius(splat))
+    }
+
+    /// Where the axis aligned box is. Like [Frustum::contains_sphere] this tests plane by plane, so spheres in a box
+    /// which is [Containment::Outside] are never contained and the ones in a box which is [Containment::Inside] always.
+    pub fn classify_box(&self, min: Vec3, max: Vec3) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            let normal = plane.truncate();
+            // The corners farthest inside and outside along the normal
+            let inner = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+            let outer = Vec3::select(normal.cmpge(Vec3::ZERO), min, max);
+            if normal.dot(inner) + plane.w <= 0.0 {
+                return Containment::Outside;
+            }
+            if normal.dot(outer) + plane.w <= 0.0 {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+}
+
+/// Indices of the splats which reach into the view of the camera in ascending order, see [Frustum::contains_splat].
+/// Chunks of the splats are culled in parallel.
+pub fn cull(splats: &[Splat], camera: &Camera, tolerance: f32) -> Vec<u32> {
+    cull_frustum(splats, &Frustum::new(camera, tolerance))
+}
+
+/// Like [cull], with any frustum.
+pub fn cull_frustum(splats: &[Splat], frustum: &Frustum) -> Vec<u32> {
+    let thread_count = std::thread::available_parallelism().map_or(1, |count| count.get());
+    let chunk_size = splats.len().div_ceil(thread_count).max(MIN_CHUNK_SIZE);
+    let cull_chunk = |(chunk, splats): (usize, &[Splat])| -> Vec<u32> {
+        let first = chunk * chunk_size;
+        (first..)
+            .zip(splats)
+            .filter(|(_index, splat)| frustum.contains_splat(splat))
+            .map(|(index, _splat)| index as u32)
+            .collect()
+    };
+    if splats.len() <= chunk_size {
+        return cull_chunk((0, splats));
+    }
+    std::thread::scope(|scope| {
+        let chunks: Vec<_> = splats
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|chunk| scope.spawn(move || cull_chunk(chunk)))
+            .collect();
+        chunks.into_iter().flat_map(|chunk| chunk.join().unwrap()).collect()
+    })
+}
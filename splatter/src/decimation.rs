@@ -0,0 +1,216 @@
+//! Reduction of scenes to fewer splats by importance
+//!
+//! Scenes larger than the GPU can take, see `Config::max_splat_count`, are reduced to a target count by ranking their
+//! splats. The importance of a splat is either how much it covers, its opacity times the area of its ellipse, or how
+//! much it actually contributes to renders from views around the scene, which also accounts for occlusion. The least
+//! important splats are dropped or merged into their nearest remaining neighbor. Renders of the same views before and
+//! after measure what the reduction cost.
+
+use crate::cpu_renderer::{contributions, render_scene, CpuRenderSettings};
+use crate::evaluation::ImageMetrics;
+use crate::lod::merge_splats;
+use crate::scene::{Camera, Scene, Splat};
+use crate::spatial::PointGrid;
+use crate::statistics::Bounds;
+use glam::{Mat4, Vec3};
+use std::fmt;
+
+/// Opacity times the area of the ellipse, up to the constant factor of π.
+pub fn importance(splat: &Splat) -> f32 {
+    splat.color[3].max(0.0) * splat.scale[0].abs() * splat.scale[1].abs()
+}
+
+/// How splats are ranked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Importance {
+    /// See [importance].
+    #[default]
+    OpacityArea,
+    /// The contribution to renders of the sample views, see [crate::cpu_renderer::contributions].
+    Contribution,
+}
+
+/// What happens to the least important splats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecimationMode {
+    #[default]
+    Drop,
+    /// Merged into the nearest remaining splat if it is within reach of both, dropped otherwise.
+    Merge,
+}
+
+/// Parameters of [decimate_with_report].
+#[derive(Debug, Clone)]
+pub struct DecimationSettings {
+    pub target_count: usize,
+    pub importance: Importance,
+    pub mode: DecimationMode,
+    /// Views around the scene for [Importance::Contribution] and the quality measurement, which zero skips.
+    pub view_count: usize,
+    pub view_width: u32,
+    pub view_height: u32,
+    pub render: CpuRenderSettings,
+}
+
+impl Default for DecimationSettings {
+    fn default() -> Self {
+        Self {
+            target_count: 0,
+            importance: Importance::default(),
+            mode: DecimationMode::default(),
+            view_count: 8,
+            view_width: 160,
+            view_height: 120,
+            render: CpuRenderSettings::default(),
+        }
+    }
+}
+
+/// What a decimation did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecimationReport {
+    pub input_count: usize,
+    pub output_count: usize,
+    /// Removed splats which were merged into remaining ones instead of dropped.
+    pub merged: usize,
+    /// Mean over the sample views of the renders after compared to the ones before.
+    pub quality: Option<ImageMetrics>,
+}
+
+impl DecimationReport {
+    pub fn removed(&self) -> usize {
+        self.input_count - self.output_count
+    }
+}
+
+impl fmt::Display for DecimationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "splats:  {}", self.input_count)?;
+        writeln!(f, "removed: {} ({} merged)", self.removed(), self.merged)?;
+        write!(f, "kept:    {}", self.output_count)?;
+        if let Some(quality) = &self.quality {
+            write!(f, "\npsnr:    {:.3}\nssim:    {:.4}", quality.psnr, quality.ssim)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cameras on a ring around the bounds of the scene, 30° above them and looking at their center.
+pub fn sample_views(scene: &Scene, count: usize, aspect_ratio: f32) -> Vec<Camera> {
+    let Some(bounds) = Bounds::of_centers(&scene.splat_data) else {
+        return Vec::new();
+    };
+    let center = (bounds.min + bounds.max) * 0.5;
+    let radius = (bounds.size().length() * 0.5).max(1.0e-3);
+    let fov_y = 60.0_f32.to_radians();
+    let distance = radius / (fov_y * 0.5).sin();
+    (0..count)
+        .map(|index| {
+            let azimuth = std::f32::consts::TAU * index as f32 / count as f32;
+            let elevation = 30.0_f32.to_radians();
+            let direction = Vec3::new(azimuth.sin() * elevation.cos(), elevation.sin(), azimuth.cos() * elevation.cos());
+            let view = Mat4::look_at_rh(center + direction * distance, center, Vec3::Y);
+            Camera::perspective(view, fov_y, aspect_ratio, distance * 1.0e-3, distance + 2.0 * radius)
+        })
+        .collect()
+}
+
+/// Importance of every splat, higher is more important.
+pub fn importance_scores(scene: &Scene, importance: Importance, views: &[Camera], settings: &DecimationSettings) -> Vec<f32> {
+    match importance {
+        Importance::OpacityArea => scene.splat_data.iter().map(self::importance).collect(),
+        Importance::Contribution => {
+            let mut scores = vec![0.0; scene.splat_data.len()];
+            for camera in views {
+                let view_scores = contributions(&scene.splat_data, camera, settings.view_width, settings.view_height, &settings.render);
+                for (score, contribution) in scores.iter_mut().zip(view_scores) {
+                    *score += contribution;
+                }
+            }
+            scores
+        }
+    }
+}
+
+/// Which splats to keep: The `target_count` with the highest scores, ties keep the splats in front.
+fn select(scores: &[f32], target_count: usize) -> Vec<bool> {
+    let mut ranking: Vec<usize> = (0..scores.len()).collect();
+    ranking.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]).then(a.cmp(b)));
+    let mut keep = vec![false; scores.len()];
+    for index in ranking.iter().take(target_count) {
+        keep[*index] = true;
+    }
+    keep
+}
+
+/// Merges every removed splat into the nearest kept one if they overlap within three standard deviations.
+/// Returns how many were merged.
+fn merge_removed(scene: &mut Scene, keep: &[bool]) -> usize {
+    let kept: Vec<usize> = (0..keep.len()).filter(|index| keep[*index]).collect();
+    if kept.is_empty() {
+        return 0;
+    }
+    let reach = |splat: &Splat| 3.0 * splat.scale[0].max(splat.scale[1]);
+    let centers: Vec<Vec3> = kept.iter().map(|index| Vec3::from(scene.splat_data[*index].center)).collect();
+    let grid = PointGrid::with_density(&centers, 4.0);
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); kept.len()];
+    let mut merged = 0;
+    for (index, splat) in scene.splat_data.iter().enumerate().filter(|(index, _)| !keep[*index]) {
+        let Some(&(nearest, distance)) = grid.k_nearest(Vec3::from(splat.center), 1).first() else {
+            continue;
+        };
+        if distance <= reach(splat) + reach(&scene.splat_data[kept[nearest]]) {
+            groups[nearest].push(index);
+            merged += 1;
+        }
+    }
+    for (kept, group) in kept.iter().zip(groups) {
+        if group.is_empty() {
+            continue;
+        }
+        let splats = &scene.splat_data;
+        let merged_splat = merge_splats(std::iter::once(&splats[*kept]).chain(group.iter().map(|index| &splats[*index])));
+        scene.splat_data[*kept] = merged_splat;
+    }
+    merged
+}
+
+/// Removes the least important splats until at most `target_count` are left. Returns how many were removed.
+pub fn decimate(scene: &mut Scene, target_count: usize) -> usize {
+    if scene.splat_data.len() <= target_count {
+        return 0;
+    }
+    let scores: Vec<f32> = scene.splat_data.iter().map(importance).collect();
+    let keep = select(&scores, target_count);
+    scene.retain(|index, _splat| keep[index])
+}
+
+/// Reduces the scene to at most `target_count` splats and measures the quality loss in renders of sample views.
+pub fn decimate_with_report(scene: &mut Scene, settings: &DecimationSettings) -> DecimationReport {
+    let input_count = scene.splat_data.len();
+    let aspect_ratio = settings.view_width as f32 / settings.view_height.max(1) as f32;
+    let views = sample_views(scene, settings.view_count, aspect_ratio);
+    let render = |scene: &Scene, camera: &Camera| render_scene(scene, camera, settings.view_width, settings.view_height, &settings.render);
+    let before: Vec<_> = views.iter().map(|camera| render(scene, camera)).collect();
+
+    let mut merged = 0;
+    if input_count > settings.target_count {
+        let keep = select(&importance_scores(scene, settings.importance, &views, settings), settings.target_count);
+        if settings.mode == DecimationMode::Merge {
+            merged = merge_removed(scene, &keep);
+        }
+        scene.retain(|index, _splat| keep[index]);
+    }
+
+    let comparisons: Vec<ImageMetrics> = views
+        .iter()
+        .zip(&before)
+        .map(|(camera, before)| ImageMetrics::compare(&render(scene, camera), before, false))
+        .collect();
+    DecimationReport {
+        input_count,
+        output_count: scene.splat_data.len(),
+        merged,
+        quality: ImageMetrics::mean(&comparisons),
+    }
+}
@@ -0,0 +1,350 @@
+//! Image quality of renders against the training photos
+//!
+//! Every training view is rendered with the [crate::cpu_renderer] at the size of its photo and compared by PSNR, SSIM
+//! and optionally the gradient magnitude similarity deviation (GMSD), a cheap perceptual proxy which needs no learned
+//! weights, unlike LPIPS. Photos with transparency are blended over the background of the render first.
+
+use crate::cpu_renderer::{render_scene, CpuImage, CpuRenderSettings};
+use crate::scene::Scene;
+use crate::training_cameras::TrainingCamera;
+use glam::{Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Identical images have an infinite PSNR, which is capped to keep reports finite.
+pub const MAX_PSNR: f32 = 100.0;
+
+/// Standard deviation in pixels of the Gaussian window of SSIM.
+const SSIM_SIGMA: f32 = 1.5;
+
+/// Stabilize the SSIM ratios for images with a dynamic range of 1.
+const SSIM_C1: f32 = 0.01 * 0.01;
+const SSIM_C2: f32 = 0.03 * 0.03;
+
+/// Stabilizes the GMSD ratio, 170 for a dynamic range of 255.
+const GMSD_C: f32 = 170.0 / (255.0 * 255.0);
+
+/// Peak signal to noise ratio in decibels of the color channels.
+pub fn psnr(image: &CpuImage, reference: &CpuImage) -> f32 {
+    assert_eq!((image.width, image.height), (reference.width, reference.height));
+    let squared_error: f32 = image
+        .pixels
+        .iter()
+        .zip(&reference.pixels)
+        .map(|(a, b)| (a.truncate() - b.truncate()).length_squared())
+        .sum();
+    let mean_squared_error = squared_error / (3 * image.pixels.len()).max(1) as f32;
+    if mean_squared_error <= 0.0 {
+        return MAX_PSNR;
+    }
+    (-10.0 * mean_squared_error.log10()).min(MAX_PSNR)
+}
+
+/// One channel of an image as rows of values.
+struct Plane {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl Plane {
+    fn from_image(image: &CpuImage, channel: impl Fn(Vec4) -> f32) -> Self {
+        Self {
+            width: image.width as usize,
+            height: image.height as usize,
+            values: image.pixels.iter().map(|pixel| channel(*pixel)).collect(),
+        }
+    }
+
+    fn map(&self, other: &Plane, f: impl Fn(f32, f32) -> f32) -> Self {
+        Self {
+            values: self.values.iter().zip(&other.values).map(|(a, b)| f(*a, *b)).collect(),
+            ..*self
+        }
+    }
+
+    /// Weighted average of the neighborhood of every value, with the weights renormalized at the borders.
+    fn convolve(&self, kernel: &[f32]) -> Self {
+        let radius = (kernel.len() / 2) as isize;
+        let pass = |values: &[f32], length: usize, stride: usize, step: usize, count: usize| {
+            let mut result = vec![0.0; values.len()];
+            for line in 0..count {
+                for position in 0..length {
+                    let (mut sum, mut weight_sum) = (0.0, 0.0);
+                    for (offset, weight) in kernel.iter().enumerate() {
+                        let neighbor = position as isize + offset as isize - radius;
+                        if (0..length as isize).contains(&neighbor) {
+                            sum += weight * values[line * stride + neighbor as usize * step];
+                            weight_sum += weight;
+                        }
+                    }
+                    result[line * stride + position * step] = sum / weight_sum;
+                }
+            }
+            result
+        };
+        let rows = pass(&self.values, self.width, self.width, 1, self.height);
+        let values = pass(&rows, self.height, 1, self.width, self.width);
+        Self { values, ..*self }
+    }
+
+    fn get(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.values[y * self.width + x]
+    }
+
+    /// Magnitude of the Prewitt gradient.
+    fn gradient_magnitude(&self) -> Self {
+        let mut values = Vec::with_capacity(self.values.len());
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let (mut dx, mut dy) = (0.0, 0.0);
+                for offset in -1..=1 {
+                    dx += self.get(x + 1, y + offset) - self.get(x - 1, y + offset);
+                    dy += self.get(x + offset, y + 1) - self.get(x + offset, y - 1);
+                }
+                values.push((dx * dx + dy * dy).sqrt() / 3.0);
+            }
+        }
+        Self { values, ..*self }
+    }
+
+    fn mean(&self) -> f32 {
+        self.values.iter().sum::<f32>() / self.values.len().max(1) as f32
+    }
+}
+
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (3.0 * sigma).ceil() as i32;
+    (-radius..=radius).map(|offset| (-0.5 * (offset as f32 / sigma).powi(2)).exp()).collect()
+}
+
+fn color_channels(image: &CpuImage) -> [Plane; 3] {
+    [0, 1, 2].map(|channel| Plane::from_image(image, |pixel| pixel[channel]))
+}
+
+/// Mean structural similarity of the color channels with a Gaussian window.
+pub fn ssim(image: &CpuImage, reference: &CpuImage) -> f32 {
+    assert_eq!((image.width, image.height), (reference.width, reference.height));
+    if image.pixels.is_empty() {
+        return 1.0;
+    }
+    let kernel = gaussian_kernel(SSIM_SIGMA);
+    let channels = color_channels(image).into_iter().zip(color_channels(reference)).map(|(x, y)| {
+        let mean_x = x.convolve(&kernel);
+        let mean_y = y.convolve(&kernel);
+        let variance_x = x
+            .map(&x, |a, b| a * b)
+            .convolve(&kernel)
+            .map(&mean_x, |squares, mean| squares - mean * mean);
+        let variance_y = y
+            .map(&y, |a, b| a * b)
+            .convolve(&kernel)
+            .map(&mean_y, |squares, mean| squares - mean * mean);
+        let products = x.map(&y, |a, b| a * b).convolve(&kernel);
+        let map = (0..x.values.len()).map(|index| {
+            let (mx, my) = (mean_x.values[index], mean_y.values[index]);
+            let covariance = products.values[index] - mx * my;
+            let (vx, vy) = (variance_x.values[index], variance_y.values[index]);
+            ((2.0 * mx * my + SSIM_C1) * (2.0 * covariance + SSIM_C2)) / ((mx * mx + my * my + SSIM_C1) * (vx + vy + SSIM_C2))
+        });
+        map.sum::<f32>() / x.values.len() as f32
+    });
+    channels.sum::<f32>() / 3.0
+}
+
+/// Gradient magnitude similarity deviation of the luminance, zero for identical images and growing with distortion.
+pub fn gmsd(image: &CpuImage, reference: &CpuImage) -> f32 {
+    assert_eq!((image.width, image.height), (reference.width, reference.height));
+    let luminance = |pixel: Vec4| pixel.truncate().dot(Vec3::new(0.299, 0.587, 0.114));
+    let gradients = Plane::from_image(image, luminance).gradient_magnitude();
+    let reference_gradients = Plane::from_image(reference, luminance).gradient_magnitude();
+    let similarity = gradients.map(&reference_gradients, |a, b| (2.0 * a * b + GMSD_C) / (a * a + b * b + GMSD_C));
+    let mean = similarity.mean();
+    similarity.map(&similarity, |value, _| (value - mean).powi(2)).mean().sqrt()
+}
+
+/// Blends an image over an opaque background, dropping its transparency.
+pub fn blend_over(image: &CpuImage, background: Vec4) -> CpuImage {
+    CpuImage {
+        pixels: image
+            .pixels
+            .iter()
+            .map(|pixel| (pixel.truncate() * pixel.w + background.truncate() * (1.0 - pixel.w)).extend(1.0))
+            .collect(),
+        ..*image
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImageMetrics {
+    /// Decibels, higher is better.
+    pub psnr: f32,
+    /// Up to 1 for identical images.
+    pub ssim: f32,
+    /// Perceptual distance, lower is better.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gmsd: Option<f32>,
+}
+
+impl ImageMetrics {
+    pub fn compare(image: &CpuImage, reference: &CpuImage, perceptual: bool) -> Self {
+        Self {
+            psnr: psnr(image, reference),
+            ssim: ssim(image, reference),
+            gmsd: perceptual.then(|| gmsd(image, reference)),
+        }
+    }
+
+    /// Average of the metrics of several images.
+    pub fn mean<'a>(metrics: impl IntoIterator<Item = &'a ImageMetrics>) -> Option<Self> {
+        let mut count = 0;
+        let mut sum = ImageMetrics {
+            psnr: 0.0,
+            ssim: 0.0,
+            gmsd: Some(0.0),
+        };
+        for metrics in metrics {
+            count += 1;
+            sum.psnr += metrics.psnr;
+            sum.ssim += metrics.ssim;
+            sum.gmsd = sum.gmsd.zip(metrics.gmsd).map(|(sum, gmsd)| sum + gmsd);
+        }
+        (count > 0).then(|| ImageMetrics {
+            psnr: sum.psnr / count as f32,
+            ssim: sum.ssim / count as f32,
+            gmsd: sum.gmsd.map(|gmsd| gmsd / count as f32),
+        })
+    }
+}
+
+impl fmt::Display for ImageMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>8.3} {:>7.4}", self.psnr, self.ssim)?;
+        match self.gmsd {
+            Some(gmsd) => write!(f, " {gmsd:>7.4}"),
+            None => write!(f, " {:>7}", "-"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewEvaluation {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(flatten)]
+    pub metrics: ImageMetrics,
+}
+
+/// Metrics of every evaluated view and their average.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    pub views: Vec<ViewEvaluation>,
+    /// Absent without any evaluated views.
+    pub mean: Option<ImageMetrics>,
+    /// Views without a photo, which were skipped.
+    pub missing: Vec<String>,
+}
+
+impl EvaluationReport {
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+impl fmt::Display for EvaluationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name_width = self.views.iter().map(|view| view.name.len()).chain([4]).max().unwrap_or(4);
+        writeln!(f, "{:name_width$} {:>8} {:>7} {:>7}", "view", "psnr", "ssim", "gmsd")?;
+        for view in &self.views {
+            writeln!(f, "{:name_width$} {}", view.name, view.metrics)?;
+        }
+        if let Some(mean) = &self.mean {
+            writeln!(f, "{:name_width$} {mean}", "mean")?;
+        }
+        write!(f, "evaluated: {}, missing: {}", self.views.len(), self.missing.len())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EvaluationSettings {
+    pub render: CpuRenderSettings,
+    /// Whether to compute the GMSD, which takes about as long as SSIM.
+    pub perceptual: bool,
+    pub z_near: f32,
+    pub z_far: f32,
+}
+
+impl Default for EvaluationSettings {
+    fn default() -> Self {
+        Self {
+            render: CpuRenderSettings::default(),
+            perceptual: true,
+            z_near: 0.01,
+            z_far: 100.0,
+        }
+    }
+}
+
+/// Renders the scene with its view dependent colors from a training view at the size of its photo and compares them.
+pub fn evaluate_view(scene: &Scene, camera: &TrainingCamera, photo: &CpuImage, settings: &EvaluationSettings) -> ImageMetrics {
+    let camera = camera.scaled(photo.width, photo.height);
+    let image = render_scene(
+        scene,
+        &camera.camera(settings.z_near, settings.z_far),
+        photo.width,
+        photo.height,
+        &settings.render,
+    );
+    ImageMetrics::compare(&image, &blend_over(photo, settings.render.background), settings.perceptual)
+}
+
+/// Where the photo of a training view is, trying common extensions for names without one like those of Inria.
+pub fn find_photo(images: &Path, camera: &TrainingCamera) -> Option<PathBuf> {
+    let path = images.join(&camera.name);
+    if path.extension().is_some() {
+        return path.is_file().then_some(path);
+    }
+    ["png", "jpg", "JPG", "jpeg", "JPEG"]
+        .into_iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|path| path.is_file())
+}
+
+/// Evaluates all training views which have a photo in the `images` directory.
+pub fn evaluate(
+    scene: &Scene,
+    cameras: &[TrainingCamera],
+    images: impl AsRef<Path>,
+    settings: &EvaluationSettings,
+) -> io::Result<EvaluationReport> {
+    let mut report = EvaluationReport {
+        views: Vec::new(),
+        mean: None,
+        missing: Vec::new(),
+    };
+    for camera in cameras {
+        let Some(path) = find_photo(images.as_ref(), camera) else {
+            report.missing.push(camera.name.clone());
+            continue;
+        };
+        let photo = CpuImage::load(&path).map_err(|error| io::Error::new(error.kind(), format!("{}: {error}", path.display())))?;
+        report.views.push(ViewEvaluation {
+            name: camera.name.clone(),
+            width: photo.width,
+            height: photo.height,
+            metrics: evaluate_view(scene, camera, &photo, settings),
+        });
+    }
+    report.mean = ImageMetrics::mean(report.views.iter().map(|view| &view.metrics));
+    Ok(report)
+}
@@ -0,0 +1,295 @@
+//! Rasterizer on the GPU for offscreen renders
+//!
+//! The splats are culled, projected and colored on the CPU exactly like in [crate::cpu_renderer], and the GPU blends
+//! their footprints front to back into a half float target, one instanced quad per splat. The images match the ones of
+//! the CPU reference up to rounding. [GpuRasterizer::new] needs an adapter, so callers have to cope without one.
+
+use crate::cpu_renderer::{scene_footprints, CpuImage, CpuRenderSettings, Footprint, MAX_ALPHA, MIN_ALPHA};
+use crate::scene::{Camera, Scene};
+use bevy::tasks::block_on;
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Bytes of a pixel of the [TARGET_FORMAT].
+const PIXEL_BYTES: u32 = 8;
+
+const SHADER: &str = "
+struct Uniforms {
+    image_size: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) center: vec2<f32>,
+    @location(1) @interpolate(flat) conic: vec3<f32>,
+    @location(2) @interpolate(flat) color: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) bounds: vec4<f32>,
+    @location(1) center: vec2<f32>,
+    @location(2) conic: vec3<f32>,
+    @location(3) color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // Every pixel the footprint touches, like the loops of the CPU renderer
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u));
+    let pixel = mix(floor(bounds.xy), ceil(bounds.zw), corner);
+    let ndc = pixel / uniforms.image_size * 2.0 - 1.0;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.center = center;
+    out.conic = conic;
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The position is the center of the pixel, with y down like the pixels of the CPU renderer
+    let offset = in.position.xy - in.center;
+    let power = in.conic.x * offset.x * offset.x + 2.0 * in.conic.y * offset.x * offset.y + in.conic.z * offset.y * offset.y;
+    let alpha = min(in.color.a * exp(-0.5 * power), MAX_ALPHA);
+    if alpha < MIN_ALPHA {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb * alpha, alpha);
+}
+";
+
+/// A [Footprint] as the instance data of the shader.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Instance {
+    bounds: [f32; 4],
+    center: [f32; 2],
+    conic: [f32; 3],
+    color: [f32; 4],
+}
+
+impl From<&Footprint> for Instance {
+    fn from(footprint: &Footprint) -> Self {
+        Self {
+            bounds: [footprint.min.x, footprint.min.y, footprint.max.x, footprint.max.y],
+            center: footprint.center.to_array(),
+            conic: footprint.conic.to_array(),
+            color: footprint.color.to_array(),
+        }
+    }
+}
+
+/// Device and pipeline to render with on the GPU.
+pub struct GpuRasterizer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl GpuRasterizer {
+    /// Requests the default adapter, None if there is none.
+    pub fn new() -> Option<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("GPU Rasterizer Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!(
+                "const MIN_ALPHA: f32 = {MIN_ALPHA:?};\nconst MAX_ALPHA: f32 = {MAX_ALPHA:?};\n{SHADER}"
+            ))),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GPU Rasterizer Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GPU Rasterizer Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        // Front to back: Every splat only adds what the ones in front of it still let through
+        let under = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("GPU Rasterizer Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vertex",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Instance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x2, 2 => Float32x3, 3 => Float32x4],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fragment",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: TARGET_FORMAT,
+                    blend: Some(wgpu::BlendState { color: under, alpha: under }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Some(Self {
+            device,
+            queue,
+            pipeline,
+            bind_group_layout,
+        })
+    }
+
+    /// Like [crate::cpu_renderer::render_scene], but blends on the GPU.
+    pub fn render_scene(&self, scene: &Scene, camera: &Camera, width: u32, height: u32, settings: &CpuRenderSettings) -> CpuImage {
+        if width == 0 || height == 0 {
+            return CpuImage::new(width, height, settings.background);
+        }
+        let mut footprints = scene_footprints(scene, camera, width, height, settings);
+        footprints.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+        let instances: Vec<Instance> = footprints.iter().map(Instance::from).collect();
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("GPU Rasterizer Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TARGET_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let uniforms = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GPU Rasterizer Uniforms"),
+            contents: bytemuck::cast_slice(&[width as f32, height as f32, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("GPU Rasterizer Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniforms.as_entire_binding(),
+            }],
+        });
+        // Rows of a copy have to be aligned
+        let row_bytes = (width * PIXEL_BYTES).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Rasterizer Readback"),
+            size: (row_bytes * height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Buffers can not be empty
+        let instance_buffer = (!instances.is_empty()).then(|| {
+            self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("GPU Rasterizer Footprints"),
+                contents: bytemuck::cast_slice(&instances),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("GPU Rasterizer Encoder"),
+        });
+        {
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("GPU Rasterizer Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            if let Some(instance_buffer) = &instance_buffer {
+                render_pass.set_pipeline(&self.pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                render_pass.draw(0..4, 0..instances.len() as u32);
+            }
+        }
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let mut image = CpuImage::new(width, height, settings.background);
+        let data = slice.get_mapped_range();
+        for (y, row) in data.chunks_exact(row_bytes as usize).enumerate() {
+            for (x, pixel) in row[..(width * PIXEL_BYTES) as usize].chunks_exact(PIXEL_BYTES as usize).enumerate() {
+                let channel = |index: usize| f16_to_f32(u16::from_le_bytes([pixel[2 * index], pixel[2 * index + 1]]));
+                let (color, coverage) = (Vec3::new(channel(0), channel(1), channel(2)), channel(3));
+                // The background shines through what the splats leave uncovered
+                let (background, transmittance) = (settings.background, 1.0 - coverage);
+                let pixel = (color + background.truncate() * transmittance).extend(coverage + background.w * transmittance);
+                image.set_pixel(x as u32, y as u32, pixel);
+            }
+        }
+        image
+    }
+}
+
+/// Half floats have no counterpart in the standard library.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits >> 15 == 1 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2.0f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2.0f32.powi(exponent - 15),
+    }
+}
@@ -0,0 +1,327 @@
+//! Bullet impact effects painted into the splat scene
+//!
+//! Impacts tint or delete the splats around the hit in place, so that indices stay stable and only the touched range
+//! has to be copied to the GPU again. Debris and scorch marks are short-lived splats taken from a pool which is reserved
+//! once at the end of the scene, so that the number of splats does not change either. The pool is a source of the
+//! scene named [EFFECT_POOL_SOURCE], so that it can be found and removed again, see [SceneDamage::remove_pools].
+//! Every modification is recorded, so that impacts can be undone or the scene can be reset to its original state.
+//! Any other change to the number of splats, like loading, streaming, merging or deleting, invalidates the record, see
+//! [SceneDamage::fits].
+
+use crate::scene::{Scene, SceneSource, Splat};
+use bevy::prelude::*;
+use glam::{Affine3A, Mat4, Vec3};
+
+/// Name of the [SceneSource] of the splats reserved for effects.
+pub const EFFECT_POOL_SOURCE: &str = "impact effects";
+
+/// Parameters of the effects an [Impact] has on the scene.
+#[derive(Resource, Debug, Clone)]
+pub struct ImpactSettings {
+    /// Splats within this distance of the hit are tinted, zero disables tinting.
+    pub radius: f32,
+    /// Multiplied onto the color of the splats at the center of the impact, fading out towards the radius.
+    pub tint: [f32; 3],
+    /// Splats within this distance of the hit are deleted, zero disables destruction.
+    pub destruction_radius: f32,
+    pub debris_count: usize,
+    pub debris_scale: f32,
+    pub debris_speed: f32,
+    pub debris_lifetime: f32,
+    pub scorch_scale: f32,
+    pub scorch_lifetime: f32,
+    pub gravity: f32,
+}
+
+impl Default for ImpactSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.3,
+            tint: [0.25, 0.22, 0.2],
+            destruction_radius: 0.0,
+            debris_count: 8,
+            debris_scale: 0.02,
+            debris_speed: 2.0,
+            debris_lifetime: 1.0,
+            scorch_scale: 0.15,
+            scorch_lifetime: 10.0,
+            gravity: -9.81,
+        }
+    }
+}
+
+/// Where a bullet hit the scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Impact {
+    pub position: Vec3,
+    /// Surface normal facing the shooter.
+    pub normal: Vec3,
+}
+
+/// A splat of the pool which is currently in use.
+#[derive(Debug, Clone)]
+struct Effect {
+    velocity: Vec3,
+    alpha: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Records the damage done to a [Scene] and owns the pool of effect splats at its end.
+///
+/// A resource for the [Scene] resource and a component next to every [Scene] component.
+#[derive(Resource, Component)]
+pub struct SceneDamage {
+    /// Number of splats in the scene before the pool was reserved.
+    base_splat_count: usize,
+    /// Number of splats in the scene after the pool was reserved.
+    splat_count: usize,
+    /// Previous state of all splats modified by each impact, in order.
+    history: Vec<Vec<(usize, Splat)>>,
+    /// Number of impacts applied so far, which seeds their effects.
+    applied: u32,
+    effects: Vec<Option<Effect>>,
+    next_effect: usize,
+}
+
+impl SceneDamage {
+    /// Reserves `effect_capacity` invisible splats at the end of the scene for debris and scorch marks. Their view
+    /// dependent colors are zero.
+    pub fn new(scene: &mut Scene, effect_capacity: usize) -> Self {
+        let base_splat_count = scene.splat_data.len();
+        let mut pool = Scene::from_splats((0..effect_capacity).map(|_| effect_splat(Vec3::ZERO, Vec3::Y, 0.0, [0.0; 4])).collect());
+        pool.coordinate_system = scene.coordinate_system;
+        scene.append(&mut pool);
+        scene.sources.push(SceneSource {
+            name: EFFECT_POOL_SOURCE.to_string(),
+            transform: Affine3A::IDENTITY,
+            splats: base_splat_count..scene.splat_count,
+        });
+        Self {
+            base_splat_count,
+            splat_count: scene.splat_count,
+            history: Vec::new(),
+            applied: 0,
+            effects: vec![None; effect_capacity],
+            next_effect: 0,
+        }
+    }
+
+    /// Removes the splats of all pools from the scene, along with the effects still visible in them, wherever the pools
+    /// ended up after splats were added or removed. Damage done to the other splats stays. Returns how many were removed.
+    pub fn remove_pools(scene: &mut Scene) -> usize {
+        let pools: Vec<_> = scene
+            .sources
+            .iter()
+            .filter(|source| source.name == EFFECT_POOL_SOURCE)
+            .map(|source| source.splats.clone())
+            .collect();
+        if pools.is_empty() {
+            return 0;
+        }
+        let removed = scene.retain(|index, _splat| !pools.iter().any(|pool| pool.contains(&index)));
+        scene.sources.retain(|source| source.name != EFFECT_POOL_SOURCE);
+        removed
+    }
+
+    /// Whether the scene still has the splats it had when the pool was reserved. Once splats were added or removed,
+    /// the recorded indices point at other splats, so that nothing is modified anymore and a new record is needed.
+    pub fn fits(&self, scene: &Scene) -> bool {
+        scene.splat_data.len() == self.splat_count
+    }
+
+    /// Number of impacts which can be undone.
+    pub fn impact_count(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Number of debris and scorch mark splats which are currently visible.
+    pub fn active_effect_count(&self) -> usize {
+        self.effects.iter().filter(|effect| effect.is_some()).count()
+    }
+
+    /// Tints and deletes splats around the impact and spawns debris and a scorch mark.
+    /// Returns the number of splats which were deleted.
+    pub fn apply_impact(&mut self, scene: &mut Scene, impact: &Impact, settings: &ImpactSettings) -> usize {
+        if !self.fits(scene) {
+            return 0;
+        }
+        let mut previous = Vec::new();
+        let mut destroyed = 0;
+        for (index, splat) in scene.splat_data[..self.base_splat_count].iter_mut().enumerate() {
+            let distance = Vec3::from(splat.center).distance(impact.position);
+            if distance > settings.radius.max(settings.destruction_radius) || splat.color[3] == 0.0 {
+                continue;
+            }
+            previous.push((index, splat.clone()));
+            if settings.destruction_radius > 0.0 && distance <= settings.destruction_radius {
+                splat.color[3] = 0.0;
+                destroyed += 1;
+            }
+            let weight = if settings.radius > 0.0 {
+                (1.0 - distance / settings.radius).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            for (channel, tint) in splat.color.iter_mut().zip(settings.tint) {
+                *channel *= 1.0 + (tint - 1.0) * weight;
+            }
+        }
+        // Impacts which touched no splat have nothing to undo
+        if let (Some(first), Some(last)) = (previous.first(), previous.last()) {
+            scene.mark_dirty(first.0..last.0 + 1);
+            self.history.push(previous);
+        }
+
+        // Effects are seeded by the number of impacts so far to be deterministic
+        self.applied += 1;
+        let seed = self.applied * 7919;
+        let normal = impact.normal.normalize_or_zero();
+        self.spawn_effect(
+            scene,
+            effect_splat(impact.position + normal * 0.005, normal, settings.scorch_scale, [0.05, 0.04, 0.03, 0.9]),
+            Vec3::ZERO,
+            settings.scorch_lifetime,
+        );
+        for debris_index in 0..settings.debris_count as u32 {
+            let jitter = Vec3::new(
+                hash_to_signed_unit(seed + debris_index * 3),
+                hash_to_signed_unit(seed + debris_index * 3 + 1),
+                hash_to_signed_unit(seed + debris_index * 3 + 2),
+            );
+            let direction = (normal + jitter * 0.8).normalize_or_zero();
+            let speed = settings.debris_speed * (0.75 + 0.25 * jitter.x);
+            let splat = effect_splat(impact.position, -direction, settings.debris_scale, [0.3, 0.28, 0.25, 1.0]);
+            self.spawn_effect(scene, splat, direction * speed, settings.debris_lifetime);
+        }
+        destroyed
+    }
+
+    fn spawn_effect(&mut self, scene: &mut Scene, splat: Splat, velocity: Vec3, lifetime: f32) {
+        if self.effects.is_empty() {
+            return;
+        }
+        // Once the pool is exhausted the oldest effect is replaced
+        let index = self.next_effect;
+        self.next_effect = (self.next_effect + 1) % self.effects.len();
+        self.effects[index] = Some(Effect {
+            velocity,
+            alpha: splat.color[3],
+            age: 0.0,
+            lifetime,
+        });
+        scene.splat_data[self.base_splat_count + index] = splat;
+        scene.mark_dirty(self.base_splat_count + index..self.base_splat_count + index + 1);
+    }
+
+    /// Moves debris and fades out all effects, hiding them at the end of their lifetime.
+    pub fn update(&mut self, scene: &mut Scene, delta_seconds: f32, settings: &ImpactSettings) {
+        if !self.fits(scene) {
+            return;
+        }
+        let mut changed = false;
+        for (index, slot) in self.effects.iter_mut().enumerate() {
+            let Some(effect) = slot else {
+                continue;
+            };
+            let splat = &mut scene.splat_data[self.base_splat_count + index];
+            effect.age += delta_seconds;
+            if effect.age >= effect.lifetime {
+                splat.color[3] = 0.0;
+                *slot = None;
+            } else {
+                if effect.velocity != Vec3::ZERO {
+                    effect.velocity.y += settings.gravity * delta_seconds;
+                    splat.center = (Vec3::from(splat.center) + effect.velocity * delta_seconds).to_array();
+                }
+                splat.color[3] = effect.alpha * (1.0 - effect.age / effect.lifetime);
+            }
+            changed = true;
+        }
+        if changed {
+            scene.mark_dirty(self.base_splat_count..self.base_splat_count + self.effects.len());
+        }
+    }
+
+    /// Restores the splats modified by the last impact. Returns false if there is nothing to undo.
+    pub fn undo(&mut self, scene: &mut Scene) -> bool {
+        if !self.fits(scene) {
+            return false;
+        }
+        let Some(previous) = self.history.pop() else {
+            return false;
+        };
+        if let (Some(first), Some(last)) = (previous.first(), previous.last()) {
+            scene.mark_dirty(first.0..last.0 + 1);
+        }
+        for (index, splat) in previous {
+            scene.splat_data[index] = splat;
+        }
+        true
+    }
+
+    /// Undoes all impacts and hides all effects.
+    pub fn reset(&mut self, scene: &mut Scene) {
+        if !self.fits(scene) {
+            return;
+        }
+        while self.undo(scene) {}
+        for (index, slot) in self.effects.iter_mut().enumerate() {
+            if slot.take().is_some() {
+                scene.splat_data[self.base_splat_count + index].color[3] = 0.0;
+            }
+        }
+        scene.mark_dirty(self.base_splat_count..self.base_splat_count + self.effects.len());
+        self.next_effect = 0;
+    }
+}
+
+/// A round splat facing along `normal`.
+fn effect_splat(center: Vec3, normal: Vec3, scale: f32, color: [f32; 4]) -> Splat {
+    Splat {
+        model_matrix: Mat4::IDENTITY,
+        center: center.to_array(),
+        color,
+        depth: 0.0,
+        scale: [scale, scale],
+        normal: normal.to_array(),
+        ellipse_basis: normal.any_orthonormal_vector().to_array(),
+    }
+}
+
+/// Maps an integer to a pseudo random number in [-1, 1].
+fn hash_to_signed_unit(mut value: u32) -> f32 {
+    value ^= value >> 16;
+    value = value.wrapping_mul(0x7feb352d);
+    value ^= value >> 15;
+    value = value.wrapping_mul(0x846ca68b);
+    value ^= value >> 16;
+    value as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+pub struct SplatImpactPlugin;
+
+impl Plugin for SplatImpactPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ImpactSettings>().add_systems(Update, update_effects);
+    }
+}
+
+fn update_effects(
+    time: Res<Time>,
+    settings: Res<ImpactSettings>,
+    scene: Option<ResMut<Scene>>,
+    damage: Option<ResMut<SceneDamage>>,
+    mut scenes: Query<(&mut Scene, &mut SceneDamage)>,
+) {
+    if let (Some(mut scene), Some(mut damage)) = (scene, damage) {
+        if damage.active_effect_count() > 0 {
+            damage.update(&mut scene, time.delta_seconds(), &settings);
+        }
+    }
+    for (mut scene, mut damage) in scenes.iter_mut() {
+        if damage.active_effect_count() > 0 {
+            damage.update(&mut scene, time.delta_seconds(), &settings);
+        }
+    }
+}
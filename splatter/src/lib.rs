@@ -1,8 +1,30 @@
 pub mod bevy_plugin; // New module for Bevy integration
+pub mod camera;
+pub mod camera_path;
+pub mod chunks;
+pub mod cleanup;
+pub mod collision;
 pub mod component; // New module for components
 pub mod config;
+pub mod coordinates;
+pub mod culling;
+pub mod cpu_renderer;
+pub mod decimation;
+pub mod evaluation;
+pub mod gpu_rasterizer;
+pub mod impact;
+pub mod loading;
+pub mod lod;
+pub mod panorama;
+pub mod projection;
+pub mod raycast;
 pub mod render_plugin; // New module for rendering
 pub mod renderer;
 pub mod scene;
+pub mod spatial;
+pub mod splat_edit;
+pub mod statistics;
+pub mod streaming;
+pub mod training_cameras;
 pub mod utils;
 pub mod player;
\ No newline at end of file
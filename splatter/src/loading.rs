@@ -0,0 +1,273 @@
+//! Loading of PLY files in the background
+//!
+//! The vertices are decoded one by one on the [AsyncComputeTaskPool] and sent to the main world in batches,
+//! which are appended to the [Scene] of the loading entity as they arrive and uploaded along with it.
+//! Despawning the entity cancels the loading.
+
+use crate::coordinates::{CoordinateConversion, CoordinateSystem};
+use crate::lod::{LodTree, SceneLod};
+use crate::scene::{ply_spherical_harmonics_layout, read_ply_spherical_harmonics, spherical_harmonics_count, Scene, Splat};
+use crate::training_cameras::{find_training_cameras, TrainingViews};
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use ply_rs::parser::Parser;
+use ply_rs::ply::{DefaultElement, Encoding};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+
+/// Number of splats decoded before they are handed to the main world.
+pub const DEFAULT_BATCH_SIZE: usize = 16 * 1024;
+
+/// How far loading a file has come.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct SplatLoadProgress {
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    pub splats_decoded: usize,
+    pub total_splats: usize,
+    pub finished: bool,
+}
+
+impl SplatLoadProgress {
+    /// Decoded fraction of all splats in [0, 1].
+    pub fn fraction(&self) -> f32 {
+        if self.total_splats == 0 {
+            return if self.finished { 1.0 } else { 0.0 };
+        }
+        self.splats_decoded as f32 / self.total_splats as f32
+    }
+}
+
+/// Sent whenever a batch of splats of a loading entity arrived and once more when loading finished.
+#[derive(Event, Debug, Clone)]
+pub struct SplatLoadProgressEvent {
+    pub entity: Entity,
+    pub progress: SplatLoadProgress,
+}
+
+/// Counts the bytes read from the inner reader.
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+/// Reads the vertices of a PLY file as splats along with their view dependent colors, like [Scene::from_ply], and calls
+/// `on_batch` with a scene of every `batch_size` of them.
+///
+/// Loading stops early if `on_batch` returns [ControlFlow::Break].
+pub fn stream_splats_from_ply(
+    path: impl AsRef<Path>,
+    batch_size: usize,
+    mut on_batch: impl FnMut(Scene, SplatLoadProgress) -> ControlFlow<()>,
+) -> io::Result<()> {
+    let file = File::open(path)?;
+    let bytes_read = Arc::new(AtomicU64::new(0));
+    let mut progress = SplatLoadProgress {
+        total_bytes: file.metadata()?.len(),
+        ..SplatLoadProgress::default()
+    };
+    let mut reader = BufReader::new(CountingReader {
+        inner: file,
+        count: bytes_read.clone(),
+    });
+    let parser = Parser::<DefaultElement>::new();
+    let header = parser.read_header(&mut reader)?;
+    let vertex = header
+        .elements
+        .get("vertex")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No vertex element in PLY"))?;
+    progress.total_splats = vertex.count;
+    let rest_count = (0..).take_while(|index| vertex.properties.contains_key(&format!("f_rest_{index}"))).count();
+    let (order, per_channel) = ply_spherical_harmonics_layout(rest_count);
+    let new_batch = || {
+        let capacity = batch_size.min(progress.total_splats);
+        let mut batch = Scene::new();
+        batch.spherical_harmonics_order = order;
+        batch.splat_data = Vec::<Splat>::with_capacity(capacity);
+        batch.spherical_harmonics = Vec::with_capacity(capacity * spherical_harmonics_count(order));
+        batch
+    };
+
+    let mut line = String::new();
+    let mut batch = new_batch();
+    for element_definition in header.elements.values() {
+        let is_vertex = element_definition.name == "vertex";
+        for _ in 0..element_definition.count {
+            let element = match header.encoding {
+                Encoding::Ascii => {
+                    line.clear();
+                    if reader.read_line(&mut line)? == 0 {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "PLY ended before all elements were read"));
+                    }
+                    parser.read_ascii_element(&line, element_definition)?
+                }
+                Encoding::BinaryBigEndian => parser.read_big_endian_element(&mut reader, element_definition)?,
+                Encoding::BinaryLittleEndian => parser.read_little_endian_element(&mut reader, element_definition)?,
+            };
+            if !is_vertex {
+                continue;
+            }
+            batch.splat_data.push(Splat::from_ply_vertex(&element));
+            read_ply_spherical_harmonics(&element, order, per_channel, &mut batch.spherical_harmonics);
+            if batch.splat_data.len() == batch_size {
+                progress.splats_decoded += batch_size;
+                progress.bytes_read = bytes_read.load(Ordering::Relaxed);
+                let mut splats = std::mem::replace(&mut batch, new_batch());
+                splats.splat_count = batch_size;
+                if on_batch(splats, progress).is_break() {
+                    return Ok(());
+                }
+            }
+        }
+        // Elements after the vertices are irrelevant
+        if is_vertex {
+            break;
+        }
+    }
+    progress.splats_decoded += batch.splat_data.len();
+    progress.bytes_read = bytes_read.load(Ordering::Relaxed);
+    progress.finished = true;
+    batch.splat_count = batch.splat_data.len();
+    let _ = on_batch(batch, progress);
+    Ok(())
+}
+
+enum LoadMessage {
+    Batch(Box<Scene>, SplatLoadProgress),
+    Failed(io::Error),
+}
+
+/// Loads a PLY file into the [Scene] of this entity in the background.
+///
+/// The splats are converted from the coordinate system saved next to the file, see [CoordinateSystem::path_for_scene],
+/// into the one of the [Scene]. The component is removed once loading finished.
+/// Removing it earlier or despawning the entity cancels loading.
+#[derive(Component)]
+pub struct SceneLoading {
+    pub path: PathBuf,
+    /// Convention of the file, Bevy's if nothing is saved next to it.
+    pub coordinate_system: CoordinateSystem,
+    cancelled: Arc<AtomicBool>,
+    messages: Mutex<Receiver<LoadMessage>>,
+    _task: Task<()>,
+}
+
+impl SceneLoading {
+    pub fn start(path: impl Into<PathBuf>) -> Self {
+        Self::with_batch_size(path, DEFAULT_BATCH_SIZE)
+    }
+
+    pub fn with_batch_size(path: impl Into<PathBuf>, batch_size: usize) -> Self {
+        let path = path.into();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+        let task = {
+            let path = path.clone();
+            let cancelled = cancelled.clone();
+            AsyncComputeTaskPool::get().spawn(async move {
+                let result = stream_splats_from_ply(&path, batch_size.max(1), |splats, progress| {
+                    if cancelled.load(Ordering::Relaxed) || sender.send(LoadMessage::Batch(Box::new(splats), progress)).is_err() {
+                        return ControlFlow::Break(());
+                    }
+                    ControlFlow::Continue(())
+                });
+                if let Err(error) = result {
+                    let _ = sender.send(LoadMessage::Failed(error));
+                }
+            })
+        };
+        let coordinate_system = match CoordinateSystem::load(CoordinateSystem::path_for_scene(&path)) {
+            Ok(coordinate_system) => coordinate_system,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => CoordinateSystem::BEVY,
+            Err(error) => {
+                warn!("Ignoring the coordinate system of {}: {}", path.display(), error);
+                CoordinateSystem::BEVY
+            }
+        };
+        Self {
+            path,
+            coordinate_system,
+            cancelled,
+            messages: Mutex::new(receiver),
+            _task: task,
+        }
+    }
+
+    /// Overrides the convention of the file.
+    pub fn with_coordinate_system(mut self, coordinate_system: CoordinateSystem) -> Self {
+        self.coordinate_system = coordinate_system;
+        self
+    }
+}
+
+impl Drop for SceneLoading {
+    fn drop(&mut self) {
+        // The decoding loop does not await anything, so dropping the task alone would not stop it
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Registers the [SplatLoadProgressEvent] and applies the batches of all [SceneLoading] components.
+pub struct SplatLoadingPlugin;
+
+impl Plugin for SplatLoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SplatLoadProgressEvent>().add_systems(Update, poll_scene_loading);
+    }
+}
+
+fn poll_scene_loading(
+    mut commands: Commands,
+    mut loading_scenes: Query<(Entity, &mut Scene, &mut SceneLoading)>,
+    mut progress_events: EventWriter<SplatLoadProgressEvent>,
+) {
+    for (entity, mut scene, mut loading) in loading_scenes.iter_mut() {
+        let mut finished = false;
+        while let Ok(message) = loading.messages.get_mut().unwrap().try_recv() {
+            match message {
+                LoadMessage::Batch(mut batch, progress) => {
+                    // Converts the view dependent colors along with the splats
+                    batch.coordinate_system = loading.coordinate_system;
+                    batch.convert_coordinates(scene.coordinate_system);
+                    scene.append(&mut batch);
+                    commands.entity(entity).insert(progress);
+                    progress_events.send(SplatLoadProgressEvent { entity, progress });
+                    finished = progress.finished;
+                }
+                LoadMessage::Failed(error) => {
+                    error!("Failed to load {}: {}", loading.path.display(), error);
+                    finished = true;
+                }
+            }
+        }
+        if finished {
+            info!("Loaded {} splats from {}", scene.splat_count, loading.path.display());
+            // Files next to the scene are in its coordinates, the splats were converted batch by batch
+            let conversion = CoordinateConversion::between(&loading.coordinate_system, &scene.coordinate_system);
+            // The level of detail hierarchy is built offline, see `splatter lod`
+            if let Ok(mut tree) = LodTree::load(LodTree::path_for_scene(&loading.path), &scene) {
+                tree.convert_merged_nodes(&conversion);
+                commands.entity(entity).insert(SceneLod::new(tree));
+            }
+            if let Ok(mut cameras) = find_training_cameras(&loading.path) {
+                cameras.iter_mut().for_each(|camera| camera.convert(&conversion));
+                info!("Found {} training views", cameras.len());
+                commands.insert_resource(TrainingViews::new(cameras));
+            }
+            commands.entity(entity).remove::<SceneLoading>();
+        }
+    }
+}
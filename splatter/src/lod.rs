@@ -0,0 +1,458 @@
+//! Level of detail hierarchy of splats
+//!
+//! The hierarchy is built bottom up on the CPU: Splats falling into the same cell of a grid are merged into a parent
+//! with the same total opacity mass and the combined mean and covariance (moment matching). The cell size doubles with
+//! every level until a single root is left. Because building takes a while for large scenes, the tree is meant to be
+//! built offline and saved next to the scene, see [LodTree::path_for_scene].
+//!
+//! At runtime a cut through the tree is selected: Starting from the roots, the node which appears largest on screen is
+//! replaced by its children until all nodes are smaller than a pixel threshold or the splat budget is exhausted. The
+//! splat buffer of a [Scene] with a [SceneLod] holds the cut instead of all splats, at most `Config::max_splat_count`.
+
+use crate::config::Config;
+use crate::coordinates::CoordinateConversion;
+use crate::scene::{Scene, ShaderSplat, Splat, PACKED_SPLAT_FLOATS};
+use crate::spatial::PointGrid;
+use bevy::prelude::*;
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use glam::{IVec3, Mat3, Vec3};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"SPLATLOD";
+const VERSION: u32 = 1;
+
+/// A splat of the hierarchy, either one of the scene or one merged from its children.
+#[derive(Clone)]
+pub struct LodNode {
+    pub splat: Splat,
+    /// Radius of the sphere around the center of the splat which bounds it and all its descendants.
+    pub radius: f32,
+    /// Range in [LodTree::children], empty for leaves.
+    pub first_child: u32,
+    pub child_count: u32,
+}
+
+/// Hierarchy over the splats of a [Scene]. The first `leaf_count` nodes are the splats of the scene in their order.
+#[derive(Clone)]
+pub struct LodTree {
+    pub leaf_count: usize,
+    pub nodes: Vec<LodNode>,
+    pub children: Vec<u32>,
+    pub roots: Vec<u32>,
+}
+
+/// Parameters of [LodTree::build].
+#[derive(Debug, Clone)]
+pub struct LodBuildSettings {
+    /// Average number of splats merged into a parent at the first level.
+    pub branching: f32,
+    /// Upper bound of levels above the leaves.
+    pub max_levels: usize,
+}
+
+impl Default for LodBuildSettings {
+    fn default() -> Self {
+        Self {
+            branching: 8.0,
+            max_levels: 24,
+        }
+    }
+}
+
+/// Bounding radius of a single splat.
+fn splat_radius(splat: &Splat) -> f32 {
+    3.0 * splat.scale[0].max(splat.scale[1])
+}
+
+/// Weight of a splat in the moment matching: Its opacity times its area.
+fn opacity_mass(splat: &Splat) -> f32 {
+    splat.color[3] * splat.scale[0] * splat.scale[1]
+}
+
+/// Merges splats into a single one with the same mean, covariance and opacity mass.
+pub fn merge_splats<'a>(splats: impl Iterator<Item = &'a Splat> + Clone) -> Splat {
+    let mut total_weight = 0.0;
+    let mut total_mass = 0.0;
+    let mut mean = Vec3::ZERO;
+    let mut color = [0.0; 3];
+    for splat in splats.clone() {
+        let mass = opacity_mass(splat);
+        // Fully transparent splats still have to contribute something, or the mean would be undefined
+        let weight = mass.max(f32::MIN_POSITIVE);
+        total_weight += weight;
+        total_mass += mass;
+        mean += Vec3::from(splat.center) * weight;
+        for (channel, value) in color.iter_mut().zip(splat.color) {
+            *channel += value * weight;
+        }
+    }
+    mean /= total_weight;
+    let mut covariance = Mat3::ZERO;
+    for splat in splats {
+        let weight = opacity_mass(splat).max(f32::MIN_POSITIVE) / total_weight;
+        let offset = Vec3::from(splat.center) - mean;
+        covariance += (splat.covariance() + Mat3::from_cols(offset * offset.x, offset * offset.y, offset * offset.z)) * weight;
+    }
+    let mut merged = Splat::from_covariance(mean, covariance, [0.0; 4]);
+    let area = (merged.scale[0] * merged.scale[1]).max(f32::MIN_POSITIVE);
+    merged.color = [
+        color[0] / total_weight,
+        color[1] / total_weight,
+        color[2] / total_weight,
+        (total_mass / area).min(1.0),
+    ];
+    merged
+}
+
+/// Diameter in pixels of a bounding sphere seen from `camera_position`, with `focal_length` in pixels.
+pub(crate) fn projected_size(center: Vec3, radius: f32, camera_position: Vec3, focal_length: f32) -> f32 {
+    // Distance to the bounding sphere, so that the size does not change when the camera only rotates
+    let distance = (camera_position.distance(center) - radius).max(1.0e-3);
+    2.0 * radius * focal_length / distance
+}
+
+impl LodTree {
+    /// A hierarchy without any merged nodes.
+    fn with_leaves(scene: &Scene) -> Self {
+        LodTree {
+            leaf_count: scene.splat_data.len(),
+            nodes: scene
+                .splat_data
+                .iter()
+                .map(|splat| LodNode {
+                    splat: splat.clone(),
+                    radius: splat_radius(splat),
+                    first_child: 0,
+                    child_count: 0,
+                })
+                .collect(),
+            children: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    /// Builds the hierarchy over all splats of the scene.
+    pub fn build(scene: &Scene, settings: &LodBuildSettings) -> Self {
+        let mut tree = Self::with_leaves(scene);
+        let mut level: Vec<u32> = (0..tree.nodes.len() as u32).collect();
+        if level.is_empty() {
+            return tree;
+        }
+        let centers: Vec<Vec3> = scene.splat_data.iter().map(|splat| Vec3::from(splat.center)).collect();
+        let origin = centers.iter().fold(Vec3::splat(f32::INFINITY), |min, center| min.min(*center));
+        let mut cell_size = PointGrid::with_density(&centers, settings.branching).cell_size();
+        for _ in 0..settings.max_levels {
+            if level.len() <= 1 {
+                break;
+            }
+            // Cells are relative to the minimum, so that everything ends up in the same cell eventually
+            let mut cells: HashMap<IVec3, Vec<u32>> = HashMap::new();
+            for index in level.iter() {
+                let center = Vec3::from(tree.nodes[*index as usize].splat.center);
+                cells.entry(((center - origin) / cell_size).floor().as_ivec3()).or_default().push(*index);
+            }
+            let mut cells: Vec<(IVec3, Vec<u32>)> = cells.into_iter().collect();
+            // Sort for a deterministic node order
+            cells.sort_by_key(|(cell, _)| cell.to_array());
+            level = cells.into_iter().map(|(_, members)| tree.add_parent(members)).collect();
+            cell_size *= 2.0;
+        }
+        tree.roots = level;
+        tree
+    }
+
+    /// Adds a node merged from the given ones, unless there is only one of them.
+    fn add_parent(&mut self, members: Vec<u32>) -> u32 {
+        if members.len() == 1 {
+            return members[0];
+        }
+        let splat = merge_splats(members.iter().map(|index| &self.nodes[*index as usize].splat));
+        let center = Vec3::from(splat.center);
+        let radius = members
+            .iter()
+            .map(|index| {
+                let child = &self.nodes[*index as usize];
+                center.distance(Vec3::from(child.splat.center)) + child.radius
+            })
+            .fold(splat_radius(&splat), f32::max);
+        let first_child = self.children.len() as u32;
+        self.children.extend_from_slice(&members);
+        self.nodes.push(LodNode {
+            splat,
+            radius,
+            first_child,
+            child_count: members.len() as u32,
+        });
+        (self.nodes.len() - 1) as u32
+    }
+
+    pub fn node_children(&self, index: u32) -> &[u32] {
+        let node = &self.nodes[index as usize];
+        let first_child = node.first_child as usize;
+        &self.children[first_child..first_child + node.child_count as usize]
+    }
+
+    /// Selects the nodes to render for a perspective camera at `camera_position`.
+    ///
+    /// `focal_length` is in pixels, nodes whose bounding sphere appears smaller than `pixel_threshold` are not refined
+    /// and at most `max_splats` nodes are selected, unless there are more roots than that.
+    pub fn select_cut(&self, camera_position: Vec3, focal_length: f32, pixel_threshold: f32, max_splats: usize) -> Vec<u32> {
+        struct Candidate {
+            projected_size: f32,
+            index: u32,
+        }
+        impl PartialEq for Candidate {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+        impl Eq for Candidate {}
+        impl PartialOrd for Candidate {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Candidate {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.projected_size.total_cmp(&other.projected_size).then(other.index.cmp(&self.index))
+            }
+        }
+        let candidate = |index: u32| {
+            let node = &self.nodes[index as usize];
+            Candidate {
+                projected_size: projected_size(Vec3::from(node.splat.center), node.radius, camera_position, focal_length),
+                index,
+            }
+        };
+
+        let mut cut = Vec::new();
+        let mut open: BinaryHeap<Candidate> = self.roots.iter().map(|index| candidate(*index)).collect();
+        while let Some(largest) = open.pop() {
+            let children = self.node_children(largest.index);
+            let budget_left = cut.len() + open.len() + children.len() <= max_splats;
+            if children.is_empty() || largest.projected_size < pixel_threshold || !budget_left {
+                cut.push(largest.index);
+                // Everything else is smaller, but might still be refined within the budget
+                if largest.projected_size < pixel_threshold {
+                    cut.extend(open.drain().map(|candidate| candidate.index));
+                }
+                continue;
+            }
+            open.extend(children.iter().map(|index| candidate(*index)));
+        }
+        cut.sort_unstable();
+        cut
+    }
+
+    pub fn cut_splats(&self, cut: &[u32]) -> Vec<Splat> {
+        cut.iter().map(|index| self.nodes[*index as usize].splat.clone()).collect()
+    }
+
+    /// Moves the merged nodes into other coordinates. The leaves are the splats of the scene the tree was loaded for, so
+    /// they are converted along with the scene instead.
+    pub fn convert_merged_nodes(&mut self, conversion: &CoordinateConversion) {
+        for node in &mut self.nodes[self.leaf_count..] {
+            node.splat = conversion.splat(&node.splat);
+            node.radius *= conversion.scale;
+        }
+    }
+
+    /// Where the hierarchy of the scene at `scene_path` is saved.
+    pub fn path_for_scene(scene_path: impl AsRef<Path>) -> PathBuf {
+        scene_path.as_ref().with_extension("lod")
+    }
+
+    /// Writes the merged nodes. The leaves are not included, as they are the splats of the scene.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        for value in [VERSION, self.leaf_count as u32, self.nodes.len() as u32, self.children.len() as u32, self.roots.len() as u32] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        for node in self.nodes[self.leaf_count..].iter() {
+            for value in node.splat.to_packed().iter().chain([node.radius].iter()) {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&node.first_child.to_le_bytes())?;
+            writer.write_all(&node.child_count.to_le_bytes())?;
+        }
+        for index in self.children.iter().chain(self.roots.iter()) {
+            writer.write_all(&index.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a hierarchy saved by [LodTree::save] for the splats of `scene`.
+    pub fn load(path: impl AsRef<Path>, scene: &Scene) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        let mut read_u32 = || -> io::Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+        if &magic != MAGIC || read_u32()? != VERSION {
+            return Err(invalid("Not a LOD file of a supported version"));
+        }
+        let [leaf_count, node_count, child_count, root_count] = [read_u32()?, read_u32()?, read_u32()?, read_u32()?].map(|value| value as usize);
+        if leaf_count != scene.splat_data.len() || node_count < leaf_count {
+            return Err(invalid("LOD file does not belong to the scene"));
+        }
+        let mut tree = Self::with_leaves(scene);
+        for _ in leaf_count..node_count {
+            let mut packed = [0.0f32; PACKED_SPLAT_FLOATS];
+            for value in packed.iter_mut() {
+                *value = f32::from_bits(read_u32()?);
+            }
+            tree.nodes.push(LodNode {
+                splat: Splat::from_packed(&packed),
+                radius: f32::from_bits(read_u32()?),
+                first_child: read_u32()?,
+                child_count: read_u32()?,
+            });
+        }
+        for _ in 0..child_count {
+            tree.children.push(read_u32()?);
+        }
+        for _ in 0..root_count {
+            tree.roots.push(read_u32()?);
+        }
+        let references_valid = tree.children.iter().chain(tree.roots.iter()).all(|index| (*index as usize) < node_count)
+            && tree.nodes.iter().all(|node| {
+                node.first_child
+                    .checked_add(node.child_count)
+                    .is_some_and(|end| end as usize <= child_count)
+            });
+        if !references_valid {
+            return Err(invalid("LOD file references nodes which do not exist"));
+        }
+        Ok(tree)
+    }
+}
+
+/// Parameters of the runtime cut through the [SceneLod].
+#[derive(Resource, Debug, Clone)]
+pub struct LodSettings {
+    /// Nodes which appear smaller than this many pixels are not refined any further.
+    pub pixel_threshold: f32,
+    /// Upper bound of the number of splats in the cut.
+    pub max_splats: usize,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            pixel_threshold: 4.0,
+            max_splats: 1_000_000,
+        }
+    }
+}
+
+/// Hierarchy of the [Scene] next to it and the cut through it for the active camera.
+///
+/// While present, the splat buffer of the scene holds the splats of the cut, see [SceneLod::shader_splats].
+#[derive(Component)]
+pub struct SceneLod {
+    pub tree: LodTree,
+    /// Indices of the selected nodes in ascending order.
+    pub cut: Vec<u32>,
+    cut_changed: bool,
+}
+
+impl SceneLod {
+    pub fn new(tree: LodTree) -> Self {
+        Self {
+            cut: tree.roots.clone(),
+            tree,
+            cut_changed: true,
+        }
+    }
+
+    /// Selects the cut for a perspective camera, see [LodTree::select_cut]. Splats added to the scene after the tree
+    /// was built, like the effects of impacts, count against `max_splats`, which the cut never exceeds. Returns whether
+    /// the cut changed.
+    pub fn update_cut(&mut self, scene: &Scene, camera_position: Vec3, focal_length: f32, pixel_threshold: f32, max_splats: usize) -> bool {
+        let max_splats = max_splats.saturating_sub(scene.splat_data.len().saturating_sub(self.tree.leaf_count));
+        let mut cut = self.tree.select_cut(camera_position, focal_length, pixel_threshold, max_splats);
+        // More roots than the budget are cut off rather than overflowing the splat buffer
+        cut.truncate(max_splats);
+        if cut == self.cut {
+            return false;
+        }
+        self.cut = cut;
+        self.cut_changed = true;
+        true
+    }
+
+    /// The splats of the cut followed by those added to the scene after the tree was built. Leaves are taken from the
+    /// scene, so that they are drawn with their view dependent colors and any changes made to them since.
+    pub fn shader_splats(&self, scene: &Scene) -> Vec<ShaderSplat> {
+        let splat_count = scene.splat_data.len();
+        let cut = self.cut.iter().map(|index| {
+            let index = *index as usize;
+            match scene.splat_data.get(index) {
+                Some(splat) if index < self.tree.leaf_count => ShaderSplat::new(splat, scene.spherical_harmonics_of(index)),
+                _ => ShaderSplat::from(&self.tree.nodes[index].splat),
+            }
+        });
+        cut.chain(scene.shader_splats(self.tree.leaf_count.min(splat_count)..splat_count)).collect()
+    }
+}
+
+/// Selects the cut of every [SceneLod] each frame and uploads it into the splat buffer of its [Scene].
+pub struct SplatLodPlugin;
+
+impl Plugin for SplatLodPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LodSettings>().add_systems(Update, (update_lod_cut, upload_lod_cut).chain());
+    }
+}
+
+fn update_lod_cut(
+    mut scenes: Query<(&Scene, &mut SceneLod)>,
+    settings: Res<LodSettings>,
+    config: Option<Res<Config>>,
+    cameras: Query<(&Camera, &Projection, &GlobalTransform), With<Camera3d>>,
+) {
+    let Some((camera, Projection::Perspective(perspective), transform)) = cameras.iter().find(|(camera, _, _)| camera.is_active) else {
+        return;
+    };
+    let Some(viewport_size) = camera.logical_viewport_size() else {
+        return;
+    };
+    let focal_length = 0.5 * viewport_size.y / (0.5 * perspective.fov).tan();
+    let camera_position = Vec3::from(transform.translation().to_array());
+    let max_splats = config.map_or(settings.max_splats, |config| settings.max_splats.min(config.max_splat_count as usize));
+    for (scene, mut scene_lod) in scenes.iter_mut() {
+        scene_lod.update_cut(scene, camera_position, focal_length, settings.pixel_threshold, max_splats);
+    }
+}
+
+fn upload_lod_cut(mut scenes: Query<(&mut Scene, &mut SceneLod)>, render_device: Res<RenderDevice>, render_queue: Res<RenderQueue>) {
+    for (mut scene, mut scene_lod) in scenes.iter_mut() {
+        if !scene_lod.cut_changed && scene.dirty_splats.is_none() {
+            continue;
+        }
+        let shader_splats = scene_lod.shader_splats(&scene);
+        let contents: &[u8] = bytemuck::cast_slice(&shader_splats);
+        match &scene.splat_buffer {
+            Some(buffer) if buffer.size() == contents.len() as u64 => render_queue.write_buffer(buffer, 0, contents),
+            _ => {
+                scene.splat_buffer = Some(render_device.create_buffer_with_data(&wgpu::util::BufferInitDescriptor {
+                    label: Some("LOD Splat Buffer"),
+                    contents,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                }))
+            }
+        }
+        scene.dirty_splats = None;
+        scene_lod.cut_changed = false;
+    }
+}
+
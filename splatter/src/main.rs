@@ -1,13 +1,20 @@
-use crate::player::PlayerPlugin;
 use crate::weapon::WeaponPlugin;
 use bevy::prelude::*;
 use bevy::window::{Window, WindowPlugin};
 use splatter::render_plugin::GaussianSplatRenderPlugin;
 use splatter::bevy_plugin::GaussianSplatPlugin;
-use bevy::scene::ScenePlugin;
+use splatter::camera::SplatCameraPlugin;
+use splatter::camera_path::CameraPathPlugin;
+use splatter::collision::SplatCollisionPlugin;
+use splatter::impact::SplatImpactPlugin;
+use splatter::lod::SplatLodPlugin;
+use splatter::player::PlayerPlugin;
+use splatter::raycast::SplatPickingPlugin;
+use splatter::scene::ScenePlugin as SplatScenePlugin;
+use splatter::streaming::{SplatStreamingPlugin, StreamingManager, StreamingSettings};
+use splatter::training_cameras::TrainingViewPlugin;
 // use bevy::render::RenderApp;
 
-mod player;
 mod weapon;
 
 // fn main() {
@@ -36,13 +43,21 @@ fn main() {
             ..default()
         }))
         .add_plugins((
-            ScenePlugin,                    // Required for GLTF scene loading
+            SplatScenePlugin,               // Splats of the scene, loaded in the background
             GaussianSplatPlugin,            // Core splatting engine
             GaussianSplatRenderPlugin,      // Your custom depth-aware splat renderer
+            SplatCameraPlugin,              // Splat cameras follow the Bevy cameras
+            SplatLodPlugin,                 // Level of detail cut through the loaded scene
+            SplatStreamingPlugin,           // Tiles of a large scene around the camera
+            SplatPickingPlugin,             // Splat under the cursor / crosshair
+            SplatCollisionPlugin,           // Voxelized splats the player collides with
+            SplatImpactPlugin,              // Debris and scorch marks of bullet impacts
             PlayerPlugin,                   // Handles movement/camera
+            TrainingViewPlugin,             // Page up / down jumps to the training views
+            CameraPathPlugin,               // Records and plays back flythroughs of the camera
             WeaponPlugin                    // Weapon logic + bullets
         ))
-        .add_systems(Startup, setup) // Setup scene geometry or lighting
+        .add_systems(Startup, (setup, open_tiled_scene)) // Setup scene geometry or lighting
         .add_systems(Update, (
             update_window_title, 
             cursor_grab_system
@@ -101,6 +116,18 @@ fn setup(
     });
 }
 
+/// Streams the tiles written by `splatter tile assets/models/test.ply assets/models/test.tiles`, if there are any.
+fn open_tiled_scene(mut commands: Commands) {
+    let path = "assets/models/test.tiles";
+    if !std::path::Path::new(path).exists() {
+        return;
+    }
+    match StreamingManager::open(path, StreamingSettings::default()) {
+        Ok(manager) => commands.insert_resource(manager),
+        Err(error) => warn!("Could not stream {path}: {error}"),
+    }
+}
+
 fn update_window_title(mut window: Query<&mut Window>, time: Res<Time>) {
     let mut window = window.single_mut();
     window.title = format!("Splatter Demo - {:.0} fps", 1.0 / time.delta_seconds());
@@ -0,0 +1,85 @@
+//! Equirectangular 360° panoramas
+//!
+//! The six faces of a cube around the position are rendered with ordinary perspective cameras and then resampled
+//! into longitude and latitude. Longitude grows to the right from -180° to 180° with the center of the image looking
+//! along -Z, latitude from 90° at the top to -90° at the bottom with +Y up, matching the Bevy convention.
+
+use crate::cpu_renderer::{render_scene, CpuImage, CpuRenderSettings};
+use crate::scene::{Camera, Scene};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// Splats closer to the position than this are not rendered.
+pub const PANORAMA_Z_NEAR: f32 = 0.01;
+
+pub const PANORAMA_Z_FAR: f32 = 1000.0;
+
+/// View direction and up vector of each cube face.
+pub const CUBE_FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y),
+];
+
+/// Cameras with a quarter turn field of view looking from `position` through the faces of a cube.
+pub fn cube_face_cameras(position: Vec3) -> [Camera; 6] {
+    CUBE_FACES.map(|(direction, up)| {
+        Camera::perspective(
+            Mat4::look_to_rh(position, direction, up),
+            FRAC_PI_2,
+            1.0,
+            PANORAMA_Z_NEAR,
+            PANORAMA_Z_FAR,
+        )
+    })
+}
+
+/// Direction through a position in pixels of an equirectangular image.
+pub fn equirectangular_direction(position: Vec2, width: u32, height: u32) -> Vec3 {
+    let longitude = (position.x / width as f32 * 2.0 - 1.0) * PI;
+    let latitude = (0.5 - position.y / height as f32) * PI;
+    Vec3::new(latitude.cos() * longitude.sin(), latitude.sin(), -latitude.cos() * longitude.cos())
+}
+
+/// Position in pixels of a direction in an equirectangular image, inverse of [equirectangular_direction].
+pub fn equirectangular_position(direction: Vec3, width: u32, height: u32) -> Vec2 {
+    let direction = direction.normalize();
+    let longitude = direction.x.atan2(-direction.z);
+    let latitude = direction.y.clamp(-1.0, 1.0).asin();
+    Vec2::new((longitude / PI + 1.0) * 0.5 * width as f32, (0.5 - latitude / PI) * height as f32)
+}
+
+/// Resamples cube faces rendered by the [cube_face_cameras] into an equirectangular image.
+pub fn equirectangular_from_cube_faces(faces: &[CpuImage; 6], cameras: &[Camera; 6], width: u32, height: u32) -> CpuImage {
+    let mut image = CpuImage::new(width, height, Vec4::ZERO);
+    for y in 0..height {
+        for x in 0..width {
+            let direction = equirectangular_direction(Vec2::new(x as f32 + 0.5, y as f32 + 0.5), width, height);
+            // The face looking most directly along the direction contains it
+            let face = (0..6)
+                .max_by(|a, b| CUBE_FACES[*a].0.dot(direction).total_cmp(&CUBE_FACES[*b].0.dot(direction)))
+                .unwrap();
+            let camera = &cameras[face];
+            let ndc = camera
+                .project_view_position(camera.view.transform_vector3(direction))
+                .unwrap_or(Vec2::ZERO);
+            let size = Vec2::new(faces[face].width as f32, faces[face].height as f32);
+            let position = Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * size;
+            image.set_pixel(x, y, faces[face].sample(position));
+        }
+    }
+    image
+}
+
+/// Renders the splats around `position` into an equirectangular image twice as wide as high.
+pub fn render_equirectangular(scene: &Scene, position: Vec3, width: u32, settings: &CpuRenderSettings) -> CpuImage {
+    let height = (width / 2).max(1);
+    // A face spans a quarter of the width along the equator
+    let face_size = (width / 4).max(1);
+    let cameras = cube_face_cameras(position);
+    let faces = cameras.each_ref().map(|camera| render_scene(scene, camera, face_size, face_size, settings));
+    equirectangular_from_cube_faces(&faces, &cameras, width, height)
+}
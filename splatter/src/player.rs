@@ -3,6 +3,7 @@ use bevy::prelude::*;
 use bevy::render::camera::Camera;
 use bevy::input::keyboard::KeyCode;
 use bevy::render::view::visibility::{InheritedVisibility, Visibility};
+use crate::collision::{Capsule, SceneCollider};
 struct CameraController {
     _speed: f32,
     _rotation_speed: f32,
@@ -34,6 +35,19 @@ pub struct Player {
     pub gravity: f32,
     pub velocity: Vec3,
     pub is_grounded: bool,
+    pub eye_height: f32,
+    pub radius: f32,
+    pub step_height: f32,
+}
+
+impl Player {
+    /// Collision shape of the body below the camera.
+    pub fn capsule(&self) -> Capsule {
+        Capsule {
+            radius: self.radius,
+            height: self.eye_height + 0.1,
+        }
+    }
 }
 
 impl Default for Player {
@@ -45,6 +59,9 @@ impl Default for Player {
             gravity: -9.81,
             velocity: Vec3::ZERO,
             is_grounded: true,
+            eye_height: 1.7,
+            radius: 0.3,
+            step_height: 0.35,
         }
     }
 }
@@ -163,14 +180,15 @@ fn spawn_player(
 fn player_movement(
     time: Res<Time>,
     keyboard: Res<Input<KeyCode>>,
+    collider: Option<Res<SceneCollider>>,
     mut query: Query<(&mut Player, &mut Transform)>,
 ) {
     for (mut player, mut transform) in query.iter_mut() {
         let mut direction = Vec3::ZERO;
 
-        // Get forward and right vectors from camera rotation
-        let forward = transform.forward();
-        let right = transform.right();
+        // Get forward and right vectors from camera rotation, flattened onto the ground
+        let forward = Vec3::new(transform.forward().x, 0.0, transform.forward().z).normalize_or_zero();
+        let right = Vec3::new(transform.right().x, 0.0, transform.right().z).normalize_or_zero();
 
         // Movement input
         if keyboard.pressed(KeyCode::W) {
@@ -191,10 +209,6 @@ fn player_movement(
             direction = direction.normalize();
         }
 
-        // Apply movement
-        let movement = direction * player.speed * time.delta_seconds();
-        transform.translation += movement;
-
         // Jump
         if keyboard.just_pressed(KeyCode::Space) && player.is_grounded {
             player.velocity.y = player.jump_force;
@@ -202,17 +216,32 @@ fn player_movement(
         }
 
         // Apply gravity
-        if !player.is_grounded {
-            player.velocity.y += player.gravity * time.delta_seconds();
-            transform.translation.y += player.velocity.y * time.delta_seconds();
-
-            // Ground check
-            if transform.translation.y <= 1.7 {
-                transform.translation.y = 1.7;
-                player.velocity.y = 0.0;
-                player.is_grounded = true;
-            }
+        player.velocity.y += player.gravity * time.delta_seconds();
+
+        let displacement = direction * player.speed * time.delta_seconds() + Vec3::Y * player.velocity.y * time.delta_seconds();
+        let feet = transform.translation - Vec3::Y * player.eye_height;
+        let (mut feet, mut grounded, hit_ceiling) = if let Some(collider) = &collider {
+            let motion = collider.grid.move_capsule(
+                &player.capsule(),
+                glam::Vec3::from(feet.to_array()),
+                glam::Vec3::from(displacement.to_array()),
+                player.step_height,
+            );
+            (Vec3::from(motion.feet.to_array()), motion.grounded, motion.hit_ceiling)
+        } else {
+            (feet + displacement, false, false)
+        };
+
+        // Ground check, the world floor is at y = 0 even without any splats
+        if feet.y <= 0.0 {
+            feet.y = 0.0;
+            grounded = true;
+        }
+        if (grounded && player.velocity.y < 0.0) || hit_ceiling {
+            player.velocity.y = 0.0;
         }
+        player.is_grounded = grounded;
+        transform.translation = feet + Vec3::Y * player.eye_height;
     }
 }
 
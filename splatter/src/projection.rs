@@ -0,0 +1,178 @@
+//! Projection of splats onto the image for the supported camera models
+//!
+//! Splats are projected to first order: The center is mapped exactly and the covariance by the Jacobian of the
+//! projection at the center, which is exact for orthographic cameras. The vertex shader does the same for every
+//! model but the perspective one, which it projects exactly through the bounding cone of the ellipsoid.
+//! All results are in normalized device coordinates.
+//!
+//! The equidistant fisheye maps the angle between a ray and the view direction linearly to the distance from the
+//! image center, using the focal scales of the projection matrix per radian. Unlike a pinhole it covers rays behind the camera.
+
+use crate::scene::{Camera, ProjectionModel, Splat};
+use glam::{Mat2, Mat3, Mat4, Vec2, Vec3, Vec4Swizzles};
+
+/// Below this ratio of distance from the view axis to depth the fisheye Jacobian uses its series expansion.
+const FISHEYE_SERIES_THRESHOLD: f32 = 1.0e-2;
+
+/// Footprint of a splat on the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProjectedSplat {
+    pub center: Vec2,
+    pub covariance: Mat2,
+    /// Distance along the view direction, or from the camera for the fisheye model.
+    pub depth: f32,
+}
+
+impl ProjectedSplat {
+    /// Standard deviations along the principal axes, larger first, and the direction of the larger one.
+    pub fn axes(&self) -> (Vec2, Vec2) {
+        let (a, b, c) = (self.covariance.x_axis.x, self.covariance.x_axis.y, self.covariance.y_axis.y);
+        let mean = 0.5 * (a + c);
+        let deviation = (0.25 * (a - c) * (a - c) + b * b).sqrt();
+        let major = mean + deviation;
+        let minor = mean - deviation;
+        let direction = if b.abs() <= 1.0e-12 * major.abs() {
+            if a >= c {
+                Vec2::X
+            } else {
+                Vec2::Y
+            }
+        } else {
+            Vec2::new(b, major - a).normalize()
+        };
+        (Vec2::new(major.max(0.0).sqrt(), minor.max(0.0).sqrt()), direction)
+    }
+}
+
+impl Camera {
+    /// Equidistant fisheye seeing `fov_y` radians from the top to the bottom edge of the image.
+    pub fn fisheye(view: Mat4, fov_y: f32, aspect_ratio: f32, z_near: f32, z_far: f32) -> Self {
+        // A pinhole with the same focal scales agrees with the fisheye in the image center
+        let pinhole_fov = 2.0 * (0.5 * fov_y).atan();
+        Self {
+            model: ProjectionModel::Fisheye,
+            ..Self::perspective(view, pinhole_fov, aspect_ratio, z_near, z_far)
+        }
+    }
+
+    /// Scales from the view plane of the pinhole and fisheye models to normalized device coordinates.
+    pub fn focal_scale(&self) -> Vec2 {
+        Vec2::new(self.projection.x_axis.x, self.projection.y_axis.y)
+    }
+
+    /// Half extent and center of the image on the view plane of the shader, see `projectOntoViewPlane`, which map a
+    /// position `p` on it to `(p - center) / half_extent` in normalized device coordinates. The half extent of the view
+    /// box for orthographic cameras, the half field of view in radians for fisheye cameras.
+    pub fn view_plane(&self) -> (Vec2, Vec2) {
+        let focal = self.focal_scale();
+        let center = match self.model {
+            ProjectionModel::Orthographic => -self.projection.w_axis.xy() / focal,
+            ProjectionModel::Perspective | ProjectionModel::Fisheye => self.projection.z_axis.xy() / focal,
+        };
+        (Vec2::ONE / focal, center)
+    }
+
+    /// Maps a point in view space to normalized device coordinates, if the camera can see it at all.
+    pub fn project_view_position(&self, view_position: Vec3) -> Option<Vec2> {
+        match self.model {
+            ProjectionModel::Perspective => {
+                let clip = self.projection * view_position.extend(1.0);
+                (clip.w > 0.0).then(|| clip.xy() / clip.w)
+            }
+            ProjectionModel::Orthographic => Some((self.projection * view_position.extend(1.0)).xy()),
+            ProjectionModel::Fisheye => {
+                let radius = view_position.truncate().length();
+                if radius <= f32::EPSILON * view_position.z.abs() {
+                    // On the view axis, where straight behind the camera the image position is undefined
+                    return (view_position.z < 0.0).then_some(Vec2::ZERO);
+                }
+                let angle = radius.atan2(-view_position.z);
+                Some(self.focal_scale() * view_position.truncate() * (angle / radius))
+            }
+        }
+    }
+
+    /// Derivatives of [Camera::project_view_position] by the view space coordinates, one row per image axis.
+    pub fn projection_jacobian(&self, view_position: Vec3) -> [Vec3; 2] {
+        match self.model {
+            ProjectionModel::Perspective => {
+                let p = self.projection.transpose();
+                let clip = self.projection * view_position.extend(1.0);
+                let w = clip.w;
+                [
+                    (p.x_axis.xyz() * w - p.w_axis.xyz() * clip.x) / (w * w),
+                    (p.y_axis.xyz() * w - p.w_axis.xyz() * clip.y) / (w * w),
+                ]
+            }
+            ProjectionModel::Orthographic => {
+                let p = self.projection.transpose();
+                [p.x_axis.xyz(), p.y_axis.xyz()]
+            }
+            ProjectionModel::Fisheye => {
+                let focal = self.focal_scale();
+                let (x, y) = (view_position.x, view_position.y);
+                let depth = -view_position.z;
+                let radius_squared = x * x + y * y;
+                let radius = radius_squared.sqrt();
+                let distance_squared = radius_squared + depth * depth;
+                // The image position is (x, y) * k with k = angle / radius, g is the derivative of k by radius divided by radius
+                let (k, g) = if depth > 0.0 && radius < FISHEYE_SERIES_THRESHOLD * depth {
+                    let depth_cubed = depth * depth * depth;
+                    (1.0 / depth - radius_squared / (3.0 * depth_cubed), -2.0 / (3.0 * depth_cubed))
+                } else {
+                    let k = radius.atan2(depth) / radius;
+                    (k, (depth / distance_squared - k) / radius_squared)
+                };
+                [
+                    focal.x * Vec3::new(k + x * x * g, x * y * g, x / distance_squared),
+                    focal.y * Vec3::new(x * y * g, k + y * y * g, y / distance_squared),
+                ]
+            }
+        }
+    }
+
+    /// Projects a gaussian given by its center and covariance in world space.
+    pub fn project_gaussian(&self, center: Vec3, covariance: Mat3) -> Option<ProjectedSplat> {
+        let view_position = self.view.transform_point3(center);
+        let projected_center = self.project_view_position(view_position)?;
+        let [row_x, row_y] = self.projection_jacobian(view_position);
+        // Rows of the Jacobian from world space, J * W
+        let view_rotation = Mat3::from_mat4(self.view);
+        let world_x = view_rotation.transpose() * row_x;
+        let world_y = view_rotation.transpose() * row_y;
+        let covariance_x = covariance * world_x;
+        let covariance_y = covariance * world_y;
+        let xy = world_x.dot(covariance_y);
+        let depth = match self.model {
+            ProjectionModel::Fisheye => view_position.length(),
+            _ => -view_position.z,
+        };
+        Some(ProjectedSplat {
+            center: projected_center,
+            covariance: Mat2::from_cols(Vec2::new(world_x.dot(covariance_x), xy), Vec2::new(xy, world_y.dot(covariance_y))),
+            depth,
+        })
+    }
+
+    pub fn project_splat(&self, splat: &Splat) -> Option<ProjectedSplat> {
+        self.project_gaussian(Vec3::from(splat.center), splat.covariance())
+    }
+
+    /// Direction in view space of the ray through a point in normalized device coordinates.
+    pub fn view_direction(&self, ndc: Vec2) -> Vec3 {
+        match self.model {
+            ProjectionModel::Fisheye => {
+                let angular = ndc / self.focal_scale();
+                let angle = angular.length();
+                if angle <= f32::EPSILON {
+                    return Vec3::NEG_Z;
+                }
+                (angular / angle * angle.sin()).extend(-angle.cos())
+            }
+            _ => {
+                let inverse = self.projection.inverse();
+                (inverse.project_point3(ndc.extend(1.0)) - inverse.project_point3(ndc.extend(-1.0))).normalize()
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Ray casting against gaussian splats
+//!
+//! Every splat is treated as a planar gaussian: It lies in the plane through its `center` with its `normal`,
+//! is stretched by `scale` along `ellipse_basis` and the axis perpendicular to both and fades out with its alpha.
+//! A ray accumulates opacity front to back (the same way the fragment shader blends) and reports a hit
+//! as soon as the accumulated opacity crosses a threshold.
+
+use crate::scene::{Camera, ProjectionModel, Scene, Splat};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use glam::{Vec2, Vec3};
+use std::cmp::Ordering;
+
+/// Opacity a ray has to accumulate before it counts as a hit.
+pub const DEFAULT_OPACITY_THRESHOLD: f32 = 0.5;
+
+/// Contributions below this are discarded, like in the fragment shader.
+const MIN_ALPHA: f32 = 1.0 / 255.0;
+
+/// Splats are cut off beyond this many standard deviations.
+const MAX_SIGMA_SQUARED: f32 = 9.0;
+
+/// A half-line starting at `origin` going along the normalized `direction`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// The point at `distance` along the ray.
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+}
+
+/// Where and at which splat a ray became opaque.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub position: Vec3,
+    pub distance: f32,
+    pub splat_index: usize,
+    /// Opacity weighted average of the normals of all splats the ray passed through, facing the ray origin.
+    pub normal: Vec3,
+    /// Opacity accumulated up to and including the hit splat.
+    pub opacity: f32,
+}
+
+impl Splat {
+    /// Distance along the ray and alpha at which the ray passes through this splat.
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let center = Vec3::from(self.center);
+        let [tangent, bitangent, normal] = self.tangent_frame();
+        let cosine = ray.direction.dot(normal);
+        if cosine.abs() < 1.0e-6 {
+            return None;
+        }
+        let distance = (center - ray.origin).dot(normal) / cosine;
+        if distance < 0.0 {
+            return None;
+        }
+        let offset = ray.at(distance) - center;
+        let u = offset.dot(tangent) / self.scale[0];
+        let v = offset.dot(bitangent) / self.scale[1];
+        let power = u * u + v * v;
+        if !power.is_finite() || power > MAX_SIGMA_SQUARED {
+            return None;
+        }
+        let alpha = self.color[3] * (-0.5 * power).exp();
+        if alpha < MIN_ALPHA {
+            return None;
+        }
+        Some((distance, alpha.min(1.0)))
+    }
+}
+
+impl Scene {
+    /// Finds the first point along `ray` (within `max_distance`) where the accumulated opacity reaches `opacity_threshold`.
+    pub fn raycast(&self, ray: &Ray, max_distance: f32, opacity_threshold: f32) -> Option<RayHit> {
+        let mut intersections = self
+            .splat_data
+            .iter()
+            .enumerate()
+            .filter_map(|(splat_index, splat)| {
+                splat
+                    .intersect_ray(ray)
+                    .filter(|(distance, _alpha)| *distance <= max_distance)
+                    .map(|(distance, alpha)| (distance, alpha, splat_index))
+            })
+            .collect::<Vec<_>>();
+        intersections.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut transmittance = 1.0;
+        let mut normal = Vec3::ZERO;
+        for (distance, alpha, splat_index) in intersections {
+            let weight = alpha * transmittance;
+            let splat_normal = Vec3::from(self.splat_data[splat_index].normal).normalize_or_zero();
+            normal += splat_normal * weight * -splat_normal.dot(ray.direction).signum();
+            transmittance *= 1.0 - alpha;
+            if 1.0 - transmittance >= opacity_threshold {
+                return Some(RayHit {
+                    position: ray.at(distance),
+                    distance,
+                    splat_index,
+                    normal: normal.normalize_or_zero(),
+                    opacity: 1.0 - transmittance,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// The nearest hit among several scenes, along with the key of the scene which was hit.
+pub fn raycast_scenes<'a, K>(
+    scenes: impl IntoIterator<Item = (K, &'a Scene)>,
+    ray: &Ray,
+    max_distance: f32,
+    opacity_threshold: f32,
+) -> Option<(K, RayHit)> {
+    scenes
+        .into_iter()
+        .filter_map(|(key, scene)| Some((key, scene.raycast(ray, max_distance, opacity_threshold)?)))
+        .min_by(|(_, a), (_, b)| a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal))
+}
+
+impl Camera {
+    /// Ray from the camera through a point given in normalized device coordinates ([-1, 1] in x and y, y up).
+    pub fn ray_through(&self, ndc: Vec2) -> Ray {
+        if self.model == ProjectionModel::Fisheye {
+            let camera_to_world = self.view.inverse();
+            let direction = camera_to_world.transform_vector3(self.view_direction(ndc));
+            return Ray::new(camera_to_world.w_axis.truncate() + direction * self.z_near, direction);
+        }
+        let inverse = (self.projection * self.view).inverse();
+        let near = inverse.project_point3(ndc.extend(-1.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        Ray::new(near, far - near)
+    }
+}
+
+/// The splat hit under the cursor of the primary window, updated every frame by [SplatPickingPlugin].
+#[derive(Resource, Default, Debug)]
+pub struct SplatPick {
+    pub hit: Option<RayHit>,
+    /// The entity of the [Scene] which was hit, none if it was the [Scene] resource.
+    pub scene: Option<Entity>,
+}
+
+pub struct SplatPickingPlugin;
+
+impl Plugin for SplatPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SplatPick>().add_systems(Update, pick_splat_under_cursor);
+    }
+}
+
+fn pick_splat_under_cursor(
+    mut pick: ResMut<SplatPick>,
+    scene: Option<Res<Scene>>,
+    scenes: Query<(Entity, &Scene)>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&bevy::render::camera::Camera, &Projection, &GlobalTransform)>,
+) {
+    (pick.hit, pick.scene) = (None, None);
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    // The first active camera by render order, like the primary splat view
+    let Some((camera, projection, transform)) = cameras.iter().filter(|(camera, _, _)| camera.is_active).min_by_key(|(camera, _, _)| camera.order)
+    else {
+        return;
+    };
+    // Without a viewport, or before Bevy computed it, the camera covers the whole window
+    let (viewport_min, viewport_size) = match camera.logical_viewport_rect() {
+        Some(viewport) => (Vec2::from(viewport.min.to_array()), Vec2::from(viewport.size().to_array())),
+        None => (Vec2::ZERO, Vec2::new(window.width(), window.height())),
+    };
+    if viewport_size.cmple(Vec2::ZERO).any() {
+        return;
+    }
+    // While the cursor is grabbed the crosshair in the center of the viewport is used instead
+    let cursor = window
+        .cursor_position()
+        .map_or(viewport_min + viewport_size * 0.5, |cursor| Vec2::from(cursor.to_array()));
+    let ndc = (cursor - viewport_min) / viewport_size * Vec2::new(2.0, -2.0) + Vec2::new(-1.0, 1.0);
+    let ray = Camera::from_bevy(transform, projection, viewport_size).ray_through(ndc);
+    let scenes = scene.as_deref().map(|scene| (None, scene)).into_iter().chain(scenes.iter().map(|(entity, scene)| (Some(entity), scene)));
+    if let Some((entity, hit)) = raycast_scenes(scenes, &ray, f32::INFINITY, DEFAULT_OPACITY_THRESHOLD) {
+        (pick.hit, pick.scene) = (Some(hit), entity);
+    }
+}
@@ -9,13 +9,24 @@ use bevy::prelude::*;
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+/// Mirrors `Uniforms` of shaders.wgsl field by field.
 struct Uniforms {
-    projection: [[f32; 4]; 4],
-    view: [[f32; 4]; 4],
-    params: [f32; 4], // z_near, z_far, frustum_culling_tolerance, ellipse_margin
-    counts: [f32; 4], // splat_scale, splat_count, visible_entries.len(), padding
+    camera_matrix: [[f32; 4]; 4],
+    view_matrix: [[f32; 4]; 4],
+    view_projection_matrix: [[f32; 4]; 4],
+    view_size: [f32; 2],
+    view_offset: [f32; 2],
+    image_size: [u32; 2],
+    frustum_culling_tolerance: f32,
+    ellipse_size_bias: f32,
+    ellipse_margin: f32,
+    splat_scale: f32,
+    projection_model: u32,
+    _padding: u32, // WGSL rounds the size of the struct up to the alignment of its matrices
 }
 
+const _: () = assert!(std::mem::size_of::<Uniforms>() == 240);
+
 #[derive(Resource)]
 pub struct Renderer {
     pub pipeline: Option<RenderPipeline>,
@@ -124,21 +135,21 @@ impl Renderer {
         if let Some(pipeline) = &self.pipeline {
             if let Some(uniform_buffer) = &self.uniform_buffer {
                 // Update uniforms
+                let (view_size, view_offset) = scene.camera.view_plane();
                 let uniforms = Uniforms {
-                    projection: scene.camera.projection.to_cols_array_2d(),
-                    view: scene.camera.view.to_cols_array_2d(),
-                    params: [
-                        scene.camera.z_near,
-                        scene.camera.z_far,
-                        self.config.frustum_culling_tolerance,
-                        self.config.ellipse_margin,
-                    ],
-                    counts: [
-                        self.config.splat_scale,
-                        scene.splat_count as f32,
-                        0.0,
-                        0.0,
-                    ],
+                    camera_matrix: scene.camera.view.inverse().to_cols_array_2d(),
+                    view_matrix: scene.camera.view.to_cols_array_2d(),
+                    view_projection_matrix: (scene.camera.projection * scene.camera.view).to_cols_array_2d(),
+                    view_size: view_size.to_array(),
+                    view_offset: view_offset.to_array(),
+                    image_size: [self.config.surface_configuration.width, self.config.surface_configuration.height],
+                    frustum_culling_tolerance: self.config.frustum_culling_tolerance,
+                    // Like the CPU renderer, which draws the ellipses without a bias
+                    ellipse_size_bias: 0.0,
+                    ellipse_margin: self.config.ellipse_margin,
+                    splat_scale: self.config.splat_scale,
+                    projection_model: scene.camera.model as u32,
+                    _padding: 0,
                 };
 
                 queue.write_buffer(uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
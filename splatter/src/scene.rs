@@ -1,17 +1,24 @@
+pub mod generate;
+
+use crate::coordinates::{CoordinateConversion, CoordinateSystem, ShTransform};
+use crate::loading::{SceneLoading, SplatLoadingPlugin};
+use crate::lod::SceneLod;
 use bevy::prelude::*;
 use bevy::render::render_resource::Buffer as BevyBuffer;
-use bevy::render::renderer::RenderDevice;
+use bevy::render::renderer::{RenderDevice, RenderQueue};
 // use bevy::render::texture::Image;
 use bytemuck;
 use bytemuck::{Pod, Zeroable};
-use glam::{Mat4, Vec3, Vec4};
+use glam::{Affine3A, Mat3, Mat4, Quat, Vec3, Vec4};
 use std::fs;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use ply_rs::parser::Parser;
-use ply_rs::ply::DefaultElement;
+use ply_rs::ply::{Addable, DefaultElement, ElementDef, Encoding, Ply, Property, PropertyDef, PropertyType, ScalarType};
+use ply_rs::writer::Writer;
 pub struct ScenePlugin;
-use wgpu::util::BufferInitDescriptor;
+use wgpu::BufferDescriptor;
 // use wgpu::Buffer as WgpuBuffer;
 #[repr(C)] // ensure C-compatible field ordering & alignment
 #[derive(Clone, Copy, Pod, Zeroable)]
@@ -27,9 +34,38 @@ pub struct ShaderSplat {
     // e.g. spherical-harmonic color coefficients
     pub color_sh: [f32; 48], // 192 bytes
 }
+
+impl ShaderSplat {
+    /// A splat with the coefficients of its view dependent colors, see [Scene::spherical_harmonics].
+    pub fn new(splat: &Splat, spherical_harmonics: &[Vec3]) -> Self {
+        let mut color_sh = [0.0; 48];
+        // The shader adds band 0 to a gray of 0.5
+        let dc = (Vec3::new(splat.color[0], splat.color[1], splat.color[2]) - 0.5) / SH_C0;
+        color_sh[0..3].copy_from_slice(&dc.to_array());
+        for (index, coefficient) in spherical_harmonics.iter().take(15).enumerate() {
+            color_sh[3 * index + 3..3 * index + 6].copy_from_slice(&coefficient.to_array());
+        }
+        ShaderSplat {
+            rotation: [0.0, 0.0, 0.0, 1.0], // Placeholder
+            center: splat.center,
+            _pad0: 0.0,
+            scale: splat.scale,
+            alpha: splat.color[3],
+            color_sh,
+            _pad1: [0.0; 3],
+        }
+    }
+}
+
+impl From<&Splat> for ShaderSplat {
+    fn from(splat: &Splat) -> Self {
+        ShaderSplat::new(splat, &[])
+    }
+}
 impl Plugin for ScenePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Scene>()
+            .add_plugins(SplatLoadingPlugin)
             .add_systems(Startup, setup_scene)
             .add_systems(Update, convert_splat_data);
     }
@@ -38,12 +74,39 @@ impl Plugin for ScenePlugin {
 #[derive(Component)]
 pub struct GaussianBackground;
 
+/// How a [Camera] maps view space onto the image.
+///
+/// The discriminants match the `PROJECTION_*` constants of the shader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProjectionModel {
+    #[default]
+    Perspective = 0,
+    Orthographic = 1,
+    /// Equidistant fisheye, see [crate::projection].
+    Fisheye = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Camera {
     pub projection: Mat4,
     pub view: Mat4,
     pub z_near: f32,
     pub z_far: f32,
+    pub model: ProjectionModel,
 }
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            projection: Mat4::perspective_rh_gl(45.0_f32.to_radians(), 16.0 / 9.0, 0.1, 100.0),
+            view: Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y),
+            z_near: 0.1,
+            z_far: 100.0,
+            model: ProjectionModel::Perspective,
+        }
+    }
+}
+
 impl Camera {
     pub fn get_clip_space_position(&self, position: &Vec3) -> Vec3 {
         let view_pos = self.view * Vec4::new(position.x, position.y, position.z, 1.0);
@@ -52,7 +115,34 @@ impl Camera {
     }
 }
 
-#[derive(Component)]
+/// Number of floats per splat in files: center, color, scale, normal and ellipse basis.
+pub const PACKED_SPLAT_FLOATS: usize = 15;
+
+/// Number of little endian floats per splat in `.splat` files: center, color, depth, scale, normal, ellipse basis and
+/// the model matrix in column major order.
+pub const SPLAT_FILE_FLOATS: usize = 32;
+
+/// File formats of splat scenes, told apart by their extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    /// The PLY files of 3D gaussian splatting, see [Scene::from_ply].
+    Ply,
+    /// Raw floats, see [SPLAT_FILE_FLOATS].
+    Splat,
+}
+
+impl SceneFormat {
+    pub fn of_path(path: impl AsRef<std::path::Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ply" => Some(SceneFormat::Ply),
+            "splat" => Some(SceneFormat::Splat),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Component, Clone)]
 pub struct Splat {
     pub model_matrix: Mat4,
     pub center: [f32; 3],
@@ -63,6 +153,176 @@ pub struct Splat {
     pub ellipse_basis: [f32; 3],
 }
 
+impl Splat {
+    /// Orthonormal frame of the splat: The first scale axis, the second scale axis and the normal.
+    pub fn tangent_frame(&self) -> [Vec3; 3] {
+        let normal = Vec3::from(self.normal).normalize_or_zero();
+        let ellipse_basis = Vec3::from(self.ellipse_basis);
+        let tangent = (ellipse_basis - normal * ellipse_basis.dot(normal)).normalize_or_zero();
+        [tangent, normal.cross(tangent), normal]
+    }
+
+    /// Converts a vertex of a PLY file.
+    ///
+    /// Besides plain point clouds this understands the properties written by 3D gaussian splatting:
+    /// `opacity` as logit, `scale_*` as logarithm, `rot_*` as quaternion (w, x, y, z) and `f_dc_*` as SH DC color.
+    /// The two largest axes of such a gaussian become the splat ellipse, the smallest one its normal.
+    pub fn from_ply_vertex(v: &DefaultElement) -> Self {
+        let x = v.get("x").and_then(|p| match p {
+            ply_rs::ply::Property::Float(f) => Some(*f as f32),
+            ply_rs::ply::Property::Double(d) => Some(*d as f32),
+            ply_rs::ply::Property::Int(i) => Some(*i as f32),
+            ply_rs::ply::Property::UInt(u) => Some(*u as f32),
+            _ => None,
+        }).unwrap_or(0.0);
+
+        let y = v.get("y").and_then(|p| match p {
+            ply_rs::ply::Property::Float(f) => Some(*f as f32),
+            ply_rs::ply::Property::Double(d) => Some(*d as f32),
+            ply_rs::ply::Property::Int(i) => Some(*i as f32),
+            ply_rs::ply::Property::UInt(u) => Some(*u as f32),
+            _ => None,
+        }).unwrap_or(0.0);
+
+        let z = v.get("z").and_then(|p| match p {
+            ply_rs::ply::Property::Float(f) => Some(*f as f32),
+            ply_rs::ply::Property::Double(d) => Some(*d as f32),
+            ply_rs::ply::Property::Int(i) => Some(*i as f32),
+            ply_rs::ply::Property::UInt(u) => Some(*u as f32),
+            _ => None,
+        }).unwrap_or(0.0);
+
+        let r = v.get("red").and_then(|p| match p {
+            ply_rs::ply::Property::UChar(u) => Some(*u as f32 / 255.0),
+            ply_rs::ply::Property::Char(i) => Some(*i as f32 / 255.0),
+            ply_rs::ply::Property::Int(i) => Some(*i as f32 / 255.0),
+            ply_rs::ply::Property::UInt(u) => Some(*u as f32 / 255.0),
+            _ => None,
+        }).unwrap_or(1.0);
+
+        let g = v.get("green").and_then(|p| match p {
+            ply_rs::ply::Property::UChar(u) => Some(*u as f32 / 255.0),
+            ply_rs::ply::Property::Char(i) => Some(*i as f32 / 255.0),
+            ply_rs::ply::Property::Int(i) => Some(*i as f32 / 255.0),
+            ply_rs::ply::Property::UInt(u) => Some(*u as f32 / 255.0),
+            _ => None,
+        }).unwrap_or(1.0);
+
+        let b = v.get("blue").and_then(|p| match p {
+            ply_rs::ply::Property::UChar(u) => Some(*u as f32 / 255.0),
+            ply_rs::ply::Property::Char(i) => Some(*i as f32 / 255.0),
+            ply_rs::ply::Property::Int(i) => Some(*i as f32 / 255.0),
+            ply_rs::ply::Property::UInt(u) => Some(*u as f32 / 255.0),
+            _ => None,
+        }).unwrap_or(1.0);
+
+        let a = v.get("alpha").and_then(|p| match p {
+            ply_rs::ply::Property::UChar(u) => Some(*u as f32 / 255.0),
+            ply_rs::ply::Property::Char(i) => Some(*i as f32 / 255.0),
+            ply_rs::ply::Property::Int(i) => Some(*i as f32 / 255.0),
+            ply_rs::ply::Property::UInt(u) => Some(*u as f32 / 255.0),
+            _ => None,
+        }).unwrap_or(1.0);
+
+        let mut splat = Splat {
+            model_matrix: Mat4::IDENTITY,
+            center: [x, y, z],
+            color: [r, g, b, a],
+            depth: 0.0,
+            scale: [0.05, 0.05], // Default scale for splats
+            normal: [0.0, 1.0, 0.0], // Default normal
+            ellipse_basis: [1.0, 0.0, 0.0], // Default basis
+        };
+        if let Some(opacity) = numeric_property(v, "opacity") {
+            splat.color[3] = 1.0 / (1.0 + (-opacity).exp());
+        }
+        if v.get("red").is_none() {
+            for (channel, name) in ["f_dc_0", "f_dc_1", "f_dc_2"].into_iter().enumerate() {
+                if let Some(dc) = numeric_property(v, name) {
+                    splat.color[channel] = (0.5 + SH_C0 * dc).clamp(0.0, 1.0);
+                }
+            }
+        }
+        if let (Some(scale_0), Some(scale_1), Some(scale_2)) =
+            (numeric_property(v, "scale_0"), numeric_property(v, "scale_1"), numeric_property(v, "scale_2"))
+        {
+            let rotation = match (
+                numeric_property(v, "rot_0"),
+                numeric_property(v, "rot_1"),
+                numeric_property(v, "rot_2"),
+                numeric_property(v, "rot_3"),
+            ) {
+                (Some(w), Some(x), Some(y), Some(z)) => Quat::from_xyzw(x, y, z, w).normalize(),
+                _ => Quat::IDENTITY,
+            };
+            let mut axes = [
+                (scale_0.exp(), rotation * Vec3::X),
+                (scale_1.exp(), rotation * Vec3::Y),
+                (scale_2.exp(), rotation * Vec3::Z),
+            ];
+            axes.sort_by(|a, b| b.0.total_cmp(&a.0));
+            splat.scale = [axes[0].0, axes[1].0];
+            splat.ellipse_basis = axes[0].1.to_array();
+            splat.normal = axes[2].1.to_array();
+        }
+        splat
+    }
+
+    /// The attributes of the splat which are stored in files, see [PACKED_SPLAT_FLOATS].
+    pub fn to_packed(&self) -> [f32; PACKED_SPLAT_FLOATS] {
+        let mut packed = [0.0; PACKED_SPLAT_FLOATS];
+        packed[0..3].copy_from_slice(&self.center);
+        packed[3..7].copy_from_slice(&self.color);
+        packed[7..9].copy_from_slice(&self.scale);
+        packed[9..12].copy_from_slice(&self.normal);
+        packed[12..15].copy_from_slice(&self.ellipse_basis);
+        packed
+    }
+
+    pub fn from_packed(packed: &[f32; PACKED_SPLAT_FLOATS]) -> Self {
+        Splat {
+            model_matrix: Mat4::IDENTITY,
+            center: [packed[0], packed[1], packed[2]],
+            color: [packed[3], packed[4], packed[5], packed[6]],
+            depth: 0.0,
+            scale: [packed[7], packed[8]],
+            normal: [packed[9], packed[10], packed[11]],
+            ellipse_basis: [packed[12], packed[13], packed[14]],
+        }
+    }
+
+    /// 3D covariance of the gaussian, which is flat along the normal.
+    pub fn covariance(&self) -> Mat3 {
+        let [tangent, bitangent, _normal] = self.tangent_frame();
+        let scaled = Mat3::from_cols(tangent * self.scale[0], bitangent * self.scale[1], Vec3::ZERO);
+        scaled * scaled.transpose()
+    }
+
+    /// Fits a splat to a 3D gaussian: The two largest axes become the ellipse, the smallest one the normal.
+    pub fn from_covariance(center: Vec3, covariance: Mat3, color: [f32; 4]) -> Self {
+        let (variances, axes) = crate::utils::symmetric_eigen(covariance);
+        Splat {
+            model_matrix: Mat4::IDENTITY,
+            center: center.to_array(),
+            color,
+            depth: 0.0,
+            scale: [variances.x.max(0.0).sqrt(), variances.y.max(0.0).sqrt()],
+            normal: axes.z_axis.to_array(),
+            ellipse_basis: axes.x_axis.to_array(),
+        }
+    }
+}
+
+/// A scene merged into another one, see [Scene::merge].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneSource {
+    pub name: String,
+    /// Where the source was placed, after the conversion into the coordinate system of the merged scene.
+    pub transform: Affine3A,
+    /// The splats of the source in `splat_data` at the time of merging.
+    pub splats: Range<usize>,
+}
+
 #[derive(Component, Resource)]
 pub struct Scene {
     pub splat_count: usize,     // Change from u32 to usize
@@ -70,11 +330,148 @@ pub struct Scene {
     pub splat_positions: Vec<[f32; 3]>,
     pub compute_bind_groups: Vec<wgpu::BindGroup>,
     pub render_bind_group: Option<wgpu::BindGroup>,
+    /// Splats in the layout of the shader, possibly with room for more than `splat_count`, see [grown_splat_capacity].
     pub splat_buffer: Option<BevyBuffer>, // ← NEW
     pub camera: Camera,
     pub sorting_buffer: Option<BevyBuffer>,
+    /// Range of `splat_data` which changed since it was last copied into the `splat_buffer`.
+    pub dirty_splats: Option<Range<usize>>,
+    /// Convention of the positions in `splat_data`.
+    pub coordinate_system: CoordinateSystem,
+    /// Highest band of the view dependent colors, from 0 to 3.
+    pub spherical_harmonics_order: u32,
+    /// Coefficients of the bands 1 up to `spherical_harmonics_order` of every splat, one color per basis function,
+    /// see [spherical_harmonics_count]. Band 0 is the color of the splat.
+    pub spherical_harmonics: Vec<Vec3>,
+    /// Scenes merged into this one in the order of their splats, empty unless it was merged.
+    pub sources: Vec<SceneSource>,
 }
 impl Scene {
+    /// Marks a range of splats whose copy in the `splat_buffer` needs to be updated.
+    pub fn mark_dirty(&mut self, range: Range<usize>) {
+        self.dirty_splats = Some(match self.dirty_splats.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range,
+        });
+    }
+
+    /// Converts the splats and their view dependent colors into another coordinate system.
+    pub fn convert_coordinates(&mut self, target: CoordinateSystem) {
+        let conversion = CoordinateConversion::between(&self.coordinate_system, &target);
+        self.coordinate_system = target;
+        if !conversion.is_identity() {
+            conversion.splats(&mut self.splat_data);
+            self.transform_spherical_harmonics(&conversion.spherical_harmonics());
+            self.mark_dirty(0..self.splat_data.len());
+        }
+    }
+
+    /// The coefficients of the view dependent colors of a splat.
+    pub fn spherical_harmonics_of(&self, index: usize) -> &[Vec3] {
+        let count = spherical_harmonics_count(self.spherical_harmonics_order);
+        self.spherical_harmonics.get(index * count..(index + 1) * count).unwrap_or(&[])
+    }
+
+    /// Bakes a transform into the splats: their centers, covariances and view dependent colors.
+    ///
+    /// Splats stay flat under any affine transform. The spherical harmonics turn with the rotation closest to the
+    /// transform, as shears and non-uniform scales have no equivalent for them.
+    pub fn transform(&mut self, transform: &Affine3A) {
+        let linear = Mat3::from(transform.matrix3);
+        for splat in &mut self.splat_data {
+            let center = transform.transform_point3(Vec3::from(splat.center));
+            let covariance = linear * splat.covariance() * linear.transpose();
+            *splat = Splat {
+                model_matrix: splat.model_matrix,
+                depth: splat.depth,
+                ..Splat::from_covariance(center, covariance, splat.color)
+            };
+        }
+        self.transform_spherical_harmonics(&ShTransform::new(crate::utils::polar_rotation(linear)));
+        self.mark_dirty(0..self.splat_data.len());
+    }
+
+    fn transform_spherical_harmonics(&mut self, sh_transform: &ShTransform) {
+        let count = spherical_harmonics_count(self.spherical_harmonics_order);
+        if count == 0 {
+            return;
+        }
+        let mut coefficients = [Vec3::ZERO; 16];
+        for splat in self.spherical_harmonics.chunks_exact_mut(count) {
+            // The transform starts with band 0, which is kept in the color
+            coefficients[1..=count].copy_from_slice(splat);
+            sh_transform.apply(&mut coefficients[..=count]);
+            splat.copy_from_slice(&coefficients[1..=count]);
+        }
+    }
+
+    /// Changes the order of the view dependent colors, padding the missing bands with zeros or dropping the higher ones.
+    pub fn set_spherical_harmonics_order(&mut self, order: u32) {
+        let order = order.min(3);
+        let count = spherical_harmonics_count(order);
+        if order == self.spherical_harmonics_order && self.spherical_harmonics.len() == count * self.splat_data.len() {
+            return;
+        }
+        self.spherical_harmonics = (0..self.splat_data.len())
+            .flat_map(|index| {
+                let coefficients = self.spherical_harmonics_of(index);
+                (0..count).map(|coefficient| coefficients.get(coefficient).copied().unwrap_or(Vec3::ZERO))
+            })
+            .collect();
+        self.spherical_harmonics_order = order;
+        self.mark_dirty(0..self.splat_data.len());
+    }
+
+    /// Appends the splats of other scenes, each converted into the coordinate system of this one and then transformed.
+    ///
+    /// The view dependent colors are padded to the highest order among the scenes, see
+    /// [Scene::set_spherical_harmonics_order] to truncate them afterwards. Every merged scene is recorded in `sources`,
+    /// splats from before the first merge become a source without name.
+    pub fn merge<'a>(&mut self, others: impl IntoIterator<Item = (&'a str, &'a Scene, Affine3A)>) {
+        if self.sources.is_empty() && !self.splat_data.is_empty() {
+            self.sources.push(SceneSource {
+                name: String::new(),
+                transform: Affine3A::IDENTITY,
+                splats: 0..self.splat_data.len(),
+            });
+        }
+        for (name, other, transform) in others {
+            let mut appended = Scene::new();
+            appended.coordinate_system = other.coordinate_system;
+            appended.splat_data = other.splat_data.clone();
+            appended.spherical_harmonics_order = other.spherical_harmonics_order;
+            appended.spherical_harmonics = other.spherical_harmonics.clone();
+            appended.set_spherical_harmonics_order(self.spherical_harmonics_order.max(other.spherical_harmonics_order));
+            appended.convert_coordinates(self.coordinate_system);
+            if transform != Affine3A::IDENTITY {
+                appended.transform(&transform);
+            }
+            self.set_spherical_harmonics_order(appended.spherical_harmonics_order);
+
+            let start = self.splat_data.len();
+            self.splat_data.append(&mut appended.splat_data);
+            self.spherical_harmonics.append(&mut appended.spherical_harmonics);
+            self.splat_count = self.splat_data.len();
+            self.sources.push(SceneSource {
+                name: name.to_string(),
+                transform,
+                splats: start..self.splat_count,
+            });
+            self.mark_dirty(start..self.splat_count);
+        }
+    }
+
+    /// The source a splat was merged from.
+    pub fn source_of(&self, index: usize) -> Option<&SceneSource> {
+        let source = self.sources.partition_point(|source| source.splats.end <= index);
+        self.sources.get(source).filter(|source| source.splats.contains(&index))
+    }
+
+    /// The splats in the layout of the storage buffer of the shader, with their view dependent colors.
+    pub fn shader_splats(&self, range: Range<usize>) -> Vec<ShaderSplat> {
+        range.map(|index| ShaderSplat::new(&self.splat_data[index], self.spherical_harmonics_of(index))).collect()
+    }
+
     pub fn load_chunk(
         &mut self,
         queue: &mut wgpu::Queue,
@@ -122,161 +519,313 @@ impl Scene {
             render_bind_group: None,
             splat_buffer: None,
             sorting_buffer: None,
-            camera: Camera {
-                projection: Mat4::perspective_rh_gl(45.0_f32.to_radians(), 16.0 / 9.0, 0.1, 100.0),
-                view: Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y),
-                z_near: 0.1,
-                z_far: 100.0,
-            },
+            dirty_splats: None,
+            camera: Camera::default(),
+            coordinate_system: CoordinateSystem::BEVY,
+            spherical_harmonics_order: 0,
+            spherical_harmonics: Vec::new(),
+            sources: Vec::new(),
         }
     }
+
+    /// A scene of the splats, without view dependent colors.
+    pub fn from_splats(splats: Vec<Splat>) -> Self {
+        let mut scene = Self::new();
+        scene.splat_count = splats.len();
+        scene.splat_data = splats;
+        scene
+    }
+
     pub fn load_splat_file(&mut self, path: &str) {
-        let raw_data = fs::read(path).expect("Failed to read splat file");
-        let float_size = std::mem::size_of::<f32>();
-        let splat_size = (3 + 4 + 1 + 2 + 3 + 3 + 16) * float_size; // Size of one Splat
-        assert_eq!(raw_data.len() % splat_size, 0, "Invalid splat data size");
+        self.splat_data = Self::read_splat_file(path).expect("Failed to read splat file");
+        self.splat_count = self.splat_data.len(); // Remove as u32 cast, use usize
+        self.mark_dirty(0..self.splat_count);
+        println!("Loaded {} splats from PLY", self.splat_data.len());
+    }
 
-        self.splat_data = raw_data
-            .chunks(splat_size)
+    /// Reads the raw splat format, see [SPLAT_FILE_FLOATS].
+    pub fn read_splat_file(path: &str) -> io::Result<Vec<Splat>> {
+        let raw_data = fs::read(path)?;
+        let splat_size = SPLAT_FILE_FLOATS * std::mem::size_of::<f32>();
+        if raw_data.len() % splat_size != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid splat data size"));
+        }
+        Ok(raw_data
+            .chunks_exact(splat_size)
             .map(|chunk| {
-                let floats = bytemuck::cast_slice::<u8, f32>(chunk);
-                let mut offset = 0;
-
-                let center = [floats[offset], floats[offset + 1], floats[offset + 2]];
-                offset += 3;
-
-                let color = [floats[offset], floats[offset + 1], floats[offset + 2], floats[offset + 3]];
-                offset += 4;
-
-                let depth = floats[offset];
-                offset += 1;
-
-                let scale = [floats[offset], floats[offset + 1]];
-                offset += 2;
-
-                let normal = [floats[offset], floats[offset + 1], floats[offset + 2]];
-                offset += 3;
-
-                let ellipse_basis = [floats[offset], floats[offset + 1], floats[offset + 2]];
-                offset += 3;
-
-                let model_matrix = Mat4::from_cols_array(&[
-                    floats[offset],
-                    floats[offset + 1],
-                    floats[offset + 2],
-                    floats[offset + 3],
-                    floats[offset + 4],
-                    floats[offset + 5],
-                    floats[offset + 6],
-                    floats[offset + 7],
-                    floats[offset + 8],
-                    floats[offset + 9],
-                    floats[offset + 10],
-                    floats[offset + 11],
-                    floats[offset + 12],
-                    floats[offset + 13],
-                    floats[offset + 14],
-                    floats[offset + 15],
-                ]);
-
+                let floats: Vec<f32> = chunk.chunks_exact(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())).collect();
                 Splat {
-                    model_matrix,
-                    center,
-                    color,
-                    depth,
-                    scale,
-                    normal,
-                    ellipse_basis,
+                    center: [floats[0], floats[1], floats[2]],
+                    color: [floats[3], floats[4], floats[5], floats[6]],
+                    depth: floats[7],
+                    scale: [floats[8], floats[9]],
+                    normal: [floats[10], floats[11], floats[12]],
+                    ellipse_basis: [floats[13], floats[14], floats[15]],
+                    model_matrix: Mat4::from_cols_slice(&floats[16..32]),
                 }
             })
-            .collect();
+            .collect())
+    }
 
-        self.splat_count = self.splat_data.len(); // Remove as u32 cast, use usize
-        println!("Loaded {} splats from PLY", self.splat_data.len());
+    /// Writes the splats in the raw splat format, see [Scene::read_splat_file].
+    pub fn save_splat_file(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for splat in &self.splat_data {
+            let floats = splat
+                .center
+                .into_iter()
+                .chain(splat.color)
+                .chain([splat.depth])
+                .chain(splat.scale)
+                .chain(splat.normal)
+                .chain(splat.ellipse_basis)
+                .chain(splat.model_matrix.to_cols_array());
+            for value in floats {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        writer.flush()
     }
-    pub fn load_splats_from_ply(&mut self, path: &str) {
-        let mut f = std::fs::File::open(path).expect("Failed to open PLY file");
-        let mut reader = std::io::BufReader::new(&mut f);
-        let parser = Parser::<DefaultElement>::new();
-        let ply = parser.read_ply(&mut reader).expect("Failed to parse PLY");
-        let vertex_list = ply.payload.get("vertex").expect("No vertex element in PLY");
-        self.splat_data.clear();
-        for v in vertex_list {
-            let x = v.get("x").and_then(|p| match p {
-                ply_rs::ply::Property::Float(f) => Some(*f as f32),
-                ply_rs::ply::Property::Double(d) => Some(*d as f32),
-                ply_rs::ply::Property::Int(i) => Some(*i as f32),
-                ply_rs::ply::Property::UInt(u) => Some(*u as f32),
-                _ => None,
-            }).unwrap_or(0.0);
-
-            let y = v.get("y").and_then(|p| match p {
-                ply_rs::ply::Property::Float(f) => Some(*f as f32),
-                ply_rs::ply::Property::Double(d) => Some(*d as f32),
-                ply_rs::ply::Property::Int(i) => Some(*i as f32),
-                ply_rs::ply::Property::UInt(u) => Some(*u as f32),
-                _ => None,
-            }).unwrap_or(0.0);
-
-            let z = v.get("z").and_then(|p| match p {
-                ply_rs::ply::Property::Float(f) => Some(*f as f32),
-                ply_rs::ply::Property::Double(d) => Some(*d as f32),
-                ply_rs::ply::Property::Int(i) => Some(*i as f32),
-                ply_rs::ply::Property::UInt(u) => Some(*u as f32),
-                _ => None,
-            }).unwrap_or(0.0);
-
-            let r = v.get("red").and_then(|p| match p {
-                ply_rs::ply::Property::UChar(u) => Some(*u as f32 / 255.0),
-                ply_rs::ply::Property::Char(i) => Some(*i as f32 / 255.0),
-                ply_rs::ply::Property::Int(i) => Some(*i as f32 / 255.0),
-                ply_rs::ply::Property::UInt(u) => Some(*u as f32 / 255.0),
-                _ => None,
-            }).unwrap_or(1.0);
-
-            let g = v.get("green").and_then(|p| match p {
-                ply_rs::ply::Property::UChar(u) => Some(*u as f32 / 255.0),
-                ply_rs::ply::Property::Char(i) => Some(*i as f32 / 255.0),
-                ply_rs::ply::Property::Int(i) => Some(*i as f32 / 255.0),
-                ply_rs::ply::Property::UInt(u) => Some(*u as f32 / 255.0),
-                _ => None,
-            }).unwrap_or(1.0);
-
-            let b = v.get("blue").and_then(|p| match p {
-                ply_rs::ply::Property::UChar(u) => Some(*u as f32 / 255.0),
-                ply_rs::ply::Property::Char(i) => Some(*i as f32 / 255.0),
-                ply_rs::ply::Property::Int(i) => Some(*i as f32 / 255.0),
-                ply_rs::ply::Property::UInt(u) => Some(*u as f32 / 255.0),
-                _ => None,
-            }).unwrap_or(1.0);
-
-            let a = v.get("alpha").and_then(|p| match p {
-                ply_rs::ply::Property::UChar(u) => Some(*u as f32 / 255.0),
-                ply_rs::ply::Property::Char(i) => Some(*i as f32 / 255.0),
-                ply_rs::ply::Property::Int(i) => Some(*i as f32 / 255.0),
-                ply_rs::ply::Property::UInt(u) => Some(*u as f32 / 255.0),
-                _ => None,
-            }).unwrap_or(1.0);
-
-            self.splat_data.push(Splat {
-                model_matrix: Mat4::IDENTITY,
-                center: [x, y, z],
-                color: [r, g, b, a],
-                depth: 0.0,
-                scale: [0.05, 0.05], // Default scale for splats
-                normal: [0.0, 1.0, 0.0], // Default normal
-                ellipse_basis: [1.0, 0.0, 0.0], // Default basis
-            });
+
+    /// Reads a scene in the format of its extension, see [SceneFormat].
+    pub fn load(path: &str) -> io::Result<Scene> {
+        match SceneFormat::of_path(path) {
+            Some(SceneFormat::Ply) => Scene::from_ply(path),
+            Some(SceneFormat::Splat) => {
+                let mut scene = Scene::new();
+                scene.splat_data = Scene::read_splat_file(path)?;
+                scene.splat_count = scene.splat_data.len();
+                Ok(scene)
+            }
+            None => Err(io::Error::new(io::ErrorKind::Unsupported, "Unknown scene format, expected .ply or .splat")),
+        }
+    }
+
+    /// Writes the scene in the format of the extension of the path, see [SceneFormat].
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        match SceneFormat::of_path(path) {
+            Some(SceneFormat::Ply) => self.save_splats_to_ply(path),
+            Some(SceneFormat::Splat) => self.save_splat_file(path),
+            None => Err(io::Error::new(io::ErrorKind::Unsupported, "Unknown scene format, expected .ply or .splat")),
+        }
+    }
+
+    /// Keeps the splats for which `keep` is true along with their view dependent colors and shrinks the `sources`
+    /// accordingly. Returns how many were removed.
+    pub fn retain(&mut self, mut keep: impl FnMut(usize, &Splat) -> bool) -> usize {
+        let kept: Vec<bool> = self.splat_data.iter().enumerate().map(|(index, splat)| keep(index, splat)).collect();
+        let count = spherical_harmonics_count(self.spherical_harmonics_order);
+        if count > 0 && self.spherical_harmonics.len() == count * kept.len() {
+            let mut kept = kept.iter().flat_map(|kept| std::iter::repeat_n(*kept, count));
+            self.spherical_harmonics.retain(|_| kept.next().unwrap());
+        }
+        let mut index = 0;
+        self.splat_data.retain(|_| {
+            index += 1;
+            kept[index - 1]
+        });
+        let mut start = 0;
+        for source in &mut self.sources {
+            let kept_count = kept.get(source.splats.clone()).map_or(0, |kept| kept.iter().filter(|kept| **kept).count());
+            source.splats = start..start + kept_count;
+            start += kept_count;
         }
+        let removed = kept.len() - self.splat_data.len();
         self.splat_count = self.splat_data.len();
+        if removed > 0 {
+            self.mark_dirty(0..self.splat_count);
+        }
+        removed
+    }
+
+    /// Reorders the splats along with their view dependent colors, so that the splat at `order[index]` moves to `index`.
+    /// The `sources` stay as they are, so the order has to keep the splats of every source within its range.
+    pub fn permute(&mut self, order: &[u32]) {
+        assert_eq!(order.len(), self.splat_data.len(), "The order has to cover all splats");
+        self.splat_data = order.iter().map(|index| self.splat_data[*index as usize].clone()).collect();
+        let count = spherical_harmonics_count(self.spherical_harmonics_order);
+        if count > 0 && self.spherical_harmonics.len() == count * order.len() {
+            self.spherical_harmonics = order
+                .iter()
+                .flat_map(|index| &self.spherical_harmonics[*index as usize * count..(*index as usize + 1) * count])
+                .copied()
+                .collect();
+        }
+        self.mark_dirty(0..self.splat_count);
+    }
+
+    pub fn load_splats_from_ply(&mut self, path: &str) {
+        self.splat_data = Self::read_splats_from_ply(path).expect("Failed to load PLY");
+        self.splat_count = self.splat_data.len();
+        self.mark_dirty(0..self.splat_count);
         println!("Loaded {} splats from PLY", self.splat_data.len());
     }
 
+    /// Reads the vertices of a PLY file as splats, see [Splat::from_ply_vertex].
+    pub fn read_splats_from_ply(path: &str) -> io::Result<Vec<Splat>> {
+        Ok(read_ply_vertices(path)?.iter().map(Splat::from_ply_vertex).collect())
+    }
+
+    /// Reads the splats of a PLY file along with their view dependent colors, the `f_rest_*` properties of 3D gaussian
+    /// splatting, which hold all coefficients of the red channel first, then green and blue.
+    pub fn from_ply(path: &str) -> io::Result<Scene> {
+        let vertices = read_ply_vertices(path)?;
+        let mut scene = Scene::new();
+        scene.splat_data = vertices.iter().map(Splat::from_ply_vertex).collect();
+        scene.splat_count = scene.splat_data.len();
+        let rest_count = vertices
+            .first()
+            .map_or(0, |vertex| (0..).take_while(|index| vertex.contains_key(&format!("f_rest_{index}"))).count());
+        let (order, per_channel) = ply_spherical_harmonics_layout(rest_count);
+        scene.spherical_harmonics_order = order;
+        scene.spherical_harmonics = Vec::with_capacity(spherical_harmonics_count(order) * vertices.len());
+        for vertex in &vertices {
+            read_ply_spherical_harmonics(vertex, order, per_channel, &mut scene.spherical_harmonics);
+        }
+        Ok(scene)
+    }
+
+    /// Moves the splats of a scene in the same coordinate system to the end of this one, padding the view dependent
+    /// colors of both to the higher order.
+    pub fn append(&mut self, other: &mut Scene) {
+        let order = self.spherical_harmonics_order.max(other.spherical_harmonics_order);
+        if self.splat_data.is_empty() {
+            self.spherical_harmonics.clear();
+            self.spherical_harmonics_order = order;
+        }
+        self.set_spherical_harmonics_order(order);
+        other.set_spherical_harmonics_order(order);
+        let start = self.splat_data.len();
+        self.splat_data.append(&mut other.splat_data);
+        self.spherical_harmonics.append(&mut other.spherical_harmonics);
+        other.splat_count = 0;
+        self.splat_count = self.splat_data.len();
+        self.mark_dirty(start..self.splat_count);
+    }
+
+    /// Writes the splats as binary PLY with the properties of 3D gaussian splatting, see [Scene::read_splats_from_ply].
+    ///
+    /// View dependent colors are written as `f_rest_*` properties.
+    pub fn save_splats_to_ply(&self, path: &str) -> io::Result<()> {
+        let count = spherical_harmonics_count(self.spherical_harmonics_order);
+        let names: Vec<String> = [
+            "x", "y", "z", "nx", "ny", "nz", "f_dc_0", "f_dc_1", "f_dc_2", "opacity", "scale_0", "scale_1", "scale_2", "rot_0", "rot_1",
+            "rot_2", "rot_3",
+        ]
+        .into_iter()
+        .map(String::from)
+        .chain((0..3 * count).map(|index| format!("f_rest_{index}")))
+        .collect();
+        let mut ply = Ply::<DefaultElement>::new();
+        ply.header.encoding = Encoding::BinaryLittleEndian;
+        let mut vertex_definition = ElementDef::new("vertex".to_string());
+        for name in &names {
+            vertex_definition.properties.add(PropertyDef::new(name.to_string(), PropertyType::Scalar(ScalarType::Float)));
+        }
+        ply.header.elements.add(vertex_definition);
+        let vertices = self
+            .splat_data
+            .iter()
+            .enumerate()
+            .map(|(index, splat)| {
+                let [tangent, bitangent, normal] = splat.tangent_frame();
+                let rotation = if normal == Vec3::ZERO || tangent == Vec3::ZERO {
+                    Quat::IDENTITY
+                } else {
+                    Quat::from_mat3(&Mat3::from_cols(tangent, bitangent, normal)).normalize()
+                };
+                // The splats are flat, so the normal axis gets a negligible extent
+                let thickness = splat.scale[0].min(splat.scale[1]) * 1.0e-3;
+                let alpha = splat.color[3].clamp(1.0e-6, 1.0 - 1.0e-6);
+                let values = [
+                    splat.center[0],
+                    splat.center[1],
+                    splat.center[2],
+                    normal.x,
+                    normal.y,
+                    normal.z,
+                    (splat.color[0] - 0.5) / SH_C0,
+                    (splat.color[1] - 0.5) / SH_C0,
+                    (splat.color[2] - 0.5) / SH_C0,
+                    (alpha / (1.0 - alpha)).ln(),
+                    splat.scale[0].max(f32::MIN_POSITIVE).ln(),
+                    splat.scale[1].max(f32::MIN_POSITIVE).ln(),
+                    thickness.max(f32::MIN_POSITIVE).ln(),
+                    rotation.w,
+                    rotation.x,
+                    rotation.y,
+                    rotation.z,
+                ];
+                let coefficients = self.spherical_harmonics_of(index);
+                let rest = (0..3).flat_map(|channel| (0..count).map(move |index| coefficients.get(index).map_or(0.0, |color| color[channel])));
+                let mut vertex = DefaultElement::new();
+                for (name, value) in names.iter().zip(values.into_iter().chain(rest)) {
+                    vertex.insert(name.to_string(), Property::Float(value));
+                }
+                vertex
+            })
+            .collect();
+        ply.payload.insert("vertex".to_string(), vertices);
+        ply.make_consistent().map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("{error:?}")))?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        Writer::new().write_ply(&mut writer, &mut ply)?;
+        writer.flush()
+    }
+
     // pub fn render(&mut self, render_device: Res<RenderDevice>, render_queue: Res<RenderQueue>, texture: &Image) {
     //     // Placeholder for rendering implementation
     // }
 }
 
+/// Zeroth order spherical harmonics constant, maps the DC coefficients of 3D gaussian splatting to colors.
+pub(crate) const SH_C0: f32 = 0.282_094_8;
+
+/// Number of coefficients of the view dependent colors per splat and channel, without band 0.
+pub fn spherical_harmonics_count(order: u32) -> usize {
+    ((order + 1) * (order + 1) - 1) as usize
+}
+
+/// Order of the view dependent colors in `rest_count` `f_rest_*` properties, along with the number of properties per
+/// channel.
+pub(crate) fn ply_spherical_harmonics_layout(rest_count: usize) -> (u32, usize) {
+    let per_channel = rest_count / 3;
+    let order = (1..=3).rev().find(|order| spherical_harmonics_count(*order) <= per_channel).unwrap_or(0);
+    (order, per_channel)
+}
+
+/// Appends the view dependent colors of a PLY vertex, which holds all coefficients of the red channel first, then
+/// green and blue, see [ply_spherical_harmonics_layout].
+pub(crate) fn read_ply_spherical_harmonics(vertex: &DefaultElement, order: u32, per_channel: usize, coefficients: &mut Vec<Vec3>) {
+    for index in 0..spherical_harmonics_count(order) {
+        let [red, green, blue] =
+            [0, 1, 2].map(|channel| numeric_property(vertex, &format!("f_rest_{}", channel * per_channel + index)).unwrap_or(0.0));
+        coefficients.push(Vec3::new(red, green, blue));
+    }
+}
+
+fn read_ply_vertices(path: &str) -> io::Result<Vec<DefaultElement>> {
+    let mut reader = io::BufReader::new(File::open(path)?);
+    let mut ply = Parser::<DefaultElement>::new().read_ply(&mut reader)?;
+    ply.payload
+        .remove("vertex")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No vertex element in PLY"))
+}
+
+/// Reads a scalar property of a PLY element as float, whatever its type.
+fn numeric_property(element: &DefaultElement, name: &str) -> Option<f32> {
+    match *element.get(name)? {
+        Property::Char(value) => Some(value as f32),
+        Property::UChar(value) => Some(value as f32),
+        Property::Short(value) => Some(value as f32),
+        Property::UShort(value) => Some(value as f32),
+        Property::Int(value) => Some(value as f32),
+        Property::UInt(value) => Some(value as f32),
+        Property::Float(value) => Some(value),
+        Property::Double(value) => Some(value as f32),
+        _ => None,
+    }
+}
+
 impl Default for Scene {
     fn default() -> Self {
         Self::new()
@@ -284,10 +833,8 @@ impl Default for Scene {
 }
 
 fn setup_scene(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut materials: ResMut<Assets<StandardMaterial>>) {
-    // Spawn the scene component
-    let mut scene = Scene::default();
-    scene.load_splats_from_ply("assets/models/test.ply");
-    commands.spawn((scene, SpatialBundle::default()));
+    // Spawn the scene component, its splats are loaded in the background
+    commands.spawn((Scene::default(), SceneLoading::start("assets/models/test.ply"), SpatialBundle::default()));
 
     // Create a simple room
     commands.spawn(PbrBundle {
@@ -327,26 +874,48 @@ fn _setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, mut material
     });
 }
 
-fn convert_splat_data(mut scene: ResMut<Scene>, render_device: Res<RenderDevice>) {
-    let shader_splats: Vec<ShaderSplat> = scene
-        .splat_data
-        .iter()
-        .map(|splat| {
-            ShaderSplat {
-                rotation: [0.0, 0.0, 0.0, 1.0], // Placeholder
-                center: splat.center,
-                _pad0: 0.0,
-                scale: splat.scale,
-                alpha: splat.color[3],
-                color_sh: [0.0; 48],
-                _pad1: [0.0; 3],
-            }
-        })
-        .collect();
-    let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-        label: Some("Splat Buffer"),
-        contents: bytemuck::cast_slice(&shader_splats),
-        usage: wgpu::BufferUsages::STORAGE,
-    });
-    scene.splat_buffer = Some(buffer);
+/// Uploads the splats of the [Scene] resource and of every [Scene] component, as soon as batches of them are loaded.
+fn convert_splat_data(
+    mut scene: ResMut<Scene>,
+    // The splat buffer of a scene with a level of detail hierarchy holds the cut through it instead
+    mut scenes: Query<&mut Scene, Without<SceneLod>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    upload_splat_data(&mut scene, &render_device, &render_queue);
+    for mut scene in scenes.iter_mut() {
+        upload_splat_data(&mut scene, &render_device, &render_queue);
+    }
+}
+
+/// Number of splats a new `splat_buffer` has room for once `splat_count` splats outgrow one for `capacity`. Grows
+/// geometrically, so that a scene loaded batch by batch is not uploaded again for every batch.
+pub fn grown_splat_capacity(capacity: usize, splat_count: usize) -> usize {
+    splat_count.max(capacity * 2)
+}
+
+fn upload_splat_data(scene: &mut Scene, render_device: &RenderDevice, render_queue: &RenderQueue) {
+    let splat_size = std::mem::size_of::<ShaderSplat>();
+    let capacity = scene.splat_buffer.as_ref().map(|buffer| buffer.size() as usize / splat_size);
+    if capacity.is_none_or(|capacity| capacity < scene.splat_data.len()) {
+        // The splats no longer fit, so everything has to be uploaded again
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("Splat Buffer"),
+            size: (grown_splat_capacity(capacity.unwrap_or(0), scene.splat_data.len()) * splat_size) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        render_queue.write_buffer(&buffer, 0, bytemuck::cast_slice(&scene.shader_splats(0..scene.splat_data.len())));
+        scene.splat_buffer = Some(buffer);
+        scene.dirty_splats = None;
+    } else if let Some(range) = scene.dirty_splats.clone() {
+        // Only upload the splats which changed
+        let shader_splats = scene.shader_splats(range.clone());
+        render_queue.write_buffer(
+            scene.splat_buffer.as_ref().unwrap(),
+            (range.start * splat_size) as u64,
+            bytemuck::cast_slice(&shader_splats),
+        );
+        scene.dirty_splats = None;
+    }
 }
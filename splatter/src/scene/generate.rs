@@ -0,0 +1,194 @@
+//! Procedural scenes with known properties
+//!
+//! Tests, benchmarks and examples use these instead of downloaded models. Every generator is deterministic: The
+//! same settings, including the `seed`, always produce the same splats.
+
+use super::{spherical_harmonics_count, Scene, Splat};
+use glam::{Mat4, Quat, Vec3};
+
+/// Appearance of the generated splats, shared by all generators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerateSettings {
+    /// Standard deviations along the two axes of the ellipse.
+    pub scale: [f32; 2],
+    /// Orientation of the ellipse relative to the frame of its position. Unrotated the ellipse lies in the xy plane
+    /// with its first axis along x.
+    pub rotation: Quat,
+    pub color: [f32; 4],
+    /// Random variation of the scale, orientation and color of every splat, from 0 for none to 1.
+    pub jitter: f32,
+    /// Highest band of the view dependent colors, from 0 to 3.
+    pub spherical_harmonics_order: u32,
+    /// Largest magnitude of the random coefficients of the bands above 0.
+    pub spherical_harmonics_amplitude: f32,
+    pub seed: u64,
+}
+
+impl Default for GenerateSettings {
+    fn default() -> Self {
+        Self {
+            scale: [0.1, 0.1],
+            rotation: Quat::IDENTITY,
+            color: [0.8, 0.8, 0.8, 1.0],
+            jitter: 0.0,
+            spherical_harmonics_order: 0,
+            spherical_harmonics_amplitude: 0.2,
+            seed: 0,
+        }
+    }
+}
+
+/// SplitMix64, good enough for scenes and without a dependency.
+struct Random(u64);
+
+impl Random {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut value = self.0;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+        value ^ (value >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn unit(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in [-1, 1).
+    fn signed_unit(&mut self) -> f32 {
+        self.unit() * 2.0 - 1.0
+    }
+
+    /// Uniform on the sphere.
+    fn direction(&mut self) -> Vec3 {
+        let z = self.signed_unit();
+        let azimuth = self.unit() * std::f32::consts::TAU;
+        let radius = (1.0 - z * z).max(0.0).sqrt();
+        Vec3::new(radius * azimuth.cos(), radius * azimuth.sin(), z)
+    }
+
+    /// Uniform over all orientations.
+    fn rotation(&mut self) -> Quat {
+        Quat::from_axis_angle(self.direction(), self.unit() * std::f32::consts::TAU)
+    }
+}
+
+/// A single splat facing along `normal`, with its first axis along [Vec3::any_orthonormal_vector] of the normal.
+pub fn splat(center: Vec3, normal: Vec3, scale: [f32; 2], color: [f32; 4]) -> Splat {
+    Splat {
+        model_matrix: Mat4::IDENTITY,
+        center: center.to_array(),
+        color,
+        depth: 0.0,
+        scale,
+        normal: normal.to_array(),
+        ellipse_basis: normal.any_orthonormal_vector().to_array(),
+    }
+}
+
+/// Builds the splats of a scene one position and frame at a time, applying the settings.
+struct Generator<'a> {
+    settings: &'a GenerateSettings,
+    random: Random,
+    scene: Scene,
+}
+
+impl<'a> Generator<'a> {
+    fn new(settings: &'a GenerateSettings) -> Self {
+        let mut scene = Scene::new();
+        scene.spherical_harmonics_order = settings.spherical_harmonics_order.min(3);
+        Self {
+            settings,
+            random: Random(settings.seed),
+            scene,
+        }
+    }
+
+    fn push(&mut self, center: Vec3, frame: Quat) {
+        let settings = self.settings;
+        let jitter = settings.jitter.clamp(0.0, 1.0);
+        let mut rotation = frame * settings.rotation;
+        let mut scale = settings.scale;
+        let mut color = settings.color;
+        if jitter > 0.0 {
+            rotation = Quat::from_axis_angle(self.random.direction(), jitter * self.random.signed_unit() * std::f32::consts::PI) * rotation;
+            for value in &mut scale {
+                *value *= 1.0 + 0.5 * jitter * self.random.signed_unit();
+            }
+            for value in &mut color[0..3] {
+                *value = (*value + 0.5 * jitter * self.random.signed_unit()).clamp(0.0, 1.0);
+            }
+        }
+        self.scene.splat_data.push(Splat {
+            model_matrix: Mat4::IDENTITY,
+            center: center.to_array(),
+            color,
+            depth: 0.0,
+            scale,
+            normal: (rotation * Vec3::Z).to_array(),
+            ellipse_basis: (rotation * Vec3::X).to_array(),
+        });
+        for _ in 0..spherical_harmonics_count(self.scene.spherical_harmonics_order) {
+            let amplitude = settings.spherical_harmonics_amplitude;
+            let coefficient = Vec3::new(self.random.signed_unit(), self.random.signed_unit(), self.random.signed_unit()) * amplitude;
+            self.scene.spherical_harmonics.push(coefficient);
+        }
+    }
+
+    fn finish(mut self) -> Scene {
+        self.scene.splat_count = self.scene.splat_data.len();
+        self.scene
+    }
+}
+
+/// A single splat at the origin, isotropic unless the settings give it two different scales.
+pub fn gaussian(settings: &GenerateSettings) -> Scene {
+    let mut generator = Generator::new(settings);
+    generator.push(Vec3::ZERO, Quat::IDENTITY);
+    generator.finish()
+}
+
+/// `counts` splats along x, y and z, `spacing` apart and centered around the origin.
+pub fn grid(counts: [usize; 3], spacing: f32, settings: &GenerateSettings) -> Scene {
+    let mut generator = Generator::new(settings);
+    let offset = Vec3::from(counts.map(|count| count.saturating_sub(1) as f32)) * spacing * 0.5;
+    for z in 0..counts[2] {
+        for y in 0..counts[1] {
+            for x in 0..counts[0] {
+                generator.push(Vec3::new(x as f32, y as f32, z as f32) * spacing - offset, Quat::IDENTITY);
+            }
+        }
+    }
+    generator.finish()
+}
+
+/// `count` splats evenly spread over a sphere around the origin on a Fibonacci spiral. Their frames are tangent to
+/// the sphere, so unrotated splats face outwards.
+pub fn sphere_shell(count: usize, radius: f32, settings: &GenerateSettings) -> Scene {
+    let mut generator = Generator::new(settings);
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+    for index in 0..count {
+        let z = 1.0 - 2.0 * (index as f32 + 0.5) / count as f32;
+        let azimuth = golden_angle * index as f32;
+        let ring = (1.0 - z * z).max(0.0).sqrt();
+        let normal = Vec3::new(ring * azimuth.cos(), ring * azimuth.sin(), z);
+        generator.push(normal * radius, Quat::from_rotation_arc(Vec3::Z, normal));
+    }
+    generator.finish()
+}
+
+/// `count` splats at random positions in the cube from `-half_extent` to `half_extent`, each with a random frame.
+pub fn random_cloud(count: usize, half_extent: f32, settings: &GenerateSettings) -> Scene {
+    let mut generator = Generator::new(settings);
+    for _ in 0..count {
+        let center = Vec3::new(
+            generator.random.signed_unit(),
+            generator.random.signed_unit(),
+            generator.random.signed_unit(),
+        ) * half_extent;
+        let frame = generator.random.rotation();
+        generator.push(center, frame);
+    }
+    generator.finish()
+}
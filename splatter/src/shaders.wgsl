@@ -6,11 +6,13 @@ struct Uniforms {
     view_matrix: mat4x4<f32>,
     view_projection_matrix: mat4x4<f32>,
     view_size: vec2<f32>,
+    view_offset: vec2<f32>,
     image_size: vec2<u32>,
     frustum_culling_tolerance: f32,
     ellipse_size_bias: f32,
     ellipse_margin: f32,
     splat_scale: f32,
+    projection_model: u32,
 }
 struct DrawIndirect {
     vertex_count: u32,
@@ -158,7 +160,7 @@ fn projectedContourOfEllipsoid(scale: vec3<f32>, rotation: vec4<f32>, translatio
     let sqrt_M = transpose(A) * camera_matrix;
     */
 
-    // Given: let pos_in_view_plane = vec3<f32>(screenToClipSpace(stage_in.gl_Position.xy) * uniforms.view_size, 1.0);
+    // Given: let pos_in_view_plane = vec3<f32>(screenToClipSpace(stage_in.gl_Position.xy) * uniforms.view_size + uniforms.view_offset, 1.0);
     // And: let local_ray_direction = camera_matrix * pos_in_view_plane * transform;
     // The matrix A would be sufficient to render the ellipse: dot(local_ray_direction, A * local_ray_direction) = 0
     // However, we want to be independent of the ray direction, as we do not need to do this work per fragment.
@@ -171,6 +173,73 @@ fn projectedContourOfEllipsoid(scale: vec3<f32>, rotation: vec4<f32>, translatio
     return M;
 }
 
+/*
+    Orthographic and fisheye cameras project to first order instead: The center exactly and the covariance through the
+    Jacobian of the projection at the center, which is exact for orthographic cameras. See projection.rs for the CPU side.
+    Positions on the view plane are moved by uniforms.view_offset, the center of the image, and scaled by uniforms.view_size
+    into clip space, which is the half extent of the view box for orthographic cameras and the half field of view in
+    radians for fisheye cameras. See Camera::view_plane() for the CPU side.
+*/
+const PROJECTION_PERSPECTIVE: u32 = 0u;
+const PROJECTION_ORTHOGRAPHIC: u32 = 1u;
+const PROJECTION_FISHEYE: u32 = 2u;
+
+fn projectOntoViewPlane(view_pos: vec3<f32>) -> vec2<f32> {
+    if(uniforms.projection_model == PROJECTION_ORTHOGRAPHIC) {
+        return view_pos.xy;
+    }
+    let depth = -view_pos.z;
+    if(uniforms.projection_model == PROJECTION_FISHEYE) {
+        let radius = length(view_pos.xy);
+        return view_pos.xy * (atan2(radius, depth) / max(radius, 0.0000001));
+    }
+    return view_pos.xy / depth;
+}
+
+// Rows are the derivatives of projectOntoViewPlane() along x and y, the last row is zero
+fn projectionJacobian(view_pos: vec3<f32>) -> mat3x3<f32> {
+    if(uniforms.projection_model == PROJECTION_ORTHOGRAPHIC) {
+        return mat3x3<f32>(
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0,
+        );
+    }
+    let depth = -view_pos.z;
+    if(uniforms.projection_model == PROJECTION_FISHEYE) {
+        let radius_squared = max(dot(view_pos.xy, view_pos.xy), 0.0000001);
+        let radius = sqrt(radius_squared);
+        let distance_squared = radius_squared + depth * depth;
+        var k = atan2(radius, depth) / radius;
+        var g = (depth / distance_squared - k) / radius_squared;
+        if(depth > 0.0 && radius < 0.01 * depth) {
+            // Series expansion, which avoids the cancellation near the view axis
+            k = 1.0 / depth - radius_squared / (3.0 * depth * depth * depth);
+            g = -2.0 / (3.0 * depth * depth * depth);
+        }
+        return mat3x3<f32>(
+            k + view_pos.x * view_pos.x * g, view_pos.x * view_pos.y * g, 0.0,
+            view_pos.x * view_pos.y * g, k + view_pos.y * view_pos.y * g, 0.0,
+            view_pos.x / distance_squared, view_pos.y / distance_squared, 0.0,
+        );
+    }
+    return mat3x3<f32>(
+        1.0 / depth, 0.0, 0.0,
+        0.0, 1.0 / depth, 0.0,
+        view_pos.x / (depth * depth), view_pos.y / (depth * depth), 0.0,
+    );
+}
+
+fn projectedCovarianceOfSplat(scale: vec3<f32>, rotation: vec4<f32>, view_pos: vec3<f32>) -> mat3x3<f32> {
+    let view_rotation = mat3x3<f32>(uniforms.view_matrix.x.xyz, uniforms.view_matrix.y.xyz, uniforms.view_matrix.z.xyz);
+    var transform = quatToMat(rotation);
+    transform.x *= scale.x;
+    transform.y *= scale.y;
+    transform.z *= scale.z;
+    let T = projectionJacobian(view_pos) * view_rotation * transform;
+    return T * transpose(T);
+}
+
 /*
     Decompose the implicit curve of the ellipse into its three components: scale, rotation, translation.
     This is not necessary for rendering but it allows optimizing rasterization by using rotated rectangles instead of axis aligned squares.
@@ -533,15 +602,31 @@ fn vertex(
     let world_position = splats[splat_index].center;
     let ray_direction = normalize(world_position - uniforms.camera_matrix.w.xyz);
     stage_out.color = vec4<f32>(sphericalHarmonicsLookup(ray_direction, splat_index), splats[splat_index].alpha);
-    let M = projectedContourOfEllipsoid(splats[splat_index].scale * uniforms.splat_scale, splats[splat_index].rotation, world_position);
-    let translation = extractTranslationOfEllipse(M);
-    let rotation = extractRotationOfEllipse(M);
+    var translation: vec2<f32>;
+    var rotation: vec2<f32>;
     var semi_axes: vec2<f32>;
-    if(USE_COVARIANCE_FOR_SCALE) {
-        let covariance = projectedCovarianceOfEllipsoid(splats[splat_index].scale * uniforms.splat_scale, splats[splat_index].rotation, world_position);
+    if(uniforms.projection_model != PROJECTION_PERSPECTIVE) {
+        let view_pos = (uniforms.view_matrix * vec4<f32>(world_position, 1.0)).xyz;
+        let covariance = projectedCovarianceOfSplat(splats[splat_index].scale * uniforms.splat_scale, splats[splat_index].rotation, view_pos);
+        // The inverse covariance is the implicit curve of the ellipse around its center
+        let inverse_covariance = mat3x3<f32>(
+            covariance.y.y, -covariance.x.y, 0.0,
+            -covariance.x.y, covariance.x.x, 0.0,
+            0.0, 0.0, 0.0,
+        );
+        translation = projectOntoViewPlane(view_pos);
+        rotation = extractRotationOfEllipse(inverse_covariance);
         semi_axes = extractScaleOfCovariance(covariance);
     } else {
-        semi_axes = extractScaleOfEllipse(M, translation, rotation);
+        let M = projectedContourOfEllipsoid(splats[splat_index].scale * uniforms.splat_scale, splats[splat_index].rotation, world_position);
+        translation = extractTranslationOfEllipse(M);
+        rotation = extractRotationOfEllipse(M);
+        if(USE_COVARIANCE_FOR_SCALE) {
+            let covariance = projectedCovarianceOfEllipsoid(splats[splat_index].scale * uniforms.splat_scale, splats[splat_index].rotation, world_position);
+            semi_axes = extractScaleOfCovariance(covariance);
+        } else {
+            semi_axes = extractScaleOfEllipse(M, translation, rotation);
+        }
     }
     var transformation = mat3x2<f32>(
         vec2<f32>(rotation.y, -rotation.x) * (uniforms.ellipse_size_bias + semi_axes.x),
@@ -561,7 +646,7 @@ fn vertex(
             vec3<f32>(transformation.z, 1.0),
         );
         stage_out.gl_TexCoord = quad_vertices[gl_VertexID] * uniforms.ellipse_margin;
-        stage_out.gl_Position = vec4<f32>((T * vec3<f32>(stage_out.gl_TexCoord, 1.0)).xy / uniforms.view_size, 0.0, 1.0);
+        stage_out.gl_Position = vec4<f32>(((T * vec3<f32>(stage_out.gl_TexCoord, 1.0)).xy - uniforms.view_offset) / uniforms.view_size, 0.0, 1.0);
     } else {
         let inverse = mat2x2<f32>(
             transformation.y.y, -transformation.x.y,
@@ -569,7 +654,7 @@ fn vertex(
         ) * (1.0 / (transformation.x.x * transformation.y.y - transformation.x.y * transformation.y.x));
         let radius = sqrt(max(dot(transformation.x, transformation.x), dot(transformation.y, transformation.y)));
         stage_out.gl_TexCoord = quad_vertices[gl_VertexID] * radius * uniforms.ellipse_margin;
-        stage_out.gl_Position = vec4<f32>((transformation.z + stage_out.gl_TexCoord) / uniforms.view_size, 0.0, 1.0);
+        stage_out.gl_Position = vec4<f32>((transformation.z + stage_out.gl_TexCoord - uniforms.view_offset) / uniforms.view_size, 0.0, 1.0);
         stage_out.gl_TexCoord = inverse * stage_out.gl_TexCoord;
     }
     return stage_out;
@@ -0,0 +1,134 @@
+//! Uniform grid over points for neighborhood queries
+//!
+//! Points are bucketed by the cell they fall into. Queries visit the cells in growing rings around the query point
+//! and stop as soon as no unvisited cell can contain anything closer than what was already found.
+
+use glam::{IVec3, Vec3};
+use std::collections::HashMap;
+
+pub struct PointGrid {
+    points: Vec<Vec3>,
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<usize>>,
+    min_cell: IVec3,
+    max_cell: IVec3,
+}
+
+impl PointGrid {
+    pub fn new(points: &[Vec3], cell_size: f32) -> Self {
+        let mut grid = Self {
+            points: points.to_vec(),
+            cell_size,
+            cells: HashMap::new(),
+            min_cell: IVec3::ZERO,
+            max_cell: IVec3::ZERO,
+        };
+        if let Some(first) = points.first() {
+            grid.min_cell = grid.cell_of(*first);
+            grid.max_cell = grid.min_cell;
+        }
+        for (index, point) in points.iter().enumerate() {
+            let cell = grid.cell_of(*point);
+            grid.min_cell = grid.min_cell.min(cell);
+            grid.max_cell = grid.max_cell.max(cell);
+            grid.cells.entry(cell).or_default().push(index);
+        }
+        grid
+    }
+
+    /// Picks a cell size so that a cell holds about `points_per_cell` points on average, assuming they fill their bounds.
+    pub fn with_density(points: &[Vec3], points_per_cell: f32) -> Self {
+        let (min, max) = points
+            .iter()
+            .fold((Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)), |(min, max), point| {
+                (min.min(*point), max.max(*point))
+            });
+        // Estimate the cell size for points spread along a line, over a plane and through a volume and take the largest,
+        // which belongs to the dimension the points actually fill
+        let mut extents = (max - min).max(Vec3::splat(1.0e-6)).to_array();
+        extents.sort_by(|a, b| b.total_cmp(a));
+        let fraction = points_per_cell / points.len().max(1) as f32;
+        let cell_size = (extents[0] * fraction)
+            .max((extents[0] * extents[1] * fraction).sqrt())
+            .max((extents[0] * extents[1] * extents[2] * fraction).cbrt());
+        Self::new(points, cell_size)
+    }
+
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn cell_of(&self, point: Vec3) -> IVec3 {
+        (point / self.cell_size).floor().as_ivec3()
+    }
+
+    /// Calls `f` with the indices of the points in all cells at Chebyshev distance `ring` from `center`.
+    fn for_each_in_ring(&self, center: IVec3, ring: i32, mut f: impl FnMut(usize)) {
+        let lower = (center - ring).max(self.min_cell);
+        let upper = (center + ring).min(self.max_cell);
+        for z in lower.z..=upper.z {
+            for y in lower.y..=upper.y {
+                for x in lower.x..=upper.x {
+                    let cell = IVec3::new(x, y, z);
+                    if (cell - center).abs().max_element() != ring {
+                        continue;
+                    }
+                    if let Some(indices) = self.cells.get(&cell) {
+                        indices.iter().copied().for_each(&mut f);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of rings after which every cell of the grid has been visited.
+    fn ring_count(&self, center: IVec3) -> i32 {
+        (center - self.min_cell).abs().max(self.max_cell - center).max_element()
+    }
+
+    /// The `k` nearest points to `point` as (index, distance) in ascending order of distance.
+    pub fn k_nearest(&self, point: Vec3, k: usize) -> Vec<(usize, f32)> {
+        let mut nearest: Vec<(usize, f32)> = Vec::with_capacity(k + 1);
+        if k == 0 || self.points.is_empty() {
+            return nearest;
+        }
+        let center = self.cell_of(point);
+        let offset_in_cell = point / self.cell_size - center.as_vec3();
+        // Distance from the point to the boundary of its own cell, unvisited cells are farther than this plus full rings
+        let margin = offset_in_cell.min(1.0 - offset_in_cell).min_element().max(0.0) * self.cell_size;
+        for ring in 0..=self.ring_count(center) {
+            self.for_each_in_ring(center, ring, |index| {
+                let distance = self.points[index].distance(point);
+                if nearest.len() == k && distance >= nearest[k - 1].1 {
+                    return;
+                }
+                let position = nearest.partition_point(|(_, other)| *other <= distance);
+                nearest.insert(position, (index, distance));
+                nearest.truncate(k);
+            });
+            if nearest.len() == k && nearest[k - 1].1 <= margin + ring as f32 * self.cell_size {
+                break;
+            }
+        }
+        nearest
+    }
+
+    /// Indices of all points within `radius` of `point`.
+    pub fn within_radius(&self, point: Vec3, radius: f32) -> Vec<usize> {
+        let rings = (radius / self.cell_size).ceil() as i32;
+        let center = self.cell_of(point);
+        let mut found = Vec::new();
+        for ring in 0..=rings.min(self.ring_count(center)) {
+            self.for_each_in_ring(center, ring, |index| {
+                if self.points[index].distance(point) <= radius {
+                    found.push(index);
+                }
+            });
+        }
+        found
+    }
+}
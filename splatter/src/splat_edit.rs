@@ -0,0 +1,222 @@
+//! Editing operations on the splats of a scene
+//!
+//! Splats are first selected by one or more [Selector]s and then deleted, kept or recolored.
+//! Every operation can be undone. Deleting moves the splats behind the deleted ones, which are all copied into the
+//! `splat_buffer` again, recoloring only marks the touched range of splats to be copied into it again.
+
+use crate::scene::{spherical_harmonics_count, Scene, SceneSource, Splat};
+use glam::{Quat, Vec3};
+
+/// Criterion for selecting splats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selector {
+    /// Splats with their center inside the axis aligned box.
+    Aabb { min: Vec3, max: Vec3 },
+    /// Splats with their center inside the box rotated by `rotation` around its `center`.
+    Obb { center: Vec3, half_extents: Vec3, rotation: Quat },
+    /// Splats with their center inside the sphere.
+    Sphere { center: Vec3, radius: f32 },
+    /// Splats which are more transparent than the threshold.
+    OpacityBelow(f32),
+    /// Splats with their larger scale axis exceeding the threshold.
+    ScaleAbove(f32),
+}
+
+impl Selector {
+    pub fn matches(&self, splat: &Splat) -> bool {
+        let center = Vec3::from(splat.center);
+        match *self {
+            Selector::Aabb { min, max } => center.cmpge(min).all() && center.cmple(max).all(),
+            Selector::Obb {
+                center: box_center,
+                half_extents,
+                rotation,
+            } => (rotation.inverse() * (center - box_center)).abs().cmple(half_extents).all(),
+            Selector::Sphere { center: sphere_center, radius } => center.distance_squared(sphere_center) <= radius * radius,
+            Selector::OpacityBelow(threshold) => splat.color[3] < threshold,
+            Selector::ScaleAbove(threshold) => splat.scale[0].max(splat.scale[1]) > threshold,
+        }
+    }
+}
+
+/// How a new selection is combined with the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionMode {
+    Replace,
+    Add,
+    Subtract,
+    Intersect,
+}
+
+enum EditRecord {
+    /// Splats which were deleted along with their view dependent colors, with the indices they had before, and the
+    /// sources of the scene before.
+    Deleted {
+        splats: Vec<(usize, Splat, Vec<Vec3>)>,
+        sources: Vec<SceneSource>,
+    },
+    /// Previous state of splats which were modified.
+    Modified(Vec<(usize, Splat)>),
+}
+
+/// Selection of splats in a [Scene] and the history of the operations applied to them.
+pub struct SplatEdit {
+    selected: Vec<bool>,
+    history: Vec<EditRecord>,
+}
+
+impl SplatEdit {
+    pub fn new(scene: &Scene) -> Self {
+        Self {
+            selected: vec![false; scene.splat_data.len()],
+            history: Vec::new(),
+        }
+    }
+
+    /// Number of operations which can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.selected.get(index).copied().unwrap_or(false)
+    }
+
+    pub fn selected_count(&self) -> usize {
+        self.selected.iter().filter(|selected| **selected).count()
+    }
+
+    /// Indices of the selected splats in ascending order.
+    pub fn selected_indices(&self) -> Vec<usize> {
+        self.selected
+            .iter()
+            .enumerate()
+            .filter_map(|(index, selected)| selected.then_some(index))
+            .collect()
+    }
+
+    /// Combines the splats matching `predicate` with the current selection.
+    pub fn select_where(&mut self, scene: &Scene, mode: SelectionMode, predicate: impl Fn(usize, &Splat) -> bool) {
+        self.selected.resize(scene.splat_data.len(), false);
+        for (index, (selected, splat)) in self.selected.iter_mut().zip(scene.splat_data.iter()).enumerate() {
+            let matches = predicate(index, splat);
+            *selected = match mode {
+                SelectionMode::Replace => matches,
+                SelectionMode::Add => *selected || matches,
+                SelectionMode::Subtract => *selected && !matches,
+                SelectionMode::Intersect => *selected && matches,
+            };
+        }
+    }
+
+    pub fn select(&mut self, scene: &Scene, selector: &Selector, mode: SelectionMode) {
+        self.select_where(scene, mode, |_index, splat| selector.matches(splat));
+    }
+
+    pub fn select_all(&mut self, scene: &Scene) {
+        self.select_where(scene, SelectionMode::Replace, |_index, _splat| true);
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected.fill(false);
+    }
+
+    pub fn invert_selection(&mut self) {
+        for selected in self.selected.iter_mut() {
+            *selected = !*selected;
+        }
+    }
+
+    /// Removes the selected splats along with their view dependent colors from the scene, see [Scene::retain].
+    /// Returns how many were removed.
+    pub fn delete_selected(&mut self, scene: &mut Scene) -> usize {
+        self.selected.resize(scene.splat_data.len(), false);
+        let deleted: Vec<_> = self
+            .selected_indices()
+            .into_iter()
+            .map(|index| (index, scene.splat_data[index].clone(), scene.spherical_harmonics_of(index).to_vec()))
+            .collect();
+        let sources = scene.sources.clone();
+        let count = scene.retain(|index, _| !self.selected[index]);
+        scene.mark_dirty(0..scene.splat_count);
+        self.selected = vec![false; scene.splat_count];
+        if count > 0 {
+            self.history.push(EditRecord::Deleted { splats: deleted, sources });
+        }
+        count
+    }
+
+    /// Removes all splats which are not selected. Returns how many were removed.
+    pub fn keep_selected(&mut self, scene: &mut Scene) -> usize {
+        self.invert_selection();
+        self.delete_selected(scene)
+    }
+
+    /// Replaces the color of the selected splats, keeping their opacity.
+    pub fn recolor_selected(&mut self, scene: &mut Scene, color: [f32; 3]) {
+        let mut previous = Vec::new();
+        for (index, (splat, selected)) in scene.splat_data.iter_mut().zip(self.selected.iter()).enumerate() {
+            if *selected {
+                previous.push((index, splat.clone()));
+                splat.color[..3].copy_from_slice(&color);
+            }
+        }
+        if let (Some(first), Some(last)) = (previous.first(), previous.last()) {
+            scene.mark_dirty(first.0..last.0 + 1);
+            self.history.push(EditRecord::Modified(previous));
+        }
+    }
+
+    /// Reverts the last operation and selects the splats it affected. Returns false if there is nothing to undo.
+    pub fn undo(&mut self, scene: &mut Scene) -> bool {
+        let Some(record) = self.history.pop() else {
+            return false;
+        };
+        match record {
+            EditRecord::Deleted { splats: deleted, sources } => {
+                let count = spherical_harmonics_count(scene.spherical_harmonics_order);
+                let with_coefficients = count > 0 && scene.spherical_harmonics.len() == count * scene.splat_data.len();
+                let total = scene.splat_data.len() + deleted.len();
+                let mut kept = std::mem::take(&mut scene.splat_data).into_iter().enumerate();
+                let kept_coefficients = std::mem::take(&mut scene.spherical_harmonics);
+                let mut deleted = deleted.into_iter().peekable();
+                let mut selected = Vec::with_capacity(total);
+                for index in 0..total {
+                    if let Some((_, splat, coefficients)) = deleted.next_if(|(deleted_index, _, _)| *deleted_index == index) {
+                        scene.splat_data.push(splat);
+                        if with_coefficients {
+                            let coefficients = (0..count).map(|coefficient| coefficients.get(coefficient).copied().unwrap_or(Vec3::ZERO));
+                            scene.spherical_harmonics.extend(coefficients);
+                        }
+                        selected.push(true);
+                    } else {
+                        let (kept_index, splat) = kept.next().expect("Scene changed outside of SplatEdit");
+                        scene.splat_data.push(splat);
+                        if with_coefficients {
+                            scene.spherical_harmonics.extend_from_slice(&kept_coefficients[kept_index * count..(kept_index + 1) * count]);
+                        }
+                        selected.push(false);
+                    }
+                }
+                if !with_coefficients {
+                    scene.spherical_harmonics = kept_coefficients;
+                }
+                scene.sources = sources;
+                scene.splat_count = scene.splat_data.len();
+                scene.mark_dirty(0..scene.splat_count);
+                self.selected = selected;
+            }
+            EditRecord::Modified(previous) => {
+                self.selected = vec![false; scene.splat_data.len()];
+                if let (Some(first), Some(last)) = (previous.first(), previous.last()) {
+                    scene.mark_dirty(first.0..last.0 + 1);
+                }
+                for (index, splat) in previous {
+                    scene.splat_data[index] = splat;
+                    self.selected[index] = true;
+                }
+            }
+        }
+        true
+    }
+}
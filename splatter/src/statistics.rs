@@ -0,0 +1,359 @@
+//! Statistics of splat scenes and differences between versions of a capture
+//!
+//! Scenes are diffed by spatial matching: Every splat of the new scene is paired with the nearest unpaired splat of the
+//! old one within a search radius, closest pairs first. Unpaired splats were added or removed, pairs which are further
+//! apart than a tolerance moved.
+
+use crate::scene::{spherical_harmonics_count, Scene, ShaderSplat, Splat, PACKED_SPLAT_FLOATS, SH_C0};
+use crate::spatial::PointGrid;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Counts of values in equally wide bins between `min` and `max`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    pub min: f32,
+    pub max: f32,
+    /// Whether the bins are equally wide in the decimal logarithm of the values, `min` and `max` are not.
+    pub logarithmic: bool,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Bins the finite values, and with `logarithmic` only the positive ones. Equal values end up in a single bin.
+    pub fn new(values: impl Iterator<Item = f32>, bin_count: usize, logarithmic: bool) -> Self {
+        let values: Vec<f32> = values
+            .filter(|value| value.is_finite() && (!logarithmic || *value > 0.0))
+            .map(|value| if logarithmic { value.log10() } else { value })
+            .collect();
+        let (min, max) = values
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| (min.min(*value), max.max(*value)));
+        let mut counts = vec![0; if min < max { bin_count.max(1) } else { 1 }];
+        if values.is_empty() {
+            return Self {
+                min: 0.0,
+                max: 0.0,
+                logarithmic,
+                counts,
+            };
+        }
+        let last = counts.len() - 1;
+        let width = (max - min) / counts.len() as f32;
+        for value in &values {
+            let bin = if width > 0.0 { ((value - min) / width) as usize } else { 0 };
+            counts[bin.min(last)] += 1;
+        }
+        let unlog = |value: f32| if logarithmic { 10.0_f32.powf(value) } else { value };
+        Self {
+            min: unlog(min),
+            max: unlog(max),
+            logarithmic,
+            counts,
+        }
+    }
+
+    /// Lower and upper edge of every bin.
+    pub fn bins(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let (min, max) = if self.logarithmic {
+            (self.min.max(f32::MIN_POSITIVE).log10(), self.max.max(f32::MIN_POSITIVE).log10())
+        } else {
+            (self.min, self.max)
+        };
+        let width = (max - min) / self.counts.len() as f32;
+        let edge = move |index: usize| {
+            let edge = min + width * index as f32;
+            if self.logarithmic {
+                10.0_f32.powf(edge)
+            } else {
+                edge
+            }
+        };
+        (0..self.counts.len()).map(move |index| (edge(index), edge(index + 1)))
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const BAR_WIDTH: usize = 40;
+        let largest = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        for (index, ((lower, upper), count)) in self.bins().zip(&self.counts).enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            let bar = "#".repeat((count * BAR_WIDTH).div_ceil(largest));
+            write!(f, "  {lower:>10.4} .. {upper:>10.4} {count:>9} {bar}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    pub fn of_centers(splats: &[Splat]) -> Option<Self> {
+        let mut centers = splats.iter().map(|splat| Vec3::from(splat.center));
+        let first = centers.next()?;
+        Some(centers.fold(Self { min: first, max: first }, |bounds, center| Self {
+            min: bounds.min.min(center),
+            max: bounds.max.max(center),
+        }))
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+}
+
+/// How large the splats of a scene are in one of the formats they are kept in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodingFootprint {
+    pub encoding: String,
+    pub bytes_per_splat: usize,
+    pub bytes: usize,
+}
+
+impl EncodingFootprint {
+    /// The encodings of the crate: PLY files, the packed splats of level of detail and tile files, the storage buffer
+    /// of the shader and `Scene` in memory.
+    pub fn all(splat_count: usize, spherical_harmonics_order: u32) -> Vec<Self> {
+        let float = std::mem::size_of::<f32>();
+        let coefficients = 3 * spherical_harmonics_count(spherical_harmonics_order);
+        [
+            ("ply", (17 + coefficients) * float),
+            ("packed", PACKED_SPLAT_FLOATS * float),
+            ("gpu", std::mem::size_of::<ShaderSplat>()),
+            ("memory", std::mem::size_of::<Splat>() + coefficients * float),
+        ]
+        .into_iter()
+        .map(|(encoding, bytes_per_splat)| Self {
+            encoding: encoding.to_string(),
+            bytes_per_splat,
+            bytes: bytes_per_splat * splat_count,
+        })
+        .collect()
+    }
+}
+
+/// Parameters of [SceneStatistics::of].
+#[derive(Debug, Clone)]
+pub struct StatisticsSettings {
+    pub opacity_bins: usize,
+    pub scale_bins: usize,
+}
+
+impl Default for StatisticsSettings {
+    fn default() -> Self {
+        Self {
+            opacity_bins: 10,
+            scale_bins: 12,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneStatistics {
+    pub splat_count: usize,
+    /// Of the splat centers, absent for empty scenes.
+    pub bounds: Option<Bounds>,
+    pub opacity: Histogram,
+    /// Of the larger scale axis, on a logarithmic scale as they span orders of magnitude.
+    pub scale: Histogram,
+    pub spherical_harmonics_order: u32,
+    /// Mean squared norm of the coefficients per splat of every band from 0 up to the order of the scene.
+    pub band_energy: Vec<f32>,
+    pub memory: Vec<EncodingFootprint>,
+}
+
+impl SceneStatistics {
+    pub fn of(scene: &Scene, settings: &StatisticsSettings) -> Self {
+        let splats = &scene.splat_data;
+        let order = scene.spherical_harmonics_order;
+        let mut band_energy = vec![0.0; order as usize + 1];
+        for (index, splat) in splats.iter().enumerate() {
+            // Band 0 is stored as color, the shader adds it to a gray of 0.5
+            let dc = (Vec3::new(splat.color[0], splat.color[1], splat.color[2]) - 0.5) / SH_C0;
+            band_energy[0] += dc.length_squared();
+            for (coefficient, value) in scene.spherical_harmonics_of(index).iter().enumerate() {
+                // Band l starts at coefficient l² - 1 without band 0
+                let band = ((coefficient + 1) as f32).sqrt().floor() as usize;
+                band_energy[band] += value.length_squared();
+            }
+        }
+        for energy in &mut band_energy {
+            *energy /= splats.len().max(1) as f32;
+        }
+        Self {
+            splat_count: splats.len(),
+            bounds: Bounds::of_centers(splats),
+            opacity: Histogram::new(splats.iter().map(|splat| splat.color[3]), settings.opacity_bins, false),
+            scale: Histogram::new(splats.iter().map(|splat| splat.scale[0].max(splat.scale[1])), settings.scale_bins, true),
+            spherical_harmonics_order: order,
+            band_energy,
+            memory: EncodingFootprint::all(splats.len(), order),
+        }
+    }
+}
+
+impl fmt::Display for SceneStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "splats:  {}", self.splat_count)?;
+        match &self.bounds {
+            Some(bounds) => {
+                let [min, max, size] = [bounds.min, bounds.max, bounds.size()].map(|v| format!("{:.3}, {:.3}, {:.3}", v.x, v.y, v.z));
+                writeln!(f, "bounds:  ({min}) .. ({max}), size ({size})")?;
+            }
+            None => writeln!(f, "bounds:  -")?,
+        }
+        writeln!(f, "opacity:\n{}", self.opacity)?;
+        writeln!(f, "scale:\n{}", self.scale)?;
+        writeln!(f, "spherical harmonics order: {}", self.spherical_harmonics_order)?;
+        for (band, energy) in self.band_energy.iter().enumerate() {
+            writeln!(f, "  band {band}: {energy:.6}")?;
+        }
+        write!(f, "memory:")?;
+        for footprint in &self.memory {
+            write!(
+                f,
+                "\n  {:<7} {:>4} bytes per splat, {:>12.3} MiB",
+                footprint.encoding,
+                footprint.bytes_per_splat,
+                footprint.bytes as f64 / (1024.0 * 1024.0)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Parameters of [diff].
+#[derive(Debug, Clone)]
+pub struct DiffSettings {
+    /// Splats further apart than this are not paired, by default three times the median larger scale axis of the old
+    /// scene.
+    pub search_radius: Option<f32>,
+    /// Paired splats further apart than this moved.
+    pub moved_distance: f32,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        Self {
+            search_radius: None,
+            moved_distance: 1.0e-4,
+        }
+    }
+}
+
+/// How a scene changed into another one, with indices into the old and new `splat_data`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SceneDiff {
+    /// Splats of the new scene without a counterpart in the old one.
+    pub added: Vec<usize>,
+    /// Splats of the old scene without a counterpart in the new one.
+    pub removed: Vec<usize>,
+    /// Paired splats of the old and the new scene which moved.
+    pub moved: Vec<(usize, usize)>,
+    pub unchanged: usize,
+    /// Distance of the moved splats.
+    pub mean_displacement: f32,
+    pub max_displacement: f32,
+    pub search_radius: f32,
+}
+
+impl SceneDiff {
+    pub fn summary(&self) -> DiffSummary {
+        DiffSummary {
+            added: self.added.len(),
+            removed: self.removed.len(),
+            moved: self.moved.len(),
+            unchanged: self.unchanged,
+            mean_displacement: self.mean_displacement,
+            max_displacement: self.max_displacement,
+            search_radius: self.search_radius,
+        }
+    }
+}
+
+/// The counts of a [SceneDiff].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub moved: usize,
+    pub unchanged: usize,
+    pub mean_displacement: f32,
+    pub max_displacement: f32,
+    pub search_radius: f32,
+}
+
+impl fmt::Display for DiffSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "added:     {}", self.added)?;
+        writeln!(f, "removed:   {}", self.removed)?;
+        writeln!(
+            f,
+            "moved:     {} (mean {:.5}, max {:.5})",
+            self.moved, self.mean_displacement, self.max_displacement
+        )?;
+        writeln!(f, "unchanged: {}", self.unchanged)?;
+        write!(f, "search radius: {:.5}", self.search_radius)
+    }
+}
+
+/// Matches the splats of two scenes by position.
+pub fn diff(old: &Scene, new: &Scene, settings: &DiffSettings) -> SceneDiff {
+    let search_radius = settings.search_radius.unwrap_or_else(|| {
+        let mut scales: Vec<f32> = old.splat_data.iter().map(|splat| splat.scale[0].max(splat.scale[1])).collect();
+        if scales.is_empty() {
+            return 0.0;
+        }
+        let middle = scales.len() / 2;
+        3.0 * *scales.select_nth_unstable_by(middle, f32::total_cmp).1
+    });
+    let old_centers: Vec<Vec3> = old.splat_data.iter().map(|splat| Vec3::from(splat.center)).collect();
+    let grid = PointGrid::with_density(&old_centers, 4.0);
+    let mut candidates: Vec<(f32, usize, usize)> = Vec::new();
+    for (new_index, splat) in new.splat_data.iter().enumerate() {
+        let center = Vec3::from(splat.center);
+        for old_index in grid.within_radius(center, search_radius) {
+            candidates.push((old_centers[old_index].distance(center), old_index, new_index));
+        }
+    }
+    // Closest pairs first, so that each splat keeps its best counterpart
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+    let mut old_paired = vec![false; old.splat_data.len()];
+    let mut new_paired = vec![false; new.splat_data.len()];
+    let mut result = SceneDiff {
+        search_radius,
+        ..SceneDiff::default()
+    };
+    let mut displacement_sum = 0.0;
+    for (distance, old_index, new_index) in candidates {
+        if old_paired[old_index] || new_paired[new_index] {
+            continue;
+        }
+        old_paired[old_index] = true;
+        new_paired[new_index] = true;
+        if distance > settings.moved_distance {
+            result.moved.push((old_index, new_index));
+            displacement_sum += distance;
+            result.max_displacement = result.max_displacement.max(distance);
+        } else {
+            result.unchanged += 1;
+        }
+    }
+    result.moved.sort_unstable();
+    result.mean_displacement = displacement_sum / result.moved.len().max(1) as f32;
+    result.removed = (0..old_paired.len()).filter(|index| !old_paired[*index]).collect();
+    result.added = (0..new_paired.len()).filter(|index| !new_paired[*index]).collect();
+    result
+}
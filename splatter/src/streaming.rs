@@ -0,0 +1,427 @@
+//! Streaming of large scenes from a tiled file
+//!
+//! The tiled file partitions the splats of a scene into the cells of a regular grid on the ground plane and starts with
+//! an index of all tiles, their bounds and where their splats are stored. Every splat is followed by the coefficients of
+//! its view dependent colors. A [StreamingManager] keeps the tiles close to the camera resident within a memory budget.
+//! The tiles are read on a background thread, so that the frame loop never waits for the disk, and are copied into the
+//! [Scene] resource once they arrive, which uploads them to the GPU.
+
+use crate::chunks::{morton_order, ChunkLayout};
+use crate::scene::{spherical_harmonics_count, Scene, SceneSource, Splat, PACKED_SPLAT_FLOATS};
+use bevy::prelude::*;
+use glam::{Affine3A, IVec2, Vec3};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+
+const MAGIC: &[u8; 8] = b"SPLATTIL";
+const VERSION: u32 = 2;
+
+/// Bytes of a splat and its view dependent colors in a tiled file.
+fn packed_splat_size(spherical_harmonics_order: u32) -> u64 {
+    ((PACKED_SPLAT_FLOATS + 3 * spherical_harmonics_count(spherical_harmonics_order)) * std::mem::size_of::<f32>()) as u64
+}
+
+/// Entry of the index of a tiled file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    /// Cell of the grid on the x-z plane.
+    pub cell: IVec2,
+    /// Bounds of the splat centers in the tile.
+    pub min: Vec3,
+    pub max: Vec3,
+    pub splat_count: u32,
+    /// Position of the first splat of the tile in the file.
+    pub offset: u64,
+}
+
+impl Tile {
+    /// Memory a resident tile occupies.
+    pub fn resident_bytes(&self, spherical_harmonics_order: u32) -> usize {
+        let coefficients = spherical_harmonics_count(spherical_harmonics_order) * std::mem::size_of::<Vec3>();
+        self.splat_count as usize * (std::mem::size_of::<Splat>() + coefficients)
+    }
+
+    pub fn distance_to(&self, point: Vec3) -> f32 {
+        (point.clamp(self.min, self.max) - point).length()
+    }
+}
+
+/// Index at the start of a tiled file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileIndex {
+    pub tile_size: f32,
+    /// Order of the view dependent colors of all splats, see [Scene::spherical_harmonics_order].
+    pub spherical_harmonics_order: u32,
+    pub tiles: Vec<Tile>,
+}
+
+impl TileIndex {
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        let mut word = [0; 4];
+        let mut read_word = |reader: &mut dyn Read| -> io::Result<[u8; 4]> {
+            reader.read_exact(&mut word)?;
+            Ok(word)
+        };
+        if &magic != MAGIC || u32::from_le_bytes(read_word(reader)?) != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a tiled splat file of a supported version"));
+        }
+        let tile_size = f32::from_le_bytes(read_word(reader)?);
+        let spherical_harmonics_order = u32::from_le_bytes(read_word(reader)?);
+        if spherical_harmonics_order > 3 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Spherical harmonics order above 3"));
+        }
+        let tile_count = u32::from_le_bytes(read_word(reader)?);
+        // Not reserved up front, a corrupt count should fail at the end of the file instead of allocating
+        let mut tiles = Vec::new();
+        for _ in 0..tile_count {
+            let mut values = [0; 9];
+            for value in values.iter_mut() {
+                *value = u32::from_le_bytes(read_word(reader)?);
+            }
+            let mut offset = [0; 8];
+            reader.read_exact(&mut offset)?;
+            tiles.push(Tile {
+                cell: IVec2::new(values[0] as i32, values[1] as i32),
+                min: Vec3::new(f32::from_bits(values[2]), f32::from_bits(values[3]), f32::from_bits(values[4])),
+                max: Vec3::new(f32::from_bits(values[5]), f32::from_bits(values[6]), f32::from_bits(values[7])),
+                splat_count: values[8],
+                offset: u64::from_le_bytes(offset),
+            });
+        }
+        Ok(Self {
+            tile_size,
+            spherical_harmonics_order,
+            tiles,
+        })
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.tile_size.to_le_bytes())?;
+        writer.write_all(&self.spherical_harmonics_order.to_le_bytes())?;
+        writer.write_all(&(self.tiles.len() as u32).to_le_bytes())?;
+        for tile in self.tiles.iter() {
+            for value in [tile.cell.x.to_le_bytes(), tile.cell.y.to_le_bytes()] {
+                writer.write_all(&value)?;
+            }
+            for value in tile.min.to_array().iter().chain(tile.max.to_array().iter()) {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&tile.splat_count.to_le_bytes())?;
+            writer.write_all(&tile.offset.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Size of the index in the file, which is where the splats of the first tile start.
+    fn size(tile_count: usize) -> u64 {
+        (MAGIC.len() + 4 * 4 + tile_count * (9 * 4 + 8)) as u64
+    }
+}
+
+/// Partitions the splats of the scene into tiles of `tile_size` and writes them along with their view dependent colors
+/// to a tiled file.
+pub fn write_tiled(scene: &Scene, tile_size: f32, path: impl AsRef<Path>) -> io::Result<TileIndex> {
+    let mut cells: BTreeMap<(i32, i32), Vec<usize>> = BTreeMap::new();
+    for (index, splat) in scene.splat_data.iter().enumerate() {
+        let cell = (Vec3::from(splat.center) / tile_size).floor().as_ivec3();
+        cells.entry((cell.x, cell.z)).or_default().push(index);
+    }
+    // Within a tile the splats are in Morton order, so that they split into chunks as they are, see [crate::chunks]
+    for splats in cells.values_mut() {
+        let centers: Vec<Vec3> = splats.iter().map(|index| Vec3::from(scene.splat_data[*index].center)).collect();
+        *splats = morton_order(&centers).into_iter().map(|index| splats[index as usize]).collect();
+    }
+    let mut index = TileIndex {
+        tile_size,
+        spherical_harmonics_order: scene.spherical_harmonics_order,
+        tiles: Vec::with_capacity(cells.len()),
+    };
+    let mut offset = TileIndex::size(cells.len());
+    for ((x, z), splats) in cells.iter() {
+        let (min, max) = splats.iter().fold((Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)), |(min, max), splat| {
+            let center = Vec3::from(scene.splat_data[*splat].center);
+            (min.min(center), max.max(center))
+        });
+        index.tiles.push(Tile {
+            cell: IVec2::new(*x, *z),
+            min,
+            max,
+            splat_count: splats.len() as u32,
+            offset,
+        });
+        offset += splats.len() as u64 * packed_splat_size(index.spherical_harmonics_order);
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    index.write(&mut writer)?;
+    let count = spherical_harmonics_count(index.spherical_harmonics_order);
+    for splat in cells.values().flatten() {
+        let coefficients = scene.spherical_harmonics_of(*splat);
+        let coefficients = (0..count).flat_map(|coefficient| coefficients.get(coefficient).copied().unwrap_or(Vec3::ZERO).to_array());
+        for value in scene.splat_data[*splat].to_packed().into_iter().chain(coefficients) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+    writer.flush()?;
+    Ok(index)
+}
+
+/// Reads the splats of a tile along with their view dependent colors from a tiled file.
+pub fn read_tile(reader: &mut (impl Read + Seek), tile: &Tile, spherical_harmonics_order: u32) -> io::Result<Scene> {
+    let splat_size = packed_splat_size(spherical_harmonics_order) as usize;
+    reader.seek(SeekFrom::Start(tile.offset))?;
+    let mut bytes = vec![0; tile.splat_count as usize * splat_size];
+    reader.read_exact(&mut bytes)?;
+    let mut scene = Scene::new();
+    scene.spherical_harmonics_order = spherical_harmonics_order;
+    for chunk in bytes.chunks_exact(splat_size) {
+        let mut values = chunk.chunks_exact(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()));
+        let mut packed = [0.0; PACKED_SPLAT_FLOATS];
+        for (value, read) in packed.iter_mut().zip(values.by_ref()) {
+            *value = read;
+        }
+        scene.splat_data.push(Splat::from_packed(&packed));
+        while let (Some(red), Some(green), Some(blue)) = (values.next(), values.next(), values.next()) {
+            scene.spherical_harmonics.push(Vec3::new(red, green, blue));
+        }
+    }
+    scene.splat_count = scene.splat_data.len();
+    Ok(scene)
+}
+
+/// Parameters of the [StreamingManager].
+#[derive(Debug, Clone)]
+pub struct StreamingSettings {
+    /// Tiles closer to the camera than this are loaded.
+    pub load_radius: f32,
+    /// Upper bound of the memory occupied by resident and loading tiles in bytes, closer tiles take precedence.
+    pub memory_budget: usize,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            load_radius: 50.0,
+            memory_budget: 512 * 1024 * 1024,
+        }
+    }
+}
+
+enum TileState {
+    Unloaded,
+    Loading,
+    Resident(Box<Scene>),
+    /// Reading the tile failed, it is not requested again.
+    Failed,
+}
+
+/// Loads and evicts the tiles of a tiled file depending on the camera position.
+#[derive(Resource)]
+pub struct StreamingManager {
+    pub index: TileIndex,
+    pub settings: StreamingSettings,
+    tiles: Vec<TileState>,
+    requests: Option<Sender<usize>>,
+    results: Mutex<Receiver<(usize, io::Result<Scene>)>>,
+    io_thread: Option<JoinHandle<()>>,
+}
+
+impl StreamingManager {
+    /// Reads the index of the tiled file and starts the background thread which reads the tiles.
+    pub fn open(path: impl AsRef<Path>, settings: StreamingSettings) -> io::Result<Self> {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        let index = TileIndex::read(&mut BufReader::new(File::open(&path)?))?;
+        let (request_sender, request_receiver) = channel::<usize>();
+        let (result_sender, result_receiver) = channel();
+        let (tiles, spherical_harmonics_order) = (index.tiles.clone(), index.spherical_harmonics_order);
+        let io_thread = std::thread::Builder::new().name("splat streaming".to_string()).spawn(move || {
+            let mut file = File::open(&path).map(BufReader::new);
+            // Ends when the manager is dropped
+            while let Ok(tile) = request_receiver.recv() {
+                let result = match file.as_mut() {
+                    Ok(file) => read_tile(file, &tiles[tile], spherical_harmonics_order),
+                    Err(error) => Err(io::Error::new(error.kind(), error.to_string())),
+                };
+                if result_sender.send((tile, result)).is_err() {
+                    break;
+                }
+            }
+        })?;
+        Ok(Self {
+            tiles: index.tiles.iter().map(|_| TileState::Unloaded).collect(),
+            index,
+            settings,
+            requests: Some(request_sender),
+            results: Mutex::new(result_receiver),
+            io_thread: Some(io_thread),
+        })
+    }
+
+    /// Number of tiles which were requested but did not arrive yet.
+    pub fn loading_count(&self) -> usize {
+        self.tiles.iter().filter(|state| matches!(state, TileState::Loading)).count()
+    }
+
+    /// Indices of the tiles which are in memory.
+    pub fn resident_tiles(&self) -> Vec<usize> {
+        (0..self.tiles.len())
+            .filter(|tile| matches!(self.tiles[*tile], TileState::Resident(_)))
+            .collect()
+    }
+
+    pub fn resident_bytes(&self) -> usize {
+        let order = self.index.spherical_harmonics_order;
+        self.resident_tiles().iter().map(|tile| self.index.tiles[*tile].resident_bytes(order)).sum()
+    }
+
+    /// The splats of all resident tiles, in the order of the tiles.
+    pub fn resident_splats(&self) -> Vec<Splat> {
+        let mut splats = Vec::new();
+        for state in self.tiles.iter() {
+            if let TileState::Resident(tile) = state {
+                splats.extend_from_slice(&tile.splat_data);
+            }
+        }
+        splats
+    }
+
+    /// Copies the splats of all resident tiles along with their view dependent colors into the scene, replacing all of
+    /// its splats. Every tile becomes a source of the scene, named after its cell.
+    pub fn copy_resident_into(&self, scene: &mut Scene) {
+        scene.splat_data.clear();
+        scene.spherical_harmonics.clear();
+        scene.sources.clear();
+        scene.spherical_harmonics_order = self.index.spherical_harmonics_order;
+        for (entry, state) in self.index.tiles.iter().zip(self.tiles.iter()) {
+            if let TileState::Resident(tile) = state {
+                let start = scene.splat_data.len();
+                scene.splat_data.extend_from_slice(&tile.splat_data);
+                scene.spherical_harmonics.extend_from_slice(&tile.spherical_harmonics);
+                scene.sources.push(SceneSource {
+                    name: format!("tile {} {}", entry.cell.x, entry.cell.y),
+                    transform: Affine3A::IDENTITY,
+                    splats: start..scene.splat_data.len(),
+                });
+            }
+        }
+        scene.splat_count = scene.splat_data.len();
+        scene.mark_dirty(0..scene.splat_count);
+    }
+
+    /// Chunks of [StreamingManager::resident_splats] which never cross the boundaries of tiles.
+    pub fn resident_chunks(&self, chunk_size: usize) -> ChunkLayout {
+        let mut layout = ChunkLayout::default();
+        let mut first = 0;
+        for state in self.tiles.iter() {
+            if let TileState::Resident(tile) = state {
+                layout.push_run(&tile.splat_data, first, chunk_size);
+                first += tile.splat_data.len();
+            }
+        }
+        layout
+    }
+
+    /// Takes the tiles which finished loading, evicts tiles and requests new ones for the camera position.
+    /// Returns true if the set of resident tiles changed.
+    pub fn update(&mut self, camera_position: Vec3) -> bool {
+        let mut changed = false;
+        while let Ok((tile, result)) = self.results.get_mut().unwrap().try_recv() {
+            // Tiles which were evicted while loading are dropped
+            if !matches!(self.tiles[tile], TileState::Loading) {
+                continue;
+            }
+            self.tiles[tile] = match result {
+                Ok(splats) => {
+                    changed = true;
+                    TileState::Resident(Box::new(splats))
+                }
+                Err(error) => {
+                    error!("Failed to read tile {:?}: {}", self.index.tiles[tile].cell, error);
+                    TileState::Failed
+                }
+            };
+        }
+
+        let mut wanted: Vec<(usize, f32)> = self
+            .index
+            .tiles
+            .iter()
+            .enumerate()
+            .map(|(tile, entry)| (tile, entry.distance_to(camera_position)))
+            .filter(|(_, distance)| *distance <= self.settings.load_radius)
+            .collect();
+        wanted.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let mut keep = vec![false; self.tiles.len()];
+        let mut budget = self.settings.memory_budget;
+        for (tile, _distance) in wanted {
+            let bytes = self.index.tiles[tile].resident_bytes(self.index.spherical_harmonics_order);
+            if bytes > budget {
+                break;
+            }
+            budget -= bytes;
+            keep[tile] = true;
+        }
+
+        for (tile, state) in self.tiles.iter_mut().enumerate() {
+            match state {
+                TileState::Unloaded if keep[tile] => {
+                    if let Some(requests) = self.requests.as_ref() {
+                        if requests.send(tile).is_ok() {
+                            *state = TileState::Loading;
+                        }
+                    }
+                }
+                TileState::Loading if !keep[tile] => *state = TileState::Unloaded,
+                TileState::Resident(_) if !keep[tile] => {
+                    *state = TileState::Unloaded;
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+}
+
+impl Drop for StreamingManager {
+    fn drop(&mut self) {
+        // Closing the channel stops the background thread
+        self.requests = None;
+        if let Some(io_thread) = self.io_thread.take() {
+            let _ = io_thread.join();
+        }
+    }
+}
+
+/// Streams the tiles around the active 3D camera into the [Scene] resource, if there is a [StreamingManager] resource.
+pub struct SplatStreamingPlugin;
+
+impl Plugin for SplatStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_streaming);
+    }
+}
+
+fn update_streaming(
+    manager: Option<ResMut<StreamingManager>>,
+    scene: Option<ResMut<Scene>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+) {
+    let (Some(mut manager), Some(mut scene)) = (manager, scene) else {
+        return;
+    };
+    let Some((_, transform)) = cameras.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+    if manager.update(Vec3::from(transform.translation().to_array())) {
+        manager.copy_resident_into(&mut scene);
+    }
+}
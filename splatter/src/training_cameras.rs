@@ -0,0 +1,470 @@
+//! Cameras the splats were trained with
+//!
+//! Reads the training views which come with 3DGS scenes: COLMAP `cameras.bin` and `images.bin`, the `cameras.json`
+//! written by the Inria implementation and the `transforms.json` of Nerfstudio. COLMAP and Inria use OpenCV camera axes
+//! (+X right, +Y down, looking along +Z), Nerfstudio uses OpenGL ones (+X right, +Y up, looking along -Z) like Bevy.
+//! All of them are converted to the latter, while the world space stays the one of the splats.
+
+use crate::camera_path::CameraPose;
+use crate::coordinates::CoordinateConversion;
+use crate::player::Player;
+use crate::scene::Camera;
+use bevy::prelude::{App, Event, EventReader, EventWriter, Input, KeyCode, Plugin, Projection, Query, Res, ResMut, Resource, Transform, Update};
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// Axes of the camera space in which a format stores its poses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraAxes {
+    /// +X right, +Y down, looking along +Z, used by COLMAP, Inria 3DGS and OpenCV.
+    OpenCv,
+    /// +X right, +Y up, looking along -Z, used by Nerfstudio, Blender, OpenGL and Bevy.
+    OpenGl,
+}
+
+impl CameraAxes {
+    /// Converts a camera to world rotation with these axes into one with OpenGL axes.
+    pub fn to_opengl(self, camera_to_world: Mat3) -> Mat3 {
+        match self {
+            CameraAxes::OpenCv => camera_to_world * Mat3::from_diagonal(Vec3::new(1.0, -1.0, -1.0)),
+            CameraAxes::OpenGl => camera_to_world,
+        }
+    }
+}
+
+/// A training view with its pinhole intrinsics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrainingCamera {
+    /// Name of the image the view was captured as.
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Focal lengths in pixels.
+    pub focal_length: Vec2,
+    /// Principal point in pixels from the upper left corner.
+    pub principal_point: Vec2,
+    pub position: Vec3,
+    /// Camera to world rotation with OpenGL axes.
+    pub rotation: Quat,
+}
+
+impl TrainingCamera {
+    /// Creates a camera from a camera to world rotation and position given with `axes`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_camera_to_world(
+        name: String,
+        width: u32,
+        height: u32,
+        focal_length: Vec2,
+        principal_point: Vec2,
+        rotation: Mat3,
+        position: Vec3,
+        axes: CameraAxes,
+    ) -> Self {
+        // Poses are not always perfectly orthonormal, especially after being written as text
+        let rotation = axes.to_opengl(rotation);
+        let rotation = Quat::from_mat3(&Mat3::from_cols(
+            rotation.x_axis.normalize(),
+            rotation.y_axis.normalize(),
+            rotation.z_axis.normalize(),
+        ))
+        .normalize();
+        Self {
+            name,
+            width,
+            height,
+            focal_length,
+            principal_point,
+            position,
+            rotation,
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+
+    pub fn fov_y(&self) -> f32 {
+        2.0 * (0.5 * self.height as f32 / self.focal_length.y).atan()
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.rotation, self.position).inverse()
+    }
+
+    /// A camera which sees exactly what the training image shows, including an off center principal point.
+    pub fn camera(&self, z_near: f32, z_far: f32) -> Camera {
+        let size = Vec2::new(self.width as f32, self.height as f32);
+        let mut camera = Camera::perspective(self.view(), self.fov_y(), self.aspect_ratio(), z_near, z_far);
+        camera.projection.x_axis.x = 2.0 * self.focal_length.x / size.x;
+        camera.projection.y_axis.y = 2.0 * self.focal_length.y / size.y;
+        // Pixel rows grow downwards, while y in normalized device coordinates grows upwards
+        camera.projection.z_axis.x = 1.0 - 2.0 * self.principal_point.x / size.x;
+        camera.projection.z_axis.y = 2.0 * self.principal_point.y / size.y - 1.0;
+        camera
+    }
+
+    /// The same view for an image resized to `width` by `height`, like the downsampled images of 3DGS datasets.
+    pub fn scaled(&self, width: u32, height: u32) -> Self {
+        let scale = Vec2::new(width as f32 / self.width as f32, height as f32 / self.height as f32);
+        Self {
+            width,
+            height,
+            focal_length: self.focal_length * scale,
+            principal_point: self.principal_point * scale,
+            ..self.clone()
+        }
+    }
+
+    /// Moves the view along with the splats into other coordinates.
+    pub fn convert(&mut self, conversion: &CoordinateConversion) {
+        self.position = conversion.position(self.position);
+        self.rotation = conversion.orientation(self.rotation);
+    }
+
+    pub fn pose(&self, time: f32) -> CameraPose {
+        CameraPose {
+            time,
+            position: self.position,
+            rotation: self.rotation,
+            fov_y: self.fov_y(),
+        }
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    read_bytes(reader).map(u64::from_le_bytes)
+}
+
+fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+    read_bytes(reader).map(i32::from_le_bytes)
+}
+
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    read_bytes(reader).map(f64::from_le_bytes)
+}
+
+/// Intrinsics of a COLMAP camera, distortion is ignored.
+struct ColmapIntrinsics {
+    width: u32,
+    height: u32,
+    focal_length: Vec2,
+    principal_point: Vec2,
+}
+
+/// Number of parameters and whether the focal length is shared by both axes for every COLMAP camera model.
+fn colmap_model(model_id: i32) -> io::Result<(usize, bool)> {
+    Ok(match model_id {
+        0 => (3, true),    // SIMPLE_PINHOLE
+        1 => (4, false),   // PINHOLE
+        2 => (4, true),    // SIMPLE_RADIAL
+        3 => (5, true),    // RADIAL
+        4 => (8, false),   // OPENCV
+        5 => (8, false),   // OPENCV_FISHEYE
+        6 => (12, false),  // FULL_OPENCV
+        7 => (5, false),   // FOV
+        8 => (4, true),    // SIMPLE_RADIAL_FISHEYE
+        9 => (5, true),    // RADIAL_FISHEYE
+        10 => (12, false), // THIN_PRISM_FISHEYE
+        _ => return Err(invalid_data(format!("Unknown COLMAP camera model {model_id}"))),
+    })
+}
+
+fn read_colmap_cameras(path: &Path) -> io::Result<Vec<(i32, ColmapIntrinsics)>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let count = read_u64(&mut reader)?;
+    let mut cameras = Vec::new();
+    for _ in 0..count {
+        let camera_id = read_i32(&mut reader)?;
+        let (parameter_count, shared_focal_length) = colmap_model(read_i32(&mut reader)?)?;
+        let width = read_u64(&mut reader)? as u32;
+        let height = read_u64(&mut reader)? as u32;
+        let parameters = (0..parameter_count)
+            .map(|_| read_f64(&mut reader).map(|value| value as f32))
+            .collect::<io::Result<Vec<f32>>>()?;
+        let (focal_length, principal_point) = if shared_focal_length {
+            (Vec2::splat(parameters[0]), Vec2::new(parameters[1], parameters[2]))
+        } else {
+            (Vec2::new(parameters[0], parameters[1]), Vec2::new(parameters[2], parameters[3]))
+        };
+        cameras.push((
+            camera_id,
+            ColmapIntrinsics {
+                width,
+                height,
+                focal_length,
+                principal_point,
+            },
+        ));
+    }
+    Ok(cameras)
+}
+
+/// Reads the training views of a COLMAP reconstruction from the `cameras.bin` and `images.bin` in `directory`.
+pub fn read_colmap(directory: impl AsRef<Path>) -> io::Result<Vec<TrainingCamera>> {
+    let directory = directory.as_ref();
+    let intrinsics = read_colmap_cameras(&directory.join("cameras.bin"))?;
+    let mut reader = BufReader::new(File::open(directory.join("images.bin"))?);
+    let count = read_u64(&mut reader)?;
+    let mut cameras = Vec::new();
+    for _ in 0..count {
+        let _image_id = read_i32(&mut reader)?;
+        let [w, x, y, z, tx, ty, tz] = [(); 7].map(|_| read_f64(&mut reader));
+        let rotation = Quat::from_xyzw(x? as f32, y? as f32, z? as f32, w? as f32).normalize();
+        let translation = Vec3::new(tx? as f32, ty? as f32, tz? as f32);
+        let camera_id = read_i32(&mut reader)?;
+        let mut name = Vec::new();
+        loop {
+            match read_bytes::<1>(&mut reader)?[0] {
+                0 => break,
+                byte => name.push(byte),
+            }
+        }
+        // Skip the 2D points, each is x and y as f64 and the id of its 3D point as i64
+        let point_count = read_u64(&mut reader)?;
+        io::copy(&mut (&mut reader).take(point_count * 24), &mut io::sink())?;
+        let (_, camera) = intrinsics
+            .iter()
+            .find(|(id, _)| *id == camera_id)
+            .ok_or_else(|| invalid_data(format!("Image refers to missing COLMAP camera {camera_id}")))?;
+        // COLMAP stores world to camera transformations
+        let camera_to_world = Mat3::from_quat(rotation).transpose();
+        cameras.push(TrainingCamera::from_camera_to_world(
+            String::from_utf8_lossy(&name).into_owned(),
+            camera.width,
+            camera.height,
+            camera.focal_length,
+            camera.principal_point,
+            camera_to_world,
+            -(camera_to_world * translation),
+            CameraAxes::OpenCv,
+        ));
+    }
+    cameras.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cameras)
+}
+
+#[derive(Deserialize)]
+struct InriaCamera {
+    img_name: String,
+    width: u32,
+    height: u32,
+    position: [f32; 3],
+    /// Rows of the camera to world rotation.
+    rotation: [[f32; 3]; 3],
+    fx: f32,
+    fy: f32,
+}
+
+/// Reads the `cameras.json` written next to the splats by the Inria 3DGS implementation.
+pub fn read_inria_cameras(path: impl AsRef<Path>) -> io::Result<Vec<TrainingCamera>> {
+    let cameras: Vec<InriaCamera> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(cameras
+        .into_iter()
+        .map(|camera| {
+            let rotation = Mat3::from_cols_array_2d(&camera.rotation).transpose();
+            TrainingCamera::from_camera_to_world(
+                camera.img_name,
+                camera.width,
+                camera.height,
+                Vec2::new(camera.fx, camera.fy),
+                Vec2::new(camera.width as f32, camera.height as f32) * 0.5,
+                rotation,
+                Vec3::from(camera.position),
+                CameraAxes::OpenCv,
+            )
+        })
+        .collect())
+}
+
+/// Intrinsics which Nerfstudio stores either for all frames or per frame.
+#[derive(Deserialize, Default, Clone, Copy)]
+struct NerfstudioIntrinsics {
+    fl_x: Option<f32>,
+    fl_y: Option<f32>,
+    cx: Option<f32>,
+    cy: Option<f32>,
+    w: Option<u32>,
+    h: Option<u32>,
+    /// Horizontal field of view, used by Blender exports instead of focal lengths.
+    camera_angle_x: Option<f32>,
+}
+
+impl NerfstudioIntrinsics {
+    fn or(self, other: Self) -> Self {
+        Self {
+            fl_x: self.fl_x.or(other.fl_x),
+            fl_y: self.fl_y.or(other.fl_y),
+            cx: self.cx.or(other.cx),
+            cy: self.cy.or(other.cy),
+            w: self.w.or(other.w),
+            h: self.h.or(other.h),
+            camera_angle_x: self.camera_angle_x.or(other.camera_angle_x),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct NerfstudioFrame {
+    file_path: String,
+    /// Rows of the camera to world transformation.
+    transform_matrix: [[f32; 4]; 4],
+    #[serde(flatten)]
+    intrinsics: NerfstudioIntrinsics,
+}
+
+#[derive(Deserialize)]
+struct NerfstudioTransforms {
+    #[serde(flatten)]
+    intrinsics: NerfstudioIntrinsics,
+    frames: Vec<NerfstudioFrame>,
+}
+
+/// Reads the `transforms.json` of Nerfstudio and Instant NGP.
+pub fn read_nerfstudio_transforms(path: impl AsRef<Path>) -> io::Result<Vec<TrainingCamera>> {
+    let transforms: NerfstudioTransforms = serde_json::from_str(&fs::read_to_string(path)?)?;
+    transforms
+        .frames
+        .into_iter()
+        .map(|frame| {
+            let intrinsics = frame.intrinsics.or(transforms.intrinsics);
+            let (Some(width), Some(height)) = (intrinsics.w, intrinsics.h) else {
+                return Err(invalid_data(format!("{}: missing image size", frame.file_path)));
+            };
+            let focal_x = match (intrinsics.fl_x, intrinsics.camera_angle_x) {
+                (Some(focal_length), _) => focal_length,
+                (None, Some(angle)) => 0.5 * width as f32 / (0.5 * angle).tan(),
+                (None, None) => return Err(invalid_data(format!("{}: missing focal length", frame.file_path))),
+            };
+            let matrix = Mat4::from_cols_array_2d(&frame.transform_matrix).transpose();
+            Ok(TrainingCamera::from_camera_to_world(
+                frame.file_path,
+                width,
+                height,
+                Vec2::new(focal_x, intrinsics.fl_y.unwrap_or(focal_x)),
+                Vec2::new(intrinsics.cx.unwrap_or(0.5 * width as f32), intrinsics.cy.unwrap_or(0.5 * height as f32)),
+                Mat3::from_mat4(matrix),
+                matrix.w_axis.truncate(),
+                CameraAxes::OpenGl,
+            ))
+        })
+        .collect()
+}
+
+/// Reads a COLMAP reconstruction from a directory and Inria or Nerfstudio cameras from a JSON file.
+pub fn read_training_cameras(path: impl AsRef<Path>) -> io::Result<Vec<TrainingCamera>> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        return read_colmap(path);
+    }
+    // Inria writes a list of cameras, Nerfstudio an object with a list of frames
+    let text = fs::read_to_string(path)?;
+    if text.trim_start().starts_with('[') {
+        read_inria_cameras(path)
+    } else {
+        read_nerfstudio_transforms(path)
+    }
+}
+
+/// Looks for training views in the directory of a scene file and the directories above it.
+///
+/// The Inria implementation saves the splats two directories below its `cameras.json`.
+pub fn find_training_cameras(scene_path: impl AsRef<Path>) -> io::Result<Vec<TrainingCamera>> {
+    let directories = scene_path.as_ref().ancestors().skip(1).take(4);
+    for directory in directories {
+        let directory = if directory.as_os_str().is_empty() { Path::new(".") } else { directory };
+        if directory.join("cameras.json").is_file() {
+            return read_inria_cameras(directory.join("cameras.json"));
+        }
+        if directory.join("transforms.json").is_file() {
+            return read_nerfstudio_transforms(directory.join("transforms.json"));
+        }
+        for colmap in [directory.to_path_buf(), directory.join("sparse/0"), directory.join("sparse")] {
+            if colmap.join("images.bin").is_file() && colmap.join("cameras.bin").is_file() {
+                return read_colmap(colmap);
+            }
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, "No training cameras found"))
+}
+
+/// The training views of the loaded scene, which the player can jump to.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TrainingViews {
+    pub cameras: Vec<TrainingCamera>,
+    /// The view the player jumped to last.
+    pub current: Option<usize>,
+}
+
+impl TrainingViews {
+    pub fn new(cameras: Vec<TrainingCamera>) -> Self {
+        Self { cameras, current: None }
+    }
+}
+
+/// Moves the player to a training view.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JumpToTrainingView(pub usize);
+
+/// Page up and page down step through the [TrainingViews], moving the [Player] there.
+pub struct TrainingViewPlugin;
+
+impl Plugin for TrainingViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrainingViews>()
+            .add_event::<JumpToTrainingView>()
+            .add_systems(Update, (step_through_training_views, jump_to_training_views));
+    }
+}
+
+fn step_through_training_views(keyboard: Option<Res<Input<KeyCode>>>, views: Res<TrainingViews>, mut jumps: EventWriter<JumpToTrainingView>) {
+    let (Some(keyboard), count) = (keyboard, views.cameras.len()) else {
+        return;
+    };
+    if count == 0 {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::PageDown) {
+        jumps.send(JumpToTrainingView(views.current.map_or(0, |current| (current + 1) % count)));
+    }
+    if keyboard.just_pressed(KeyCode::PageUp) {
+        jumps.send(JumpToTrainingView(
+            views.current.map_or(count - 1, |current| (current + count - 1) % count),
+        ));
+    }
+}
+
+fn jump_to_training_views(
+    mut jumps: EventReader<JumpToTrainingView>,
+    mut views: ResMut<TrainingViews>,
+    mut players: Query<(&mut Player, &mut Transform, Option<&mut Projection>)>,
+) {
+    for JumpToTrainingView(index) in jumps.read() {
+        let Some(camera) = views.cameras.get(*index) else {
+            continue;
+        };
+        for (mut player, mut transform, projection) in players.iter_mut() {
+            *transform = camera.pose(0.0).to_transform().with_scale(transform.scale);
+            player.velocity = bevy::math::Vec3::ZERO;
+            if let Some(mut projection) = projection {
+                if let Projection::Perspective(perspective) = projection.as_mut() {
+                    perspective.fov = camera.fov_y();
+                }
+            }
+        }
+        views.current = Some(*index);
+    }
+}
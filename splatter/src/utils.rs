@@ -2,6 +2,7 @@
 #![allow(dead_code)]
 
 use geometric_algebra::{ppga3d, Transformation, Zero};
+use glam::{Mat3, Vec3};
 use std::convert::TryInto;
 
 /// Transmutes a vector.
@@ -68,3 +69,64 @@ pub fn mat4_multiplication(a: &[ppga3d::Point; 4], b: &[ppga3d::Point; 4]) -> [p
 pub fn mat4_transform(a: &[ppga3d::Point; 4], b: &ppga3d::Point) -> ppga3d::Point {
     a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
 }
+
+/// Eigen decomposition of a symmetric 3x3 matrix using Jacobi rotations.
+///
+/// Returns the eigenvalues in descending order and the corresponding eigenvectors as columns of a rotation matrix.
+pub fn symmetric_eigen(matrix: Mat3) -> (Vec3, Mat3) {
+    let mut a = matrix.to_cols_array_2d();
+    let mut v = Mat3::IDENTITY.to_cols_array_2d();
+    for _ in 0..32 {
+        let off_diagonal = a[0][1] * a[0][1] + a[0][2] * a[0][2] + a[1][2] * a[1][2];
+        if off_diagonal <= 1.0e-24 * (a[0][0] * a[0][0] + a[1][1] * a[1][1] + a[2][2] * a[2][2]).max(f32::MIN_POSITIVE) {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            for row in a.iter_mut() {
+                let (akp, akq) = (row[p], row[q]);
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            for (k, (apk, aqk)) in row_p.into_iter().zip(row_q).enumerate() {
+                a[p][k] = c * apk - s * aqk;
+                a[q][k] = s * apk + c * aqk;
+            }
+            for column in v.iter_mut() {
+                let (vp, vq) = (column[p], column[q]);
+                column[p] = c * vp - s * vq;
+                column[q] = s * vp + c * vq;
+            }
+        }
+    }
+    // v holds the eigenvectors as rows, because the rotations were applied to its columns' components
+    let vectors = Mat3::from_cols_array_2d(&v).transpose();
+    let mut order = [0, 1, 2];
+    order.sort_by(|i, j| a[*j][*j].total_cmp(&a[*i][*i]));
+    let values = Vec3::new(a[order[0]][order[0]], a[order[1]][order[1]], a[order[2]][order[2]]);
+    let columns = [vectors.col(order[0]), vectors.col(order[1]), vectors.col(order[2])];
+    // Keep the basis right handed
+    let third = columns[0].cross(columns[1]);
+    (values, Mat3::from_cols(columns[0], columns[1], third))
+}
+
+/// Orthogonal factor of the polar decomposition, the rotation or reflection closest to the matrix.
+///
+/// Singular matrices have no unique closest rotation, for them this is the identity.
+pub fn polar_rotation(matrix: Mat3) -> Mat3 {
+    if matrix.determinant().abs() <= f32::EPSILON * matrix.to_cols_array().iter().map(|value| value.abs()).fold(0.0, f32::max).powi(3) {
+        return Mat3::IDENTITY;
+    }
+    // matrix = rotation * stretch with stretch = sqrt(matrixᵀ * matrix)
+    let (values, vectors) = symmetric_eigen(matrix.transpose() * matrix);
+    let inverse_roots = Vec3::from(values.max(Vec3::splat(f32::MIN_POSITIVE)).to_array().map(|value| value.sqrt().recip()));
+    let inverse_stretch = vectors * Mat3::from_diagonal(inverse_roots) * vectors.transpose();
+    matrix * inverse_stretch
+}
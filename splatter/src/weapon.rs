@@ -1,13 +1,22 @@
 use bevy::prelude::*;
 use bevy::render::camera::Camera;
-use crate::player::Player;
+use splatter::player::Player;
+use splatter::collision::RebuildSceneCollider;
+use splatter::impact::{Impact, ImpactSettings, SceneDamage};
+use splatter::loading::SceneLoading;
+use splatter::raycast::{raycast_scenes, Ray, RayHit, DEFAULT_OPACITY_THRESHOLD};
+use splatter::scene::Scene as SplatScene;
 
 pub struct WeaponPlugin;
 
+/// Number of splats reserved for debris and scorch marks.
+const IMPACT_EFFECT_CAPACITY: usize = 256;
+
 impl Plugin for WeaponPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_weapon)
-           .add_systems(Update, (weapon_controls, update_bullets));
+        app.add_event::<BulletImpact>()
+           .add_systems(Startup, setup_weapon)
+           .add_systems(Update, (weapon_controls, update_bullets, paint_impacts, damage_controls));
     }
 }
 
@@ -39,6 +48,14 @@ pub struct Bullet {
     pub direction: Vec3,
 }
 
+/// Sent when a bullet hits the splatted environment.
+#[derive(Event)]
+pub struct BulletImpact {
+    pub hit: RayHit,
+    /// The entity of the splat scene which was hit, none if it was the scene resource.
+    pub scene: Option<Entity>,
+}
+
 #[derive(Component)]
 pub struct ReloadTimer {
     pub _weapon: Entity,
@@ -134,14 +151,128 @@ fn weapon_controls(
 fn update_bullets(
     mut commands: Commands,
     time: Res<Time>,
+    scene: Option<Res<SplatScene>>,
+    scenes: Query<(Entity, &SplatScene)>,
+    mut impacts: EventWriter<BulletImpact>,
     mut bullets: Query<(Entity, &Bullet, &mut Transform)>,
 ) {
     for (entity, bullet, mut transform) in bullets.iter_mut() {
-        transform.translation += bullet.direction * bullet.speed * time.delta_seconds();
-        
+        let step = bullet.speed * time.delta_seconds();
+
+        // Test the segment travelled this frame against the splats
+        let ray = Ray::new(
+            glam::Vec3::from(transform.translation.to_array()),
+            glam::Vec3::from(bullet.direction.to_array()),
+        );
+        let splat_scenes = scene.as_deref().map(|scene| (None, scene)).into_iter().chain(scenes.iter().map(|(entity, scene)| (Some(entity), scene)));
+        if let Some((scene, hit)) = raycast_scenes(splat_scenes, &ray, step, DEFAULT_OPACITY_THRESHOLD) {
+            impacts.send(BulletImpact { hit, scene });
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.translation += bullet.direction * step;
+
         // Despawn the bullet if it goes too far
         if transform.translation.length() > 100.0 {
             commands.entity(entity).despawn();
         }
     }
 }
+
+/// Applies the impacts to the scene. Returns a new record of the damage if there was none or the old one no longer fits
+/// the scene, along with the number of splats which were deleted. The pool of an old record is removed first, so that
+/// its effects do not freeze in place.
+fn apply_impacts(
+    scene: &mut SplatScene,
+    damage: Option<&mut SceneDamage>,
+    impacts: &[Impact],
+    settings: &ImpactSettings,
+) -> (Option<SceneDamage>, usize) {
+    let mut created_damage = None;
+    let damage = match damage {
+        Some(damage) if damage.fits(scene) => damage,
+        _ => {
+            SceneDamage::remove_pools(scene);
+            created_damage.insert(SceneDamage::new(scene, IMPACT_EFFECT_CAPACITY))
+        }
+    };
+    let destroyed = impacts.iter().map(|impact| damage.apply_impact(scene, impact, settings)).sum();
+    (created_damage, destroyed)
+}
+
+fn paint_impacts(
+    mut commands: Commands,
+    mut impacts: EventReader<BulletImpact>,
+    settings: Res<ImpactSettings>,
+    scene: Option<ResMut<SplatScene>>,
+    damage: Option<ResMut<SceneDamage>>,
+    // Scenes which are still loading grow with every batch, so they are damaged once they are complete
+    mut scenes: Query<(Entity, &mut SplatScene, Option<&mut SceneDamage>), Without<SceneLoading>>,
+    mut rebuild_collider: EventWriter<RebuildSceneCollider>,
+) {
+    let impacts: Vec<(Option<Entity>, Impact)> = impacts
+        .read()
+        .map(|impact| {
+            let hit = Impact {
+                position: impact.hit.position,
+                normal: impact.hit.normal,
+            };
+            (impact.scene, hit)
+        })
+        .collect();
+    let impacts_on = |scene: Option<Entity>| -> Vec<Impact> {
+        impacts.iter().filter(|(hit_scene, _)| *hit_scene == scene).map(|(_, impact)| *impact).collect()
+    };
+    let mut destroyed = 0;
+    let resource_impacts = impacts_on(None);
+    if let (Some(mut scene), false) = (scene, resource_impacts.is_empty()) {
+        let (created_damage, count) = apply_impacts(&mut scene, damage.map(ResMut::into_inner), &resource_impacts, &settings);
+        if let Some(damage) = created_damage {
+            commands.insert_resource(damage);
+        }
+        destroyed += count;
+    }
+    for (entity, mut scene, damage) in scenes.iter_mut() {
+        let entity_impacts = impacts_on(Some(entity));
+        if entity_impacts.is_empty() {
+            continue;
+        }
+        let (created_damage, count) = apply_impacts(&mut scene, damage.map(Mut::into_inner), &entity_impacts, &settings);
+        if let Some(damage) = created_damage {
+            commands.entity(entity).insert(damage);
+        }
+        destroyed += count;
+    }
+    if destroyed > 0 {
+        // Let the player walk through the holes
+        rebuild_collider.send(RebuildSceneCollider);
+    }
+}
+
+/// Undoes the last impact on every scene or resets them all.
+fn damage_controls(
+    keyboard: Res<Input<KeyCode>>,
+    scene: Option<ResMut<SplatScene>>,
+    damage: Option<ResMut<SceneDamage>>,
+    mut scenes: Query<(&mut SplatScene, &mut SceneDamage)>,
+    mut rebuild_collider: EventWriter<RebuildSceneCollider>,
+) {
+    let (undo, reset) = (keyboard.just_pressed(KeyCode::U), keyboard.just_pressed(KeyCode::Back));
+    if !undo && !reset {
+        return;
+    }
+    let resource = scene.zip(damage).map(|(scene, damage)| (scene.into_inner(), damage.into_inner()));
+    let mut changed = false;
+    for (scene, damage) in resource.into_iter().chain(scenes.iter_mut().map(|(scene, damage)| (scene.into_inner(), damage.into_inner()))) {
+        if reset {
+            damage.reset(scene);
+            changed = true;
+        } else {
+            changed |= damage.undo(scene);
+        }
+    }
+    if changed {
+        rebuild_collider.send(RebuildSceneCollider);
+    }
+}
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use bevy::render::camera::{CameraProjection, ScalingMode, Viewport};
+use bevy::transform::TransformPlugin;
+use glam::{Vec2 as GlamVec2, Vec3 as GlamVec3};
+use splatter::camera::{SplatCameraPlugin, SplatViews};
+use splatter::scene::{Camera as SplatCamera, ProjectionModel, Scene};
+
+fn ndc(camera: &SplatCamera, point: GlamVec3) -> GlamVec3 {
+    (camera.projection * camera.view).project_point3(point)
+}
+
+#[test]
+fn perspective_matches_bevy() {
+    let transform = GlobalTransform::from(Transform::from_xyz(1.0, 2.0, 3.0).looking_at(Vec3::new(0.0, 0.5, -2.0), Vec3::Y));
+    let perspective = PerspectiveProjection {
+        fov: 0.9,
+        aspect_ratio: 1.5,
+        ..default()
+    };
+    let camera = SplatCamera::from_bevy(&transform, &Projection::Perspective(perspective.clone()), GlamVec2::new(300.0, 200.0));
+    assert_eq!(camera.model, ProjectionModel::Perspective);
+
+    let bevy_view_projection = perspective.get_projection_matrix() * transform.compute_matrix().inverse();
+    for point in [Vec3::new(0.0, 0.5, -2.0), Vec3::new(0.7, 0.1, -1.0), Vec3::new(-1.0, 1.5, -4.0)] {
+        let expected = bevy_view_projection.project_point3(point);
+        let actual = ndc(&camera, GlamVec3::from(point.to_array()));
+        // Bevy uses reversed infinite depth, so only x and y agree
+        assert!(
+            (actual.x - expected.x).abs() < 1.0e-4 && (actual.y - expected.y).abs() < 1.0e-4,
+            "{actual} != {expected}"
+        );
+    }
+}
+
+#[test]
+fn aspect_ratio_comes_from_the_viewport() {
+    let transform = GlobalTransform::IDENTITY;
+    // Bevy has not updated the aspect ratio of the projection yet
+    let projection = Projection::Perspective(PerspectiveProjection {
+        fov: std::f32::consts::FRAC_PI_2,
+        aspect_ratio: 1.0,
+        ..default()
+    });
+    let camera = SplatCamera::from_bevy(&transform, &projection, GlamVec2::new(1600.0, 800.0));
+    // A quarter turn of vertical field of view sees y = -z at the top edge and x = -2z at the right edge
+    assert!((ndc(&camera, GlamVec3::new(0.0, 1.0, -1.0)).y - 1.0).abs() < 1.0e-5);
+    assert!((ndc(&camera, GlamVec3::new(2.0, 0.0, -1.0)).x - 1.0).abs() < 1.0e-5);
+}
+
+#[test]
+fn orthographic_uses_the_scaling_mode() {
+    let transform = GlobalTransform::from(Transform::from_xyz(0.0, 0.0, 10.0));
+    let projection = Projection::Orthographic(OrthographicProjection {
+        scaling_mode: ScalingMode::FixedVertical(4.0),
+        ..default()
+    });
+    let camera = SplatCamera::from_bevy(&transform, &projection, GlamVec2::new(400.0, 200.0));
+    assert_eq!(camera.model, ProjectionModel::Orthographic);
+    // 4 units high and 8 units wide, independent of the distance
+    for z in [5.0, -20.0] {
+        let corner = ndc(&camera, GlamVec3::new(4.0, 2.0, z));
+        assert!((corner.x - 1.0).abs() < 1.0e-5 && (corner.y - 1.0).abs() < 1.0e-5, "{corner}");
+    }
+    // Rays through the image are parallel
+    let center = camera.ray_through(GlamVec2::ZERO);
+    let corner = camera.ray_through(GlamVec2::ONE);
+    assert!(center.direction.abs_diff_eq(corner.direction, 1.0e-5));
+    assert!(corner.origin.truncate().abs_diff_eq(GlamVec2::new(4.0, 2.0), 1.0e-4));
+}
+
+fn spawn_camera(app: &mut App, order: isize, position: UVec2, is_active: bool) -> Entity {
+    app.world
+        .spawn(Camera3dBundle {
+            camera: Camera {
+                order,
+                is_active,
+                viewport: Some(Viewport {
+                    physical_position: position,
+                    physical_size: UVec2::new(320, 240),
+                    ..default()
+                }),
+                ..default()
+            },
+            transform: Transform::from_xyz(order as f32, 0.0, 5.0),
+            ..default()
+        })
+        .id()
+}
+
+#[test]
+fn follows_multiple_cameras_in_render_order() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TransformPlugin, SplatCameraPlugin))
+        .init_resource::<Scene>();
+    let right = spawn_camera(&mut app, 1, UVec2::new(320, 0), true);
+    let left = spawn_camera(&mut app, -1, UVec2::ZERO, true);
+    spawn_camera(&mut app, 2, UVec2::ZERO, false);
+    app.update();
+
+    let views = &app.world.resource::<SplatViews>().views;
+    assert_eq!(views.iter().map(|view| view.entity).collect::<Vec<_>>(), vec![left, right]);
+    assert_eq!(views[1].viewport_position, glam::UVec2::new(320, 0));
+    assert_eq!(views[1].viewport_size, glam::UVec2::new(320, 240));
+    let primary = views[0].camera;
+    assert_eq!(app.world.resource::<Scene>().camera, primary);
+    assert!(ndc(&primary, GlamVec3::new(-1.0, 0.0, 0.0))
+        .truncate()
+        .abs_diff_eq(GlamVec2::ZERO, 1.0e-5));
+
+    // Moving the camera moves the splat camera in the next frame
+    app.world.get_mut::<Transform>(left).unwrap().translation.x = 3.0;
+    app.update();
+    let camera = app.world.resource::<Scene>().camera;
+    assert!(ndc(&camera, GlamVec3::new(3.0, 0.0, 0.0)).truncate().abs_diff_eq(GlamVec2::ZERO, 1.0e-5));
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use glam::{Quat as GlamQuat, Vec3 as GlamVec3};
+use splatter::camera_path::{CameraPath, CameraPathPlayback, CameraPathPlugin, CameraPathRecorder, CameraPose};
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+fn pose(time: f32, position: GlamVec3, yaw: f32) -> CameraPose {
+    CameraPose {
+        time,
+        position,
+        rotation: GlamQuat::from_rotation_y(yaw),
+        fov_y: 1.0,
+    }
+}
+
+fn square() -> CameraPath {
+    let mut path = CameraPath::default();
+    for (index, position) in [GlamVec3::ZERO, GlamVec3::X, GlamVec3::new(1.0, 0.0, 1.0), GlamVec3::Z].into_iter().enumerate() {
+        path.insert(pose(index as f32, position, index as f32 * FRAC_PI_2));
+    }
+    path
+}
+
+#[test]
+fn interpolates_through_keyframes() {
+    let path = square();
+    assert_eq!(path.duration(), 3.0);
+    for keyframe in &path.keyframes {
+        let sampled = path.sample(keyframe.time).unwrap();
+        assert!(sampled.position.abs_diff_eq(keyframe.position, 1.0e-6));
+        assert!(sampled.rotation.abs_diff_eq(keyframe.rotation, 1.0e-6));
+    }
+    // Uniform Catmull-Rom in the middle of the inner segment
+    let [p0, p1, p2, p3] = [0, 1, 2, 3].map(|index| path.keyframes[index].position);
+    let expected = (-p0 + p1 * 9.0 + p2 * 9.0 - p3) / 16.0;
+    let middle = path.sample(1.5).unwrap();
+    assert!(middle.position.abs_diff_eq(expected, 1.0e-6), "{} != {expected}", middle.position);
+    // Halfway between two quarter turns
+    assert!(middle.rotation.abs_diff_eq(GlamQuat::from_rotation_y(1.5 * FRAC_PI_2), 1.0e-6));
+    // Clamped at the ends
+    assert_eq!(path.sample(-1.0).unwrap().position, GlamVec3::ZERO);
+    assert_eq!(path.sample(10.0).unwrap().position, GlamVec3::Z);
+    assert!(CameraPath::default().sample(0.0).is_none());
+    assert!(path.sample(f32::NAN).is_none());
+    assert!(path.sample(f32::INFINITY).is_none());
+    assert_eq!(path.frames(f32::NAN).count(), 0);
+    assert_eq!(path.frames(f32::INFINITY).count(), 0);
+    assert_eq!(path.frames(-1.0).count(), 0);
+}
+
+#[test]
+fn keeps_constant_velocity_with_uneven_keyframes() {
+    let mut path = CameraPath::default();
+    for time in [0.0, 0.5, 2.0, 2.25, 4.0] {
+        path.insert(pose(time, GlamVec3::new(2.0 * time, 1.0, -time), 0.0));
+    }
+    for time in [0.3, 1.1, 2.1, 3.7] {
+        let position = path.sample(time).unwrap().position;
+        assert!(position.abs_diff_eq(GlamVec3::new(2.0 * time, 1.0, -time), 1.0e-5), "{position}");
+    }
+}
+
+#[test]
+fn inserts_keyframes_in_order() {
+    let mut path = CameraPath::default();
+    path.insert(pose(2.0, GlamVec3::X, 0.0));
+    path.insert(pose(0.0, GlamVec3::ZERO, 0.0));
+    path.insert(pose(1.0, GlamVec3::Y, 0.0));
+    path.insert(pose(1.0, GlamVec3::Z, 0.0));
+    assert_eq!(path.keyframes.iter().map(|keyframe| keyframe.time).collect::<Vec<_>>(), vec![0.0, 1.0, 2.0]);
+    assert_eq!(path.keyframes[1].position, GlamVec3::Z);
+    assert_eq!(path.frames(2.0).count(), 5);
+}
+
+#[test]
+fn saves_json_and_ron() {
+    let path = square();
+    for extension in ["json", "ron"] {
+        let file = std::env::temp_dir().join(format!("splatter_camera_path_{}.{extension}", std::process::id()));
+        path.save(&file).unwrap();
+        let text = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(text.trim_start().starts_with('{'), extension == "json");
+        let loaded = CameraPath::load(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(loaded, path);
+    }
+    // The field of view is optional
+    let file = std::env::temp_dir().join(format!("splatter_camera_path_fov_{}.json", std::process::id()));
+    std::fs::write(&file, r#"{"keyframes": [{"time": 0.0, "position": [1.0, 2.0, 3.0], "rotation": [0.0, 0.0, 0.0, 1.0]}]}"#).unwrap();
+    let loaded = CameraPath::load(&file).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(loaded.keyframes[0].position, GlamVec3::new(1.0, 2.0, 3.0));
+    assert_eq!(loaded.keyframes[0].fov_y, std::f32::consts::FRAC_PI_4);
+}
+
+#[test]
+fn sorts_keyframes_on_load() {
+    let file = std::env::temp_dir().join(format!("splatter_camera_path_unsorted_{}.json", std::process::id()));
+    let keyframe = |time: &str, x: f32| format!(r#"{{"time": {time}, "position": [{x}, 0.0, 0.0], "rotation": [0.0, 0.0, 0.0, 1.0]}}"#);
+    let keyframes = [keyframe("2.0", 2.0), keyframe("0.0", 0.0), keyframe("1.0", 1.0)].join(", ");
+    std::fs::write(&file, format!(r#"{{"keyframes": [{keyframes}]}}"#)).unwrap();
+    let loaded = CameraPath::load(&file).unwrap();
+    assert_eq!(loaded.keyframes.iter().map(|keyframe| keyframe.time).collect::<Vec<_>>(), vec![0.0, 1.0, 2.0]);
+    assert!(loaded.sample(0.5).unwrap().position.abs_diff_eq(GlamVec3::new(0.5, 0.0, 0.0), 1.0e-6));
+    std::fs::remove_file(&file).unwrap();
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TransformPlugin, CameraPathPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(250)));
+    app
+}
+
+#[test]
+fn plays_back_and_records_in_the_app() {
+    let mut app = app();
+    let mut playback = CameraPathPlayback::new(square());
+    playback.looping = false;
+    let camera = app
+        .world
+        .spawn((Camera3dBundle::default(), playback, CameraPathRecorder::new(0.5)))
+        .id();
+    for _ in 0..20 {
+        app.update();
+    }
+    let playback = app.world.get::<CameraPathPlayback>(camera).unwrap();
+    assert!(playback.finished());
+    let transform = app.world.get::<Transform>(camera).unwrap();
+    assert!(transform.translation.abs_diff_eq(Vec3::Z, 1.0e-6));
+    let Projection::Perspective(perspective) = app.world.get::<Projection>(camera).unwrap() else {
+        panic!("Expected a perspective projection");
+    };
+    assert_eq!(perspective.fov, 1.0);
+
+    // The recorder saw the transforms after they were propagated, so its poses lag one frame behind
+    let recorded = &app.world.get::<CameraPathRecorder>(camera).unwrap().path;
+    assert!(recorded.keyframes.len() >= 8);
+    let played = square();
+    for keyframe in &recorded.keyframes[2..] {
+        let expected = played.sample(keyframe.time - 0.25).unwrap();
+        assert!(keyframe.position.abs_diff_eq(expected.position, 1.0e-5), "{} != {}", keyframe.position, expected.position);
+    }
+}
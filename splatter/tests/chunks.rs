@@ -0,0 +1,178 @@
+use glam::{Affine3A, Mat4, Vec3};
+use splatter::chunks::{morton_code, morton_order, sort_into_chunks, ChunkLayout, CHUNK_SIZE};
+use splatter::cpu_renderer::{render_chunks, render_scene, CpuRenderSettings};
+use splatter::culling::{bounding_radius, cull, Containment, Frustum};
+use splatter::scene::generate::{grid, random_cloud, GenerateSettings};
+use splatter::scene::{Camera, Scene, SceneSource};
+
+fn cloud(count: usize, seed: u64) -> Scene {
+    let settings = GenerateSettings {
+        jitter: 0.5,
+        spherical_harmonics_order: 1,
+        seed,
+        ..GenerateSettings::default()
+    };
+    random_cloud(count, 10.0, &settings)
+}
+
+/// Looks into the cloud from its edge, so that chunks end up on every side of the frustum.
+fn camera() -> Camera {
+    Camera::perspective(
+        Mat4::look_at_rh(Vec3::new(2.0, 1.0, 9.0), Vec3::new(-1.0, 0.0, 0.0), Vec3::Y),
+        0.9,
+        1.5,
+        0.1,
+        30.0,
+    )
+}
+
+#[test]
+fn interleaves_coordinates() {
+    assert_eq!(morton_code([1, 0, 0]), 0b001);
+    assert_eq!(morton_code([0, 1, 0]), 0b010);
+    assert_eq!(morton_code([0, 0, 1]), 0b100);
+    assert_eq!(morton_code([3, 2, 1]), 0b011_101);
+    assert_eq!(morton_code([1023, 1023, 1023]), (1 << 30) - 1);
+    // The octant of the minimum comes first
+    let centers: Vec<Vec3> = [
+        [1.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0],
+        [3.0, 0.0, 0.0],
+        [0.0, 1.0, 1.0],
+        [1.0, 0.0, 0.0],
+        [3.0; 3],
+    ]
+    .map(Vec3::from)
+    .to_vec();
+    assert_eq!(morton_order(&centers), [1, 4, 0, 3, 2, 5]);
+}
+
+#[test]
+fn sorts_scenes_into_compact_chunks() {
+    let mut scene = cloud(3000, 1);
+    let original = scene.splat_data.clone();
+    let layout = sort_into_chunks(&mut scene, CHUNK_SIZE);
+    assert_eq!(layout.chunks.len(), 12);
+    assert_eq!(layout.splat_count(), 3000);
+    assert_eq!(layout.chunks[11].splats, 2816..3000);
+
+    // The splats only moved, along with their view dependent colors
+    let mut centers: Vec<[u32; 3]> = scene.splat_data.iter().map(|splat| splat.center.map(f32::to_bits)).collect();
+    let mut expected: Vec<[u32; 3]> = original.iter().map(|splat| splat.center.map(f32::to_bits)).collect();
+    centers.sort_unstable();
+    expected.sort_unstable();
+    assert_eq!(centers, expected);
+    let unsorted = cloud(3000, 1);
+    let moved = original.iter().position(|splat| splat.center == scene.splat_data[0].center).unwrap();
+    assert_eq!(scene.spherical_harmonics_of(0), unsorted.spherical_harmonics_of(moved));
+
+    let volume = |layout: &ChunkLayout| -> f32 {
+        layout
+            .chunks
+            .iter()
+            .map(|chunk| (chunk.max - chunk.min).to_array().iter().product::<f32>())
+            .sum()
+    };
+    for chunk in &layout.chunks {
+        for splat in &scene.splat_data[chunk.splats.clone()] {
+            let (center, radius) = (Vec3::from(splat.center), bounding_radius(splat));
+            assert!((center - radius).cmpge(chunk.min - 1.0e-5).all() && (center + radius).cmple(chunk.max + 1.0e-5).all());
+            assert!(center.distance(chunk.center) + radius <= chunk.radius + 1.0e-5);
+        }
+    }
+    // Chunks of the unsorted splats span the whole cloud
+    let unsorted_layout = ChunkLayout::new(&unsorted.splat_data, std::iter::once(0..3000), CHUNK_SIZE);
+    assert!(
+        volume(&layout) * 3.0 < volume(&unsorted_layout),
+        "{} {}",
+        volume(&layout),
+        volume(&unsorted_layout)
+    );
+}
+
+#[test]
+fn chunks_stay_within_sources() {
+    let mut scene = Scene::new();
+    let part = grid([10, 10, 3], 1.0, &GenerateSettings::default());
+    scene.merge([("a", &part, Affine3A::IDENTITY), ("b", &part, Affine3A::from_translation(Vec3::X * 20.0))]);
+    let layout = sort_into_chunks(&mut scene, CHUNK_SIZE);
+    let ranges: Vec<_> = layout.chunks.iter().map(|chunk| chunk.splats.clone()).collect();
+    assert_eq!(ranges, [0..256, 256..300, 300..556, 556..600]);
+    assert!(scene.splat_data[..300].iter().all(|splat| splat.center[0] < 10.0));
+    assert_eq!(scene.source_of(400).unwrap().name, "b");
+}
+
+#[test]
+fn chunks_cover_splats_outside_of_sources() {
+    // Like the base splats of a loaded scene after the effect pool of impacts was appended as a source
+    let mut scene = grid([10, 10, 3], 1.0, &GenerateSettings::default());
+    let mut pool = grid([2, 2, 1], 1.0, &GenerateSettings::default());
+    let start = scene.splat_count;
+    scene.append(&mut pool);
+    scene.sources.push(SceneSource {
+        name: "pool".to_string(),
+        transform: Affine3A::IDENTITY,
+        splats: start..scene.splat_count,
+    });
+    scene.append(&mut grid([5, 1, 1], 1.0, &GenerateSettings::default()));
+    let layout = sort_into_chunks(&mut scene, CHUNK_SIZE);
+    let ranges: Vec<_> = layout.chunks.iter().map(|chunk| chunk.splats.clone()).collect();
+    assert_eq!(ranges, [0..256, 256..300, 300..304, 304..309]);
+    assert_eq!(scene.source_of(302).unwrap().name, "pool");
+    assert!(scene.source_of(306).is_none());
+}
+
+#[test]
+fn culls_whole_chunks_like_single_splats() {
+    let mut scene = cloud(20_000, 2);
+    let layout = sort_into_chunks(&mut scene, CHUNK_SIZE);
+    let camera = camera();
+    for tolerance in [0.8, 1.0, 1.2] {
+        let frustum = Frustum::new(&camera, tolerance);
+        let containments = layout.classify(&frustum);
+        for containment in [Containment::Outside, Containment::Intersecting, Containment::Inside] {
+            assert!(containments.contains(&containment), "No chunk is {containment:?}");
+        }
+        let mut visible = layout.cull(&scene.splat_data, &camera, tolerance);
+        visible.sort_unstable();
+        assert_eq!(visible, cull(&scene.splat_data, &camera, tolerance));
+    }
+    assert!(ChunkLayout::default().cull(&scene.splat_data, &camera, 1.0).is_empty());
+}
+
+#[test]
+fn renders_through_the_chunks_like_without_them() {
+    let mut scene = cloud(5_000, 4);
+    let layout = sort_into_chunks(&mut scene, CHUNK_SIZE);
+    let settings = CpuRenderSettings::default();
+    let image = render_chunks(&scene, &layout, &camera(), 48, 32, &settings);
+    assert_eq!(image, render_scene(&scene, &camera(), 48, 32, &settings));
+    assert!(image.pixels.iter().any(|pixel| *pixel != settings.background));
+}
+
+#[test]
+fn draws_small_chunks_as_proxies() {
+    let mut scene = cloud(20_000, 3);
+    let layout = sort_into_chunks(&mut scene, CHUNK_SIZE);
+    let camera = camera();
+    let visible = layout.cull(&scene.splat_data, &camera, 1.0);
+    // Without a threshold everything visible is drawn in detail
+    let detailed = layout.select_detail(&scene.splat_data, &camera, 1.0, 50.0, 0.0);
+    assert_eq!((&detailed.splats, detailed.proxies.len()), (&visible, 0));
+
+    let selection = layout.select_detail(&scene.splat_data, &camera, 1.0, 50.0, 100.0);
+    assert!(!selection.proxies.is_empty() && selection.splats.len() < visible.len());
+    let camera_position = Vec3::new(2.0, 1.0, 9.0);
+    let frustum = Frustum::new(&camera, 1.0);
+    for index in &selection.proxies {
+        let chunk = &layout.chunks[*index];
+        assert_ne!(layout.classify(&frustum)[*index], Containment::Outside);
+        // Proxies are the far chunks
+        assert!(camera_position.distance(chunk.center) > 5.0);
+        let opacity = chunk.proxy.color[3];
+        assert!(opacity > 0.0 && opacity <= 1.0);
+    }
+    // Every visible splat is either drawn or stands behind a proxy
+    let proxied: usize = selection.proxies.iter().map(|index| layout.chunks[*index].splats.len()).sum();
+    assert!(selection.splats.len() + proxied >= visible.len());
+}
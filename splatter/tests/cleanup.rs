@@ -0,0 +1,135 @@
+use glam::{Mat4, Vec3};
+use splatter::cleanup::{remove_outliers, select_outliers, CleanupSettings};
+use splatter::scene::{generate, Scene, SceneFormat, Splat};
+use splatter::spatial::PointGrid;
+use splatter::splat_edit::SplatEdit;
+
+/// A dense 20 x 20 grid of splats on the floor plus the given extra splats.
+fn floor_with(extra: Vec<Splat>) -> Scene {
+    let mut splats = Vec::new();
+    for i in 0..20 {
+        for j in 0..20 {
+            splats.push(generate::splat(Vec3::new(i as f32 * 0.1, 0.0, j as f32 * 0.1), Vec3::Y, [0.05, 0.05], [0.5, 0.5, 0.5, 0.9]));
+        }
+    }
+    splats.extend(extra);
+    let mut scene = Scene::new();
+    scene.splat_count = splats.len();
+    scene.splat_data = splats;
+    scene
+}
+
+#[test]
+fn k_nearest_matches_brute_force() {
+    let points: Vec<Vec3> = (0..500)
+        .map(|i| {
+            let i = i as f32;
+            Vec3::new((i * 0.37).sin() * 3.0, (i * 0.73).cos() * 2.0, (i * 0.11).sin() * 5.0)
+        })
+        .collect();
+    let grid = PointGrid::with_density(&points, 4.0);
+    for query in [Vec3::ZERO, Vec3::new(1.0, -1.0, 2.0), Vec3::new(20.0, 0.0, 0.0)] {
+        let mut expected: Vec<f32> = points.iter().map(|point| point.distance(query)).collect();
+        expected.sort_by(f32::total_cmp);
+        let found: Vec<f32> = grid.k_nearest(query, 6).into_iter().map(|(_, distance)| distance).collect();
+        assert_eq!(found, expected[..6]);
+        assert_eq!(grid.within_radius(query, expected[5]).len(), 6);
+    }
+}
+
+#[test]
+fn removes_isolated_splats() {
+    let mut scene = floor_with(vec![
+        generate::splat(Vec3::new(1.0, 3.0, 1.0), Vec3::Y, [0.05, 0.05], [0.5, 0.5, 0.5, 0.9]),
+        generate::splat(Vec3::new(-4.0, 0.0, 0.0), Vec3::Y, [0.05, 0.05], [0.5, 0.5, 0.5, 0.9]),
+    ]);
+    let report = remove_outliers(&mut scene, &CleanupSettings::default());
+    assert_eq!(report.input_count, 402);
+    assert_eq!(report.outliers, 2);
+    assert_eq!(report.floaters, 0);
+    assert_eq!(scene.splat_count, 400);
+    assert!(scene.splat_data.iter().all(|splat| splat.center[1] == 0.0 && splat.center[0] >= 0.0));
+}
+
+#[test]
+fn removes_large_faint_floaters() {
+    let extra = vec![
+        generate::splat(Vec3::new(1.0, 0.05, 1.0), Vec3::Y, [1.0, 1.0], [0.5, 0.5, 0.5, 0.1]),
+        // Large but opaque splats are kept
+        generate::splat(Vec3::new(1.0, 0.05, 0.5), Vec3::Y, [1.0, 1.0], [0.5, 0.5, 0.5, 0.9]),
+    ];
+    let settings = CleanupSettings {
+        neighbor_count: 0,
+        ..CleanupSettings::default()
+    };
+    let mut scene = floor_with(extra);
+    let report = remove_outliers(&mut scene, &settings);
+    assert_eq!(report.outliers, 0);
+    assert_eq!(report.floaters, 1);
+    assert!((report.scale_threshold - 0.5).abs() < 1.0e-6);
+    assert_eq!(scene.splat_count, 401);
+    assert_eq!(scene.splat_data[400].color[3], 0.9);
+}
+
+#[test]
+fn selection_can_be_undone() {
+    let mut scene = floor_with(vec![generate::splat(Vec3::new(1.0, 3.0, 1.0), Vec3::Y, [0.05, 0.05], [0.5, 0.5, 0.5, 0.9])]);
+    let mut edit = SplatEdit::new(&scene);
+    let report = select_outliers(&mut edit, &scene, &CleanupSettings::default());
+    assert_eq!(edit.selected_indices(), vec![400]);
+    assert_eq!(edit.delete_selected(&mut scene), report.removed());
+    assert!(edit.undo(&mut scene));
+    assert_eq!(scene.splat_count, 401);
+}
+
+#[test]
+fn ply_round_trip_keeps_splats() {
+    let mut original = generate::splat(Vec3::new(1.0, 2.0, 3.0), Vec3::Y, [0.2, 0.2], [0.5, 0.5, 0.5, 0.75]);
+    original.scale = [0.3, 0.1];
+    original.color = [0.2, 0.4, 0.8, 0.75];
+    original.normal = Vec3::new(1.0, 1.0, 0.0).normalize().to_array();
+    original.ellipse_basis = Vec3::new(-1.0, 1.0, 0.0).normalize().to_array();
+    let scene = floor_with(vec![original.clone()]);
+    let path = std::env::temp_dir().join(format!("splatter_round_trip_{}.ply", std::process::id()));
+    let path = path.to_str().unwrap();
+    scene.save_splats_to_ply(path).unwrap();
+    let splats = Scene::read_splats_from_ply(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(splats.len(), 401);
+    let loaded = &splats[400];
+    assert!(Vec3::from(loaded.center).abs_diff_eq(Vec3::from(original.center), 1.0e-6));
+    for (loaded, original) in loaded.color.iter().zip(original.color) {
+        assert!((loaded - original).abs() < 1.0e-5);
+    }
+    assert!((loaded.scale[0] - 0.3).abs() < 1.0e-5 && (loaded.scale[1] - 0.1).abs() < 1.0e-5);
+    assert!(Vec3::from(loaded.normal).dot(Vec3::from(original.normal)).abs() > 0.9999);
+    assert!(Vec3::from(loaded.ellipse_basis).dot(Vec3::from(original.ellipse_basis)).abs() > 0.9999);
+}
+
+#[test]
+fn splat_file_round_trip_keeps_splats() {
+    let mut scene = floor_with(vec![generate::splat(Vec3::new(1.0, 2.0, 3.0), Vec3::Y, [0.2, 0.2], [0.5, 0.5, 0.5, 0.75])]);
+    scene.splat_data[400].model_matrix = Mat4::from_translation(Vec3::new(4.0, 5.0, 6.0));
+    scene.splat_data[400].depth = 2.5;
+    let path = std::env::temp_dir().join(format!("splatter_round_trip_{}.splat", std::process::id()));
+    let path = path.to_str().unwrap();
+    assert_eq!(SceneFormat::of_path(path), Some(SceneFormat::Splat));
+    scene.save(path).unwrap();
+    let loaded = Scene::load(path).unwrap();
+    std::fs::write(path, [0u8; 7]).unwrap();
+    let truncated = Scene::load(path);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(loaded.splat_count, 401);
+    for (loaded, original) in loaded.splat_data.iter().zip(&scene.splat_data) {
+        assert_eq!(loaded.to_packed(), original.to_packed());
+        assert_eq!((loaded.depth, loaded.model_matrix), (original.depth, original.model_matrix));
+    }
+    assert_eq!(truncated.err().map(|error| error.kind()), Some(std::io::ErrorKind::InvalidData));
+    assert_eq!(SceneFormat::of_path("scene.PLY"), Some(SceneFormat::Ply));
+    assert_eq!(
+        Scene::load("scene.obj").err().map(|error| error.kind()),
+        Some(std::io::ErrorKind::Unsupported)
+    );
+}
@@ -0,0 +1,93 @@
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const TEST_PLY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/test.ply");
+
+fn temporary_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("splatter_cli_{}_{name}", std::process::id()))
+}
+
+fn splatter(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_splatter")).args(args).output().unwrap()
+}
+
+/// Runs a command with `--format json` and parses its report.
+fn report(args: &[&str]) -> Value {
+    let output = splatter(&[args, &["--format", "json"]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn inspects_a_scene() {
+    let report = report(&["inspect", TEST_PLY]);
+    assert_eq!(report["format"], "ply");
+    assert_eq!(report["splat_count"], 8);
+    assert_eq!(report["bounds_min"], serde_json::json!([0.0, 0.0, 0.0]));
+    assert_eq!(report["bounds_max"], serde_json::json!([1.0, 1.0, 1.0]));
+}
+
+#[test]
+fn converts_between_formats() {
+    let output = temporary_path("convert.splat");
+    let converted = report(&["convert", TEST_PLY, output.to_str().unwrap()]);
+    assert_eq!(converted["splat_count"], 8);
+    let inspected = report(&["inspect", output.to_str().unwrap()]);
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(inspected["format"], "splat");
+    assert_eq!(inspected["splat_count"], 8);
+}
+
+#[test]
+fn crops_to_a_box() {
+    let output = temporary_path("crop.ply");
+    let report = report(&["crop", TEST_PLY, output.to_str().unwrap(), "--min", "0,0,0", "--max", "1,1,0.5"]);
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(report["kept"], 4);
+    assert_eq!(report["removed"], 4);
+}
+
+#[test]
+fn decimates_to_a_count() {
+    let output = temporary_path("decimate.ply");
+    let report = report(&["decimate", TEST_PLY, output.to_str().unwrap(), "--count", "3", "--views", "0"]);
+    let inspected = self::report(&["inspect", output.to_str().unwrap()]);
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(report["kept"], 3);
+    assert_eq!(report["removed"], 5);
+    assert_eq!(inspected["splat_count"], 3);
+}
+
+#[test]
+fn renders_a_view() {
+    let output = temporary_path("render.png");
+    let report = report(&["render", TEST_PLY, output.to_str().unwrap(), "--width", "32", "--height", "24"]);
+    let image = image::open(&output).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(report["splat_count"], 8);
+    assert_eq!((image.width(), image.height()), (32, 24));
+}
+
+#[test]
+fn cleans_up_into_the_format_of_the_output() {
+    let output = temporary_path("cleanup.splat");
+    let cleaned = report(&["cleanup", TEST_PLY, output.to_str().unwrap()]);
+    let inspected = report(&["inspect", output.to_str().unwrap()]);
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(inspected["format"], "splat");
+    assert_eq!(inspected["splat_count"], cleaned["remaining"]);
+    let unknown = temporary_path("cleanup.txt");
+    assert_eq!(splatter(&["cleanup", TEST_PLY, unknown.to_str().unwrap()]).status.code(), Some(2));
+    assert!(!unknown.exists());
+}
+
+#[test]
+fn exit_codes_tell_failures_apart() {
+    assert_eq!(splatter(&["inspect"]).status.code(), Some(2));
+    assert_eq!(splatter(&["inspect", "does/not/exist.ply"]).status.code(), Some(3));
+    assert_eq!(
+        splatter(&["crop", TEST_PLY, "out.ply", "--min", "1,1,1", "--max", "0,0,0"]).status.code(),
+        Some(2)
+    );
+}
@@ -0,0 +1,151 @@
+use bevy::prelude::{App, MinimalPlugins};
+use glam::Vec3;
+use splatter::collision::{Capsule, CollisionSettings, SceneCollider, SplatCollisionPlugin, VoxelGrid, DEFAULT_VOXEL_SIZE, MAX_VOXEL_COUNT};
+use splatter::scene::{generate, Scene};
+use std::time::{Duration, Instant};
+
+/// A floor at y = 0, a platform of 0.4 height for x > 1 and a wall at x = -1.5.
+fn room() -> Scene {
+    let mut splats = Vec::new();
+    for i in -15..=15 {
+        for j in -15..=15 {
+            let (x, z) = (i as f32 * 0.2, j as f32 * 0.2);
+            splats.push(generate::splat(Vec3::new(x, 0.0, z), Vec3::Y, [0.15, 0.15], [1.0; 4]));
+            if x > 1.0 {
+                splats.push(generate::splat(Vec3::new(x, 0.4, z), Vec3::Y, [0.15, 0.15], [1.0; 4]));
+            }
+            if i >= 0 {
+                splats.push(generate::splat(Vec3::new(-1.5, x, z), Vec3::X, [0.15, 0.15], [1.0; 4]));
+            }
+        }
+    }
+    let mut scene = Scene::new();
+    scene.splat_count = splats.len();
+    scene.splat_data = splats;
+    scene
+}
+
+const CAPSULE: Capsule = Capsule { radius: 0.3, height: 1.8 };
+
+#[test]
+fn voxelizes_surfaces() {
+    let grid = VoxelGrid::from_scene(&room(), 0.1, 0.5);
+    assert!(grid.is_solid_at(Vec3::new(0.0, 0.0, 0.0)));
+    assert!(grid.is_solid_at(Vec3::new(-1.5, 1.0, 0.5)));
+    assert!(!grid.is_solid_at(Vec3::new(0.0, 1.0, 0.0)));
+    assert!(!grid.is_solid_at(Vec3::new(100.0, 0.0, 0.0)));
+}
+
+#[test]
+fn faint_splats_are_not_solid() {
+    let mut scene = room();
+    for splat in scene.splat_data.iter_mut() {
+        splat.color[3] = 0.1;
+    }
+    let grid = VoxelGrid::from_scene(&scene, 0.1, 0.5);
+    assert!(!grid.is_solid_at(Vec3::new(0.0, 0.0, 0.0)));
+}
+
+#[test]
+fn empty_scene_has_no_obstacles() {
+    let grid = VoxelGrid::from_scene(&Scene::new(), 0.1, 0.5);
+    assert!(!grid.capsule_overlaps(&CAPSULE, Vec3::ZERO));
+    assert_eq!(grid.ground_height(&CAPSULE, Vec3::ZERO, 10.0), None);
+}
+
+#[test]
+fn falls_onto_the_ground() {
+    let grid = VoxelGrid::from_scene(&room(), 0.1, 0.5);
+    let mut feet = Vec3::new(0.0, 1.0, 0.0);
+    let mut grounded = false;
+    for _ in 0..100 {
+        let motion = grid.move_capsule(&CAPSULE, feet, Vec3::new(0.0, -0.05, 0.0), 0.35);
+        feet = motion.feet;
+        grounded = motion.grounded;
+    }
+    assert!(grounded);
+    assert!(feet.y > 0.0 && feet.y < 0.1, "{feet}");
+    assert!(!grid.capsule_overlaps(&CAPSULE, feet));
+}
+
+#[test]
+fn wall_blocks_motion() {
+    let grid = VoxelGrid::from_scene(&room(), 0.1, 0.5);
+    let mut feet = Vec3::new(0.0, grid.ground_height(&CAPSULE, Vec3::new(0.0, 0.5, 0.0), 1.0).unwrap(), 0.5);
+    let mut blocked = false;
+    for _ in 0..40 {
+        let motion = grid.move_capsule(&CAPSULE, feet, Vec3::new(-0.05, 0.0, 0.05), 0.35);
+        feet = motion.feet;
+        blocked |= motion.blocked;
+    }
+    assert!(blocked);
+    assert!(feet.x > -1.5 + CAPSULE.radius - 0.1, "{feet}");
+    // Slides along the wall instead of stopping
+    assert!(feet.z > 1.5, "{feet}");
+}
+
+#[test]
+fn steps_up_onto_low_platforms() {
+    let grid = VoxelGrid::from_scene(&room(), 0.1, 0.5);
+    let mut feet = Vec3::new(0.0, grid.ground_height(&CAPSULE, Vec3::new(0.0, 0.5, 0.0), 1.0).unwrap(), 0.0);
+    for _ in 0..40 {
+        feet = grid.move_capsule(&CAPSULE, feet, Vec3::new(0.05, 0.0, 0.0), 0.5).feet;
+    }
+    assert!(feet.x > 1.5, "{feet}");
+    assert!(feet.y > 0.35 && feet.y < 0.5, "{feet}");
+
+    // The same platform is too high with a smaller step height
+    let mut feet = Vec3::new(0.0, grid.ground_height(&CAPSULE, Vec3::new(0.0, 0.5, 0.0), 1.0).unwrap(), 0.0);
+    for _ in 0..40 {
+        feet = grid.move_capsule(&CAPSULE, feet, Vec3::new(0.05, 0.0, 0.0), 0.35).feet;
+    }
+    assert!(feet.x < 1.0, "{feet}");
+}
+
+#[test]
+fn outliers_enlarge_the_voxels() {
+    let mut scene = room();
+    scene
+        .splat_data
+        .push(generate::splat(Vec3::new(1.0e6, -1.0e6, 1.0e6), Vec3::Y, [0.15, 0.15], [1.0; 4]));
+    scene
+        .splat_data
+        .push(generate::splat(Vec3::new(f32::NAN, 0.0, f32::INFINITY), Vec3::Y, [0.15, 0.15], [1.0; 4]));
+    let grid = VoxelGrid::from_scene(&scene, 0.1, 0.5);
+    assert_eq!(grid.occupancy.len(), (grid.dimensions.x * grid.dimensions.y * grid.dimensions.z) as usize);
+    assert!(grid.occupancy.len() <= MAX_VOXEL_COUNT);
+    assert!(grid.voxel_size > 0.1 && grid.origin.is_finite());
+    assert!(grid.index(grid.cell_of(Vec3::new(1.0e6, -1.0e6, 1.0e6))).is_some());
+}
+
+#[test]
+fn invalid_voxel_sizes_fall_back_to_the_default() {
+    let scene = room();
+    let expected = VoxelGrid::from_scene(&scene, DEFAULT_VOXEL_SIZE, 0.5);
+    for voxel_size in [0.0, -0.1, f32::NAN, f32::INFINITY] {
+        let grid = VoxelGrid::from_scene(&scene, voxel_size, 0.5);
+        assert_eq!((grid.voxel_size, grid.dimensions), (expected.voxel_size, expected.dimensions), "{voxel_size}");
+        let settings = CollisionSettings {
+            voxel_size,
+            ..CollisionSettings::default()
+        };
+        assert_eq!(settings.valid_voxel_size(), DEFAULT_VOXEL_SIZE);
+    }
+}
+
+#[test]
+fn builds_the_collider_of_scene_entities_in_the_background() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, SplatCollisionPlugin));
+    let scene = room();
+    let splat_count = scene.splat_count;
+    app.world.spawn(scene);
+    let start = Instant::now();
+    while app.world.get_resource::<SceneCollider>().is_none() {
+        assert!(start.elapsed() < Duration::from_secs(10), "The collider was not built");
+        app.update();
+    }
+    let collider = app.world.resource::<SceneCollider>();
+    assert_eq!(collider.splat_count, splat_count);
+    assert!(collider.grid.is_solid_at(Vec3::new(-1.5, 1.0, 0.5)));
+}
@@ -0,0 +1,205 @@
+use glam::{Mat3, Quat, Vec3};
+use splatter::coordinates::{estimate_up, evaluate_spherical_harmonics, Axis, CoordinateConversion, CoordinateSystem, Handedness, ShTransform};
+use splatter::scene::{generate, Scene, Splat};
+
+/// Deterministic values in [-1, 1).
+fn pseudo_random(seed: u32) -> f32 {
+    (seed.wrapping_mul(2654435761).rotate_left(13).wrapping_mul(2246822519) >> 8) as f32 / (1 << 23) as f32 - 1.0
+}
+
+fn pseudo_random_vec3(seed: u32) -> Vec3 {
+    Vec3::new(pseudo_random(3 * seed), pseudo_random(3 * seed + 1), pseudo_random(3 * seed + 2))
+}
+
+fn conversions() -> Vec<CoordinateConversion> {
+    let systems = [CoordinateSystem::Z_UP, CoordinateSystem::OPENCV, CoordinateSystem::LEFT_HANDED_Y_UP];
+    let mut conversions: Vec<_> = systems
+        .iter()
+        .map(|system| CoordinateConversion::between(system, &CoordinateSystem::BEVY))
+        .collect();
+    conversions.push(CoordinateConversion::from_rotation(Quat::from_euler(glam::EulerRot::YXZ, 0.3, -1.1, 2.0)));
+    conversions
+}
+
+#[test]
+fn converts_between_conventions() {
+    let z_up = CoordinateConversion::between(&CoordinateSystem::Z_UP, &CoordinateSystem::BEVY);
+    assert!(z_up.position(Vec3::Z).abs_diff_eq(Vec3::Y, 1.0e-6));
+    assert!(z_up.direction(Vec3::Y).abs_diff_eq(Vec3::NEG_Z, 1.0e-6));
+    assert!(z_up.direction(Vec3::X).abs_diff_eq(Vec3::X, 1.0e-6));
+    assert!(!z_up.is_reflection());
+
+    let opencv = CoordinateConversion::between(&CoordinateSystem::OPENCV, &CoordinateSystem::BEVY);
+    assert!(opencv.direction(Vec3::NEG_Y).abs_diff_eq(Vec3::Y, 1.0e-6));
+    assert!(opencv.direction(Vec3::Z).abs_diff_eq(Vec3::NEG_Z, 1.0e-6));
+
+    let unity = CoordinateConversion::between(&CoordinateSystem::LEFT_HANDED_Y_UP, &CoordinateSystem::BEVY);
+    assert!(unity.is_reflection());
+    assert!(unity.direction(Vec3::new(1.0, 2.0, 3.0)).abs_diff_eq(Vec3::new(1.0, 2.0, -3.0), 1.0e-6));
+
+    let centimeters = CoordinateSystem {
+        meters_per_unit: 0.01,
+        ..CoordinateSystem::Z_UP
+    };
+    let conversion = CoordinateConversion::between(&centimeters, &CoordinateSystem::BEVY);
+    assert!(conversion
+        .position(Vec3::new(100.0, 0.0, 250.0))
+        .abs_diff_eq(Vec3::new(1.0, 2.5, 0.0), 1.0e-5));
+    let round_trip = conversion.then(&CoordinateConversion::between(&CoordinateSystem::BEVY, &centimeters));
+    assert!(round_trip.linear.abs_diff_eq(Mat3::IDENTITY, 1.0e-6) && (round_trip.scale - 1.0).abs() < 1.0e-6);
+    let inverse = conversion.then(&conversion.inverse());
+    assert!(inverse.linear.abs_diff_eq(Mat3::IDENTITY, 1.0e-6) && (inverse.scale - 1.0).abs() < 1.0e-6);
+    assert!(CoordinateConversion::between(&CoordinateSystem::BEVY, &CoordinateSystem::BEVY).is_identity());
+}
+
+#[test]
+fn converts_rotations_and_splats() {
+    let rotation = Quat::from_euler(glam::EulerRot::XYZ, 0.4, 1.2, -0.7);
+    for (index, conversion) in conversions().iter().enumerate() {
+        let vector = pseudo_random_vec3(index as u32);
+        // A rotation applies to converted vectors like the original one to the original vectors
+        let expected = conversion.direction(rotation * vector);
+        assert!((conversion.rotation(rotation) * conversion.direction(vector)).abs_diff_eq(expected, 1.0e-5));
+        if !conversion.is_reflection() {
+            assert!((conversion.orientation(rotation) * vector).abs_diff_eq(expected, 1.0e-5));
+        }
+
+        let original = generate::splat(
+            vector,
+            pseudo_random_vec3(index as u32 + 10).normalize(),
+            [0.3, 0.3 * 0.5],
+            [0.5, 0.5, 0.5, 1.0],
+        );
+        let converted = conversion.splat(&original);
+        let expected = conversion.linear * original.covariance() * conversion.linear.transpose() * conversion.scale.powi(2);
+        assert!(converted.covariance().abs_diff_eq(expected, 1.0e-5));
+        assert!(Vec3::from(converted.center).abs_diff_eq(conversion.position(vector), 1.0e-6));
+    }
+}
+
+#[test]
+fn rotates_spherical_harmonics_with_the_splats() {
+    let coefficients: Vec<Vec3> = (0..16).map(|index| pseudo_random_vec3(100 + index)).collect();
+    for conversion in conversions() {
+        let mut converted = coefficients.clone();
+        conversion.spherical_harmonics().apply(&mut converted);
+        assert_eq!(converted[0], coefficients[0]);
+        // The color seen from a direction moves with the direction
+        for index in 0..20 {
+            let direction = pseudo_random_vec3(200 + index).normalize();
+            let original = evaluate_spherical_harmonics(&coefficients, direction);
+            let rotated = evaluate_spherical_harmonics(&converted, conversion.direction(direction));
+            assert!(rotated.abs_diff_eq(original, 1.0e-4), "{rotated} != {original}");
+        }
+        // And back
+        conversion.inverse().spherical_harmonics().apply(&mut converted);
+        for (converted, original) in converted.iter().zip(&coefficients) {
+            assert!(converted.abs_diff_eq(*original, 1.0e-4));
+        }
+    }
+
+    // Lower orders leave the missing bands alone, the identity everything
+    let mut first_order = coefficients[..4].to_vec();
+    ShTransform::new(Mat3::from_rotation_y(0.5)).apply(&mut first_order);
+    assert_eq!(first_order.len(), 4);
+    let mut unchanged = coefficients.clone();
+    ShTransform::new(Mat3::IDENTITY).apply(&mut unchanged);
+    for (unchanged, original) in unchanged.iter().zip(&coefficients) {
+        assert!(unchanged.abs_diff_eq(*original, 1.0e-5));
+    }
+}
+
+/// A Z up capture: A wide ground with a few walls and some clutter on top.
+fn z_up_capture() -> Vec<Splat> {
+    let mut splats = Vec::new();
+    for x in -20..20 {
+        for y in -20..20 {
+            splats.push(generate::splat(
+                Vec3::new(x as f32 * 0.25, y as f32 * 0.25, 0.0),
+                Vec3::Z,
+                [0.15, 0.15 * 0.5],
+                [0.5, 0.5, 0.5, 1.0],
+            ));
+        }
+    }
+    for step in 0..80 {
+        let height = step as f32 * 0.05;
+        splats.push(generate::splat(
+            Vec3::new(-5.0, step as f32 * 0.1 - 4.0, height),
+            Vec3::X,
+            [0.15, 0.15 * 0.5],
+            [0.5, 0.5, 0.5, 1.0],
+        ));
+        splats.push(generate::splat(
+            Vec3::new(step as f32 * 0.1 - 4.0, 5.0, height),
+            Vec3::Y,
+            [0.15, 0.15 * 0.5],
+            [0.5, 0.5, 0.5, 1.0],
+        ));
+    }
+    for index in 0..300 {
+        let position = pseudo_random_vec3(index) * Vec3::new(4.0, 4.0, 1.0) + Vec3::new(0.0, 0.0, 1.5);
+        splats.push(generate::splat(
+            position,
+            pseudo_random_vec3(index + 1000).normalize(),
+            [0.05, 0.05 * 0.5],
+            [0.5, 0.5, 0.5, 1.0],
+        ));
+    }
+    splats
+}
+
+#[test]
+fn estimates_up_from_the_splats() {
+    let splats = z_up_capture();
+    let up = estimate_up(&splats).unwrap();
+    assert!(up.dot(Vec3::Z) > 0.99, "{up}");
+
+    // The same capture upside down and tilted
+    let conversion = CoordinateConversion::from_rotation(Quat::from_rotation_x(2.8) * Quat::from_rotation_z(0.6));
+    let mut rotated = splats.clone();
+    conversion.splats(&mut rotated);
+    let up = estimate_up(&rotated).unwrap();
+    assert!(up.dot(conversion.direction(Vec3::Z)) > 0.99, "{up}");
+    let aligned = CoordinateConversion::aligning_up(up);
+    assert!(aligned.direction(conversion.direction(Vec3::Z)).dot(Vec3::Y) > 0.99);
+
+    assert_eq!(estimate_up(&[]), None);
+}
+
+#[test]
+fn saves_the_coordinate_system_next_to_scenes() {
+    let path = std::env::temp_dir().join(format!("splatter_coordinates_{}.ply", std::process::id()));
+    let metadata = CoordinateSystem::path_for_scene(&path);
+    assert!(metadata.to_string_lossy().ends_with(".coordinates.json"));
+    let system = CoordinateSystem {
+        meters_per_unit: 0.001,
+        ..CoordinateSystem::OPENCV
+    };
+    system.save(&metadata).unwrap();
+    assert!(std::fs::read_to_string(&metadata).unwrap().contains("\"-Y\""));
+    assert_eq!(CoordinateSystem::load(&metadata).unwrap(), system);
+
+    std::fs::write(&metadata, r#"{"up": "+Z", "forward": "-Z", "handedness": "Right"}"#).unwrap();
+    assert_eq!(CoordinateSystem::load(&metadata).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    std::fs::write(&metadata, r#"{"up": "+Z", "forward": "+X", "handedness": "Left"}"#).unwrap();
+    let loaded = CoordinateSystem::load(&metadata).unwrap();
+    std::fs::remove_file(&metadata).unwrap();
+    assert_eq!(
+        (loaded.up, loaded.handedness, loaded.meters_per_unit),
+        (Axis::PositiveZ, Handedness::Left, 1.0)
+    );
+}
+
+#[test]
+fn converts_scenes() {
+    let mut scene = Scene::new();
+    scene.splat_data = vec![generate::splat(Vec3::new(1.0, 2.0, 3.0), Vec3::Z, [0.1, 0.1 * 0.5], [0.5, 0.5, 0.5, 1.0])];
+    scene.splat_count = 1;
+    scene.coordinate_system = CoordinateSystem::Z_UP;
+    scene.convert_coordinates(CoordinateSystem::BEVY);
+    assert_eq!(scene.coordinate_system, CoordinateSystem::BEVY);
+    assert!(Vec3::from(scene.splat_data[0].center).abs_diff_eq(Vec3::new(1.0, 3.0, -2.0), 1.0e-6));
+    assert!(Vec3::from(scene.splat_data[0].normal).abs_diff_eq(Vec3::Y, 1.0e-6));
+    assert_eq!(scene.dirty_splats, Some(0..1));
+}
@@ -0,0 +1,87 @@
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use splatter::cpu_renderer::{contributions, depth_order, render, CpuImage, CpuRenderSettings};
+use splatter::scene::{generate, Camera, Splat};
+use std::f32::consts::FRAC_PI_2;
+
+fn camera() -> Camera {
+    Camera::perspective(Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y), FRAC_PI_2, 1.0, 0.1, 100.0)
+}
+
+#[test]
+fn renders_a_gaussian_at_the_projected_center() {
+    let settings = CpuRenderSettings {
+        blur_variance: 0.0,
+        ..CpuRenderSettings::default()
+    };
+    // At a distance of 5 with a focal scale of 1, one unit covers a tenth of the 64 pixels wide image
+    let image = render(&[generate::splat(Vec3::new(1.0, 0.0, 0.0), Vec3::Z, [0.1, 0.1], [1.0, 0.0, 0.0, 0.8])], &camera(), 64, 64, &settings);
+    let center = image.pixel(38, 31);
+    assert!(center.x > 0.5 && center.y == 0.0 && center.z == 0.0, "{center}");
+    assert!((center.w - 1.0).abs() < 1.0e-6);
+    // One standard deviation is 0.64 pixels, so three pixels away nothing is left
+    assert_eq!(image.pixel(41, 31), Vec4::new(0.0, 0.0, 0.0, 1.0));
+    assert_eq!(image.pixel(5, 5), Vec4::new(0.0, 0.0, 0.0, 1.0));
+
+    // The falloff follows the gaussian
+    let wide = render(&[generate::splat(Vec3::ZERO, Vec3::Z, [0.5, 0.5], [1.0, 1.0, 1.0, 0.5])], &camera(), 64, 64, &settings);
+    let sigma = 0.5 / 5.0 * 32.0;
+    for distance in [0.5f32, 2.5, 4.5] {
+        let expected = 0.5 * (-0.5 * (distance * distance + 0.25) / (sigma * sigma)).exp();
+        let actual = wide.pixel(32 + distance as u32, 32).x;
+        assert!((actual - expected).abs() < 1.0e-4, "{actual} != {expected}");
+    }
+}
+
+#[test]
+fn blends_front_to_back() {
+    let front = generate::splat(Vec3::new(0.0, 0.0, 1.0), Vec3::Z, [2.0, 2.0], [1.0, 0.0, 0.0, 0.5]);
+    let back = generate::splat(Vec3::ZERO, Vec3::Z, [2.0, 2.0], [0.0, 0.0, 1.0, 1.0]);
+    let settings = CpuRenderSettings {
+        background: Vec4::ZERO,
+        ..CpuRenderSettings::default()
+    };
+    // The order in the scene does not matter
+    for splats in [[front.clone(), back.clone()], [back, front]] {
+        let pixel = render(&splats, &camera(), 32, 32, &settings).sample(Vec2::splat(16.0));
+        assert!((pixel.x - 0.5).abs() < 0.02, "{pixel}");
+        assert!((pixel.z - 0.5 * 0.99).abs() < 0.02, "{pixel}");
+        assert!(pixel.w > 0.98, "{pixel}");
+    }
+}
+
+#[test]
+fn orders_splats_back_to_front() {
+    let splats: Vec<Splat> = [0.5, -3.0, 2.0, -3.0, 7.0].map(|z| generate::splat(Vec3::new(1.0, 0.0, z), Vec3::Z, [0.1, 0.1], [1.0; 4])).to_vec();
+    // The one behind the camera comes last, ties keep their order
+    assert_eq!(depth_order(&splats, &camera()), [1, 3, 0, 2, 4]);
+    assert!(depth_order(&[], &camera()).is_empty());
+}
+
+#[test]
+fn culls_splats_behind_the_camera() {
+    let image = render(&[generate::splat(Vec3::new(0.0, 0.0, 6.0), Vec3::Z, [1.0, 1.0], [1.0; 4])], &camera(), 16, 16, &CpuRenderSettings::default());
+    assert!(image.pixels.iter().all(|pixel| *pixel == Vec4::new(0.0, 0.0, 0.0, 1.0)));
+}
+
+#[test]
+fn culling_keeps_splats_reaching_into_the_view() {
+    // The right edge of the view is 5 units to the side, the splat reaches 0.9 units past its center
+    let splats = [generate::splat(Vec3::new(5.2, 0.0, 0.0), Vec3::Z, [0.3, 0.3], [1.0; 4])];
+    let image = render(&splats, &camera(), 64, 64, &CpuRenderSettings::default());
+    assert!(image.pixel(63, 32).x > 0.1, "{}", image.pixel(63, 32));
+    let contributions = contributions(&splats, &camera(), 64, 64, &CpuRenderSettings::default());
+    assert!(contributions[0] > 0.0);
+}
+
+#[test]
+fn saves_and_loads_png() {
+    let mut image = CpuImage::new(5, 3, Vec4::new(0.0, 0.0, 0.0, 1.0));
+    image.set_pixel(4, 2, Vec4::new(1.0, 0.5, 0.25, 1.0));
+    let path = std::env::temp_dir().join(format!("splatter_cpu_renderer_{}.png", std::process::id()));
+    image.save_png(&path).unwrap();
+    let loaded = CpuImage::load_png(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!((loaded.width, loaded.height), (5, 3));
+    assert!(loaded.pixel(4, 2).abs_diff_eq(image.pixel(4, 2), 1.0 / 255.0));
+    assert_eq!(loaded.pixel(0, 0), image.pixel(0, 0));
+}
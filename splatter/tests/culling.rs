@@ -0,0 +1,90 @@
+use glam::{Mat4, Vec2, Vec3};
+use splatter::cpu_renderer::{depth_order, visible_depth_order};
+use splatter::culling::{bounding_radius, cull, is_in_frustum, world_to_clip_space, Frustum};
+use splatter::scene::generate::{gaussian, random_cloud, GenerateSettings};
+use splatter::scene::Camera;
+
+/// The shaders keep depths from 0 to 1, which is the far half of the GL style projections.
+fn cameras() -> [Camera; 2] {
+    let view = Mat4::look_at_rh(Vec3::new(1.0, 2.0, 6.0), Vec3::new(0.0, 0.5, 0.0), Vec3::Y);
+    [
+        Camera::perspective(view, 1.0, 1.5, 0.5, 20.0),
+        Camera::orthographic(view, Vec2::new(-3.0, -2.0), Vec2::new(3.0, 2.0), -20.0, 20.0),
+    ]
+}
+
+#[test]
+fn points_agree_with_the_shader() {
+    for camera in cameras() {
+        let view_projection = camera.projection * camera.view;
+        for tolerance in [0.5, 1.0, 1.3] {
+            let frustum = Frustum::new(&camera, tolerance);
+            let mut inside = 0;
+            for splat in &random_cloud(4000, 1.0, &GenerateSettings::default()).splat_data {
+                // From behind the camera to beyond the far plane, and a little wider than the view
+                let view_position = Vec3::from(splat.center) * Vec3::new(6.0, 4.0, 14.0) - Vec3::new(0.0, 0.0, 10.0);
+                let point = camera.view.inverse().transform_point3(view_position);
+                let expected = is_in_frustum(world_to_clip_space(&view_projection, point), tolerance);
+                assert_eq!(frustum.contains_sphere(point, 0.0), expected, "{point} with {tolerance}");
+                inside += expected as usize;
+            }
+            // Enough of the samples fall on either side
+            assert!((100..3900).contains(&inside), "{inside}");
+        }
+    }
+}
+
+#[test]
+fn keeps_splats_reaching_into_the_view() {
+    let camera = &cameras()[1];
+    let frustum = Frustum::new(camera, 1.0);
+    let splat = |center: Vec3, scale: f32| {
+        let settings = GenerateSettings {
+            scale: [scale, scale * 0.5],
+            ..GenerateSettings::default()
+        };
+        let mut splat = gaussian(&settings).splat_data.remove(0);
+        splat.center = center.to_array();
+        splat
+    };
+    // The right edge of the view is 3 units to the side of the view direction
+    let right = camera.view.inverse().x_axis.truncate();
+    let target = Vec3::new(0.0, 0.5, 0.0);
+    let outside = splat(target + right * 3.5, 0.2);
+    assert!((bounding_radius(&outside) - 0.6).abs() < 1.0e-6);
+    assert!(frustum.contains_splat(&outside));
+    assert!(!frustum.contains_splat(&splat(target + right * 3.7, 0.2)));
+    assert!(!is_in_frustum(
+        world_to_clip_space(&(camera.projection * camera.view), Vec3::from(outside.center)),
+        1.0
+    ));
+    // A larger tolerance widens the view
+    assert!(Frustum::new(camera, 1.2).contains_splat(&splat(target + right * 3.7, 0.2)));
+}
+
+#[test]
+fn culls_in_parallel_chunks() {
+    let settings = GenerateSettings {
+        jitter: 0.5,
+        seed: 11,
+        ..GenerateSettings::default()
+    };
+    let scene = random_cloud(50_000, 10.0, &settings);
+    for camera in cameras() {
+        let frustum = Frustum::new(&camera, 1.1);
+        let expected: Vec<u32> = (0..scene.splat_data.len() as u32)
+            .filter(|index| frustum.contains_splat(&scene.splat_data[*index as usize]))
+            .collect();
+        let visible = cull(&scene.splat_data, &camera, 1.1);
+        assert!(!visible.is_empty() && visible.len() < scene.splat_data.len());
+        assert_eq!(visible, expected);
+
+        // Sorting the visible splats keeps the order of sorting all of them
+        let sorted = visible_depth_order(&scene.splat_data, &camera, 1.1);
+        let all: Vec<u32> = depth_order(&scene.splat_data, &camera)
+            .into_iter()
+            .filter(|index| visible.binary_search(index).is_ok())
+            .collect();
+        assert_eq!(sorted, all);
+    }
+}
@@ -0,0 +1,170 @@
+use glam::{Affine3A, Mat4, Vec3};
+use splatter::cpu_renderer::contributions;
+use splatter::decimation::{decimate, decimate_with_report, importance, sample_views, DecimationMode, DecimationSettings, Importance};
+use splatter::evaluation::MAX_PSNR;
+use splatter::scene::{generate, Camera, Scene, Splat};
+
+/// Splats along x with first order coefficients holding their index.
+fn scene(splats: Vec<Splat>) -> Scene {
+    let mut scene = Scene::from_splats(splats);
+    scene.set_spherical_harmonics_order(1);
+    scene.spherical_harmonics = (0..scene.splat_count * 3).map(|index| Vec3::splat((index / 3) as f32)).collect();
+    scene
+}
+
+fn xs(scene: &Scene) -> Vec<f32> {
+    scene.splat_data.iter().map(|splat| splat.center[0]).collect()
+}
+
+#[test]
+fn keeps_the_most_important_splats() {
+    assert_eq!(importance(&generate::splat(Vec3::ZERO, Vec3::Z, [0.5, 0.5], [0.5, 0.5, 0.5, 0.5])), 0.125);
+    let mut scene = scene(vec![
+        generate::splat(Vec3::ZERO, Vec3::Z, [0.1, 0.1], [0.5, 0.5, 0.5, 1.0]),
+        generate::splat(Vec3::X, Vec3::Z, [1.0, 1.0], [0.5, 0.5, 0.5, 0.1]),
+        generate::splat(Vec3::new(2.0, 0.0, 0.0), Vec3::Z, [0.5, 0.5], [0.5, 0.5, 0.5, 1.0]),
+        generate::splat(Vec3::new(3.0, 0.0, 0.0), Vec3::Z, [0.01, 0.01], [0.5, 0.5, 0.5, 1.0]),
+        generate::splat(Vec3::new(4.0, 0.0, 0.0), Vec3::Z, [1.0, 1.0], [0.5, 0.5, 0.5, 0.0]),
+    ]);
+    assert_eq!(decimate(&mut scene, 10), 0);
+    // As if the splats were uploaded already
+    scene.dirty_splats = None;
+    assert_eq!(decimate(&mut scene, 2), 3);
+    assert_eq!(xs(&scene), [1.0, 2.0]);
+    assert_eq!(scene.splat_count, 2);
+    // The view dependent colors follow their splats
+    assert_eq!(scene.spherical_harmonics_of(1), [Vec3::splat(2.0); 3]);
+    assert_eq!(scene.dirty_splats, Some(0..2));
+}
+
+#[test]
+fn retains_view_dependent_colors_and_sources() {
+    let mut merged = Scene::new();
+    let first = scene(
+        (0..4)
+            .map(|x| generate::splat(Vec3::new(x as f32, 0.0, 0.0), Vec3::Z, [0.1, 0.1], [0.5, 0.5, 0.5, 1.0]))
+            .collect(),
+    );
+    let second = scene(
+        (4..7)
+            .map(|x| generate::splat(Vec3::new(x as f32, 0.0, 0.0), Vec3::Z, [0.1, 0.1], [0.5, 0.5, 0.5, 1.0]))
+            .collect(),
+    );
+    merged.merge([("first", &first, Affine3A::IDENTITY), ("second", &second, Affine3A::IDENTITY)]);
+    let removed = merged.retain(|index, splat| index != 1 && splat.center[0] != 5.0);
+    assert_eq!(removed, 2);
+    assert_eq!(xs(&merged), [0.0, 2.0, 3.0, 4.0, 6.0]);
+    let sources: Vec<_> = merged.sources.iter().map(|source| source.splats.clone()).collect();
+    assert_eq!(sources, [0..3, 3..5]);
+    let first_coefficients: Vec<f32> = (0..5).map(|index| merged.spherical_harmonics_of(index)[0].x).collect();
+    assert_eq!(first_coefficients, [0.0, 2.0, 3.0, 0.0, 2.0]);
+    assert_eq!(merged.retain(|_, _| true), 0);
+}
+
+/// A wall of overlapping splats at z = 0 with a large one hidden behind it.
+fn occluded() -> Scene {
+    let mut splats: Vec<Splat> = (0..25)
+        .map(|index| {
+            let mut splat = generate::splat(
+                Vec3::new((index % 5) as f32 * 0.3 - 0.6, 0.0, 0.0),
+                Vec3::Z,
+                [0.2, 0.2],
+                [0.5, 0.5, 0.5, 0.95],
+            );
+            splat.center[1] = (index / 5) as f32 * 0.3 - 0.6;
+            splat
+        })
+        .collect();
+    let mut hidden = generate::splat(Vec3::ZERO, Vec3::Z, [0.25, 0.25], [0.5, 0.5, 0.5, 1.0]);
+    hidden.center[2] = -1.0;
+    splats.push(hidden);
+    scene(splats)
+}
+
+fn front_camera() -> Camera {
+    Camera::perspective(Mat4::look_at_rh(Vec3::new(0.0, 0.0, 4.0), Vec3::ZERO, Vec3::Y), 1.0, 1.0, 0.1, 100.0)
+}
+
+#[test]
+fn measures_contributions_with_occlusion() {
+    let scene = occluded();
+    let settings = DecimationSettings::default();
+    let contributions = contributions(&scene.splat_data, &front_camera(), 64, 64, &settings.render);
+    assert_eq!(contributions.len(), 26);
+    // The center of the wall covers more pixels than the hidden splat, which is larger
+    assert!(contributions[12] > 10.0 * contributions[25], "{contributions:?}");
+    // Every pixel adds up to at most one
+    assert!(contributions.iter().sum::<f32>() <= 64.0 * 64.0);
+
+    // By area the hidden splat is the most important, by contribution one of the least
+    let mut by_area = occluded();
+    decimate(&mut by_area, 25);
+    assert!(by_area.splat_data.iter().any(|splat| splat.center[2] == -1.0));
+    let mut by_contribution = occluded();
+    let settings = DecimationSettings {
+        target_count: 25,
+        importance: Importance::Contribution,
+        view_count: 1,
+        ..DecimationSettings::default()
+    };
+    assert_eq!(sample_views(&by_contribution, 3, 1.0).len(), 3);
+    let report = decimate_with_report(&mut by_contribution, &settings);
+    assert!(by_contribution.splat_data.iter().all(|splat| splat.center[2] == 0.0));
+    assert_eq!((report.input_count, report.output_count, report.removed()), (26, 25, 1));
+}
+
+#[test]
+fn reports_the_quality_loss() {
+    let mut unchanged = occluded();
+    let report = decimate_with_report(
+        &mut unchanged,
+        &DecimationSettings {
+            target_count: 100,
+            view_count: 2,
+            ..DecimationSettings::default()
+        },
+    );
+    assert_eq!(report.quality.unwrap().psnr, MAX_PSNR);
+    assert_eq!(report.removed(), 0);
+
+    let mut losses = Vec::new();
+    for target_count in [20, 5] {
+        let mut scene = occluded();
+        let report = decimate_with_report(
+            &mut scene,
+            &DecimationSettings {
+                target_count,
+                view_count: 4,
+                ..DecimationSettings::default()
+            },
+        );
+        assert_eq!(scene.splat_count, target_count);
+        losses.push(report.quality.unwrap());
+    }
+    assert!(losses[0].psnr > losses[1].psnr && losses[0].psnr < MAX_PSNR, "{losses:?}");
+    assert!(losses[0].ssim > losses[1].ssim);
+}
+
+#[test]
+fn merges_removed_splats_into_their_neighbors() {
+    let mut scene = occluded();
+    let opacity_mass: f32 = scene.splat_data[..25].iter().map(importance).sum();
+    let settings = DecimationSettings {
+        target_count: 10,
+        mode: DecimationMode::Merge,
+        view_count: 2,
+        ..DecimationSettings::default()
+    };
+    let report = decimate_with_report(&mut scene, &settings);
+    assert_eq!(scene.splat_count, 10);
+    // The wall is dense enough to merge everything, the hidden splat is kept by its area
+    assert_eq!(report.merged, 16);
+    assert!(scene.splat_data.iter().any(|splat| splat.center[2] == -1.0));
+    // Merging keeps the coverage of the wall, up to opacities clamped to one
+    let merged_mass: f32 = scene.splat_data.iter().filter(|splat| splat.center[2] == 0.0).map(importance).sum();
+    assert!(
+        merged_mass > 0.5 * opacity_mass && merged_mass <= opacity_mass * 1.001,
+        "{merged_mass} {opacity_mass}"
+    );
+    assert_eq!(scene.spherical_harmonics.len(), 3 * 10);
+}
@@ -0,0 +1,135 @@
+use glam::{Quat, Vec2, Vec3, Vec4};
+use splatter::cpu_renderer::{render_scene, CpuImage};
+use splatter::evaluation::{evaluate, gmsd, psnr, ssim, EvaluationReport, EvaluationSettings, ImageMetrics, MAX_PSNR};
+use splatter::scene::{generate, Scene};
+use splatter::training_cameras::TrainingCamera;
+
+/// Three splats whose colors change with the view direction.
+fn scene() -> Scene {
+    let mut scene = Scene::from_splats(vec![
+        generate::splat(Vec3::new(-0.5, 0.2, 0.0), Vec3::Z, [0.3, 0.3], [1.0, 0.2, 0.1, 0.9]),
+        generate::splat(Vec3::new(0.4, -0.3, -0.5), Vec3::Z, [0.5, 0.5], [0.1, 0.6, 1.0, 0.8]),
+        generate::splat(Vec3::new(0.0, 0.5, 0.3), Vec3::Z, [0.2, 0.2], [0.9, 0.9, 0.2, 0.7]),
+    ]);
+    scene.set_spherical_harmonics_order(1);
+    for coefficient in scene.spherical_harmonics.iter_mut() {
+        *coefficient = Vec3::new(0.8, -0.8, 0.4);
+    }
+    scene
+}
+
+fn training_camera(name: &str, position: Vec3) -> TrainingCamera {
+    TrainingCamera {
+        name: name.to_string(),
+        width: 64,
+        height: 48,
+        focal_length: Vec2::splat(60.0),
+        principal_point: Vec2::new(30.0, 26.0),
+        position,
+        rotation: Quat::IDENTITY,
+    }
+}
+
+/// A smooth image with some texture, so that the structure terms of SSIM have something to compare.
+fn pattern(width: u32, height: u32) -> CpuImage {
+    let mut image = CpuImage::new(width, height, Vec4::ONE);
+    for y in 0..height {
+        for x in 0..width {
+            let value = 0.5 + 0.3 * (x as f32 * 0.4).sin() * (y as f32 * 0.3).cos();
+            image.set_pixel(x, y, Vec4::new(value, 1.0 - value, 0.5, 1.0));
+        }
+    }
+    image
+}
+
+#[test]
+fn identical_images_are_perfect() {
+    let image = pattern(32, 24);
+    let metrics = ImageMetrics::compare(&image, &image, true);
+    assert_eq!(metrics.psnr, MAX_PSNR);
+    assert!((metrics.ssim - 1.0).abs() < 1.0e-5, "{}", metrics.ssim);
+    assert!(metrics.gmsd.unwrap() < 1.0e-6);
+    assert_eq!(ImageMetrics::compare(&image, &image, false).gmsd, None);
+}
+
+#[test]
+fn metrics_follow_the_distortion() {
+    // A uniform error of 0.1 is a mean squared error of 0.01
+    let gray = CpuImage::new(8, 8, Vec4::new(0.5, 0.5, 0.5, 1.0));
+    let lighter = CpuImage::new(8, 8, Vec4::new(0.6, 0.6, 0.6, 1.0));
+    assert!((psnr(&gray, &lighter) - 20.0).abs() < 1.0e-3);
+
+    let reference = pattern(32, 24);
+    let noisy = |amplitude: f32| {
+        let mut image = reference.clone();
+        for (index, pixel) in image.pixels.iter_mut().enumerate() {
+            // Deterministic noise from a hash of the index
+            let noise = ((index as u32).wrapping_mul(2654435761) >> 16) as f32 / 65536.0 - 0.5;
+            *pixel = (*pixel + Vec4::splat(noise * amplitude)).with_w(1.0);
+        }
+        image
+    };
+    let (slightly, heavily) = (noisy(0.05), noisy(0.3));
+    assert!(psnr(&slightly, &reference) > psnr(&heavily, &reference));
+    assert!(ssim(&slightly, &reference) > ssim(&heavily, &reference));
+    assert!(ssim(&heavily, &reference) < 0.9);
+    assert!(gmsd(&slightly, &reference) < gmsd(&heavily, &reference));
+    // A flat image misses all the structure, except in the blue channel which is flat in both
+    let flat = CpuImage::new(32, 24, Vec4::new(0.5, 0.5, 0.5, 1.0));
+    assert!(ssim(&flat, &reference) < 0.4);
+}
+
+#[test]
+fn evaluates_training_views_against_photos() {
+    let directory = std::env::temp_dir().join(format!("splatter_evaluation_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let scene = scene();
+    let settings = EvaluationSettings::default();
+    let cameras = [
+        training_camera("front", Vec3::new(0.0, 0.0, 3.0)),
+        training_camera("side.png", Vec3::new(0.3, 0.0, 2.5)),
+        training_camera("missing", Vec3::new(0.0, 0.0, 4.0)),
+    ];
+    // The first photo was downsampled by two like the images_2 directories of 3DGS datasets
+    let half = cameras[0].scaled(32, 24);
+    let photo = render_scene(&scene, &half.camera(settings.z_near, settings.z_far), 32, 24, &settings.render);
+    photo.save_png(directory.join("front.png")).unwrap();
+    // The second one is wrong
+    let camera = cameras[1].camera(settings.z_near, settings.z_far);
+    let mut photo = render_scene(&scene, &camera, 64, 48, &settings.render);
+    photo.pixels.iter_mut().for_each(|pixel| pixel.x = 1.0 - pixel.x);
+    photo.save_png(directory.join("side.png")).unwrap();
+
+    let report = evaluate(&scene, &cameras, &directory, &settings).unwrap();
+    let path = directory.join("report.json");
+    report.save(&path).unwrap();
+    let loaded = EvaluationReport::load(&path).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(loaded, report);
+    assert_eq!(report.missing, ["missing"]);
+    let [front, side] = [&report.views[0], &report.views[1]];
+    assert_eq!((front.name.as_str(), front.width, front.height), ("front", 32, 24));
+    // Only quantized to eight bits
+    assert!(front.metrics.psnr > 45.0, "{}", front.metrics.psnr);
+    assert!(front.metrics.ssim > 0.99);
+    assert!(side.metrics.psnr < 20.0, "{}", side.metrics.psnr);
+    let mean = report.mean.unwrap();
+    assert!((mean.psnr - (front.metrics.psnr + side.metrics.psnr) / 2.0).abs() < 1.0e-4);
+    assert!(report.to_string().lines().any(|line| line.starts_with("mean")));
+}
+
+#[test]
+fn transparent_photos_are_blended_over_the_background() {
+    let directory = std::env::temp_dir().join(format!("splatter_evaluation_alpha_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let settings = EvaluationSettings::default();
+    let camera = training_camera("empty.png", Vec3::new(0.0, 0.0, 3.0));
+    // Nothing in front of the camera renders the black background, which a fully transparent white photo becomes
+    CpuImage::new(64, 48, Vec4::new(1.0, 1.0, 1.0, 0.0))
+        .save_png(directory.join("empty.png"))
+        .unwrap();
+    let report = evaluate(&Scene::new(), &[camera], &directory, &settings).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(report.views[0].metrics.psnr, MAX_PSNR);
+}
@@ -0,0 +1,86 @@
+use glam::{Quat, Vec3};
+use splatter::scene::generate::{gaussian, grid, random_cloud, sphere_shell, GenerateSettings};
+use splatter::scene::{spherical_harmonics_count, Splat};
+
+fn center(splat: &Splat) -> Vec3 {
+    Vec3::from(splat.center)
+}
+
+#[test]
+fn generates_a_single_gaussian() {
+    let scene = gaussian(&GenerateSettings::default());
+    assert_eq!((scene.splat_count, scene.splat_data.len()), (1, 1));
+    let splat = &scene.splat_data[0];
+    assert_eq!((splat.center, splat.scale, splat.color), ([0.0; 3], [0.1, 0.1], [0.8, 0.8, 0.8, 1.0]));
+    assert!(scene.spherical_harmonics.is_empty());
+}
+
+#[test]
+fn generates_centered_rotated_grids() {
+    let settings = GenerateSettings {
+        scale: [0.3, 0.1],
+        rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        ..GenerateSettings::default()
+    };
+    let scene = grid([4, 3, 2], 0.5, &settings);
+    assert_eq!(scene.splat_count, 24);
+    let sum: Vec3 = scene.splat_data.iter().map(center).sum();
+    assert!(sum.length() < 1.0e-5);
+    assert_eq!(scene.splat_data[0].center, [-0.75, -0.5, -0.25]);
+    assert_eq!(scene.splat_data[1].center, [-0.25, -0.5, -0.25]);
+    for splat in &scene.splat_data {
+        assert_eq!(splat.scale, [0.3, 0.1]);
+        assert!(Vec3::from(splat.ellipse_basis).abs_diff_eq(Vec3::Y, 1.0e-6));
+        assert!(Vec3::from(splat.normal).abs_diff_eq(Vec3::Z, 1.0e-6));
+    }
+}
+
+#[test]
+fn sphere_shells_face_outwards() {
+    let scene = sphere_shell(200, 2.0, &GenerateSettings::default());
+    assert_eq!(scene.splat_count, 200);
+    for splat in &scene.splat_data {
+        assert!((center(splat).length() - 2.0).abs() < 1.0e-5);
+        assert!(Vec3::from(splat.normal).abs_diff_eq(center(splat) / 2.0, 1.0e-5));
+    }
+    // Evenly spread: Every octant gets about an eighth
+    let octant = scene
+        .splat_data
+        .iter()
+        .filter(|splat| splat.center.iter().all(|value| *value > 0.0))
+        .count();
+    assert!((20..=30).contains(&octant), "{octant}");
+}
+
+#[test]
+fn random_clouds_depend_on_the_seed() {
+    let settings = GenerateSettings {
+        jitter: 0.5,
+        spherical_harmonics_order: 2,
+        seed: 7,
+        ..GenerateSettings::default()
+    };
+    let scene = random_cloud(100, 3.0, &settings);
+    assert_eq!(scene.spherical_harmonics_order, 2);
+    assert_eq!(scene.spherical_harmonics.len(), 100 * spherical_harmonics_count(2));
+    assert!(scene
+        .spherical_harmonics
+        .iter()
+        .all(|coefficient| coefficient.abs().max_element() <= settings.spherical_harmonics_amplitude));
+    assert!(scene.splat_data.iter().all(|splat| center(splat).abs().max_element() <= 3.0));
+    assert!(scene
+        .splat_data
+        .iter()
+        .all(|splat| splat.scale.iter().all(|scale| (0.075..=0.125).contains(scale))));
+    assert!(scene
+        .splat_data
+        .iter()
+        .all(|splat| splat.color[0..3].iter().all(|value| (0.0..=1.0).contains(value))));
+
+    let packed = |splats: &[Splat]| splats.iter().map(Splat::to_packed).collect::<Vec<_>>();
+    let again = random_cloud(100, 3.0, &settings);
+    assert_eq!(packed(&again.splat_data), packed(&scene.splat_data));
+    assert_eq!(again.spherical_harmonics, scene.spherical_harmonics);
+    let other = random_cloud(100, 3.0, &GenerateSettings { seed: 8, ..settings });
+    assert_ne!(packed(&other.splat_data), packed(&scene.splat_data));
+}
@@ -0,0 +1,221 @@
+//! Golden image regression tests
+//!
+//! The fixture scenes in `tests/fixtures` are rendered from fixed cameras with the CPU reference renderer and compared
+//! against the PNGs in `tests/golden`. On a mismatch the render and an amplified difference image are written next to
+//! the build artifacts. Run with `SPLATTER_UPDATE_GOLDENS=1` to accept the current renders as the new goldens.
+//!
+//! The GPU rasterizer has goldens of its own and is skipped on machines without an adapter.
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use splatter::cpu_renderer::{render_scene, CpuImage, CpuRenderSettings};
+use splatter::gpu_rasterizer::GpuRasterizer;
+use splatter::scene::generate::{sphere_shell, GenerateSettings};
+use splatter::scene::{Camera, Scene};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const UPDATE_VARIABLE: &str = "SPLATTER_UPDATE_GOLDENS";
+
+/// Largest difference of a channel which still matches, a little more than the rounding to eight bits.
+const CHANNEL_TOLERANCE: f32 = 3.0 / 255.0;
+
+/// Fraction of pixels which may exceed the channel tolerance, for the odd edge pixel which rounds differently.
+const MISMATCH_TOLERANCE: f32 = 0.002;
+
+const WIDTH: u32 = 96;
+const HEIGHT: u32 = 64;
+
+fn fixture(name: &str) -> Scene {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    Scene::load(path.to_str().unwrap()).unwrap_or_else(|error| panic!("{}: {error}", path.display()))
+}
+
+/// The cameras every fixture is rendered from, looking at `center` from `distance`.
+fn cameras(center: Vec3, distance: f32) -> Vec<(&'static str, Camera)> {
+    let aspect_ratio = WIDTH as f32 / HEIGHT as f32;
+    let look = |direction: Vec3, up: Vec3| Mat4::look_at_rh(center + direction.normalize() * distance, center, up);
+    let half_size = Vec2::new(aspect_ratio, 1.0) * distance * 0.4;
+    vec![
+        ("front", Camera::perspective(look(Vec3::Z, Vec3::Y), 1.0, aspect_ratio, 0.1, 100.0)),
+        (
+            "oblique",
+            Camera::perspective(look(Vec3::new(1.0, 0.8, -0.6), Vec3::Y), 0.8, aspect_ratio, 0.1, 100.0),
+        ),
+        ("top", Camera::orthographic(look(Vec3::Y, Vec3::NEG_Z), -half_size, half_size, 0.1, 100.0)),
+    ]
+}
+
+/// The GPU backend, shared by the tests. None without an adapter.
+fn gpu() -> Option<&'static GpuRasterizer> {
+    static GPU: OnceLock<Option<GpuRasterizer>> = OnceLock::new();
+    GPU.get_or_init(|| {
+        let gpu = GpuRasterizer::new();
+        if gpu.is_none() {
+            eprintln!("No GPU adapter, only the CPU renders are compared with their goldens");
+        }
+        gpu
+    })
+    .as_ref()
+}
+
+/// Renders of a scene with every available backend, named after the backend and the camera.
+fn renders(scene: &Scene, center: Vec3, distance: f32) -> Vec<(String, CpuImage)> {
+    let settings = CpuRenderSettings::default();
+    let cameras = cameras(center, distance);
+    let mut renders: Vec<(String, CpuImage)> = cameras
+        .iter()
+        .map(|(name, camera)| (format!("cpu_{name}"), render_scene(scene, camera, WIDTH, HEIGHT, &settings)))
+        .collect();
+    if let Some(gpu) = gpu() {
+        renders.extend(
+            cameras
+                .iter()
+                .map(|(name, camera)| (format!("gpu_{name}"), gpu.render_scene(scene, camera, WIDTH, HEIGHT, &settings))),
+        );
+    }
+    renders
+}
+
+/// Where renders which do not match their golden are written to.
+fn failure_directory() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden")
+}
+
+/// Compares the render with its golden, or replaces the golden if asked to. Returns a description of the mismatch.
+fn check_golden(name: &str, image: &CpuImage) -> Result<(), String> {
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.png"));
+    if std::env::var_os(UPDATE_VARIABLE).is_some_and(|value| value != "0") {
+        image.save_png(&golden_path).unwrap();
+        return Ok(());
+    }
+    let golden = CpuImage::load_png(&golden_path).map_err(|error| {
+        format!(
+            "{name}: no golden at {} ({error}), run with {UPDATE_VARIABLE}=1 to create it",
+            golden_path.display()
+        )
+    })?;
+    if (golden.width, golden.height) != (image.width, image.height) {
+        return Err(format!(
+            "{name}: the golden is {}x{} but the render {}x{}",
+            golden.width, golden.height, image.width, image.height
+        ));
+    }
+    // The golden went through eight bits, so the render has to as well
+    let actual = CpuImage::from_rgba8(&image.to_rgba8());
+    let mut difference = CpuImage::new(actual.width, actual.height, Vec4::new(0.0, 0.0, 0.0, 1.0));
+    let mut mismatches = 0;
+    let mut max_difference = 0.0f32;
+    for ((pixel, expected), diff) in actual.pixels.iter().zip(&golden.pixels).zip(&mut difference.pixels) {
+        let delta = (*pixel - *expected).abs();
+        max_difference = max_difference.max(delta.max_element());
+        if delta.max_element() > CHANNEL_TOLERANCE {
+            mismatches += 1;
+        }
+        // Alpha differences show up in all channels
+        *diff = ((delta.truncate() + Vec3::splat(delta.w)) * 10.0).min(Vec3::ONE).extend(1.0);
+    }
+    if mismatches as f32 <= MISMATCH_TOLERANCE * actual.pixels.len() as f32 {
+        return Ok(());
+    }
+    let directory = failure_directory();
+    std::fs::create_dir_all(&directory).unwrap();
+    let (actual_path, difference_path) = (directory.join(format!("{name}.actual.png")), directory.join(format!("{name}.diff.png")));
+    actual.save_png(&actual_path).unwrap();
+    difference.save_png(&difference_path).unwrap();
+    Err(format!(
+        "{name}: {mismatches} of {} pixels differ by up to {:.1}/255, see {} and {}",
+        actual.pixels.len(),
+        max_difference * 255.0,
+        actual_path.display(),
+        difference_path.display()
+    ))
+}
+
+fn check_goldens(renders: Vec<(String, CpuImage)>) {
+    let failures: Vec<String> = renders.iter().filter_map(|(name, image)| check_golden(name, image).err()).collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn cube_corners() {
+    let scene = fixture("test.ply");
+    let renders = renders(&scene, Vec3::splat(0.5), 12.0);
+    check_goldens(renders.into_iter().map(|(name, image)| (format!("test_{name}"), image)).collect());
+}
+
+#[test]
+fn view_dependent_colors() {
+    let scene = fixture("spherical_harmonics.ply");
+    assert_eq!((scene.splat_count, scene.spherical_harmonics_order), (6, 1));
+    let renders = renders(&scene, Vec3::ZERO, 3.0);
+    check_goldens(
+        renders
+            .into_iter()
+            .map(|(name, image)| (format!("spherical_harmonics_{name}"), image))
+            .collect(),
+    );
+}
+
+#[test]
+fn generated_sphere_shell() {
+    let settings = GenerateSettings {
+        scale: [0.12, 0.06],
+        jitter: 0.4,
+        spherical_harmonics_order: 1,
+        seed: 3,
+        ..GenerateSettings::default()
+    };
+    let scene = sphere_shell(64, 1.0, &settings);
+    let renders = renders(&scene, Vec3::ZERO, 3.5);
+    check_goldens(renders.into_iter().map(|(name, image)| (format!("sphere_shell_{name}"), image)).collect());
+}
+
+#[test]
+fn gpu_matches_the_cpu_reference() {
+    let Some(gpu) = gpu() else {
+        return;
+    };
+    let scene = fixture("spherical_harmonics.ply");
+    let settings = CpuRenderSettings::default();
+    for (name, camera) in cameras(Vec3::ZERO, 3.0) {
+        let cpu = CpuImage::from_rgba8(&render_scene(&scene, &camera, WIDTH, HEIGHT, &settings).to_rgba8());
+        let gpu = CpuImage::from_rgba8(&gpu.render_scene(&scene, &camera, WIDTH, HEIGHT, &settings).to_rgba8());
+        let mismatches = cpu
+            .pixels
+            .iter()
+            .zip(&gpu.pixels)
+            .filter(|(cpu, gpu)| (**cpu - **gpu).abs().max_element() > CHANNEL_TOLERANCE)
+            .count();
+        assert!(
+            mismatches as f32 <= MISMATCH_TOLERANCE * cpu.pixels.len() as f32,
+            "{name}: {mismatches} pixels differ"
+        );
+    }
+}
+
+#[test]
+fn renders_are_deterministic() {
+    let scene = fixture("spherical_harmonics.ply");
+    let first = renders(&scene, Vec3::ZERO, 3.0);
+    assert_eq!(first, renders(&scene, Vec3::ZERO, 3.0));
+}
+
+#[test]
+fn mismatches_write_diff_images() {
+    let scene = fixture("spherical_harmonics.ply");
+    let (_, mut image) = renders(&scene, Vec3::ZERO, 3.0).swap_remove(0);
+    // A quarter of the image turns white
+    for y in 0..HEIGHT / 2 {
+        for x in 0..WIDTH / 2 {
+            image.set_pixel(x, y, Vec4::ONE);
+        }
+    }
+    if std::env::var_os(UPDATE_VARIABLE).is_some() {
+        return;
+    }
+    let message = check_golden("spherical_harmonics_cpu_front", &image).unwrap_err();
+    assert!(message.contains("pixels differ"), "{message}");
+    let difference = CpuImage::load_png(failure_directory().join("spherical_harmonics_cpu_front.diff.png")).unwrap();
+    assert!(difference.pixel(WIDTH - 1, HEIGHT - 1).truncate().max_element() <= CHANNEL_TOLERANCE * 20.0);
+    assert!(check_golden("missing", &image).unwrap_err().contains(UPDATE_VARIABLE));
+}
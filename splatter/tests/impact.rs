@@ -0,0 +1,229 @@
+use glam::{Mat4, Vec3};
+use splatter::cpu_renderer::{render_scene, CpuRenderSettings};
+use splatter::impact::{Impact, ImpactSettings, SceneDamage, EFFECT_POOL_SOURCE};
+use splatter::raycast::Ray;
+use splatter::scene::generate::{grid, GenerateSettings};
+use splatter::scene::{Camera, Scene};
+
+/// A wall of white splats in the plane z = 0, spaced 0.1 apart.
+fn wall() -> Scene {
+    let settings = GenerateSettings {
+        color: [1.0; 4],
+        ..GenerateSettings::default()
+    };
+    grid([21, 21, 1], 0.1, &settings)
+}
+
+const HIT: Impact = Impact {
+    position: Vec3::ZERO,
+    normal: Vec3::Z,
+};
+
+fn colors(scene: &Scene, count: usize) -> Vec<[f32; 4]> {
+    scene.splat_data[..count].iter().map(|splat| splat.color).collect()
+}
+
+#[test]
+fn reserves_effect_pool_once() {
+    let mut scene = wall();
+    let base = scene.splat_count;
+    let mut damage = SceneDamage::new(&mut scene, 16);
+    assert_eq!(scene.splat_count, base + 16);
+    assert!(scene.splat_data[base..].iter().all(|splat| splat.color[3] == 0.0));
+    damage.apply_impact(&mut scene, &HIT, &ImpactSettings::default());
+    assert_eq!(scene.splat_count, base + 16);
+    assert_eq!(damage.active_effect_count(), 1 + ImpactSettings::default().debris_count);
+}
+
+#[test]
+fn pads_the_view_dependent_colors_of_the_pool() {
+    let mut scene = wall();
+    scene.set_spherical_harmonics_order(1);
+    scene.spherical_harmonics.fill(Vec3::ONE);
+    let base = scene.splat_count;
+    let _damage = SceneDamage::new(&mut scene, 16);
+    assert_eq!(scene.spherical_harmonics.len(), scene.splat_count * 3);
+    assert!(scene.spherical_harmonics[base * 3..].iter().all(|coefficient| *coefficient == Vec3::ZERO));
+    assert_eq!(scene.sources[0].name, EFFECT_POOL_SOURCE);
+    assert_eq!(scene.sources[0].splats, base..base + 16);
+
+    // Removing splats keeps the remaining ones with their own coefficients
+    scene.retain(|index, _splat| index % 2 == 0);
+    assert_eq!(scene.spherical_harmonics.len(), scene.splat_count * 3);
+    assert_eq!(scene.spherical_harmonics_of(0), [Vec3::ONE; 3]);
+}
+
+#[test]
+fn tints_only_splats_within_radius() {
+    let mut scene = wall();
+    let base = scene.splat_count;
+    let mut damage = SceneDamage::new(&mut scene, 16);
+    scene.dirty_splats = None;
+    let settings = ImpactSettings {
+        debris_count: 0,
+        ..ImpactSettings::default()
+    };
+    damage.apply_impact(&mut scene, &HIT, &settings);
+    for splat in scene.splat_data[..base].iter() {
+        let distance = Vec3::from(splat.center).length();
+        if distance < settings.radius - 1.0e-3 {
+            assert!(splat.color[0] < 1.0);
+        } else {
+            assert_eq!(splat.color, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+    // Only the touched rows and the scorch mark need to be uploaded again
+    let dirty = scene.dirty_splats.clone().unwrap();
+    assert!(dirty.start > 0);
+}
+
+#[test]
+fn a_zero_radius_does_not_tint() {
+    let mut scene = wall();
+    let mut damage = SceneDamage::new(&mut scene, 16);
+    let settings = ImpactSettings {
+        radius: 0.0,
+        ..ImpactSettings::default()
+    };
+    damage.apply_impact(&mut scene, &HIT, &settings);
+    assert!(scene.splat_data.iter().all(|splat| splat.color.iter().all(|channel| channel.is_finite())));
+    assert!(scene.splat_data[..441].iter().all(|splat| splat.color == [1.0; 4]));
+}
+
+#[test]
+fn misses_leave_nothing_to_undo() {
+    let mut scene = wall();
+    let mut damage = SceneDamage::new(&mut scene, 16);
+    let settings = ImpactSettings::default();
+    damage.apply_impact(&mut scene, &HIT, &settings);
+    let far = Impact {
+        position: Vec3::new(5.0, 0.0, 0.0),
+        normal: Vec3::Z,
+    };
+    damage.apply_impact(&mut scene, &far, &settings);
+    assert_eq!(damage.impact_count(), 1);
+    // A single undo restores the wall
+    assert!(damage.undo(&mut scene));
+    assert!(scene.splat_data[..441].iter().all(|splat| splat.color == [1.0; 4]));
+}
+
+#[test]
+fn destruction_removes_splats_from_raycasts() {
+    let mut scene = wall();
+    let mut damage = SceneDamage::new(&mut scene, 0);
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::NEG_Z);
+    assert!(scene.raycast(&ray, 10.0, 0.5).is_some());
+    let settings = ImpactSettings {
+        destruction_radius: 0.5,
+        ..ImpactSettings::default()
+    };
+    let destroyed = damage.apply_impact(&mut scene, &HIT, &settings);
+    assert!(destroyed > 0);
+    assert!(scene.raycast(&ray, 10.0, 0.5).is_none());
+}
+
+#[test]
+fn effects_fade_out() {
+    let mut scene = wall();
+    let base = scene.splat_count;
+    let mut damage = SceneDamage::new(&mut scene, 16);
+    let settings = ImpactSettings::default();
+    damage.apply_impact(&mut scene, &HIT, &settings);
+    let debris_start = scene.splat_data[base + 1].center;
+    damage.update(&mut scene, 0.1, &settings);
+    assert_ne!(scene.splat_data[base + 1].center, debris_start);
+    assert!(scene.splat_data[base + 1].color[3] < 1.0);
+    for _ in 0..200 {
+        damage.update(&mut scene, 0.1, &settings);
+    }
+    assert_eq!(damage.active_effect_count(), 0);
+    assert!(scene.splat_data[base..].iter().all(|splat| splat.color[3] == 0.0));
+}
+
+#[test]
+fn undo_and_reset_restore_the_scene() {
+    let mut scene = wall();
+    let base = scene.splat_count;
+    let original = colors(&scene, base);
+    let mut damage = SceneDamage::new(&mut scene, 16);
+    let settings = ImpactSettings {
+        destruction_radius: 0.1,
+        ..ImpactSettings::default()
+    };
+    damage.apply_impact(&mut scene, &HIT, &settings);
+    let after_first = colors(&scene, base);
+    damage.apply_impact(
+        &mut scene,
+        &Impact {
+            position: Vec3::new(0.5, 0.5, 0.0),
+            normal: Vec3::Z,
+        },
+        &settings,
+    );
+    assert_eq!(damage.impact_count(), 2);
+    assert!(damage.undo(&mut scene));
+    assert_eq!(colors(&scene, base), after_first);
+    damage.apply_impact(&mut scene, &HIT, &settings);
+    damage.reset(&mut scene);
+    assert_eq!(colors(&scene, base), original);
+    assert_eq!(damage.active_effect_count(), 0);
+    assert!(!damage.undo(&mut scene));
+}
+
+#[test]
+fn added_or_removed_splats_invalidate_the_damage() {
+    let mut scene = wall();
+    let mut damage = SceneDamage::new(&mut scene, 4);
+    assert!(damage.fits(&scene));
+    damage.apply_impact(&mut scene, &HIT, &ImpactSettings::default());
+
+    // Splats appended after the pool, like the batches of a scene which is still loading
+    let mut appended = wall();
+    scene.merge([("more", &appended, glam::Affine3A::from_translation(Vec3::Z))]);
+    assert!(!damage.fits(&scene));
+    let before = scene.splat_data.clone();
+    assert_eq!(damage.apply_impact(&mut scene, &HIT, &ImpactSettings::default()), 0);
+    damage.update(&mut scene, 0.1, &ImpactSettings::default());
+    assert!(!damage.undo(&mut scene));
+    damage.reset(&mut scene);
+    for (splat, before) in scene.splat_data.iter().zip(&before) {
+        assert_eq!((splat.center, splat.color), (before.center, before.color));
+    }
+
+    // Splats removed before the pool
+    appended.splat_data.truncate(10);
+    let mut damage = SceneDamage::new(&mut appended, 4);
+    appended.retain(|index, _splat| index != 0);
+    assert!(!damage.fits(&appended));
+    assert_eq!(damage.apply_impact(&mut appended, &HIT, &ImpactSettings::default()), 0);
+}
+
+#[test]
+fn removing_an_outdated_pool_hides_its_effects() {
+    let camera = Camera::perspective(Mat4::look_at_rh(Vec3::new(0.0, 0.0, 2.0), Vec3::ZERO, Vec3::Y), 1.0, 1.0, 0.1, 10.0);
+    let center = |scene: &Scene| render_scene(scene, &camera, 32, 32, &CpuRenderSettings::default()).pixel(16, 16);
+    let settings = ImpactSettings {
+        radius: 0.0,
+        debris_count: 0,
+        ..ImpactSettings::default()
+    };
+    let mut scene = wall();
+    let mut damage = SceneDamage::new(&mut scene, 16);
+    damage.apply_impact(&mut scene, &HIT, &settings);
+    // The scorch mark covers the white wall
+    assert!(center(&scene).x < 0.5, "{}", center(&scene));
+
+    // A batch of splats behind the wall arrives, like while the scene is loading
+    let mut batch = wall();
+    batch.transform(&glam::Affine3A::from_translation(Vec3::new(0.0, 0.0, -1.0)));
+    scene.append(&mut batch);
+    assert!(!damage.fits(&scene));
+    assert_eq!(SceneDamage::remove_pools(&mut scene), 16);
+    assert_eq!(scene.splat_count, 2 * 441);
+    assert!(scene.sources.is_empty());
+    let mut damage = SceneDamage::new(&mut scene, 16);
+    assert!(damage.fits(&scene));
+    assert!(center(&scene).x > 0.9, "{}", center(&scene));
+    damage.apply_impact(&mut scene, &HIT, &settings);
+    assert!(center(&scene).x < 0.5, "{}", center(&scene));
+}
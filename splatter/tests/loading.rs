@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+use glam::Vec3 as GlamVec3;
+use splatter::coordinates::CoordinateSystem;
+use splatter::loading::{stream_splats_from_ply, SceneLoading, SplatLoadProgress, SplatLoadProgressEvent, SplatLoadingPlugin};
+use splatter::scene::{grown_splat_capacity, Scene};
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+const TEST_PLY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/test.ply");
+
+#[test]
+fn streams_ascii_in_batches() {
+    let mut batches = Vec::new();
+    let mut last_progress = SplatLoadProgress::default();
+    stream_splats_from_ply(TEST_PLY, 3, |splats, progress| {
+        batches.push(splats.splat_count);
+        last_progress = progress;
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    assert_eq!(batches, vec![3, 3, 2]);
+    assert!(last_progress.finished);
+    assert_eq!(last_progress.total_splats, 8);
+    assert_eq!(last_progress.splats_decoded, 8);
+    assert_eq!(last_progress.bytes_read, last_progress.total_bytes);
+    assert_eq!(last_progress.fraction(), 1.0);
+}
+
+#[test]
+fn streams_binary_like_the_blocking_loader() {
+    let path = spherical_harmonics_ply("binary");
+    let mut streamed = Scene::new();
+    stream_splats_from_ply(&path, 5, |mut splats, _progress| {
+        streamed.append(&mut splats);
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    let expected = Scene::from_ply(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(streamed.splat_count, expected.splat_count);
+    for (streamed, expected) in streamed.splat_data.iter().zip(expected.splat_data.iter()) {
+        assert_eq!(streamed.center, expected.center);
+        assert_eq!(streamed.color, expected.color);
+        assert!(GlamVec3::from(streamed.normal).abs_diff_eq(GlamVec3::from(expected.normal), 1.0e-6));
+    }
+    assert_eq!(streamed.spherical_harmonics_order, 2);
+    assert_eq!(streamed.spherical_harmonics, expected.spherical_harmonics);
+}
+
+/// Writes the test scene with distinct view dependent colors of order 2 to a binary PLY file.
+fn spherical_harmonics_ply(name: &str) -> std::path::PathBuf {
+    let mut scene = Scene::new();
+    scene.splat_data = Scene::read_splats_from_ply(TEST_PLY).unwrap();
+    scene.splat_count = scene.splat_data.len();
+    scene.set_spherical_harmonics_order(2);
+    for (index, coefficient) in scene.spherical_harmonics.iter_mut().enumerate() {
+        *coefficient = GlamVec3::new(index as f32, -(index as f32), 0.5);
+    }
+    let path = std::env::temp_dir().join(format!("splatter_loading_{name}_{}.ply", std::process::id()));
+    scene.save_splats_to_ply(path.to_str().unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn stops_when_asked_to() {
+    let mut batches = 0;
+    stream_splats_from_ply(TEST_PLY, 2, |_splats, _progress| {
+        batches += 1;
+        ControlFlow::Break(())
+    })
+    .unwrap();
+    assert_eq!(batches, 1);
+    assert!(stream_splats_from_ply("does/not/exist.ply", 2, |_, _| ControlFlow::Continue(())).is_err());
+}
+
+#[test]
+fn the_splat_buffer_grows_geometrically_while_loading() {
+    let (mut capacity, mut uploads, mut uploaded) = (0, 0, 0);
+    for splat_count in (1000..=100_000).step_by(1000) {
+        if splat_count > capacity {
+            capacity = grown_splat_capacity(capacity, splat_count);
+            uploads += 1;
+            uploaded += splat_count;
+        } else {
+            uploaded += 1000;
+        }
+    }
+    // Recreating the buffer for every batch would upload about 5 million splats
+    assert_eq!(uploads, 8);
+    assert!(uploaded < 300_000, "{uploaded}");
+}
+
+#[derive(Resource, Default)]
+struct ReceivedProgress(Vec<SplatLoadProgress>);
+
+fn collect_progress(mut events: EventReader<SplatLoadProgressEvent>, mut received: ResMut<ReceivedProgress>) {
+    received.0.extend(events.read().map(|event| event.progress));
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, SplatLoadingPlugin))
+        .init_resource::<ReceivedProgress>()
+        .add_systems(Update, collect_progress);
+    app
+}
+
+#[test]
+fn loads_in_the_background() {
+    let mut app = app();
+    let entity = app.world.spawn((Scene::default(), SceneLoading::with_batch_size(TEST_PLY, 3))).id();
+    let start = Instant::now();
+    while app.world.get::<SceneLoading>(entity).is_some() {
+        assert!(start.elapsed() < Duration::from_secs(10), "Loading did not finish");
+        app.update();
+    }
+    // Lets the events of the last batch be read
+    app.update();
+    assert_eq!(app.world.get::<Scene>(entity).unwrap().splat_count, 8);
+    assert!(app.world.get::<SplatLoadProgress>(entity).unwrap().finished);
+    let received = &app.world.resource::<ReceivedProgress>().0;
+    assert_eq!(received.iter().map(|progress| progress.splats_decoded).collect::<Vec<_>>(), vec![3, 6, 8]);
+}
+
+#[test]
+fn loads_view_dependent_colors_in_the_background() {
+    let path = spherical_harmonics_ply("background");
+    let mut app = app();
+    let entity = app.world.spawn((Scene::default(), SceneLoading::with_batch_size(&path, 3))).id();
+    let start = Instant::now();
+    while app.world.get::<SceneLoading>(entity).is_some() {
+        assert!(start.elapsed() < Duration::from_secs(10), "Loading did not finish");
+        app.update();
+    }
+    let expected = Scene::from_ply(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let scene = app.world.get::<Scene>(entity).unwrap();
+    assert_eq!(scene.splat_count, 8);
+    assert_eq!(scene.spherical_harmonics_order, 2);
+    assert_eq!(scene.spherical_harmonics, expected.spherical_harmonics);
+}
+
+#[test]
+fn despawning_cancels_loading() {
+    let mut app = app();
+    let entity = app.world.spawn((Scene::default(), SceneLoading::with_batch_size(TEST_PLY, 1))).id();
+    app.update();
+    app.world.despawn(entity);
+    for _ in 0..10 {
+        app.update();
+    }
+    assert!(app.world.get_entity(entity).is_none());
+}
+
+#[test]
+fn converts_from_the_coordinate_system_next_to_the_file() {
+    let directory = std::env::temp_dir().join(format!("splatter_loading_coordinates_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("z_up.ply");
+    std::fs::copy(TEST_PLY, &path).unwrap();
+    let z_up_centimeters = CoordinateSystem {
+        meters_per_unit: 0.01,
+        ..CoordinateSystem::Z_UP
+    };
+    z_up_centimeters.save(CoordinateSystem::path_for_scene(&path)).unwrap();
+
+    let mut app = app();
+    let loading = SceneLoading::start(&path);
+    assert_eq!(loading.coordinate_system, z_up_centimeters);
+    let entity = app.world.spawn((Scene::default(), loading)).id();
+    let start = Instant::now();
+    while app.world.get::<SceneLoading>(entity).is_some() {
+        assert!(start.elapsed() < Duration::from_secs(10), "Loading did not finish");
+        app.update();
+    }
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    let original = Scene::read_splats_from_ply(TEST_PLY).unwrap();
+    let scene = app.world.get::<Scene>(entity).unwrap();
+    assert_eq!(scene.coordinate_system, CoordinateSystem::BEVY);
+    for (loaded, original) in scene.splat_data.iter().zip(&original) {
+        let [x, y, z] = original.center;
+        assert!(GlamVec3::from(loaded.center).abs_diff_eq(GlamVec3::new(x, z, -y) * 0.01, 1.0e-6));
+    }
+}
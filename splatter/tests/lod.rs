@@ -0,0 +1,190 @@
+use glam::{Mat3, Vec3};
+use splatter::coordinates::{CoordinateConversion, CoordinateSystem};
+use splatter::lod::{merge_splats, LodBuildSettings, LodTree, SceneLod};
+use splatter::scene::{generate, Scene, ShaderSplat, Splat, PACKED_SPLAT_FLOATS};
+use splatter::utils::symmetric_eigen;
+
+fn floor(size: usize) -> Scene {
+    let mut splats = Vec::new();
+    for i in 0..size {
+        for j in 0..size {
+            let center = Vec3::new(i as f32 * 0.1, 0.0, j as f32 * 0.1);
+            splats.push(generate::splat(center, Vec3::Y, [0.05, 0.05], [center.x.fract(), 0.5, 0.5, 0.9]));
+        }
+    }
+    let mut scene = Scene::new();
+    scene.splat_count = splats.len();
+    scene.splat_data = splats;
+    scene
+}
+
+#[test]
+fn eigen_decomposition_reconstructs_matrix() {
+    let rotation = Mat3::from_euler(glam::EulerRot::XYZ, 0.3, -1.2, 0.7);
+    let matrix = rotation * Mat3::from_diagonal(Vec3::new(0.5, 4.0, 2.0)) * rotation.transpose();
+    let (values, vectors) = symmetric_eigen(matrix);
+    assert!(values.abs_diff_eq(Vec3::new(4.0, 2.0, 0.5), 1.0e-4), "{values}");
+    let reconstructed = vectors * Mat3::from_diagonal(values) * vectors.transpose();
+    assert!(reconstructed.abs_diff_eq(matrix, 1.0e-4));
+    assert!((vectors.determinant() - 1.0).abs() < 1.0e-4);
+}
+
+#[test]
+fn covariance_round_trip() {
+    let mut original = generate::splat(Vec3::ONE, Vec3::Y, [0.4, 0.1], [0.0, 0.5, 0.5, 1.0]);
+    original.normal = Vec3::new(0.0, 1.0, 1.0).normalize().to_array();
+    let covariance = original.covariance();
+    let fitted = Splat::from_covariance(Vec3::ONE, covariance, original.color);
+    assert!((fitted.scale[0] - 0.4).abs() < 1.0e-4 && (fitted.scale[1] - 0.1).abs() < 1.0e-4);
+    assert!(Vec3::from(fitted.normal).dot(Vec3::from(original.normal)).abs() > 0.9999);
+    assert!(fitted.covariance().abs_diff_eq(covariance, 1.0e-5));
+}
+
+#[test]
+fn merging_matches_moments() {
+    let a = generate::splat(Vec3::NEG_X, Vec3::Y, [0.1, 0.1], [0.0, 0.5, 0.5, 1.0]);
+    let b = generate::splat(Vec3::X, Vec3::Y, [0.1, 0.1], [0.0, 0.5, 0.5, 1.0]);
+    let merged = merge_splats([a, b].iter());
+    assert!(Vec3::from(merged.center).abs_diff_eq(Vec3::ZERO, 1.0e-6));
+    // Variance along x is the spread of the centers plus the variance of each splat
+    assert!((merged.scale[0] - (1.0f32 + 0.01).sqrt()).abs() < 1.0e-4);
+    assert!((merged.scale[1] - 0.1).abs() < 1.0e-4);
+    assert!(Vec3::from(merged.ellipse_basis).dot(Vec3::X).abs() > 0.9999);
+    // The opacity mass of both splats is spread over the larger ellipse
+    let mass = merged.color[3] * merged.scale[0] * merged.scale[1];
+    assert!((mass - 2.0 * 0.01).abs() < 1.0e-5);
+}
+
+#[test]
+fn builds_a_single_rooted_hierarchy() {
+    let scene = floor(32);
+    let tree = LodTree::build(&scene, &LodBuildSettings::default());
+    assert_eq!(tree.leaf_count, 1024);
+    assert_eq!(tree.roots.len(), 1);
+    let mut leaves = Vec::new();
+    let mut stack = tree.roots.clone();
+    while let Some(index) = stack.pop() {
+        let children = tree.node_children(index);
+        if children.is_empty() {
+            leaves.push(index);
+        }
+        let node = &tree.nodes[index as usize];
+        for child in children {
+            let child = &tree.nodes[*child as usize];
+            let distance = Vec3::from(node.splat.center).distance(Vec3::from(child.splat.center));
+            assert!(distance + child.radius <= node.radius + 1.0e-5);
+        }
+        stack.extend_from_slice(children);
+    }
+    leaves.sort_unstable();
+    assert_eq!(leaves, (0..1024).collect::<Vec<u32>>());
+}
+
+#[test]
+fn cut_refines_near_the_camera() {
+    let scene = floor(32);
+    let tree = LodTree::build(&scene, &LodBuildSettings::default());
+    let far = tree.select_cut(Vec3::new(1.5, 1000.0, 1.5), 1000.0, 4.0, usize::MAX);
+    let near = tree.select_cut(Vec3::new(1.5, 0.5, 1.5), 1000.0, 4.0, usize::MAX);
+    assert!(far.len() < 64, "{}", far.len());
+    assert!(near.len() > 100, "{}", near.len());
+    let budgeted = tree.select_cut(Vec3::new(1.5, 0.5, 1.5), 1000.0, 4.0, 50);
+    assert!(budgeted.len() <= 50);
+    // At close range everything is refined down to the leaves
+    let all = tree.select_cut(Vec3::new(1.5, 0.5, 1.5), 1.0e6, 4.0, usize::MAX);
+    assert_eq!(all, (0..1024).collect::<Vec<u32>>());
+}
+
+#[test]
+fn the_rendered_cut_stays_within_the_splat_budget() {
+    let mut scene = floor(32);
+    scene.set_spherical_harmonics_order(1);
+    scene.spherical_harmonics.fill(Vec3::ONE);
+    let mut scene_lod = SceneLod::new(LodTree::build(&scene, &LodBuildSettings::default()));
+    // Splats added after the hierarchy was built, like the effects of impacts
+    scene.append(&mut Scene::from_splats(vec![generate::splat(Vec3::Y, Vec3::Y, [0.1, 0.1], [1.0; 4]); 10]));
+    let near = Vec3::new(1.5, 0.5, 1.5);
+    assert!(scene_lod.update_cut(&scene, near, 1.0e6, 4.0, 100));
+    assert!((50..=90).contains(&scene_lod.cut.len()), "{}", scene_lod.cut.len());
+    assert!(!scene_lod.update_cut(&scene, near, 1.0e6, 4.0, 100));
+    // Fewer than the roots are still a hard limit
+    scene_lod.update_cut(&scene, near, 1.0e6, 4.0, 10);
+    assert!(scene_lod.cut.is_empty());
+
+    // Leaves are drawn with the view dependent colors of the scene, merged nodes without
+    scene_lod.update_cut(&scene, near, 1.0e6, 4.0, usize::MAX);
+    assert_eq!(scene_lod.cut.len(), 1024);
+    let shader_splats = scene_lod.shader_splats(&scene);
+    assert_eq!(shader_splats.len(), 1024 + 10);
+    let leaf = ShaderSplat::new(&scene.splat_data[5], scene.spherical_harmonics_of(5));
+    assert_eq!(bytemuck::bytes_of(&shader_splats[5]), bytemuck::bytes_of(&leaf));
+    scene_lod.update_cut(&scene, Vec3::new(1.5, 1000.0, 1.5), 1000.0, 4.0, usize::MAX);
+    let root = scene_lod.cut[0] as usize;
+    assert!(root >= 1024);
+    let merged = ShaderSplat::from(&scene_lod.tree.nodes[root].splat);
+    assert_eq!(bytemuck::bytes_of(&scene_lod.shader_splats(&scene)[0]), bytemuck::bytes_of(&merged));
+}
+
+#[test]
+fn saves_next_to_the_scene() {
+    let scene = floor(16);
+    let tree = LodTree::build(&scene, &LodBuildSettings::default());
+    let path = std::env::temp_dir().join(format!("splatter_lod_{}.lod", std::process::id()));
+    tree.save(&path).unwrap();
+    let loaded = LodTree::load(&path, &scene).unwrap();
+    assert!(LodTree::load(&path, &floor(4)).is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.nodes.len(), tree.nodes.len());
+    assert_eq!(loaded.children, tree.children);
+    assert_eq!(loaded.roots, tree.roots);
+    let camera = Vec3::new(0.8, 0.5, 0.8);
+    assert_eq!(loaded.select_cut(camera, 1000.0, 4.0, 100), tree.select_cut(camera, 1000.0, 4.0, 100));
+    assert_eq!(LodTree::path_for_scene("assets/models/test.ply"), std::path::PathBuf::from("assets/models/test.lod"));
+}
+
+#[test]
+fn merged_nodes_follow_the_scene_into_other_coordinates() {
+    // The hierarchy is built offline in the coordinates of the file, the scene is converted while loading
+    let scene = floor(16);
+    let tree = LodTree::build(&scene, &LodBuildSettings::default());
+    let z_up_centimeters = CoordinateSystem {
+        meters_per_unit: 0.01,
+        ..CoordinateSystem::Z_UP
+    };
+    let conversion = CoordinateConversion::between(&z_up_centimeters, &CoordinateSystem::BEVY);
+    let mut splats = scene.splat_data.clone();
+    conversion.splats(&mut splats);
+    let converted = Scene::from_splats(splats);
+    let path = std::env::temp_dir().join(format!("splatter_lod_coordinates_{}.lod", std::process::id()));
+    tree.save(&path).unwrap();
+    let mut loaded = LodTree::load(&path, &converted).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    loaded.convert_merged_nodes(&conversion);
+    // Every merged node still bounds its children, which are converted leaves or converted merged nodes
+    for (index, node) in loaded.nodes.iter().enumerate().skip(loaded.leaf_count) {
+        let expected = conversion.splat(&tree.nodes[index].splat);
+        assert!(Vec3::from(node.splat.center).distance(Vec3::from(expected.center)) < 1.0e-6);
+        let children = &loaded.children[node.first_child as usize..(node.first_child + node.child_count) as usize];
+        for &child in children {
+            let child = &loaded.nodes[child as usize];
+            let distance = Vec3::from(node.splat.center).distance(Vec3::from(child.splat.center)) + child.radius;
+            assert!(distance <= node.radius * 1.001, "{distance} > {}", node.radius);
+        }
+    }
+}
+
+#[test]
+fn rejects_child_ranges_which_overflow() {
+    let scene = floor(16);
+    let tree = LodTree::build(&scene, &LodBuildSettings::default());
+    let path = std::env::temp_dir().join(format!("splatter_lod_overflow_{}.lod", std::process::id()));
+    tree.save(&path).unwrap();
+    // The first child of the first merged node follows the header and its packed splat and radius
+    let mut bytes = std::fs::read(&path).unwrap();
+    let offset = 8 + 5 * 4 + (PACKED_SPLAT_FLOATS + 1) * 4;
+    bytes[offset..offset + 4].copy_from_slice(&(u32::MAX - 1).to_le_bytes());
+    std::fs::write(&path, bytes).unwrap();
+    let error = LodTree::load(&path, &scene).err().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,85 @@
+use glam::{Affine3A, Quat, Vec3};
+use splatter::coordinates::CoordinateSystem;
+use splatter::scene::{generate, Scene, ShaderSplat};
+
+/// A prop of a few splats in a row whose coefficients count up from `first`.
+fn prop(splat_count: usize, order: u32, first: f32) -> Scene {
+    let mut scene = Scene::new();
+    scene.splat_data = (0..splat_count)
+        .map(|index| generate::splat(Vec3::new(index as f32, 0.0, 0.0), Vec3::Z, [0.2, 0.1], [0.2, 0.4, 0.6, 1.0]))
+        .collect();
+    scene.splat_count = splat_count;
+    scene.spherical_harmonics_order = order;
+    let count = splatter::scene::spherical_harmonics_count(order) * splat_count;
+    scene.spherical_harmonics = (0..count).map(|index| Vec3::splat(first + index as f32)).collect();
+    scene
+}
+
+#[test]
+fn merges_scenes_with_their_transforms() {
+    let mut level = prop(2, 1, 0.0);
+    let chair = prop(3, 3, 100.0);
+    let mut lamp = prop(1, 0, 0.0);
+    lamp.coordinate_system = CoordinateSystem::Z_UP;
+    lamp.splat_data[0].center = [0.0, 0.0, 2.0];
+    let placement = Affine3A::from_rotation_translation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2), Vec3::new(0.0, 0.0, 5.0));
+    level.merge([("chair", &chair, placement), ("lamp", &lamp, Affine3A::from_translation(Vec3::X))]);
+
+    assert_eq!(level.splat_count, 6);
+    assert_eq!(level.splat_data.len(), 6);
+    assert_eq!(level.spherical_harmonics_order, 3);
+    assert_eq!(level.spherical_harmonics.len(), 6 * 15);
+    assert_eq!(level.dirty_splats, Some(0..6));
+    // Provenance, with the splats from before as a source without name
+    let names: Vec<_> = level.sources.iter().map(|source| (source.name.as_str(), source.splats.clone())).collect();
+    assert_eq!(names, [("", 0..2), ("chair", 2..5), ("lamp", 5..6)]);
+    assert_eq!(level.source_of(3).unwrap().name, "chair");
+    assert_eq!(level.source_of(5).unwrap().name, "lamp");
+    assert_eq!(level.source_of(6), None);
+
+    // The second chair splat at x = 1 turns onto -z before it moves by 5 along z
+    assert!(Vec3::from(level.splat_data[3].center).abs_diff_eq(Vec3::new(0.0, 0.0, 4.0), 1.0e-5));
+    // The lamp is converted from Z up before it is moved
+    assert!(Vec3::from(level.splat_data[5].center).abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), 1.0e-5));
+
+    // The first order coefficients of the level are padded with zeros
+    assert_eq!(
+        level.spherical_harmonics_of(1)[..3],
+        [Vec3::splat(3.0), Vec3::splat(4.0), Vec3::splat(5.0)]
+    );
+    assert!(level.spherical_harmonics_of(1)[3..].iter().all(|coefficient| *coefficient == Vec3::ZERO));
+    assert!(level.spherical_harmonics_of(5).iter().all(|coefficient| *coefficient == Vec3::ZERO));
+}
+
+#[test]
+fn truncates_view_dependent_colors() {
+    let mut scene = Scene::new();
+    scene.merge([("a", &prop(2, 3, 0.0), Affine3A::IDENTITY), ("b", &prop(1, 2, 50.0), Affine3A::IDENTITY)]);
+    assert_eq!(scene.spherical_harmonics_order, 3);
+    assert_eq!(scene.sources.len(), 2);
+    scene.set_spherical_harmonics_order(1);
+    assert_eq!(scene.spherical_harmonics.len(), 3 * 3);
+    assert_eq!(scene.spherical_harmonics_of(1), [Vec3::splat(15.0), Vec3::splat(16.0), Vec3::splat(17.0)]);
+    assert_eq!(scene.spherical_harmonics_of(2), [Vec3::splat(50.0), Vec3::splat(51.0), Vec3::splat(52.0)]);
+}
+
+#[test]
+fn packs_the_merged_splats_for_the_gpu() {
+    let mut scene = prop(2, 1, 1.0);
+    scene.merge([("prop", &prop(2, 2, 10.0), Affine3A::IDENTITY)]);
+    let shader_splats = scene.shader_splats(0..scene.splat_count);
+    let bytes: &[u8] = bytemuck::cast_slice(&shader_splats);
+    assert_eq!(bytes.len(), 4 * std::mem::size_of::<ShaderSplat>());
+
+    // The shader adds band 0 to a gray of 0.5
+    let shader_splat = &shader_splats[3];
+    let dc = Vec3::from_slice(&shader_splat.color_sh[0..3]);
+    assert!((dc * 0.282_094_8 + 0.5).abs_diff_eq(Vec3::new(0.2, 0.4, 0.6), 1.0e-5));
+    assert_eq!(shader_splat.color_sh[3..6], [18.0; 3]);
+    assert_eq!(shader_splat.color_sh[24..27], [25.0; 3]);
+    assert!(shader_splat.color_sh[27..].iter().all(|value| *value == 0.0));
+    assert_eq!(shader_splat.center, scene.splat_data[3].center);
+    // The padded bands of the first scene
+    assert_eq!(shader_splats[0].color_sh[3..12], [1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 3.0, 3.0, 3.0]);
+    assert!(shader_splats[0].color_sh[12..].iter().all(|value| *value == 0.0));
+}
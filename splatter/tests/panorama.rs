@@ -0,0 +1,71 @@
+use glam::{Vec2, Vec3};
+use splatter::cpu_renderer::CpuRenderSettings;
+use splatter::panorama::{equirectangular_direction, equirectangular_position, render_equirectangular};
+use splatter::scene::{generate, Scene, Splat};
+
+/// A round splat facing `position`.
+fn splat_facing(position: Vec3, center: Vec3, radius: f32, color: [f32; 4]) -> Splat {
+    generate::splat(center, (position - center).normalize(), [radius, radius], color)
+}
+
+#[test]
+fn maps_directions_to_longitude_and_latitude() {
+    let (width, height) = (400, 200);
+    for (position, direction) in [
+        (Vec2::new(200.0, 100.0), Vec3::NEG_Z),
+        (Vec2::new(300.0, 100.0), Vec3::X),
+        (Vec2::new(100.0, 100.0), Vec3::NEG_X),
+        (Vec2::new(0.0, 100.0), Vec3::Z),
+        (Vec2::new(200.0, 0.0), Vec3::Y),
+        (Vec2::new(200.0, 200.0), Vec3::NEG_Y),
+    ] {
+        assert!(equirectangular_direction(position, width, height).abs_diff_eq(direction, 1.0e-6), "{position}");
+    }
+    for position in [Vec2::new(13.5, 40.5), Vec2::new(250.0, 150.0), Vec2::new(399.5, 100.5)] {
+        let direction = equirectangular_direction(position, width, height);
+        assert!(equirectangular_position(direction * 3.0, width, height).abs_diff_eq(position, 1.0e-3));
+    }
+}
+
+#[test]
+fn renders_splats_in_every_direction() {
+    let position = Vec3::new(1.0, 2.0, 3.0);
+    let directions = [Vec3::NEG_Z, Vec3::X, Vec3::Z, Vec3::Y, Vec3::NEG_Y, Vec3::new(1.0, 0.0, -1.0).normalize()];
+    let scene = Scene::from_splats(
+        directions
+            .iter()
+            .enumerate()
+            .map(|(index, direction)| {
+                let color = [index as f32 / 5.0, 1.0 - index as f32 / 5.0, 1.0, 0.95];
+                splat_facing(position, position + *direction * 4.0, 0.3, color)
+            })
+            .collect(),
+    );
+    let image = render_equirectangular(&scene, position, 256, &CpuRenderSettings::default());
+    assert_eq!((image.width, image.height), (256, 128));
+    for (splat, direction) in scene.splat_data.iter().zip(directions) {
+        // Away from the poles the pixel centers lie half a pixel next to the direction
+        let pixel = equirectangular_position(direction, 256, 128).min(Vec2::new(255.5, 127.5));
+        let color = image.sample(pixel.max(Vec2::splat(0.5)));
+        let expected = glam::Vec4::from(splat.color).truncate() * 0.95;
+        assert!(color.truncate().abs_diff_eq(expected, 0.1), "{direction}: {color} != {expected}");
+    }
+    // In between the splats the background shows
+    let between = image.pixel(160, 40);
+    assert!(between.truncate().length() < 1.0e-3, "{between}");
+}
+
+#[test]
+fn seams_between_faces_are_continuous() {
+    let position = Vec3::ZERO;
+    // Straddles the faces looking along -Z and +X
+    let direction = Vec3::new(1.0, 0.0, -1.0).normalize();
+    let splat = splat_facing(position, direction * 5.0, 0.4, [1.0, 1.0, 1.0, 0.9]);
+    let image = render_equirectangular(&Scene::from_splats(vec![splat]), position, 512, &CpuRenderSettings::default());
+    let center = equirectangular_position(direction, 512, 256);
+    let left = image.sample(center - Vec2::new(3.0, 0.0)).x;
+    let right = image.sample(center + Vec2::new(3.0, 0.0)).x;
+    let peak = image.sample(center).x;
+    assert!(peak > 0.85, "{peak}");
+    assert!((left - right).abs() < 0.02, "{left} != {right}");
+}
@@ -0,0 +1,195 @@
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3};
+use splatter::scene::{Camera, ProjectionModel};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_3, PI};
+
+fn assert_close(actual: f32, expected: f32, relative: f32) {
+    assert!((actual - expected).abs() <= relative * expected.abs(), "{actual} != {expected}");
+}
+
+/// Covariance of an ellipsoid with the given standard deviations along its rotated axes.
+fn ellipsoid(rotation: Quat, scale: Vec3) -> Mat3 {
+    let axes = Mat3::from_quat(rotation) * Mat3::from_diagonal(scale);
+    axes * axes.transpose()
+}
+
+fn sphere(radius: f32) -> Mat3 {
+    Mat3::from_diagonal(Vec3::splat(radius * radius))
+}
+
+#[test]
+fn perspective_stretches_off_axis_spheres() {
+    // Unit focal scale, so the view plane is normalized device coordinates
+    let camera = Camera::perspective(Mat4::IDENTITY, FRAC_PI_2, 1.0, 0.1, 100.0);
+    let radius = 0.01;
+
+    let centered = camera.project_gaussian(Vec3::new(0.0, 0.0, -2.0), sphere(radius)).unwrap();
+    let (axes, _) = centered.axes();
+    assert_close(axes.x, radius / 2.0, 1.0e-4);
+    assert_close(axes.y, radius / 2.0, 1.0e-4);
+    assert_close(centered.depth, 2.0, 1.0e-6);
+
+    // At an angle the sphere is stretched radially by 1 / cos, while its tangential extent is that at the center
+    let angle = FRAC_PI_3 * 0.75;
+    let depth = 2.0;
+    let off_axis = camera
+        .project_gaussian(Vec3::new(depth * angle.tan(), 0.0, -depth), sphere(radius))
+        .unwrap();
+    assert_close(off_axis.center.x, angle.tan(), 1.0e-5);
+    let (axes, direction) = off_axis.axes();
+    assert_close(axes.x, radius / (depth * angle.cos()), 1.0e-4);
+    assert_close(axes.y, radius / depth, 1.0e-4);
+    assert!(direction.abs().abs_diff_eq(Vec2::X, 1.0e-5));
+
+    assert!(camera.project_gaussian(Vec3::new(0.0, 0.0, 1.0), sphere(radius)).is_none());
+}
+
+#[test]
+fn perspective_uses_the_aspect_ratio() {
+    let camera = Camera::perspective(Mat4::IDENTITY, FRAC_PI_2, 2.0, 0.1, 100.0);
+    let projected = camera
+        .project_gaussian(Vec3::new(0.0, 0.0, -4.0), ellipsoid(Quat::IDENTITY, Vec3::new(0.2, 0.1, 0.3)))
+        .unwrap();
+    let (axes, _) = projected.axes();
+    // Both axes are 0.1 / 4 on screen, x is squeezed by the aspect ratio
+    assert_close(axes.x, 0.1 / 4.0, 1.0e-4);
+    assert_close(axes.y, 0.1 / 4.0, 1.0e-4);
+    assert!(projected.covariance.x_axis.y.abs() < 1.0e-9);
+}
+
+#[test]
+fn orthographic_is_the_affine_projection_of_the_covariance() {
+    let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO, Vec3::Y);
+    let camera = Camera::orthographic(view, Vec2::new(-4.0, -2.0), Vec2::new(4.0, 2.0), 0.1, 100.0);
+    assert_eq!(camera.model, ProjectionModel::Orthographic);
+    let scale = Vec3::new(0.3, 0.2, 0.1);
+
+    // Turned in the image plane, the axes keep their length and turn along
+    let angle = 0.5;
+    for center in [Vec3::ZERO, Vec3::new(3.0, -1.5, 5.0), Vec3::new(-2.0, 1.0, -40.0)] {
+        let projected = camera.project_gaussian(center, ellipsoid(Quat::from_rotation_z(angle), scale)).unwrap();
+        // Normalized device coordinates are a quarter of the units in x and half of them in y
+        let screen = Mat3::from_diagonal(Vec3::new(4.0, 2.0, 1.0));
+        let world = (screen * projected.center.extend(0.0)).truncate();
+        assert!(world.abs_diff_eq(center.truncate(), 1.0e-5), "{world} != {center}");
+        let covariance = screen
+            * Mat3::from_cols(
+                projected.covariance.x_axis.extend(0.0),
+                projected.covariance.y_axis.extend(0.0),
+                Vec3::ZERO,
+            )
+            * screen;
+        let expected = ellipsoid(Quat::from_rotation_z(angle), scale);
+        assert!(covariance.x_axis.truncate().abs_diff_eq(expected.x_axis.truncate(), 1.0e-6));
+        assert!(covariance.y_axis.truncate().abs_diff_eq(expected.y_axis.truncate(), 1.0e-6));
+    }
+
+    // Tilted out of the image plane, the depth axis shows up in the image
+    let tilt = 0.7;
+    let projected = camera
+        .project_gaussian(Vec3::ZERO, ellipsoid(Quat::from_rotation_x(tilt), scale))
+        .unwrap();
+    assert_close(projected.covariance.x_axis.x.sqrt(), 0.3 / 4.0, 1.0e-5);
+    let tilted = (scale.y * scale.y * tilt.cos().powi(2) + scale.z * scale.z * tilt.sin().powi(2)).sqrt();
+    assert_close(projected.covariance.y_axis.y.sqrt(), tilted / 2.0, 1.0e-5);
+}
+
+#[test]
+fn fisheye_maps_angles_linearly() {
+    let camera = Camera::fisheye(Mat4::IDENTITY, PI, 1.0, 0.1, 100.0);
+    assert_eq!(camera.model, ProjectionModel::Fisheye);
+    // Half a turn across the image, so a quarter turn off axis reaches the edge
+    assert!(camera.focal_scale().abs_diff_eq(Vec2::splat(2.0 / PI), 1.0e-6));
+    let side = camera.project_view_position(Vec3::new(0.0, 3.0, 0.0)).unwrap();
+    assert!(side.abs_diff_eq(Vec2::Y, 1.0e-6));
+    let behind = camera.project_view_position(Vec3::new(-1.0, 0.0, 1.0)).unwrap();
+    assert!(behind.abs_diff_eq(Vec2::new(-1.5, 0.0), 1.0e-6));
+    assert!(camera.project_view_position(Vec3::new(0.0, 0.0, 1.0)).is_none());
+
+    for ndc in [Vec2::ZERO, Vec2::new(0.3, -0.2), Vec2::new(-1.2, 0.9)] {
+        let direction = camera.view_direction(ndc);
+        assert_close(direction.length(), 1.0, 1.0e-5);
+        assert!(camera.project_view_position(direction * 7.0).unwrap().abs_diff_eq(ndc, 1.0e-5));
+    }
+}
+
+#[test]
+fn fisheye_scales_splats_by_angle() {
+    let camera = Camera::fisheye(Mat4::IDENTITY, PI, 1.0, 0.1, 100.0);
+    let focal = 2.0 / PI;
+    let radius = 1.0e-3;
+    let distance = 3.0;
+    // The radial extent is the angle the sphere covers, the tangential one is stretched by angle / sin(angle)
+    for angle in [0.0, 0.4, FRAC_PI_3, 2.0, 2.8] {
+        let center = distance * Vec3::new(angle.sin(), 0.0, -angle.cos());
+        let projected = camera.project_gaussian(center, sphere(radius)).unwrap();
+        assert_close(projected.center.x, focal * angle, 1.0e-5);
+        assert_close(projected.depth, distance, 1.0e-5);
+        let tangential = if angle == 0.0 { 1.0 } else { angle / angle.sin() };
+        let (axes, direction) = projected.axes();
+        assert_close(axes.x.max(axes.y), focal * radius / distance * tangential.max(1.0), 1.0e-3);
+        assert_close(axes.x.min(axes.y), focal * radius / distance, 1.0e-3);
+        if angle > 0.0 {
+            // The larger axis is the tangential one
+            assert!(direction.abs().abs_diff_eq(Vec2::Y, 1.0e-3), "{direction}");
+        }
+    }
+}
+
+#[test]
+fn jacobians_match_finite_differences() {
+    let view = Mat4::look_at_rh(Vec3::new(1.0, 2.0, 3.0), Vec3::new(0.0, 0.5, -1.0), Vec3::Y);
+    let cameras = [
+        Camera::perspective(view, 1.1, 1.5, 0.1, 100.0),
+        Camera::orthographic(view, Vec2::new(-3.0, -1.0), Vec2::new(2.0, 2.5), 0.1, 100.0),
+        Camera::fisheye(view, 3.5, 1.5, 0.1, 100.0),
+    ];
+    let points = [
+        Vec3::new(0.0, 0.0, -2.0),
+        Vec3::new(0.7, -0.4, -1.5),
+        Vec3::new(-2.0, 1.0, -0.5),
+        Vec3::new(1.0e-4, 2.0e-4, -3.0),
+    ];
+    let step = 1.0e-3;
+    for camera in &cameras {
+        for point in points {
+            let [row_x, row_y] = camera.projection_jacobian(point);
+            for (axis, unit) in [Vec3::X, Vec3::Y, Vec3::Z].into_iter().enumerate() {
+                let forward = camera.project_view_position(point + unit * step).unwrap();
+                let backward = camera.project_view_position(point - unit * step).unwrap();
+                let derivative = (forward - backward) / (2.0 * step);
+                let analytic = Vec2::new(row_x[axis], row_y[axis]);
+                assert!(
+                    derivative.abs_diff_eq(analytic, 2.0e-3 * (1.0 + analytic.abs().max_element())),
+                    "{:?} at {point} along {axis}: {derivative} != {analytic}",
+                    camera.model
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn view_plane_maps_like_the_projection() {
+    let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO, Vec3::Y);
+    let off_centre = Camera::orthographic(view, Vec2::new(1.0, -3.0), Vec2::new(5.0, 1.0), 0.1, 100.0);
+    let (view_size, view_offset) = off_centre.view_plane();
+    assert!(view_size.abs_diff_eq(Vec2::new(2.0, 2.0), 1.0e-6));
+    assert!(view_offset.abs_diff_eq(Vec2::new(3.0, -1.0), 1.0e-6));
+
+    let fisheye = Camera::fisheye(view, PI, 1.0, 0.1, 100.0);
+    let (view_size, view_offset) = fisheye.view_plane();
+    assert!(view_size.abs_diff_eq(Vec2::splat(FRAC_PI_2), 1.0e-5));
+    assert_eq!(view_offset, Vec2::ZERO);
+
+    // Positions on the view plane of the shader are the view space positions for orthographic cameras
+    for view_position in [Vec3::new(3.0, -1.0, -5.0), Vec3::new(4.5, 0.5, -20.0)] {
+        let (view_size, view_offset) = off_centre.view_plane();
+        let expected = off_centre.project_view_position(view_position).unwrap();
+        assert!(((view_position.truncate() - view_offset) / view_size).abs_diff_eq(expected, 1.0e-5));
+    }
+    let perspective = Camera::perspective(view, FRAC_PI_3, 2.0, 0.1, 100.0);
+    let (view_size, view_offset) = perspective.view_plane();
+    let view_position = Vec3::new(1.0, 0.5, -4.0);
+    let expected = perspective.project_view_position(view_position).unwrap();
+    assert!(((view_position.truncate() / 4.0 - view_offset) / view_size).abs_diff_eq(expected, 1.0e-5));
+}
@@ -0,0 +1,111 @@
+use bevy::prelude::{App, Camera3dBundle, MinimalPlugins, Transform, Window};
+use bevy::transform::TransformPlugin;
+use bevy::window::PrimaryWindow;
+use glam::{Vec2, Vec3};
+use splatter::loading::{SceneLoading, SplatLoadingPlugin};
+use splatter::raycast::{Ray, SplatPick, SplatPickingPlugin};
+use splatter::scene::{generate, Scene};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[test]
+fn hits_opaque_splat_head_on() {
+    let scene = Scene::from_splats(vec![generate::splat(
+        Vec3::new(0.0, 0.0, -5.0),
+        Vec3::Z,
+        [0.5, 0.5],
+        [1.0, 1.0, 1.0, 1.0],
+    )]);
+    let hit = scene.raycast(&Ray::new(Vec3::ZERO, Vec3::NEG_Z), 100.0, 0.5).unwrap();
+    assert_eq!(hit.splat_index, 0);
+    assert!((hit.distance - 5.0).abs() < 1.0e-5);
+    assert!(hit.position.abs_diff_eq(Vec3::new(0.0, 0.0, -5.0), 1.0e-5));
+    assert!(hit.normal.abs_diff_eq(Vec3::Z, 1.0e-5));
+}
+
+#[test]
+fn normal_faces_the_ray_origin() {
+    let scene = Scene::from_splats(vec![generate::splat(
+        Vec3::new(0.0, 0.0, -5.0),
+        Vec3::NEG_Z,
+        [0.5, 0.5],
+        [1.0, 1.0, 1.0, 1.0],
+    )]);
+    let hit = scene.raycast(&Ray::new(Vec3::ZERO, Vec3::NEG_Z), 100.0, 0.5).unwrap();
+    assert!(hit.normal.abs_diff_eq(Vec3::Z, 1.0e-5));
+}
+
+#[test]
+fn misses_outside_of_the_footprint() {
+    let scene = Scene::from_splats(vec![generate::splat(
+        Vec3::new(0.0, 0.0, -5.0),
+        Vec3::Z,
+        [0.5, 0.5],
+        [1.0, 1.0, 1.0, 1.0],
+    )]);
+    assert!(scene.raycast(&Ray::new(Vec3::new(2.0, 0.0, 0.0), Vec3::NEG_Z), 100.0, 0.5).is_none());
+    assert!(scene.raycast(&Ray::new(Vec3::ZERO, Vec3::Z), 100.0, 0.5).is_none());
+}
+
+#[test]
+fn respects_max_distance() {
+    let scene = Scene::from_splats(vec![generate::splat(
+        Vec3::new(0.0, 0.0, -5.0),
+        Vec3::Z,
+        [0.5, 0.5],
+        [1.0, 1.0, 1.0, 1.0],
+    )]);
+    assert!(scene.raycast(&Ray::new(Vec3::ZERO, Vec3::NEG_Z), 4.0, 0.5).is_none());
+}
+
+#[test]
+fn accumulates_opacity_front_to_back() {
+    // Three layers of 40% opacity: 0.4, 0.64, 0.784 accumulated
+    let scene = Scene::from_splats(vec![
+        generate::splat(Vec3::new(0.0, 0.0, -3.0), Vec3::Z, [0.5, 0.5], [1.0, 1.0, 1.0, 0.4]),
+        generate::splat(Vec3::NEG_Z, Vec3::Z, [0.5, 0.5], [1.0, 1.0, 1.0, 0.4]),
+        generate::splat(Vec3::new(0.0, 0.0, -2.0), Vec3::Z, [0.5, 0.5], [1.0, 1.0, 1.0, 0.4]),
+    ]);
+    let ray = Ray::new(Vec3::ZERO, Vec3::NEG_Z);
+    let hit = scene.raycast(&ray, 100.0, 0.5).unwrap();
+    assert_eq!(hit.splat_index, 2);
+    assert!((hit.opacity - 0.64).abs() < 1.0e-5);
+    let hit = scene.raycast(&ray, 100.0, 0.7).unwrap();
+    assert_eq!(hit.splat_index, 0);
+    assert!(scene.raycast(&ray, 100.0, 0.9).is_none());
+}
+
+#[test]
+fn camera_ray_through_center_looks_at_target() {
+    let scene = Scene::new();
+    let ray = scene.camera.ray_through(Vec2::ZERO);
+    // The default camera sits at (0, 0, 5) looking at the origin
+    assert!(ray.direction.abs_diff_eq(Vec3::NEG_Z, 1.0e-5));
+    assert!((ray.origin.z - (5.0 - scene.camera.z_near)).abs() < 1.0e-3);
+}
+
+#[test]
+fn picks_the_scene_of_the_loading_entity() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TransformPlugin, SplatLoadingPlugin, SplatPickingPlugin));
+    app.world.spawn((Window::default(), PrimaryWindow));
+    // The crosshair looks at the corners of the cube at x = 0.2 and y = 0.3
+    app.world.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(0.2, 0.3, 5.0),
+        ..Default::default()
+    });
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/test.ply");
+    let entity = app.world.spawn((Scene::default(), SceneLoading::start(path))).id();
+    let start = Instant::now();
+    while app.world.get::<SceneLoading>(entity).is_some() {
+        assert!(start.elapsed() < Duration::from_secs(10), "Loading did not finish");
+        app.update();
+    }
+    app.update();
+
+    let pick = app.world.resource::<SplatPick>();
+    assert_eq!(pick.scene, Some(entity));
+    let hit = pick.hit.unwrap();
+    assert!(hit.position.abs_diff_eq(Vec3::new(0.2, 0.3, 1.0), 1.0e-3), "{}", hit.position);
+    assert!(Vec3::from(app.world.get::<Scene>(entity).unwrap().splat_data[hit.splat_index].center).z == 1.0);
+}
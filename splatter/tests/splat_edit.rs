@@ -0,0 +1,150 @@
+use glam::{Affine3A, Quat, Vec3};
+use splatter::scene::{generate, Scene, SceneSource, ShaderSplat};
+use splatter::splat_edit::{SelectionMode, Selector, SplatEdit};
+
+/// Splats along the x axis at x = 0, 1, .., 9 with increasing opacity and scale.
+fn line() -> Scene {
+    let mut scene = Scene::new();
+    for i in 0..10 {
+        let center = Vec3::new(i as f32, 0.0, 0.0);
+        scene
+            .splat_data
+            .push(generate::splat(center, Vec3::Z, [0.1 * i as f32, 0.05], [1.0, 1.0, 1.0, i as f32 / 10.0]));
+    }
+    scene.splat_count = scene.splat_data.len();
+    scene
+}
+
+fn xs(scene: &Scene) -> Vec<f32> {
+    scene.splat_data.iter().map(|splat| splat.center[0]).collect()
+}
+
+#[test]
+fn selectors() {
+    let scene = line();
+    let mut edit = SplatEdit::new(&scene);
+    let mut check = |selector: Selector, expected: Vec<usize>| {
+        edit.select(&scene, &selector, SelectionMode::Replace);
+        assert_eq!(edit.selected_indices(), expected, "{selector:?}");
+    };
+    check(
+        Selector::Aabb {
+            min: Vec3::new(1.5, -1.0, -1.0),
+            max: Vec3::new(4.0, 1.0, 1.0),
+        },
+        vec![2, 3, 4],
+    );
+    check(
+        Selector::Obb {
+            center: Vec3::new(5.0, 0.0, 0.0),
+            half_extents: Vec3::new(0.5, 1.5, 0.5),
+            rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        },
+        vec![4, 5, 6],
+    );
+    check(
+        Selector::Sphere {
+            center: Vec3::new(8.0, 0.5, 0.0),
+            radius: 1.2,
+        },
+        vec![7, 8, 9],
+    );
+    check(Selector::OpacityBelow(0.25), vec![0, 1, 2]);
+    check(Selector::ScaleAbove(0.75), vec![8, 9]);
+}
+
+#[test]
+fn combines_and_inverts_selections() {
+    let scene = line();
+    let mut edit = SplatEdit::new(&scene);
+    edit.select(&scene, &Selector::OpacityBelow(0.45), SelectionMode::Replace);
+    edit.select(&scene, &Selector::ScaleAbove(0.75), SelectionMode::Add);
+    assert_eq!(edit.selected_indices(), vec![0, 1, 2, 3, 4, 8, 9]);
+    edit.select(&scene, &Selector::ScaleAbove(0.25), SelectionMode::Intersect);
+    assert_eq!(edit.selected_indices(), vec![3, 4, 8, 9]);
+    edit.select(&scene, &Selector::OpacityBelow(0.35), SelectionMode::Subtract);
+    assert_eq!(edit.selected_indices(), vec![4, 8, 9]);
+    edit.invert_selection();
+    assert_eq!(edit.selected_indices(), vec![0, 1, 2, 3, 5, 6, 7]);
+}
+
+#[test]
+fn delete_and_keep_with_undo() {
+    let mut scene = line();
+    let mut edit = SplatEdit::new(&scene);
+    edit.select(&scene, &Selector::OpacityBelow(0.25), SelectionMode::Replace);
+    assert_eq!(edit.delete_selected(&mut scene), 3);
+    assert_eq!(scene.splat_count, 7);
+    assert_eq!(xs(&scene), vec![3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+    assert_eq!(scene.dirty_splats, Some(0..7));
+
+    edit.select(
+        &scene,
+        &Selector::Sphere {
+            center: Vec3::new(6.0, 0.0, 0.0),
+            radius: 1.5,
+        },
+        SelectionMode::Replace,
+    );
+    assert_eq!(edit.keep_selected(&mut scene), 4);
+    assert_eq!(xs(&scene), vec![5.0, 6.0, 7.0]);
+
+    assert!(edit.undo(&mut scene));
+    assert_eq!(xs(&scene), vec![3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+    assert_eq!(edit.selected_indices(), vec![0, 1, 5, 6]);
+    assert!(edit.undo(&mut scene));
+    assert_eq!(xs(&scene), (0..10).map(|i| i as f32).collect::<Vec<_>>());
+    assert_eq!(scene.splat_count, 10);
+    assert_eq!(edit.selected_indices(), vec![0, 1, 2]);
+    assert!(!edit.undo(&mut scene));
+}
+
+fn bytes(splats: &[ShaderSplat]) -> &[u8] {
+    bytemuck::cast_slice(splats)
+}
+
+#[test]
+fn delete_and_undo_keep_view_dependent_colors_aligned() {
+    let mut scene = line();
+    scene.set_spherical_harmonics_order(1);
+    for (index, coefficient) in scene.spherical_harmonics.iter_mut().enumerate() {
+        *coefficient = Vec3::splat(index as f32);
+    }
+    scene.sources = ["left", "right"]
+        .into_iter()
+        .zip([0..5, 5..10])
+        .map(|(name, splats)| SceneSource {
+            name: name.to_string(),
+            transform: Affine3A::IDENTITY,
+            splats,
+        })
+        .collect();
+    let original = scene.shader_splats(0..10);
+    let original_sources = scene.sources.clone();
+
+    let mut edit = SplatEdit::new(&scene);
+    edit.select(&scene, &Selector::OpacityBelow(0.25), SelectionMode::Replace);
+    assert_eq!(edit.delete_selected(&mut scene), 3);
+    assert_eq!(scene.spherical_harmonics.len(), 7 * 3);
+    assert_eq!(bytes(&scene.shader_splats(0..7)), bytes(&original[3..]));
+    assert_eq!(scene.sources.iter().map(|source| source.splats.clone()).collect::<Vec<_>>(), [0..2, 2..7]);
+
+    assert!(edit.undo(&mut scene));
+    assert_eq!(bytes(&scene.shader_splats(0..10)), bytes(&original));
+    assert_eq!(scene.sources, original_sources);
+}
+
+#[test]
+fn recolor_with_undo() {
+    let mut scene = line();
+    let mut edit = SplatEdit::new(&scene);
+    edit.select(&scene, &Selector::ScaleAbove(0.65), SelectionMode::Replace);
+    scene.dirty_splats = None;
+    edit.recolor_selected(&mut scene, [1.0, 0.0, 0.0]);
+    assert_eq!(scene.dirty_splats, Some(7..10));
+    assert_eq!(scene.splat_data[8].color, [1.0, 0.0, 0.0, 0.8]);
+    assert_eq!(scene.splat_data[6].color, [1.0, 1.0, 1.0, 0.6]);
+    assert_eq!(edit.history_len(), 1);
+    assert!(edit.undo(&mut scene));
+    assert_eq!(scene.splat_data[8].color, [1.0, 1.0, 1.0, 0.8]);
+}
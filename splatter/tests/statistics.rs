@@ -0,0 +1,119 @@
+use glam::Vec3;
+use splatter::scene::{generate, Scene};
+use splatter::statistics::{diff, Bounds, DiffSettings, Histogram, SceneStatistics, StatisticsSettings};
+
+/// A grid of splats one unit apart.
+fn grid(size: usize) -> Scene {
+    let mut scene = Scene::new();
+    scene.splat_data = (0..size * size)
+        .map(|index| {
+            generate::splat(
+                Vec3::new((index % size) as f32, (index / size) as f32, 0.0),
+                Vec3::Z,
+                [0.1, 0.1 * 0.5],
+                [0.5, 0.5, 0.5, 0.5],
+            )
+        })
+        .collect();
+    scene.splat_count = scene.splat_data.len();
+    scene
+}
+
+#[test]
+fn bins_values() {
+    let histogram = Histogram::new([0.0, 0.1, 0.5, 0.95, 1.0, f32::NAN].into_iter(), 4, false);
+    assert_eq!((histogram.min, histogram.max), (0.0, 1.0));
+    assert_eq!(histogram.counts, [2, 0, 1, 2]);
+    assert_eq!(histogram.total(), 5);
+
+    // Scales spread over orders of magnitude, which the logarithmic bins show evenly
+    let histogram = Histogram::new([0.001, 0.01, 0.1, 1.0, -1.0].into_iter(), 3, true);
+    assert_eq!(histogram.counts, [1, 1, 2]);
+    let bins: Vec<_> = histogram.bins().collect();
+    assert!((bins[1].0 - 0.01).abs() < 1.0e-6 && (bins[2].1 - 1.0).abs() < 1.0e-5, "{bins:?}");
+
+    assert_eq!(Histogram::new(std::iter::empty(), 3, false).total(), 0);
+}
+
+#[test]
+fn reports_scene_statistics() {
+    let mut scene = grid(4);
+    scene.splat_data[0].color = [0.5 + 0.282_094_8, 0.5, 0.5, 1.0];
+    scene.spherical_harmonics_order = 2;
+    scene.spherical_harmonics = vec![Vec3::ZERO; 16 * 8];
+    // One coefficient of band 1 and one of band 2
+    scene.spherical_harmonics[8 * 2] = Vec3::new(4.0, 0.0, 0.0);
+    scene.spherical_harmonics[8 * 3 + 7] = Vec3::new(0.0, 0.0, 2.0);
+    let statistics = SceneStatistics::of(&scene, &StatisticsSettings::default());
+
+    assert_eq!(statistics.splat_count, 16);
+    assert_eq!(
+        statistics.bounds,
+        Some(Bounds {
+            min: Vec3::ZERO,
+            max: Vec3::new(3.0, 3.0, 0.0)
+        })
+    );
+    assert_eq!(statistics.opacity.total(), 16);
+    assert_eq!(*statistics.opacity.counts.last().unwrap(), 1);
+    assert_eq!(statistics.scale.total(), 16);
+    let expected = [1.0 / 16.0, 1.0, 0.25];
+    for (energy, expected) in statistics.band_energy.iter().zip(expected) {
+        assert!((energy - expected).abs() < 1.0e-5, "{:?}", statistics.band_energy);
+    }
+    let ply = statistics.memory.iter().find(|footprint| footprint.encoding == "ply").unwrap();
+    assert_eq!(ply.bytes_per_splat, (17 + 24) * 4);
+    assert_eq!(ply.bytes, 16 * ply.bytes_per_splat);
+    assert!(statistics.to_string().contains("band 2"));
+
+    let json = serde_json::to_string(&statistics).unwrap();
+    assert_eq!(serde_json::from_str::<SceneStatistics>(&json).unwrap(), statistics);
+
+    let empty = SceneStatistics::of(&Scene::new(), &StatisticsSettings::default());
+    assert_eq!((empty.splat_count, empty.bounds), (0, None));
+}
+
+#[test]
+fn diffs_retrained_scenes() {
+    let old = grid(10);
+    let mut new = grid(10);
+    // Removes two splats, moves three a little, adds one in between and one far away
+    new.splat_data.remove(55);
+    new.splat_data.remove(12);
+    for index in [0, 20, 40] {
+        new.splat_data[index].center[2] += 0.05;
+    }
+    new.splat_data
+        .push(generate::splat(Vec3::new(4.5, 4.5, 0.0), Vec3::Z, [0.1, 0.1 * 0.5], [0.5, 0.5, 0.5, 0.5]));
+    new.splat_data.push(generate::splat(
+        Vec3::new(50.0, 0.0, 0.0),
+        Vec3::Z,
+        [0.1, 0.1 * 0.5],
+        [0.5, 0.5, 0.5, 0.5],
+    ));
+    new.splat_count = new.splat_data.len();
+
+    let result = diff(&old, &new, &DiffSettings::default());
+    assert!((result.search_radius - 0.3).abs() < 1.0e-6);
+    assert_eq!(result.removed, [12, 55]);
+    assert_eq!(result.added, [98, 99]);
+    assert_eq!(result.moved, [(0, 0), (21, 20), (41, 40)]);
+    assert_eq!(result.unchanged, 95);
+    assert!((result.max_displacement - 0.05).abs() < 1.0e-5);
+
+    let summary = result.summary();
+    assert_eq!((summary.added, summary.removed, summary.moved), (2, 2, 3));
+    assert!(summary.to_string().starts_with("added:     2"));
+
+    // A looser tolerance counts the small moves as unchanged
+    let loose = diff(
+        &old,
+        &new,
+        &DiffSettings {
+            search_radius: Some(0.3),
+            moved_distance: 0.1,
+        },
+    );
+    assert_eq!((loose.moved.len(), loose.unchanged), (0, 98));
+    assert_eq!(diff(&old, &old, &DiffSettings::default()).unchanged, 100);
+}
@@ -0,0 +1,147 @@
+use glam::Vec3;
+use splatter::scene::{generate, Scene};
+use splatter::streaming::{read_tile, write_tiled, StreamingManager, StreamingSettings, TileIndex};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Splats every meter along the x axis from 0 to 99.
+fn street() -> Scene {
+    Scene::from_splats(
+        (0..100)
+            .map(|i| {
+                generate::splat(
+                    Vec3::new(i as f32 + 0.5, 0.0, 0.5),
+                    Vec3::Y,
+                    [0.5, 0.25],
+                    [i as f32 / 100.0, 0.5, 0.5, 1.0],
+                )
+            })
+            .collect(),
+    )
+}
+
+fn temporary_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("splatter_{name}_{}.tiles", std::process::id()))
+}
+
+/// Updates the manager until no tile is loading anymore.
+fn settle(manager: &mut StreamingManager, camera_position: Vec3) {
+    let start = Instant::now();
+    manager.update(camera_position);
+    while manager.loading_count() > 0 {
+        assert!(start.elapsed() < Duration::from_secs(10), "Tiles did not arrive");
+        std::thread::sleep(Duration::from_millis(1));
+        manager.update(camera_position);
+    }
+}
+
+#[test]
+fn tiled_file_round_trip() {
+    let path = temporary_path("round_trip");
+    let written = write_tiled(&street(), 10.0, &path).unwrap();
+    let mut reader = BufReader::new(File::open(&path).unwrap());
+    let index = TileIndex::read(&mut reader).unwrap();
+    assert_eq!(index, written);
+    assert_eq!(index.tiles.len(), 10);
+    let tile = &index.tiles[3];
+    assert_eq!(tile.splat_count, 10);
+    assert_eq!(tile.min, Vec3::new(30.5, 0.0, 0.5));
+    let tile = read_tile(&mut reader, tile, index.spherical_harmonics_order).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let splats = &tile.splat_data;
+    assert_eq!(splats.len(), 10);
+    assert_eq!(splats[0].center, [30.5, 0.0, 0.5]);
+    assert_eq!(splats[0].color, [0.3, 0.5, 0.5, 1.0]);
+    assert_eq!(splats[0].scale, [0.5, 0.25]);
+}
+
+#[test]
+fn rejects_tile_counts_beyond_the_end_of_the_file() {
+    let path = temporary_path("corrupt_count");
+    write_tiled(&street(), 10.0, &path).unwrap();
+    // The tile count follows the magic, the version, the tile size and the order of the view dependent colors
+    let mut bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    bytes[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+    bytes.truncate(24 + 44);
+    let error = TileIndex::read(&mut bytes.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn streams_view_dependent_colors_with_their_splats() {
+    let path = temporary_path("spherical_harmonics");
+    let mut street = street();
+    street.set_spherical_harmonics_order(1);
+    for (index, coefficient) in street.spherical_harmonics.iter_mut().enumerate() {
+        *coefficient = Vec3::splat(index as f32);
+    }
+    let index = write_tiled(&street, 10.0, &path).unwrap();
+    assert_eq!(index.spherical_harmonics_order, 1);
+    let settings = StreamingSettings {
+        load_radius: 15.0,
+        ..StreamingSettings::default()
+    };
+    let mut manager = StreamingManager::open(&path, settings).unwrap();
+    settle(&mut manager, Vec3::new(55.0, 0.0, 0.0));
+    let mut scene = Scene::new();
+    manager.copy_resident_into(&mut scene);
+    drop(manager);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(scene.splat_count, 30);
+    assert_eq!(scene.spherical_harmonics_order, 1);
+    assert_eq!(scene.spherical_harmonics.len(), 30 * 3);
+    assert_eq!(scene.sources.len(), 3);
+    assert_eq!(scene.sources[1].splats, 10..20);
+    // Every splat still has the coefficients it was written with
+    for (index, splat) in scene.splat_data.iter().enumerate() {
+        let original = street.splat_data.iter().position(|original| original.center == splat.center).unwrap();
+        assert_eq!(scene.spherical_harmonics_of(index), street.spherical_harmonics_of(original));
+    }
+}
+
+#[test]
+fn loads_tiles_near_the_camera() {
+    let path = temporary_path("near");
+    write_tiled(&street(), 10.0, &path).unwrap();
+    let settings = StreamingSettings {
+        load_radius: 15.0,
+        ..StreamingSettings::default()
+    };
+    let mut manager = StreamingManager::open(&path, settings).unwrap();
+    settle(&mut manager, Vec3::new(5.0, 0.0, 0.0));
+    assert_eq!(manager.resident_tiles(), vec![0, 1]);
+    assert!(manager.resident_splats().iter().all(|splat| splat.center[0] < 20.0));
+
+    // Moving along the street evicts the tiles behind
+    settle(&mut manager, Vec3::new(55.0, 0.0, 0.0));
+    assert_eq!(manager.resident_tiles(), vec![4, 5, 6]);
+    assert_eq!(manager.resident_splats().len(), 30);
+    // Chunks stop at the end of every tile
+    let chunks = manager.resident_chunks(4);
+    let ranges: Vec<_> = chunks.chunks.iter().map(|chunk| chunk.splats.clone()).collect();
+    assert_eq!(ranges, [0..4, 4..8, 8..10, 10..14, 14..18, 18..20, 20..24, 24..28, 28..30]);
+    assert_eq!(chunks.chunks[3].min.x, 50.5 - 1.5);
+    drop(manager);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn respects_the_memory_budget() {
+    let path = temporary_path("budget");
+    let index = write_tiled(&street(), 10.0, &path).unwrap();
+    let settings = StreamingSettings {
+        load_radius: 1000.0,
+        memory_budget: index.tiles[0].resident_bytes(index.spherical_harmonics_order) * 3,
+    };
+    let mut manager = StreamingManager::open(&path, settings).unwrap();
+    settle(&mut manager, Vec3::new(52.0, 0.0, 0.0));
+    // The closest tiles take precedence
+    assert_eq!(manager.resident_tiles(), vec![4, 5, 6]);
+    assert!(manager.resident_bytes() <= manager.settings.memory_budget);
+    drop(manager);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use glam::{Mat3 as GlamMat3, Quat as GlamQuat, Vec2 as GlamVec2, Vec3 as GlamVec3};
+use splatter::player::Player;
+use splatter::training_cameras::{
+    find_training_cameras, read_colmap, read_inria_cameras, read_nerfstudio_transforms, JumpToTrainingView, TrainingCamera, TrainingViewPlugin,
+    TrainingViews,
+};
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("splatter_training_cameras_{name}_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// A COLMAP style view: world to camera rotation and translation with OpenCV axes.
+struct ColmapView {
+    name: &'static str,
+    rotation: GlamQuat,
+    translation: GlamVec3,
+}
+
+fn views() -> [ColmapView; 2] {
+    [
+        ColmapView {
+            name: "b.png",
+            rotation: GlamQuat::from_rotation_y(0.7) * GlamQuat::from_rotation_x(-0.2),
+            translation: GlamVec3::new(0.5, -1.0, 4.0),
+        },
+        ColmapView {
+            name: "a.png",
+            rotation: GlamQuat::IDENTITY,
+            translation: GlamVec3::new(1.0, 2.0, 3.0),
+        },
+    ]
+}
+
+fn write_colmap(directory: &std::path::Path) {
+    let mut cameras = Vec::new();
+    cameras.extend(2u64.to_le_bytes());
+    // PINHOLE with fx, fy, cx, cy
+    cameras.extend(1i32.to_le_bytes());
+    cameras.extend(1i32.to_le_bytes());
+    cameras.extend(640u64.to_le_bytes());
+    cameras.extend(480u64.to_le_bytes());
+    for parameter in [500.0f64, 400.0, 300.0, 250.0] {
+        cameras.extend(parameter.to_le_bytes());
+    }
+    // SIMPLE_RADIAL with f, cx, cy, k
+    cameras.extend(2i32.to_le_bytes());
+    cameras.extend(2i32.to_le_bytes());
+    cameras.extend(100u64.to_le_bytes());
+    cameras.extend(80u64.to_le_bytes());
+    for parameter in [90.0f64, 50.0, 40.0, 0.01] {
+        cameras.extend(parameter.to_le_bytes());
+    }
+    std::fs::write(directory.join("cameras.bin"), cameras).unwrap();
+
+    let mut images = Vec::new();
+    images.extend(2u64.to_le_bytes());
+    for (index, view) in views().iter().enumerate() {
+        images.extend((index as i32 + 1).to_le_bytes());
+        let [x, y, z, w] = view.rotation.to_array();
+        for value in [w, x, y, z].into_iter().chain(view.translation.to_array()) {
+            images.extend((value as f64).to_le_bytes());
+        }
+        images.extend((index as i32 + 1).to_le_bytes());
+        images.extend(view.name.as_bytes());
+        images.push(0);
+        images.extend(3u64.to_le_bytes());
+        images.extend([0; 3 * 24]);
+    }
+    std::fs::write(directory.join("images.bin"), images).unwrap();
+}
+
+/// Pixel of a world position in an image, computed with the OpenCV pinhole model.
+fn opencv_pixel(view: &ColmapView, focal_length: GlamVec2, principal_point: GlamVec2, position: GlamVec3) -> GlamVec2 {
+    let camera = view.rotation * position + view.translation;
+    camera.truncate() / camera.z * focal_length + principal_point
+}
+
+fn pixel(camera: &TrainingCamera, position: GlamVec3) -> GlamVec2 {
+    let rendered = camera.camera(0.1, 100.0);
+    let ndc = rendered.project_view_position(rendered.view.transform_point3(position)).unwrap();
+    GlamVec2::new(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * GlamVec2::new(camera.width as f32, camera.height as f32)
+}
+
+#[test]
+fn reads_colmap_and_projects_like_opencv() {
+    let directory = temp_dir("colmap");
+    write_colmap(&directory);
+    let cameras = read_colmap(&directory).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(cameras.iter().map(|camera| camera.name.as_str()).collect::<Vec<_>>(), ["a.png", "b.png"]);
+    let [b, a] = views();
+    assert_eq!((cameras[0].width, cameras[0].height), (100, 80));
+    assert_eq!(cameras[0].focal_length, GlamVec2::splat(90.0));
+    assert!(cameras[0].position.abs_diff_eq(-a.translation, 1.0e-6));
+    // An unrotated OpenCV camera looks along +Z with +Y down
+    assert!((cameras[0].rotation * GlamVec3::NEG_Z).abs_diff_eq(GlamVec3::Z, 1.0e-6));
+    assert!((cameras[0].rotation * GlamVec3::Y).abs_diff_eq(GlamVec3::NEG_Y, 1.0e-6));
+
+    assert_eq!(cameras[1].principal_point, GlamVec2::new(300.0, 250.0));
+    for position in [GlamVec3::new(0.3, 0.2, 1.0), GlamVec3::new(-1.0, 0.5, 2.0), GlamVec3::ZERO] {
+        let expected = opencv_pixel(&b, GlamVec2::new(500.0, 400.0), GlamVec2::new(300.0, 250.0), position);
+        let actual = pixel(&cameras[1], position);
+        assert!(actual.abs_diff_eq(expected, 1.0e-2), "{actual} != {expected}");
+        let expected = opencv_pixel(&a, GlamVec2::splat(90.0), GlamVec2::new(50.0, 40.0), position);
+        let actual = pixel(&cameras[0], position);
+        assert!(actual.abs_diff_eq(expected, 1.0e-2), "{actual} != {expected}");
+    }
+}
+
+#[test]
+fn formats_agree_on_the_same_view() {
+    let directory = temp_dir("formats");
+    let view = &views()[0];
+    let camera_to_world = GlamMat3::from_quat(view.rotation).transpose();
+    let position = -(camera_to_world * view.translation);
+    let rows = |matrix: GlamMat3| matrix.transpose().to_cols_array_2d();
+    let inria = serde_json::json!([{
+        "id": 0, "img_name": "b", "width": 640, "height": 480,
+        "position": position.to_array(), "rotation": rows(camera_to_world), "fx": 500.0, "fy": 400.0,
+    }]);
+    // Nerfstudio flips the Y and Z camera axes relative to OpenCV
+    let opengl = camera_to_world * GlamMat3::from_diagonal(GlamVec3::new(1.0, -1.0, -1.0));
+    let mut matrix = rows(opengl).map(|row| [row[0], row[1], row[2], 0.0]);
+    for (row, value) in matrix.iter_mut().zip(position.to_array()) {
+        row[3] = value;
+    }
+    let nerfstudio = serde_json::json!({
+        "fl_x": 500.0, "fl_y": 400.0, "w": 640, "h": 480,
+        "frames": [{"file_path": "images/b.png", "transform_matrix": [matrix[0], matrix[1], matrix[2], [0.0, 0.0, 0.0, 1.0]]}],
+    });
+    std::fs::write(directory.join("cameras.json"), inria.to_string()).unwrap();
+    std::fs::write(directory.join("transforms.json"), nerfstudio.to_string()).unwrap();
+    let inria = read_inria_cameras(directory.join("cameras.json")).unwrap().remove(0);
+    let nerfstudio = read_nerfstudio_transforms(directory.join("transforms.json")).unwrap().remove(0);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    for camera in [&inria, &nerfstudio] {
+        assert!(camera.position.abs_diff_eq(position, 1.0e-5));
+        assert!(camera.rotation.abs_diff_eq(inria.rotation, 1.0e-5));
+        assert_eq!(camera.focal_length, GlamVec2::new(500.0, 400.0));
+        assert_eq!(camera.principal_point, GlamVec2::new(320.0, 240.0));
+        let point = GlamVec3::new(0.3, 0.2, 1.0);
+        let expected = opencv_pixel(view, camera.focal_length, camera.principal_point, point);
+        assert!(pixel(camera, point).abs_diff_eq(expected, 1.0e-2));
+    }
+    assert!((inria.fov_y() - 2.0 * (240.0f32 / 400.0).atan()).abs() < 1.0e-6);
+}
+
+#[test]
+fn nerfstudio_intrinsics_per_frame_and_from_field_of_view() {
+    let directory = temp_dir("nerfstudio");
+    let identity = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+    let transforms = serde_json::json!({
+        "camera_angle_x": std::f32::consts::FRAC_PI_2, "w": 800, "h": 600,
+        "frames": [
+            {"file_path": "a", "transform_matrix": identity},
+            {"file_path": "b", "transform_matrix": identity, "fl_x": 100.0, "cx": 410.0, "w": 820},
+        ],
+    });
+    let path = directory.join("transforms.json");
+    std::fs::write(&path, transforms.to_string()).unwrap();
+    let cameras = read_nerfstudio_transforms(&path).unwrap();
+    assert!(cameras[0].focal_length.abs_diff_eq(GlamVec2::splat(400.0), 1.0e-3));
+    assert_eq!(cameras[0].principal_point, GlamVec2::new(400.0, 300.0));
+    assert_eq!(cameras[0].rotation, GlamQuat::IDENTITY);
+    assert_eq!(
+        (cameras[1].width, cameras[1].focal_length, cameras[1].principal_point.x),
+        (820, GlamVec2::splat(100.0), 410.0)
+    );
+
+    std::fs::write(
+        &path,
+        r#"{"fl_x": 100.0, "frames": [{"file_path": "a", "transform_matrix": [[1,0,0,0],[0,1,0,0],[0,0,1,0],[0,0,0,1]]}]}"#,
+    )
+    .unwrap();
+    let error = read_nerfstudio_transforms(&path).unwrap_err();
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn finds_cameras_next_to_trained_scenes() {
+    let directory = temp_dir("find");
+    let scene = directory.join("point_cloud/iteration_30000/point_cloud.ply");
+    std::fs::create_dir_all(scene.parent().unwrap()).unwrap();
+    assert_eq!(find_training_cameras(&scene).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    std::fs::create_dir_all(directory.join("sparse/0")).unwrap();
+    write_colmap(&directory.join("sparse/0"));
+    assert_eq!(find_training_cameras(&scene).unwrap().len(), 2);
+    // The cameras written by the trainer take precedence
+    std::fs::write(directory.join("cameras.json"), "[]").unwrap();
+    assert!(find_training_cameras(&scene).unwrap().is_empty());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn player_jumps_to_training_views() {
+    let camera = TrainingCamera {
+        name: "a".to_string(),
+        width: 200,
+        height: 100,
+        focal_length: GlamVec2::splat(100.0),
+        principal_point: GlamVec2::new(100.0, 50.0),
+        position: GlamVec3::new(1.0, 2.0, 3.0),
+        rotation: GlamQuat::from_rotation_y(0.5),
+    };
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TrainingViewPlugin))
+        .insert_resource(TrainingViews::new(vec![camera.clone()]));
+    let player = Player {
+        velocity: Vec3::new(0.0, -3.0, 0.0),
+        ..Player::default()
+    };
+    let entity = app
+        .world
+        .spawn((player, Transform::default(), Projection::Perspective(PerspectiveProjection::default())))
+        .id();
+    app.world.send_event(JumpToTrainingView(0));
+    app.update();
+
+    let transform = app.world.get::<Transform>(entity).unwrap();
+    assert_eq!(transform.translation, Vec3::new(1.0, 2.0, 3.0));
+    assert!(transform.rotation.abs_diff_eq(Quat::from_rotation_y(0.5), 1.0e-6));
+    assert_eq!(app.world.get::<Player>(entity).unwrap().velocity, Vec3::ZERO);
+    let Projection::Perspective(perspective) = app.world.get::<Projection>(entity).unwrap() else {
+        panic!("Expected a perspective projection");
+    };
+    assert!((perspective.fov - camera.fov_y()).abs() < 1.0e-6);
+    assert_eq!(app.world.resource::<TrainingViews>().current, Some(0));
+}
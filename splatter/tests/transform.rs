@@ -0,0 +1,118 @@
+use glam::{Affine3A, Mat3, Mat4, Quat, Vec3, Vec4};
+use splatter::coordinates::evaluate_spherical_harmonics;
+use splatter::cpu_renderer::{render_scene, CpuImage, CpuRenderSettings};
+use splatter::scene::{generate, spherical_harmonics_count, Camera, Scene};
+use splatter::utils::polar_rotation;
+use std::f32::consts::FRAC_PI_3;
+
+/// Deterministic values in [-1, 1).
+fn pseudo_random(seed: u32) -> f32 {
+    (seed.wrapping_mul(2654435761).rotate_left(13).wrapping_mul(2246822519) >> 8) as f32 / (1 << 23) as f32 - 1.0
+}
+
+fn pseudo_random_vec3(seed: u32) -> Vec3 {
+    Vec3::new(pseudo_random(3 * seed), pseudo_random(3 * seed + 1), pseudo_random(3 * seed + 2))
+}
+
+/// A few tilted splats around the origin with view dependent colors of the given order.
+fn scene(order: u32) -> Scene {
+    let mut scene = Scene::new();
+    scene.splat_data = (0..12)
+        .map(|index| {
+            let normal = pseudo_random_vec3(index + 50).normalize();
+            generate::splat(pseudo_random_vec3(index) * 0.8, normal, [0.25, 0.12], [0.4, 0.5, 0.6, 0.8])
+        })
+        .collect();
+    scene.splat_count = scene.splat_data.len();
+    scene.spherical_harmonics_order = order;
+    let count = spherical_harmonics_count(order) * scene.splat_count;
+    scene.spherical_harmonics = (0..count as u32).map(|index| pseudo_random_vec3(index + 100) * 0.2).collect();
+    scene
+}
+
+fn camera(view: Mat4) -> Camera {
+    Camera::perspective(view, FRAC_PI_3, 1.0, 0.1, 100.0)
+}
+
+fn max_difference(a: &CpuImage, b: &CpuImage) -> f32 {
+    a.pixels
+        .iter()
+        .zip(&b.pixels)
+        .map(|(a, b)| (*a - *b).abs().max_element())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn a_transformed_scene_renders_like_a_transformed_camera() {
+    let settings = CpuRenderSettings {
+        blur_variance: 0.0,
+        ..CpuRenderSettings::default()
+    };
+    let view = Mat4::look_at_rh(Vec3::new(0.5, 1.0, 4.0), Vec3::ZERO, Vec3::Y);
+    let rigid = Affine3A::from_rotation_translation(Quat::from_euler(glam::EulerRot::YXZ, 0.7, -0.4, 1.9), Vec3::new(2.0, -1.0, 0.5));
+    let uniform = Affine3A::from_scale_rotation_translation(Vec3::splat(2.5), Quat::from_rotation_z(-0.6), Vec3::new(0.0, 3.0, 0.0));
+    let stretched = Affine3A::from_scale_rotation_translation(Vec3::new(1.0, 2.0, 0.5), Quat::from_rotation_x(0.3), Vec3::ZERO);
+    // Only the rotation part of a non-uniform scale turns the view dependent colors, so compare flat colors there
+    for (transform, order) in [(rigid, 3), (uniform, 2), (stretched, 0)] {
+        let original = scene(order);
+        let mut transformed = scene(order);
+        transformed.transform(&transform);
+        assert_eq!(transformed.dirty_splats, Some(0..original.splat_count));
+        let expected = render_scene(&original, &camera(view), 48, 48, &settings);
+        let actual = render_scene(&transformed, &camera(view * Mat4::from(transform.inverse())), 48, 48, &settings);
+        assert!(expected.pixels.iter().any(|pixel| pixel.truncate() != Vec3::ZERO));
+        let difference = max_difference(&expected, &actual);
+        assert!(difference < 2.0e-3, "order {order}: {difference}");
+    }
+}
+
+#[test]
+fn view_dependent_colors_turn_with_the_scene() {
+    let original = scene(3);
+    let mut rotated = scene(3);
+    let rotation = Quat::from_euler(glam::EulerRot::XYZ, 1.1, -0.3, 2.2);
+    // The scale and the translation do not change the directions
+    rotated.transform(&Affine3A::from_scale_rotation_translation(Vec3::splat(0.5), rotation, Vec3::ONE));
+    for index in 0..original.splat_count {
+        let coefficients = |scene: &Scene| {
+            [Vec3::ZERO]
+                .into_iter()
+                .chain(scene.spherical_harmonics_of(index).iter().copied())
+                .collect::<Vec<_>>()
+        };
+        let (before, after) = (coefficients(&original), coefficients(&rotated));
+        for seed in 0..8 {
+            let direction = pseudo_random_vec3(1000 + seed).normalize();
+            let expected = evaluate_spherical_harmonics(&before, direction);
+            assert!(evaluate_spherical_harmonics(&after, rotation * direction).abs_diff_eq(expected, 1.0e-4));
+        }
+    }
+}
+
+#[test]
+fn finds_the_closest_rotation() {
+    let rotation = Mat3::from_quat(Quat::from_euler(glam::EulerRot::ZYX, 0.2, 0.9, -1.4));
+    let stretch = Mat3::from_diagonal(Vec3::new(3.0, 0.5, 1.5));
+    assert!(polar_rotation(rotation * stretch).abs_diff_eq(rotation, 1.0e-4));
+    assert!(polar_rotation(rotation).abs_diff_eq(rotation, 1.0e-5));
+    let mirrored = rotation * Mat3::from_diagonal(Vec3::new(-2.0, 1.0, 1.0));
+    assert!(polar_rotation(mirrored).abs_diff_eq(rotation * Mat3::from_diagonal(Vec3::new(-1.0, 1.0, 1.0)), 1.0e-4));
+    assert_eq!(polar_rotation(Mat3::ZERO), Mat3::IDENTITY);
+}
+
+#[test]
+fn saves_view_dependent_colors_to_ply() {
+    let path = std::env::temp_dir().join(format!("splatter_transform_{}.ply", std::process::id()));
+    let path = path.to_str().unwrap();
+    let scene = scene(2);
+    scene.save_splats_to_ply(path).unwrap();
+    let loaded = Scene::from_ply(path).unwrap();
+    let splats = Scene::read_splats_from_ply(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(loaded.splat_count, scene.splat_count);
+    assert_eq!(loaded.spherical_harmonics_order, 2);
+    assert_eq!(loaded.spherical_harmonics, scene.spherical_harmonics);
+    assert_eq!(splats.len(), scene.splat_count);
+    assert!(Vec4::from(loaded.splat_data[0].color).abs_diff_eq(Vec4::from(scene.splat_data[0].color), 1.0e-5));
+}